@@ -17,9 +17,17 @@ fn main() {
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    // Each chip has its own memory map, none of them is named memory.x in the crate root because
+    // the linker looks in the current directory before the output directory
+    let rp2350 = env::var_os("CARGO_FEATURE_BOARD_PICO2").is_some();
+    let memory: &[u8] = if rp2350 {
+        include_bytes!("memory_rp2350.x")
+    } else {
+        include_bytes!("memory_rp2040.x")
+    };
     File::create(out.join("memory.x"))
         .unwrap()
-        .write_all(include_bytes!("memory.x"))
+        .write_all(memory)
         .unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
@@ -27,10 +35,14 @@ fn main() {
     // any file in the project changes. By specifying `memory.x`
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
-    println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=memory_rp2040.x");
+    println!("cargo:rerun-if-changed=memory_rp2350.x");
 
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
-    println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
+    // The boot2 of the RP2040, the RP2350 boots from the IMAGE_DEF block of the main.rs
+    if !rp2350 {
+        println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
+    }
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 
-//! This example tests the RP Pico W onboard LED via the CYW43 Wi-Fi chip.
+//! Firmware of the Smart Vivarium, a PID temperature controller for the Raspberry Pi Pico boards.
 //!
-//! It does not work with the original RP Pico board (non-W version). See `blinky.rs` for that.
+//! The board is chosen with the cargo features board-pico, board-pico-w or board-pico2 (board.rs).
 
 #![no_std] // Don't link the standard library (needed for embedded targets)
 #![no_main] // Disable normal main entry point; we use `#[embassy_executor::main]` instead
@@ -10,22 +10,48 @@
  
 mod modular;
 
+use core::cell::RefCell;
+
 use defmt::*; // For logging via RTT
-use embassy_executor::Spawner;
+use embassy_executor::{InterruptExecutor, Spawner};
 
 use embassy_rp::adc::{Adc, Async, Channel, Config as AdcConfig, InterruptHandler as AdcIrq};
 use embassy_rp::bind_interrupts;
-use embassy_rp::gpio::{AnyPin, Flex, Level, Output, Pull};
+use embassy_rp::flash::Flash;
+use embassy_rp::gpio::{Input, Level, Output, Pull};
 use embassy_rp::i2c::{Config as I2c_config, I2c, InterruptHandler};
-use embassy_rp::peripherals::{I2C0};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_rp::interrupt;
+use embassy_rp::interrupt::{InterruptExt, Priority};
+use embassy_rp::peripherals::{I2C0, PIO1, UART1, USB};
+use embassy_rp::pio::{InterruptHandler as PioIrq, Pio};
+use embassy_rp::uart::{BufferedInterruptHandler, BufferedUart, Config as UartConfig, Parity};
+use embassy_rp::usb::{Driver as UsbDriver, InterruptHandler as UsbIrq};
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_time::{Timer};
 use static_cell::StaticCell;
 use embassy_rp::pwm::{Config as PwmConfig, Pwm};
+use embassy_rp::spi::{Config as SpiConfig, Spi};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State as CdcState};
+use embassy_usb::{Builder as UsbBuilder, Config as UsbConfig};
 use {defmt_rtt as _, panic_probe as _}; // RTT logging and panic handler
 
 
+// The boot ROM of the RP2350 looks for this block in the first 4K of the flash
+#[cfg(feature = "board-pico2")]
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: embassy_rp::block::ImageDef = embassy_rp::block::ImageDef::secure_exe();
+
+// Executor of the acquisition and the control loop, it preempts the tasks of the thread mode, so a
+// flush of the OLED or a write of the SD card does not delay a step of the control
+static EXECUTOR_CONTROL: InterruptExecutor = InterruptExecutor::new();
+
+#[interrupt]
+unsafe fn SWI_IRQ_1() {
+    unsafe { EXECUTOR_CONTROL.on_interrupt() }
+}
 
 bind_interrupts!(struct Irqs {
 
@@ -35,71 +61,244 @@ bind_interrupts!(struct Irqs {
     // Bind the interrupt handler to  I2C IRQ
     I2C0_IRQ => InterruptHandler<I2C0>;
 
+    // Bind the interrupt handler to  USB IRQ
+    USBCTRL_IRQ => UsbIrq<USB>;
+
+    // Bind the interrupt handler to  UART1 IRQ (Modbus RTU)
+    UART1_IRQ => BufferedInterruptHandler<UART1>;
+
+    // Bind the interrupt handler to  PIO1 IRQ (DHT)
+    PIO1_IRQ_0 => PioIrq<PIO1>;
+
+    // Bind the interrupt handler to  PIO0 IRQ (CYW43)
+    #[cfg(feature = "wifi")]
+    PIO0_IRQ_0 => PioIrq<embassy_rp::peripherals::PIO0>;
+
 });
 
 
 /// Main async entry point
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
-    // Initialize Embassy peripherals and clocks
-    let p = embassy_rp::init(Default::default());
-
-    // Create an Output to the LED
-    let blinky_led = Output::new(p.PIN_25, Level::Low); // Led external
+    // Initialize Embassy peripherals and clocks, the board gives each role its pins (board.rs)
+    let board = modular::Board::new(embassy_rp::init(Default::default()));
+    info!("Board: {}", modular::BOARD_NAME);
+
+    // Share the flash between the configuration and the data log, each one in its own partition
+    static FLASH: StaticCell<modular::SharedFlash> = StaticCell::new();
+    let flash = FLASH.init(BlockingMutex::new(RefCell::new(Flash::new_blocking(board.flash))));
+
+    // Load the configuration saved in the last sectors of the flash
+    let mut config_store = modular::ConfigStore::new(modular::config_partition(flash), 0, modular::CONFIG_SECTORS);
+    let config = modular::load_config(&mut config_store);
+    modular::apply_config(&config);
+    static CONFIG_STORE: StaticCell<modular::SharedConfigStore> = StaticCell::new();
+    let config_store = CONFIG_STORE.init(Mutex::new(config_store));
+
+    // Find the end of the data log
+    let mut data_log = modular::DataLog::new(modular::log_partition(flash), modular::LOG_SECTORS);
+    modular::open_data_log(&mut data_log);
+    let boot = data_log.boot();
+    static DATA_LOG: StaticCell<modular::SharedDataLog> = StaticCell::new();
+    let data_log = DATA_LOG.init(Mutex::new(data_log));
 
     // Configure I2C
-    let sda = p.PIN_20;
-    let scl = p.PIN_21;
+    let sda = board.i2c_sda;
+    let scl = board.i2c_scl;
 
     let mut i2c_config = I2c_config::default();
     i2c_config.frequency = 100_000;
-    let i2c = I2c::new_async(p.I2C0, scl, sda, Irqs, i2c_config);
+    let i2c = I2c::new_async(board.i2c, scl, sda, Irqs, i2c_config);
+    // Share the I2C0 between the OLED and the sensors, each driver receives its own device
+    static I2C_BUS: StaticCell<modular::I2cBus> = StaticCell::new();
+    let i2c_bus = I2C_BUS.init(Mutex::new(i2c));
+    let sht3x = modular::Sht3x::new(
+        modular::I2cBusDevice::new(i2c_bus, modular::I2cDeviceId::Sht3x),
+        modular::SHT3X_ADDRESS,
+        modular::Repeatability::High,
+    );
+    let bme280 = modular::Bme280::new(
+        modular::I2cBusDevice::new(i2c_bus, modular::I2cDeviceId::Bme280),
+        modular::BME280_ADDRESS,
+        modular::Bme280Config {
+            oversampling: modular::Oversampling::X1,
+            filter: modular::Filter::Off,
+            mode: modular::MeasurementMode::Forced,
+        },
+    );
 
     // Create an ADC peripheral
-    let adc = Adc::new(p.ADC, Irqs, AdcConfig::default());
+    let adc = Adc::new(board.adc, Irqs, AdcConfig::default());
     // Create a mutex to protect the ADC
-    static ADC: StaticCell<Mutex<ThreadModeRawMutex, Adc<'static, Async>>> = StaticCell::new();
+    static ADC: StaticCell<Mutex<CriticalSectionRawMutex, Adc<'static, Async>>> = StaticCell::new();
     // Initialize the ADC
     let adc_mutex = ADC.init(Mutex::new(adc));
     // Create the ADC thats read the internal temperature of the DIE, like usage in the watchdog feed, if the temperature goes high we turn off the system
-    let temp_adc = Channel::new_temp_sensor(p.ADC_TEMP_SENSOR);
-    // Create the ADC 0 to read the luminosity of the system
-    let lum_adc_0 = Channel::new_pin(p.PIN_26, Pull::Down);
+    let temp_adc = Channel::new_temp_sensor(board.adc_temp_sensor);
+    // Create the ADC 0 to read the reference temperature, it is also the knob of the setpoint when enabled
+    let ref_adc_0 = Channel::new_pin(board.ref_temp, Pull::Down);
     // Create the ADC 1 to read the ADC 1
     //let adc_1 = Channel::new_pin(p.PIN_27, Pull::Down);
     // Create the ADC 2 to read the ADC 2
     //let adc_2 = Channel::new_pin(p.PIN_28, Pull::Down);
 
-    // Create a GPIO to read the DHT11/DHT22
-    //let dht_pin = Flex::new(AnyPin::from(p.PIN_22));
+    // Read the DHT11/DHT22 with a state machine of the PIO1, the PIO0 stays free for the CYW43
+    // The 1-Wire program must be at the address 0 of the PIO1, so the DS18B20 is loaded first
+    let mut pio1 = Pio::new(board.pio, Irqs);
+
+    // DS18B20 probes of the enclosure in the 1-Wire bus, state machine 1 of the PIO1
+    let ds18b20 = modular::Ds18b20Bus::new(&mut pio1.common, pio1.sm1, board.ds18b20, modular::Resolution::Bits12);
+    let dht = modular::Dht::new(&mut pio1.common, pio1.sm0, board.dht, modular::DhtKind::Dht22);
+
+    // Status LED of the board, the PWM dims it for the breathing
+    #[cfg(not(feature = "status-ws2812"))]
+    let status_led = {
+        let mut led_config = PwmConfig::default();
+        led_config.top = 999; // 1 kHz with the divider of 125 at 125 MHz
+        led_config.divider = 125u8.into();
+        modular::StatusLedPin::Pwm(Pwm::new_output_b(board.status_led_pwm, board.status_led, led_config))
+    };
+    // WS2812 RGB status LED, state machine 2 of the PIO1 fed by the DMA channel 3
+    #[cfg(feature = "status-ws2812")]
+    let status_led = modular::StatusLedPin::Ws2812(modular::ws2812::Ws2812Led::new(
+        &mut pio1.common,
+        pio1.sm2,
+        board.status_ws2812_dma,
+        board.status_ws2812,
+    ));
+
+    // Relay of the mister, active high
+    let mister_relay = Output::new(board.mister_relay, Level::Low);
+
+    // Button of the pages of the OLED, pressed to the GND
+    let page_button = Input::new(board.page_button, Pull::Up);
+
+    // Alarms: acknowledge button pressed to the GND, LED and buzzer
+    let alarm_button = Input::new(board.alarm_button, Pull::Up);
+    let alarm_led = Output::new(board.alarm_led, Level::Low);
+    let buzzer = Output::new(board.buzzer, Level::Low);
+
+    // Rotary encoder of the menu, channels A and B and the button
+    let encoder = modular::RotaryEncoder::new(Input::new(board.encoder_a, Pull::Up), Input::new(board.encoder_b, Pull::Up));
+    let encoder_button = Input::new(board.encoder_button, Pull::Up);
 
     // Demonstrate PWM by setting duty cycle
     //
-    // Using GP4 in Slice2, make sure to use an appropriate resistor.
-    let desired_freq_hz = 100_000;
+    // Heater in the GP2 (Slice1 channel A), make sure to use an appropriate resistor.
+    let desired_freq_hz = config.pwm_frequency_hz;
     let clock_freq_hz = embassy_rp::clocks::clk_sys_freq();
     let divider = 8u8;
-    let period = (clock_freq_hz / (desired_freq_hz * divider as u32)) as u16 - 1; 
+    // The configuration keeps the frequency in its range, the top is limited to the 16 bits of the counter anyway
+    let period = (clock_freq_hz / (desired_freq_hz * divider as u32)).saturating_sub(1).min(u16::MAX as u32) as u16;
 
     let mut c = PwmConfig::default();
     c.top = period;
     c.divider = divider.into();
 
-    let slice_1 = p.PWM_SLICE1;
-    let pin_2 = p.PIN_2;
+    let slice_1 = board.heater_pwm;
+    let pin_2 = board.heater;
 
 
     let pwm_temp = Pwm::new_output_a(slice_1, pin_2, c.clone());// Small delay to let the PWM initialize properly
 
+    // Configure the USB serial console (CDC-ACM)
+    let usb_driver = UsbDriver::new(board.usb, Irqs);
+    let mut usb_config = UsbConfig::new(0xc0de, 0xcafe);
+    usb_config.manufacturer = Some("Everton Oriente");
+    usb_config.product = Some("Smart Vivarium");
+    usb_config.serial_number = Some("pid-rp2040");
+    usb_config.max_power = 100;
+    usb_config.max_packet_size_0 = 64;
+
+    static USB_CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static USB_BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static USB_CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+    static CONSOLE_STATE: StaticCell<CdcState> = StaticCell::new();
+    let mut usb_builder = UsbBuilder::new(
+        usb_driver,
+        usb_config,
+        USB_CONFIG_DESCRIPTOR.init([0; 256]),
+        USB_BOS_DESCRIPTOR.init([0; 256]),
+        &mut [], // no msos descriptors
+        USB_CONTROL_BUF.init([0; 64]),
+    );
+    let console_class = CdcAcmClass::new(&mut usb_builder, CONSOLE_STATE.init(CdcState::new()), 64);
+
+    // The telemetry goes to a second USB serial port, or to the UART0 TX
+    #[cfg(not(feature = "telemetry-uart"))]
+    let telemetry_link = {
+        static TELEMETRY_STATE: StaticCell<CdcState> = StaticCell::new();
+        CdcAcmClass::new(&mut usb_builder, TELEMETRY_STATE.init(CdcState::new()), 64)
+    };
+    #[cfg(feature = "telemetry-uart")]
+    let telemetry_link = {
+        let mut uart_config = embassy_rp::uart::Config::default();
+        uart_config.baudrate = 115_200;
+        embassy_rp::uart::UartTx::new(board.telemetry_uart, board.telemetry_tx, board.telemetry_dma, uart_config)
+    };
+    let usb = usb_builder.build();
+
+    // Configure the Modbus RTU in the UART1 and the driver enable of the RS-485
+    let mut modbus_config = UartConfig::default();
+    modbus_config.baudrate = modular::MODBUS_BAUDRATE;
+    modbus_config.parity = Parity::ParityEven;
+    static MODBUS_TX_BUF: StaticCell<[u8; 256]> = StaticCell::new();
+    static MODBUS_RX_BUF: StaticCell<[u8; 256]> = StaticCell::new();
+    let modbus_uart = BufferedUart::new(
+        board.modbus_uart,
+        board.modbus_tx,
+        board.modbus_rx,
+        Irqs,
+        MODBUS_TX_BUF.init([0; 256]),
+        MODBUS_RX_BUF.init([0; 256]),
+        modbus_config,
+    );
+    let modbus_de = Output::new(board.modbus_de, Level::Low);
+
+    // Configure the SD card in the SPI1 with its chip select
+    let sd_spi = Spi::new(
+        board.sd_spi,
+        board.sd_sck,
+        board.sd_mosi,
+        board.sd_miso,
+        board.sd_tx_dma,
+        board.sd_rx_dma,
+        SpiConfig::default(),
+    );
+    let sd_card = modular::SdCard::new(sd_spi, Output::new(board.sd_cs, Level::High));
+
+    // SPI of the CYW43 in the state machine 0 of the PIO0, fed by the DMA channel 4
+    #[cfg(feature = "wifi")]
+    let (wifi_power, wifi_spi) = {
+        let mut pio0 = Pio::new(board.wifi_pio, Irqs);
+        let spi = cyw43_pio::PioSpi::new(
+            &mut pio0.common,
+            pio0.sm0,
+            cyw43_pio::DEFAULT_CLOCK_DIVIDER,
+            pio0.irq0,
+            Output::new(board.wifi_cs, Level::High),
+            board.wifi_dio,
+            board.wifi_clk,
+            board.wifi_dma,
+        );
+        (Output::new(board.wifi_power, Level::Low), spi)
+    };
+
 
     // Spawn the LED task
-    info!("Starting LED toggle task");
-    unwrap!(spawner.spawn(modular::toogle_led(blinky_led)));
+    info!("Starting status LED task");
+    unwrap!(spawner.spawn(modular::status_led_task(status_led)));
     Timer::after_millis(100).await; // Small delay to let the LED task start properly
 
-    // Spawn the luminosity task to read the luminosity
-    info!("Starting luminosity ADC task");
-    unwrap!(spawner.spawn(modular::read_adc_channels(adc_mutex, lum_adc_0, temp_adc))); // Here you should add in compliance how many adc are going to use
+    // Start the executor of the control in the software interrupt 1, below the priority of the interrupts of
+    // the ADC and the time driver it waits for. The values cross between the executors in the Watch and
+    // Channel statics locked with a critical section
+    interrupt::SWI_IRQ_1.set_priority(Priority::P2);
+    let control_spawner = EXECUTOR_CONTROL.start(interrupt::SWI_IRQ_1);
+
+    // Spawn the ADC task to read the reference temperature and the temperature of the die
+    info!("Starting ADC task");
+    unwrap!(control_spawner.spawn(modular::read_adc_channels(adc_mutex, ref_adc_0, temp_adc))); // Here you should add in compliance how many adc are going to use
     Timer::after_millis(100).await; // Small delay to let the ADC task start properly
 
     // Spawn the process_adc_channel_0 task
@@ -107,21 +306,98 @@ async fn main(spawner: Spawner) {
     unwrap!(spawner.spawn(modular::process_adc_channel_0())); // Here you should add in compliance how many adc are going to use
     Timer::after_millis(100).await; // Small delay to let the ADC task start properly
 
-    // Spawn the process_adc_channel_temp task
-    info!("Starting ADC temperature processing task");
-    unwrap!(spawner.spawn(modular::process_adc_channel_temp())); // Here you should add in compliance how many adc are going to use
-    Timer::after_millis(100).await; // Small delay to let the ADC task start properly
+    // Spawn the alarm tasks, they watch the temperature of the die together with the control loop
+    info!("Starting alarm tasks");
+    unwrap!(spawner.spawn(modular::alarm_task(alarm_led, buzzer)));
+    unwrap!(spawner.spawn(modular::alarm_button_task(alarm_button)));
+    Timer::after_millis(100).await; // Small delay to let the alarm tasks start properly
+
+    // Spawn the setpoint task
+    info!("Starting setpoint task");
+    unwrap!(spawner.spawn(modular::setpoint_task(config.stored_setpoint_c, config.knob_enabled)));
+    Timer::after_millis(100).await; // Small delay to let the setpoint task start properly
+
+    // Spawn the DHT task
+    info!("Starting DHT task");
+    unwrap!(spawner.spawn(modular::dht_task(dht)));
+    Timer::after_millis(100).await; // Small delay to let the DHT task start properly
+
+    // Spawn the DS18B20 task
+    info!("Starting DS18B20 task");
+    unwrap!(spawner.spawn(modular::ds18b20_task(ds18b20)));
+    Timer::after_millis(100).await; // Small delay to let the DS18B20 task start properly
+
+    // Spawn the humidity task
+    info!("Starting humidity task");
+    unwrap!(spawner.spawn(modular::humidity_task(mister_relay, modular::MistStrategy::Hysteresis { band_pct: 6.0 })));
+    Timer::after_millis(100).await; // Small delay to let the humidity task start properly
+
+    // Spawn the tasks of the I2C sensors
+    info!("Starting SHT3x and BME280 tasks");
+    unwrap!(spawner.spawn(modular::sht3x_task(sht3x)));
+    unwrap!(spawner.spawn(modular::bme280_task(bme280)));
+    Timer::after_millis(100).await; // Small delay to let the sensor tasks start properly
 
     // Spawn the I2C Display task
     info!("Starting OLED display task");
-    unwrap!(spawner.spawn(modular::oled_task(i2c)));
+    unwrap!(spawner.spawn(modular::oled_task(
+        i2c_bus,
+        modular::Navigation::AutoRotate { dwell_s: 5 },
+        modular::TrendWindow::TwoMinutes,
+    )));
+    unwrap!(spawner.spawn(modular::page_button_task(page_button)));
     Timer::after_millis(100).await; // Small delay to let the OLED task start properly
 
+    // Spawn the tasks of the encoder and its menu
+    info!("Starting encoder and menu tasks");
+    unwrap!(spawner.spawn(modular::encoder_task(encoder)));
+    unwrap!(spawner.spawn(modular::encoder_button_task(encoder_button)));
+    unwrap!(spawner.spawn(modular::menu_task(config_store)));
+    Timer::after_millis(100).await; // Small delay to let the menu tasks start properly
+
     // Spawn the PWM task
     info!("Starting PWM task");
-    unwrap!(spawner.spawn(modular::pwm_set_dutycycle(pwm_temp)));
+    unwrap!(control_spawner.spawn(modular::pwm_set_dutycycle(pwm_temp, config.gains)));
     Timer::after_millis(100).await; // Small delay to let the PWM task
 
+    // Spawn the USB tasks
+    info!("Starting USB console task");
+    unwrap!(spawner.spawn(modular::usb_task(usb)));
+    unwrap!(spawner.spawn(modular::console_task(console_class, config_store, data_log)));
+    Timer::after_millis(100).await; // Small delay to let the USB tasks start properly
+
+    // Spawn the telemetry task
+    info!("Starting telemetry task");
+    unwrap!(spawner.spawn(modular::telemetry_task(telemetry_link)));
+    Timer::after_millis(100).await; // Small delay to let the telemetry task start properly
+
+    // Spawn the data log task
+    info!("Starting data log task");
+    unwrap!(spawner.spawn(modular::datalog_task(data_log)));
+    Timer::after_millis(100).await; // Small delay to let the data log task start properly
+
+    // Spawn the SD card tasks
+    info!("Starting SD card tasks");
+    unwrap!(spawner.spawn(modular::sd_sample_task(boot)));
+    unwrap!(spawner.spawn(modular::sd_card_task(sd_card, boot)));
+    Timer::after_millis(100).await; // Small delay to let the SD card tasks start properly
+
+    // Spawn the Modbus tasks, the receiver times the frames in the executor of the control
+    info!("Starting Modbus RTU tasks");
+    let (modbus_tx, modbus_rx) = modbus_uart.split();
+    unwrap!(control_spawner.spawn(modular::modbus_rx_task(modbus_rx)));
+    unwrap!(spawner.spawn(modular::modbus_task(modbus_tx, modbus_de)));
+    Timer::after_millis(100).await; // Small delay to let the Modbus tasks start properly
+
+    // Start the Wi-Fi and spawn the MQTT and HTTP tasks, they work when the DHCP gives the address
+    #[cfg(feature = "wifi")]
+    {
+        info!("Starting Wi-Fi, MQTT and HTTP tasks");
+        let stack = modular::start_wifi(spawner, wifi_power, wifi_spi).await;
+        unwrap!(spawner.spawn(modular::mqtt_task(stack)));
+        unwrap!(spawner.spawn(modular::http_task(stack)));
+        Timer::after_millis(100).await; // Small delay to let the Wi-Fi tasks start properly
+    }
 
 
 }
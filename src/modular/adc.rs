@@ -15,9 +15,12 @@
  *
  */
 
+use core::cell::Cell;
+
 use defmt::*; // For logging via RTT
 use embassy_rp::adc::{Adc, Async, Channel};
-use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::mutex::Mutex;
 use embassy_sync::watch::{DynReceiver, Watch};
 use embassy_time::Timer;
@@ -29,7 +32,7 @@ use {defmt_rtt as _, panic_probe as _}; // RTT logging and panic handler
 
 // Development of a ring buffer using heapless and embassy_sync
 pub struct HeaplessMutexRingBuffer {
-    mutex: Mutex<ThreadModeRawMutex, HistoryBuf<u16, 16>>,
+    mutex: Mutex<CriticalSectionRawMutex, HistoryBuf<u16, 16>>,
 }
 
 impl HeaplessMutexRingBuffer {
@@ -70,15 +73,17 @@ impl HeaplessMutexRingBuffer {
 }
 
 
-const ADC0_CONSUMERS: usize = 3;
-static ADC0_CHANNEL: Watch<ThreadModeRawMutex, u16, ADC0_CONSUMERS> = Watch::new();
+// The ADC is read in the interrupt executor of the control loop, so its channels are locked with a
+// critical section, a ThreadModeRawMutex only works in the thread mode
+const ADC0_CONSUMERS: usize = 4;
+static ADC0_CHANNEL: Watch<CriticalSectionRawMutex, u16, ADC0_CONSUMERS> = Watch::new();
 
 pub fn get_receiver_adc0() -> Option<DynReceiver<'static, u16>> {
     ADC0_CHANNEL.dyn_receiver()
 }
 /*
 const ADC1_CONSUMERS: usize = 1;
-static ADC1_CHANNEL: Watch<ThreadModeRawMutex, u16, ADC1_CONSUMERS> = Watch::new();
+static ADC1_CHANNEL: Watch<CriticalSectionRawMutex, u16, ADC1_CONSUMERS> = Watch::new();
 
 pub fn get_receiver_adc1() -> Option<DynReceiver<'static, u16>> {
     ADC1_CHANNEL.dyn_receiver()
@@ -87,7 +92,7 @@ pub fn get_receiver_adc1() -> Option<DynReceiver<'static, u16>> {
 
 /*
 const ADC2_CONSUMERS: usize = 1;
-static ADC2_CHANNEL: Watch<ThreadModeRawMutex, u16, ADC2_CONSUMERS> = Watch::new();
+static ADC2_CHANNEL: Watch<CriticalSectionRawMutex, u16, ADC2_CONSUMERS> = Watch::new();
 
 pub fn get_receiver_adc2() -> Option<DynReceiver<'static, u16>> {
     ADC2_CHANNEL.dyn_receiver()
@@ -95,17 +100,34 @@ pub fn get_receiver_adc2() -> Option<DynReceiver<'static, u16>> {
 */
 
 const ADCTEMP_CONSUMERS: usize = 2;
-static ADCTEMP_CHANNEL: Watch<ThreadModeRawMutex, u16, ADCTEMP_CONSUMERS> = Watch::new();
+static ADCTEMP_CHANNEL: Watch<CriticalSectionRawMutex, u16, ADCTEMP_CONSUMERS> = Watch::new();
 
 pub fn get_receiver_adctemp() -> Option<DynReceiver<'static, u16>> {
     ADCTEMP_CHANNEL.dyn_receiver()
 }
 
+// Calibration offset of the temperature of the die, loaded from the configuration
+static DIE_TEMP_OFFSET_C: BlockingMutex<CriticalSectionRawMutex, Cell<f32>> = BlockingMutex::new(Cell::new(0.0));
+
+pub fn die_temp_offset_c() -> f32 {
+    DIE_TEMP_OFFSET_C.lock(|offset| offset.get())
+}
+
+pub fn set_die_temp_offset_c(offset_c: f32) {
+    DIE_TEMP_OFFSET_C.lock(|cell| cell.set(offset_c));
+}
+
+// Convert the raw value of the ADC3 into the temperature of the die, formula from the RP2040 datasheet
+pub fn die_raw_to_celsius(raw: u16) -> f32 {
+    let voltage = raw as f32 * 3.3 / 4096.0;
+    27.0 - (voltage - 0.706) / 0.001721 + die_temp_offset_c()
+}
+
 // This task is used to read all the ADC channels regarding the RPI Pico W, where ADC0-ADC2 can be used to measure anything from 0 to 3.3V.
 // ADC3 is used to measure the temperature die of the RP2040 or RP2350.
 #[embassy_executor::task]
 pub async fn read_adc_channels(
-    adc_mutex: &'static Mutex<ThreadModeRawMutex,
+    adc_mutex: &'static Mutex<CriticalSectionRawMutex,
     Adc<'static, Async>>,
     mut chan_0: Channel<'static>, // Ref Temp
     //mut chan_1: Channel<'static>,
@@ -182,8 +204,7 @@ pub async fn read_adc_channels(
 
         match result_adc_temp {
             Ok(raw) => {
-                let voltage = raw as f32 * 3.3 / 4096.0;
-                let temp = 27.0 - (voltage - 0.706) / 0.001721;
+                let temp = die_raw_to_celsius(raw);
                 info!("Temp Die: {} °C (raw: {})", temp, raw);
                 tx_adctemp.send(raw);
             }
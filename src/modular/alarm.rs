@@ -0,0 +1,530 @@
+// Alarm file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Alarm file for the modular project.
+ *  File        : alarm.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the fault flags of the system and the alarms of the operator.
+ *      The trip limits of the process variable (trip-high and trip-low) raise faults that are latched
+ *      until they are acknowledged (button, command "ack" or Modbus coil), the saturation of the output
+ *      is only reported while it happens. The trip limits are set by "set trip <low|high>", the menu and
+ *      the Modbus. While a latched fault is set the heater is held at 0 %.
+ *
+ *      Besides the faults, the alarm engine warns the operator before a fault: PV high, PV low,
+ *      deviation from the SP, sensor out of range and die too hot. Each alarm has its own threshold,
+ *      hysteresis, delay-on and can be latching or self-clearing:
+ *
+ *      normal -> pending    the condition is present, waiting the delay
+ *      pending -> active    the condition lasted the delay (or the delay is 0)
+ *      active -> acked      acknowledged by the button or the command "ack"
+ *      active -> normal     the condition cleared (threshold - hysteresis) in a self-clearing alarm
+ *      active -> latched    the condition cleared in a latching alarm, it waits the acknowledge
+ *      acked -> normal      the condition cleared
+ *      latched -> normal    acknowledged
+ *
+ *      The settings of the alarms are saved in the configuration by the command "save" and by the item
+ *      "Save" of the menu, they are applied in the boot with the other values of the configuration.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::cell::Cell;
+
+use defmt::Format;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, ThreadModeRawMutex};
+use portable_atomic::{AtomicBool, AtomicU16, Ordering};
+
+// Bits of the fault flags, the same values are sent in the telemetry and in the Modbus
+pub const FAULT_OVER_TEMP: u16 = 1 << 0; // Die above the limit of the chip
+pub const FAULT_OUTPUT_SATURATED: u16 = 1 << 1; // PID output stuck in a limit
+pub const FAULT_TRIP_HIGH: u16 = 1 << 2; // PV above the high trip limit
+pub const FAULT_TRIP_LOW: u16 = 1 << 3; // PV below the low trip limit
+const LATCHED_FAULTS: u16 = FAULT_OVER_TEMP | FAULT_TRIP_HIGH | FAULT_TRIP_LOW;
+
+pub const FAULT_NAMES: [(u16, &str); 4] = [
+    (FAULT_OVER_TEMP, "over-temp"),
+    (FAULT_OUTPUT_SATURATED, "saturated"),
+    (FAULT_TRIP_HIGH, "trip-high"),
+    (FAULT_TRIP_LOW, "trip-low"),
+];
+
+// The temperature of the die should never rise up to 75°C, because this is the limit of the chip
+pub const DIE_TEMP_LIMIT_C: f32 = 75.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct TripLimits {
+    pub high_c: f32,
+    pub low_c: f32,
+}
+
+pub const DEFAULT_TRIP_LIMITS: TripLimits = TripLimits {
+    high_c: 35.0,
+    low_c: 18.0,
+};
+
+static TRIP_LIMITS: Mutex<CriticalSectionRawMutex, Cell<TripLimits>> = Mutex::new(Cell::new(DEFAULT_TRIP_LIMITS));
+static LATCHED: AtomicU16 = AtomicU16::new(0);
+
+pub fn trip_limits() -> TripLimits {
+    TRIP_LIMITS.lock(|limits| limits.get())
+}
+
+pub fn set_trip_limits(limits: TripLimits) {
+    TRIP_LIMITS.lock(|cell| cell.set(limits));
+}
+
+// Clear the latched faults, they come back in the next step if the condition is still present
+pub fn reset_faults() {
+    LATCHED.store(0, Ordering::Relaxed);
+}
+
+// Evaluate the faults after a step of the controller and return the current flags, the over
+// temperature is always checked in the die even when the process variable comes from another sensor
+pub fn evaluate_faults(pv: f32, die_c: f32, saturated: bool) -> u16 {
+    let limits = trip_limits();
+    let mut faults = 0;
+
+    if die_c >= DIE_TEMP_LIMIT_C {
+        faults |= FAULT_OVER_TEMP;
+    }
+    if pv > limits.high_c {
+        faults |= FAULT_TRIP_HIGH;
+    }
+    if pv < limits.low_c {
+        faults |= FAULT_TRIP_LOW;
+    }
+    if saturated {
+        faults |= FAULT_OUTPUT_SATURATED;
+    }
+    LATCHED.fetch_or(faults & LATCHED_FAULTS, Ordering::Relaxed) | faults
+}
+
+// Range of every temperature limit, the trip limits and the thresholds of the alarms, the same in the
+// console, the menu, the Modbus and the configuration. It is the operating range of the RP2040.
+pub const LIMIT_MIN_C: f32 = -40.0;
+pub const LIMIT_MAX_C: f32 = 85.0;
+
+// The heater is cut while a latched fault is set, a PV far below the low trip is more often a probe out of
+// the enclosure than a cold enclosure, so the heat is cut as well
+pub fn interlock_output(output: f32, faults: u16) -> f32 {
+    if faults & LATCHED_FAULTS != 0 { 0.0 } else { output }
+}
+
+// Ranges accepted for the other settings of the alarms
+pub const ALARM_HYSTERESIS_MAX_C: f32 = 10.0;
+pub const ALARM_DELAY_MAX_S: u32 = 3_600;
+
+pub const ALARM_COUNT: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum AlarmId {
+    PvHigh,
+    PvLow,
+    Deviation,   // Distance between the PV and the SP
+    SensorRange, // PV outside the values a working sensor can read in the enclosure
+    DieHot,      // Die above its warning, before the over-temp fault
+}
+
+impl AlarmId {
+    pub const ALL: [AlarmId; ALARM_COUNT] = [
+        AlarmId::PvHigh,
+        AlarmId::PvLow,
+        AlarmId::Deviation,
+        AlarmId::SensorRange,
+        AlarmId::DieHot,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AlarmId::PvHigh => "pv-high",
+            AlarmId::PvLow => "pv-low",
+            AlarmId::Deviation => "deviation",
+            AlarmId::SensorRange => "sensor",
+            AlarmId::DieHot => "die-hot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<AlarmId> {
+        AlarmId::ALL.iter().find(|id| name.eq_ignore_ascii_case(id.name())).copied()
+    }
+
+    // Bit of the alarm in the telemetry
+    pub fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Format, Debug)]
+pub enum Threshold {
+    Above(f32),
+    Below(f32),
+    Outside(f32, f32), // Low and high
+}
+
+impl Threshold {
+    // The margin is the hysteresis while the alarm stands, so it clears inside the threshold
+    fn exceeded(self, value: f32, margin: f32) -> bool {
+        match self {
+            Threshold::Above(limit) => value > limit - margin,
+            Threshold::Below(limit) => value < limit + margin,
+            // A NaN of a broken sensor is outside any range
+            Threshold::Outside(low, high) => !(low + margin..=high - margin).contains(&value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Format, Debug)]
+pub struct AlarmSettings {
+    pub enabled: bool,
+    pub threshold: Threshold,
+    pub hysteresis_c: f32,
+    pub delay_s: u32,   // Time the condition must last before the alarm
+    pub latching: bool, // Stays until acknowledged, even after the condition clears
+}
+
+pub const DEFAULT_ALARM_SETTINGS: [AlarmSettings; ALARM_COUNT] = [
+    AlarmSettings {
+        enabled: true,
+        threshold: Threshold::Above(32.0),
+        hysteresis_c: 0.5,
+        delay_s: 30,
+        latching: false,
+    },
+    AlarmSettings {
+        enabled: true,
+        threshold: Threshold::Below(22.0),
+        hysteresis_c: 0.5,
+        delay_s: 30,
+        latching: false,
+    },
+    // A change of the setpoint takes minutes to be followed, the long delay avoids the warning
+    AlarmSettings {
+        enabled: true,
+        threshold: Threshold::Above(3.0),
+        hysteresis_c: 0.5,
+        delay_s: 600,
+        latching: false,
+    },
+    AlarmSettings {
+        enabled: true,
+        threshold: Threshold::Outside(0.0, 60.0),
+        hysteresis_c: 0.0,
+        delay_s: 5,
+        latching: true,
+    },
+    AlarmSettings {
+        enabled: true,
+        threshold: Threshold::Above(60.0),
+        hysteresis_c: 2.0,
+        delay_s: 10,
+        latching: true,
+    },
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum AlarmState {
+    Normal,
+    Pending,      // Condition present, waiting the delay
+    Active,       // Not acknowledged yet
+    Acknowledged, // Condition still present
+    Latched,      // Condition cleared, waiting the acknowledge
+}
+
+impl AlarmState {
+    pub fn name(self) -> &'static str {
+        match self {
+            AlarmState::Normal => "normal",
+            AlarmState::Pending => "pending",
+            AlarmState::Active => "active",
+            AlarmState::Acknowledged => "acked",
+            AlarmState::Latched => "latched",
+        }
+    }
+}
+
+// Values checked by the alarms
+#[derive(Clone, Copy, PartialEq)]
+pub struct AlarmInputs {
+    pub pv: f32,
+    pub sp: f32,
+    pub die_c: f32,
+}
+
+impl AlarmInputs {
+    fn value(&self, id: AlarmId) -> f32 {
+        match id {
+            AlarmId::PvHigh | AlarmId::PvLow | AlarmId::SensorRange => self.pv,
+            AlarmId::Deviation => (self.pv - self.sp).abs(),
+            AlarmId::DieHot => self.die_c,
+        }
+    }
+}
+
+// States of all the alarms, published to the display, the console and the telemetry
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AlarmStatus {
+    pub states: [AlarmState; ALARM_COUNT],
+}
+
+impl AlarmStatus {
+    pub const fn new() -> Self {
+        Self {
+            states: [AlarmState::Normal; ALARM_COUNT],
+        }
+    }
+
+    pub fn state(&self, id: AlarmId) -> AlarmState {
+        self.states[id as usize]
+    }
+
+    fn bits(&self, states: &[AlarmState]) -> u16 {
+        AlarmId::ALL
+            .iter()
+            .filter(|&&id| states.contains(&self.state(id)))
+            .fold(0, |bits, id| bits | id.bit())
+    }
+
+    // Alarms with the condition present, acknowledged or not
+    pub fn active(&self) -> u16 {
+        self.bits(&[AlarmState::Active, AlarmState::Acknowledged])
+    }
+
+    // Alarms waiting the acknowledge of the operator
+    pub fn unacknowledged(&self) -> u16 {
+        self.bits(&[AlarmState::Active, AlarmState::Latched])
+    }
+}
+
+pub struct AlarmEngine {
+    status: AlarmStatus,
+    pending_since_s: [u32; ALARM_COUNT],
+}
+
+impl AlarmEngine {
+    pub const fn new() -> Self {
+        Self {
+            status: AlarmStatus::new(),
+            pending_since_s: [0; ALARM_COUNT],
+        }
+    }
+
+    pub fn status(&self) -> AlarmStatus {
+        self.status
+    }
+
+    // Check the inputs against the settings, the time is in seconds since the boot
+    pub fn evaluate(&mut self, now_s: u32, inputs: &AlarmInputs, settings: &[AlarmSettings; ALARM_COUNT]) {
+        for id in AlarmId::ALL {
+            let index = id as usize;
+            let settings = settings[index];
+            let state = self.status.states[index];
+            if !settings.enabled {
+                self.status.states[index] = AlarmState::Normal;
+                continue;
+            }
+
+            let value = inputs.value(id);
+            let standing = matches!(state, AlarmState::Active | AlarmState::Acknowledged);
+            let margin = if standing { settings.hysteresis_c } else { 0.0 };
+            let present = settings.threshold.exceeded(value, margin);
+
+            self.status.states[index] = match (state, present) {
+                (AlarmState::Normal, true) => {
+                    self.pending_since_s[index] = now_s;
+                    if settings.delay_s == 0 { AlarmState::Active } else { AlarmState::Pending }
+                }
+                (AlarmState::Pending, true) if now_s.wrapping_sub(self.pending_since_s[index]) >= settings.delay_s => {
+                    AlarmState::Active
+                }
+                (AlarmState::Pending, false) => AlarmState::Normal,
+                (AlarmState::Active, false) if settings.latching => AlarmState::Latched,
+                (AlarmState::Active | AlarmState::Acknowledged, false) => AlarmState::Normal,
+                // The condition came back before the acknowledge
+                (AlarmState::Latched, true) => AlarmState::Active,
+                (state, _) => state,
+            };
+        }
+    }
+
+    // Acknowledge all the alarms, returns false when there was nothing to acknowledge
+    pub fn acknowledge(&mut self) -> bool {
+        let unacknowledged = self.status.unacknowledged() != 0;
+        for state in self.status.states.iter_mut() {
+            *state = match *state {
+                AlarmState::Active => AlarmState::Acknowledged,
+                AlarmState::Latched => AlarmState::Normal,
+                state => state,
+            };
+        }
+        unacknowledged
+    }
+}
+
+static ALARM_SETTINGS: Mutex<ThreadModeRawMutex, Cell<[AlarmSettings; ALARM_COUNT]>> =
+    Mutex::new(Cell::new(DEFAULT_ALARM_SETTINGS));
+static ACKNOWLEDGE: AtomicBool = AtomicBool::new(false);
+
+pub fn alarm_settings() -> [AlarmSettings; ALARM_COUNT] {
+    ALARM_SETTINGS.lock(|settings| settings.get())
+}
+
+pub fn set_alarm_settings(id: AlarmId, settings: AlarmSettings) {
+    ALARM_SETTINGS.lock(|cell| {
+        let mut all = cell.get();
+        all[id as usize] = settings;
+        cell.set(all);
+    });
+}
+
+// Request the acknowledge of the alarms, it is done in the next cycle of the alarm task, the latched
+// faults are cleared now and the heater runs again if their conditions are gone
+pub fn acknowledge_alarms() {
+    ACKNOWLEDGE.store(true, Ordering::Relaxed);
+    reset_faults();
+}
+
+pub fn take_acknowledge() -> bool {
+    ACKNOWLEDGE.swap(false, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(pv: f32) -> AlarmInputs {
+        AlarmInputs { pv, sp: pv, die_c: 30.0 }
+    }
+
+    // PV high at 32 °C with 0.5 °C of hysteresis and 30 s of delay, the other alarms disabled
+    fn pv_high(latching: bool) -> [AlarmSettings; ALARM_COUNT] {
+        let mut settings = DEFAULT_ALARM_SETTINGS.map(|settings| AlarmSettings { enabled: false, ..settings });
+        settings[AlarmId::PvHigh as usize] = AlarmSettings { latching, ..DEFAULT_ALARM_SETTINGS[0] };
+        settings
+    }
+
+    #[test]
+    fn alarm_waits_the_delay_and_clears_with_the_hysteresis() {
+        let settings = pv_high(false);
+        let mut engine = AlarmEngine::new();
+
+        engine.evaluate(100, &inputs(32.5), &settings);
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Pending);
+        engine.evaluate(129, &inputs(32.5), &settings);
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Pending);
+        engine.evaluate(130, &inputs(32.5), &settings);
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Active);
+        assert_eq!(engine.status().unacknowledged(), AlarmId::PvHigh.bit());
+
+        // Below the threshold but inside the hysteresis it stands
+        engine.evaluate(131, &inputs(31.8), &settings);
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Active);
+        engine.evaluate(132, &inputs(31.5), &settings);
+        assert_eq!(engine.status(), AlarmStatus::new());
+    }
+
+    #[test]
+    fn condition_gone_before_the_delay_is_not_an_alarm() {
+        let settings = pv_high(false);
+        let mut engine = AlarmEngine::new();
+
+        engine.evaluate(0, &inputs(33.0), &settings);
+        engine.evaluate(10, &inputs(31.0), &settings);
+        engine.evaluate(40, &inputs(33.0), &settings);
+        // The delay starts again
+        engine.evaluate(60, &inputs(33.0), &settings);
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Pending);
+    }
+
+    #[test]
+    fn acknowledge_of_a_standing_and_of_a_latched_alarm() {
+        let mut engine = AlarmEngine::new();
+        let settings = pv_high(false);
+        engine.evaluate(0, &inputs(33.0), &settings);
+        engine.evaluate(30, &inputs(33.0), &settings);
+
+        assert!(engine.acknowledge());
+        assert!(!engine.acknowledge());
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Acknowledged);
+        assert_eq!(engine.status().active(), AlarmId::PvHigh.bit());
+        assert_eq!(engine.status().unacknowledged(), 0);
+
+        // A latching alarm waits the acknowledge after the condition clears
+        let mut engine = AlarmEngine::new();
+        let settings = pv_high(true);
+        engine.evaluate(0, &inputs(33.0), &settings);
+        engine.evaluate(30, &inputs(33.0), &settings);
+        engine.evaluate(31, &inputs(25.0), &settings);
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Latched);
+        assert_eq!(engine.status().active(), 0);
+        // The condition back before the acknowledge makes it active again
+        engine.evaluate(32, &inputs(33.0), &settings);
+        assert_eq!(engine.status().state(AlarmId::PvHigh), AlarmState::Active);
+        engine.evaluate(33, &inputs(25.0), &settings);
+        assert!(engine.acknowledge());
+        assert_eq!(engine.status(), AlarmStatus::new());
+    }
+
+    #[test]
+    fn range_and_deviation_alarms() {
+        let mut settings = DEFAULT_ALARM_SETTINGS.map(|settings| AlarmSettings { delay_s: 0, ..settings });
+        let mut engine = AlarmEngine::new();
+
+        // A NaN of a broken sensor is outside the range
+        engine.evaluate(0, &AlarmInputs { pv: f32::NAN, sp: 28.0, die_c: 30.0 }, &settings);
+        assert_eq!(engine.status().state(AlarmId::SensorRange), AlarmState::Active);
+
+        let mut engine = AlarmEngine::new();
+        engine.evaluate(0, &AlarmInputs { pv: 24.0, sp: 28.0, die_c: 30.0 }, &settings);
+        assert_eq!(engine.status().state(AlarmId::Deviation), AlarmState::Active);
+        assert_eq!(engine.status().state(AlarmId::PvLow), AlarmState::Normal);
+
+        // A disabled alarm goes back to normal
+        settings[AlarmId::Deviation as usize].enabled = false;
+        engine.evaluate(1, &AlarmInputs { pv: 24.0, sp: 28.0, die_c: 30.0 }, &settings);
+        assert_eq!(engine.status().state(AlarmId::Deviation), AlarmState::Normal);
+    }
+
+    #[test]
+    fn latched_fault_holds_the_heater_at_zero() {
+        assert_eq!(interlock_output(65.0, 0), 65.0);
+        assert_eq!(interlock_output(65.0, FAULT_OUTPUT_SATURATED), 65.0);
+        for fault in [FAULT_OVER_TEMP, FAULT_TRIP_HIGH, FAULT_TRIP_LOW] {
+            assert_eq!(interlock_output(65.0, fault | FAULT_OUTPUT_SATURATED), 0.0);
+        }
+    }
+
+    // One test only, the latched faults are shared by the whole module
+    #[test]
+    fn over_temp_comes_from_the_die_and_is_latched() {
+        reset_faults();
+
+        assert_eq!(evaluate_faults(30.0, 30.0, false), 0);
+        // A hot process variable from a probe does not trip the limit of the chip
+        assert_eq!(evaluate_faults(80.0, 30.0, false), FAULT_TRIP_HIGH);
+
+        let faults = evaluate_faults(30.0, DIE_TEMP_LIMIT_C, true);
+        assert_eq!(faults, FAULT_OVER_TEMP | FAULT_TRIP_HIGH | FAULT_OUTPUT_SATURATED);
+        // The latched faults stay until they are cleared, the saturation does not latch
+        let faults = evaluate_faults(30.0, 30.0, false);
+        assert_eq!(faults, FAULT_OVER_TEMP | FAULT_TRIP_HIGH);
+        // The heater stays at 0 % while they are latched, even with the conditions gone
+        assert_eq!(interlock_output(80.0, faults), 0.0);
+
+        acknowledge_alarms();
+        assert!(take_acknowledge());
+        let faults = evaluate_faults(30.0, 30.0, false);
+        assert_eq!(faults, 0);
+        assert_eq!(interlock_output(80.0, faults), 80.0);
+
+        // The saturation alone does not cut the heater, a cold PV below the low trip does
+        assert_eq!(interlock_output(100.0, evaluate_faults(30.0, 30.0, true)), 100.0);
+        assert_eq!(interlock_output(100.0, evaluate_faults(5.0, 30.0, true)), 0.0);
+        reset_faults();
+    }
+}
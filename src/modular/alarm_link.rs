@@ -0,0 +1,124 @@
+// Alarm link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Alarm link file for the modular project.
+ *  File        : alarm_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about to run the alarm engine of the alarm.rs after each step of the
+ *      control loop, with the PV, the SP and the temperature of the die. The states are published to
+ *      the display, the console and the telemetry, and a new alarm shows the alarms page.
+ *
+ *      Outputs, both active high:
+ *      GP8 LED     blinks while an alarm is not acknowledged, steady while an acknowledged one stands
+ *      GP9 buzzer  beeps once a second while an alarm is not acknowledged
+ *
+ *      The button in the GP7 (pressed to the GND) or the console command "ack" acknowledges the alarms.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_rp::gpio::{Input, Output};
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::{Instant, Timer};
+
+use crate::modular::alarm::{
+    AlarmEngine, AlarmId, AlarmInputs, AlarmState, AlarmStatus, acknowledge_alarms, alarm_settings, take_acknowledge,
+};
+use crate::modular::oled::get_sender_page;
+use crate::modular::pwm::get_receiver_control_status;
+use crate::modular::screen::{NavEvent, Page};
+
+const ALARM_TICK_MS: u64 = 250;
+const BUZZER_TICKS: u32 = 4; // One beep of one tick every 4 ticks
+const BUTTON_DEBOUNCE_MS: u64 = 20;
+
+const ALARM_STATUS_CONSUMERS: usize = 3; // OLED, console and telemetry
+static ALARM_STATUS_CHANNEL: Watch<ThreadModeRawMutex, AlarmStatus, ALARM_STATUS_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_alarm_status() -> Option<DynReceiver<'static, AlarmStatus>> {
+    ALARM_STATUS_CHANNEL.dyn_receiver()
+}
+
+fn log_changes(before: &AlarmStatus, after: &AlarmStatus) {
+    for id in AlarmId::ALL {
+        let state = after.state(id);
+        if state == before.state(id) {
+            continue;
+        }
+        match state {
+            AlarmState::Active => warn!("Alarm {} active", id.name()),
+            _ => info!("Alarm {} {}", id.name(), state.name()),
+        }
+    }
+}
+
+// This task evaluates the alarms and drives the LED and the buzzer
+#[embassy_executor::task]
+pub async fn alarm_task(mut led: Output<'static>, mut buzzer: Output<'static>) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let tx_alarms = ALARM_STATUS_CHANNEL.sender();
+    let tx_page = get_sender_page();
+    let mut engine = AlarmEngine::new();
+    let mut tick: u32 = 0;
+    tx_alarms.send(engine.status());
+
+    loop {
+        let before = engine.status();
+        if take_acknowledge() && engine.acknowledge() {
+            info!("Alarms acknowledged");
+        }
+        // The alarms are checked after each step of the control loop
+        if let Some(status) = rx_status.try_changed() {
+            let inputs = AlarmInputs {
+                pv: status.pv,
+                sp: status.setpoint.celsius,
+                die_c: status.die_c,
+            };
+            engine.evaluate(Instant::now().as_secs() as u32, &inputs, &alarm_settings());
+        }
+
+        let after = engine.status();
+        if after != before {
+            log_changes(&before, &after);
+            if after.active() & !before.active() & after.unacknowledged() != 0
+                && tx_page.try_send(NavEvent::Show(Page::Alarms)).is_err()
+            {
+                debug!("Alarms page not shown, the display is busy");
+            }
+            tx_alarms.send(after);
+        }
+
+        if after.unacknowledged() != 0 {
+            led.toggle();
+            buzzer.set_level(tick.is_multiple_of(BUZZER_TICKS).into());
+        } else {
+            led.set_level((after.active() != 0).into());
+            buzzer.set_low();
+        }
+        tick = tick.wrapping_add(1);
+
+        Timer::after_millis(ALARM_TICK_MS).await;
+    }
+}
+
+// This task reads the button of the acknowledge in the GP7
+#[embassy_executor::task]
+pub async fn alarm_button_task(mut button: Input<'static>) {
+    loop {
+        button.wait_for_falling_edge().await;
+        Timer::after_millis(BUTTON_DEBOUNCE_MS).await;
+        if button.is_high() {
+            continue; // Bounce
+        }
+        acknowledge_alarms();
+        button.wait_for_high().await;
+        Timer::after_millis(BUTTON_DEBOUNCE_MS).await;
+    }
+}
@@ -0,0 +1,307 @@
+// BME280 file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : BME280 file for the modular project.
+ *  File        : bme280.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the registers, the settings and the compensation of the BME280
+ *      (temperature, humidity and pressure), address 0x76 (SDO low). The calibration is parsed from the
+ *      two blocks of its memory and the compensation is the integer code of the datasheet.
+ *
+ *      The BME280 has no CRC, so a measurement is validated by the values that the sensor writes when
+ *      a measurement was skipped. The sensor is read by the bme280_link.rs.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::Format;
+
+pub const BME280_ADDRESS: u8 = 0x76;
+
+pub const CHIP_ID: u8 = 0x60;
+pub const REG_CALIBRATION_TP: u8 = 0x88; // 26 bytes, 0x88 up to 0xA1
+pub const REG_CHIP_ID: u8 = 0xD0;
+pub const REG_RESET: u8 = 0xE0;
+pub const REG_CALIBRATION_H: u8 = 0xE1; // 7 bytes, 0xE1 up to 0xE7
+pub const REG_CTRL_HUM: u8 = 0xF2;
+pub const REG_STATUS: u8 = 0xF3;
+pub const REG_CTRL_MEAS: u8 = 0xF4;
+pub const REG_CONFIG: u8 = 0xF5;
+pub const REG_DATA: u8 = 0xF7; // 8 bytes, pressure, temperature and humidity
+
+pub const RESET_WORD: u8 = 0xB6;
+pub const STATUS_IM_UPDATE: u8 = 0x01; // The calibration is being copied to the registers
+pub const MODE_FORCED: u8 = 0b01;
+pub const MODE_NORMAL: u8 = 0b11;
+pub const STANDBY_1000_MS: u8 = 0b101;
+
+// Values of the registers of a measurement that was skipped
+const SKIPPED_20_BITS: i32 = 0x80000;
+const SKIPPED_16_BITS: i32 = 0x8000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Format)]
+pub enum Oversampling {
+    X1,
+    #[allow(dead_code)] // Not chosen in the main.rs
+    X2,
+    #[allow(dead_code)]
+    X4,
+    #[allow(dead_code)]
+    X8,
+    #[allow(dead_code)]
+    X16,
+}
+
+impl Oversampling {
+    pub fn code(self) -> u8 {
+        self as u8 + 1
+    }
+
+    pub fn samples(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format)]
+pub enum Filter {
+    Off,
+    #[allow(dead_code)] // Not chosen in the main.rs
+    X2,
+    #[allow(dead_code)]
+    X4,
+    #[allow(dead_code)]
+    X8,
+    #[allow(dead_code)]
+    X16,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format)]
+pub enum MeasurementMode {
+    Forced, // One measurement for each read, the sensor sleeps between them
+    #[allow(dead_code)] // Not chosen in the main.rs
+    Normal, // Measurement every second, needed by the IIR filter
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format)]
+pub struct Bme280Config {
+    pub oversampling: Oversampling, // Same oversampling for the three values
+    pub filter: Filter,
+    pub mode: MeasurementMode,
+}
+
+impl Bme280Config {
+    // Maximum time of one measurement from the datasheet (appendix B), rounded up
+    pub fn measurement_ms(&self) -> u64 {
+        let samples = self.oversampling.samples();
+        let max_us = 1_250 + 2_300 * samples + 2 * (2_300 * samples + 575);
+        max_us.div_ceil(1_000) as u64
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Format)]
+pub enum Bme280Error {
+    Bus,        // NACK or another error of the I2C
+    ChipId(u8), // Another chip in the address, BMP280 is 0x58
+    NotReady,   // The calibration was not copied after the reset
+    Skipped,    // The registers have the value of a skipped measurement
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Format)]
+pub struct Bme280Reading {
+    pub temperature_c: f32,
+    pub humidity_pct: f32,
+    pub pressure_hpa: f32,
+}
+
+// Trimming parameters written in the factory, names of the datasheet
+#[derive(Clone, Copy)]
+pub struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+    h1: u8,
+    h2: i16,
+    h3: u8,
+    h4: i16,
+    h5: i16,
+    h6: i8,
+}
+
+impl Calibration {
+    pub fn parse(tp: &[u8; 26], h: &[u8; 7]) -> Self {
+        let u16_at = |offset: usize| u16::from_le_bytes([tp[offset], tp[offset + 1]]);
+        let i16_at = |offset: usize| i16::from_le_bytes([tp[offset], tp[offset + 1]]);
+        Self {
+            t1: u16_at(0),
+            t2: i16_at(2),
+            t3: i16_at(4),
+            p1: u16_at(6),
+            p2: i16_at(8),
+            p3: i16_at(10),
+            p4: i16_at(12),
+            p5: i16_at(14),
+            p6: i16_at(16),
+            p7: i16_at(18),
+            p8: i16_at(20),
+            p9: i16_at(22),
+            h1: tp[25],
+            h2: i16::from_le_bytes([h[0], h[1]]),
+            h3: h[2],
+            // Values of 12 bits that share the byte 0xE5
+            h4: ((h[3] as i8 as i16) << 4) | (h[4] & 0x0F) as i16,
+            h5: ((h[5] as i8 as i16) << 4) | (h[4] >> 4) as i16,
+            h6: h[6] as i8,
+        }
+    }
+
+    // Returns the temperature in 0.01 °C and the t_fine used by the other two
+    fn temperature(&self, adc_t: i32) -> (i32, i32) {
+        let t1 = self.t1 as i32;
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * self.t2 as i32) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * self.t3 as i32) >> 14;
+        let t_fine = var1 + var2;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    // Pressure in Pa as Q24.8
+    fn pressure(&self, adc_p: i32, t_fine: i32) -> u32 {
+        let mut var1 = t_fine as i64 - 128_000;
+        let mut var2 = var1 * var1 * self.p6 as i64;
+        var2 += (var1 * self.p5 as i64) << 17;
+        var2 += (self.p4 as i64) << 35;
+        var1 = ((var1 * var1 * self.p3 as i64) >> 8) + ((var1 * self.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.p1 as i64) >> 33;
+        if var1 == 0 {
+            return 0; // Avoid the division by zero
+        }
+        let mut p = 1_048_576 - adc_p as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        var1 = (self.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        var2 = (self.p8 as i64 * p) >> 19;
+        (((p + var1 + var2) >> 8) + ((self.p7 as i64) << 4)) as u32
+    }
+
+    // Humidity in % as Q22.10
+    fn humidity(&self, adc_h: i32, t_fine: i32) -> u32 {
+        let mut v = t_fine - 76_800;
+        v = ((((adc_h << 14) - ((self.h4 as i32) << 20) - (self.h5 as i32 * v)) + 16_384) >> 15)
+            * (((((((v * self.h6 as i32) >> 10) * (((v * self.h3 as i32) >> 11) + 32_768)) >> 10) + 2_097_152)
+                * self.h2 as i32
+                + 8_192)
+                >> 14);
+        v -= ((((v >> 15) * (v >> 15)) >> 7) * self.h1 as i32) >> 4;
+        (v.clamp(0, 419_430_400) >> 12) as u32
+    }
+
+    pub fn compensate(&self, data: &[u8; 8]) -> Result<Bme280Reading, Bme280Error> {
+        let adc_p = ((data[0] as i32) << 12) | ((data[1] as i32) << 4) | (data[2] as i32 >> 4);
+        let adc_t = ((data[3] as i32) << 12) | ((data[4] as i32) << 4) | (data[5] as i32 >> 4);
+        let adc_h = ((data[6] as i32) << 8) | data[7] as i32;
+        if adc_t == SKIPPED_20_BITS || adc_p == SKIPPED_20_BITS || adc_h == SKIPPED_16_BITS {
+            return Err(Bme280Error::Skipped);
+        }
+
+        let (temperature, t_fine) = self.temperature(adc_t);
+        Ok(Bme280Reading {
+            temperature_c: temperature as f32 / 100.0,
+            humidity_pct: self.humidity(adc_h, t_fine) as f32 / 1_024.0,
+            pressure_hpa: self.pressure(adc_p, t_fine) as f32 / 256.0 / 100.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimming of the example of the datasheet of Bosch (BMP280, 3.12) and humidity trimming of a BME280
+    const T: [i32; 3] = [27_504, 26_435, -1_000];
+    const P: [i32; 9] = [36_477, -10_685, 3_024, 2_855, 140, -7, 15_500, -14_600, 6_000];
+    const H: [i32; 6] = [75, 362, 0, 313, 50, 30];
+
+    fn calibration() -> Calibration {
+        let mut tp = [0u8; 26];
+        for (index, value) in T.iter().chain(P.iter()).enumerate() {
+            tp[2 * index..2 * index + 2].copy_from_slice(&(*value as u16).to_le_bytes());
+        }
+        tp[25] = H[0] as u8;
+        let h2 = (H[1] as i16).to_le_bytes();
+        let h = [h2[0], h2[1], H[2] as u8, (H[3] >> 4) as u8, ((H[3] & 0x0F) | ((H[4] & 0x0F) << 4)) as u8, (H[4] >> 4) as u8, H[5] as u8];
+        Calibration::parse(&tp, &h)
+    }
+
+    // Registers 0xF7 up to 0xFE for the raw values
+    fn data(adc_p: u32, adc_t: u32, adc_h: u16) -> [u8; 8] {
+        let [h_msb, h_lsb] = adc_h.to_be_bytes();
+        [(adc_p >> 12) as u8, (adc_p >> 4) as u8, (adc_p << 4) as u8, (adc_t >> 12) as u8, (adc_t >> 4) as u8, (adc_t << 4) as u8, h_msb, h_lsb]
+    }
+
+    // Floating point formula of the humidity of the datasheet of the BME280 (8.1)
+    fn humidity_reference(adc_h: f64, t_fine: f64) -> f64 {
+        let [h1, h2, h3, h4, h5, h6] = H.map(|value| value as f64);
+        let v = t_fine - 76_800.0;
+        let v = (adc_h - (h4 * 64.0 + h5 / 16_384.0 * v)) * (h2 / 65_536.0 * (1.0 + h6 / 67_108_864.0 * v * (1.0 + h3 / 67_108_864.0 * v)));
+        (v * (1.0 - h1 * v / 524_288.0)).clamp(0.0, 100.0)
+    }
+
+    #[test]
+    fn temperature_and_pressure_of_the_datasheet_example() {
+        let calibration = calibration();
+        assert_eq!(calibration.temperature(519_888), (2_508, 128_422));
+
+        // 100653.27 Pa with the floating point formula
+        let pressure_pa = calibration.pressure(415_148, 128_422) as f64 / 256.0;
+        assert!((pressure_pa - 100_653.27).abs() < 0.05, "{}", pressure_pa);
+    }
+
+    #[test]
+    fn humidity_follows_the_floating_point_formula() {
+        let calibration = calibration();
+        for adc_h in [27_000, 30_000, 35_000] {
+            let humidity_pct = calibration.humidity(adc_h, 128_422) as f64 / 1_024.0;
+            let reference = humidity_reference(adc_h as f64, 128_422.0);
+            assert!((humidity_pct - reference).abs() < 0.01, "{} {} {}", adc_h, humidity_pct, reference);
+        }
+        // Limited to 0 and 100 %
+        assert_eq!(calibration.humidity(0, 128_422), 0);
+        assert_eq!(calibration.humidity(65_535, 128_422), 100 * 1_024);
+    }
+
+    #[test]
+    fn registers_are_compensated_together() {
+        let reading = calibration().compensate(&data(415_148, 519_888, 30_000)).unwrap();
+
+        assert_eq!(reading.temperature_c, 25.08);
+        assert!((reading.pressure_hpa - 1006.5327).abs() < 0.001, "{:?}", reading);
+        assert!((reading.humidity_pct - 55.0).abs() < 0.01, "{:?}", reading);
+    }
+
+    #[test]
+    fn skipped_measurements_are_rejected() {
+        let calibration = calibration();
+        assert_eq!(calibration.compensate(&data(0x80000, 519_888, 30_000)), Err(Bme280Error::Skipped));
+        assert_eq!(calibration.compensate(&data(415_148, 0x80000, 30_000)), Err(Bme280Error::Skipped));
+        assert_eq!(calibration.compensate(&data(415_148, 519_888, 0x8000)), Err(Bme280Error::Skipped));
+    }
+
+    #[test]
+    fn negative_humidity_trimming_of_12_bits() {
+        // h4 and h5 share the byte 0xE5, the high bytes carry the sign
+        let calibration = Calibration::parse(&[0; 26], &[0, 0, 0, 0xFE, 0x3C, 0xFF, 0]);
+        assert_eq!((calibration.h4, calibration.h5), (-20, -13));
+    }
+}
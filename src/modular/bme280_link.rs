@@ -0,0 +1,180 @@
+// BME280 link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : BME280 link file for the modular project.
+ *  File        : bme280_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the BME280 (temperature, humidity and pressure) in the shared
+ *      I2C0 bus, address 0x76 (SDO low). In the start the chip id is checked, the sensor receives a
+ *      soft reset, the calibration is read from its memory and the oversampling, the filter and the
+ *      mode are written. In the forced mode each read starts one measurement and the task sleeps
+ *      during it, in the normal mode the sensor measures alone and the task only reads the result.
+ *
+ *      The BME280 has no CRC, so a measurement is validated by the chip id of the start and by the
+ *      values that the sensor writes when a measurement was skipped. The compensation is in the
+ *      bme280.rs. After some failed reads in a row the sensor is started again.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::Timer;
+use embedded_hal_async::i2c::I2c;
+
+use crate::modular::bme280::*;
+use crate::modular::i2c_bus::I2cBusDevice;
+
+pub const BME280_PERIOD_S: u64 = 2;
+const BME280_RESTART_AFTER: u8 = 3; // Failed reads in a row before the sensor is started again
+
+const BME280_CONSUMERS: usize = 2; // Control loop and console
+static BME280_CHANNEL: Watch<CriticalSectionRawMutex, Bme280Reading, BME280_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_bme280() -> Option<DynReceiver<'static, Bme280Reading>> {
+    BME280_CHANNEL.dyn_receiver()
+}
+
+pub struct Bme280<I> {
+    i2c: I,
+    address: u8,
+    config: Bme280Config,
+    calibration: Option<Calibration>, // None until the start works
+}
+
+impl<I: I2c> Bme280<I> {
+    pub fn new(i2c: I, address: u8, config: Bme280Config) -> Self {
+        Self {
+            i2c,
+            address,
+            config,
+            calibration: None,
+        }
+    }
+
+    async fn write_register(&mut self, register: u8, value: u8) -> Result<(), Bme280Error> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .await
+            .map_err(|_| Bme280Error::Bus)
+    }
+
+    async fn read_registers(&mut self, register: u8, data: &mut [u8]) -> Result<(), Bme280Error> {
+        self.i2c
+            .write_read(self.address, &[register], data)
+            .await
+            .map_err(|_| Bme280Error::Bus)
+    }
+
+    fn ctrl_meas(&self, mode: u8) -> u8 {
+        let oversampling = self.config.oversampling.code();
+        (oversampling << 5) | (oversampling << 2) | mode
+    }
+
+    // Soft reset, calibration and configuration, it is called again to recover the sensor
+    pub async fn start(&mut self) -> Result<(), Bme280Error> {
+        self.calibration = None;
+        self.calibration = Some(self.configure().await?);
+        Ok(())
+    }
+
+    async fn configure(&mut self) -> Result<Calibration, Bme280Error> {
+        let mut id = [0u8];
+        self.read_registers(REG_CHIP_ID, &mut id).await?;
+        if id[0] != CHIP_ID {
+            return Err(Bme280Error::ChipId(id[0]));
+        }
+
+        self.write_register(REG_RESET, RESET_WORD).await?;
+        Timer::after_millis(2).await;
+        let mut status = [STATUS_IM_UPDATE];
+        for _ in 0..10 {
+            self.read_registers(REG_STATUS, &mut status).await?;
+            if status[0] & STATUS_IM_UPDATE == 0 {
+                break;
+            }
+            Timer::after_millis(1).await;
+        }
+        if status[0] & STATUS_IM_UPDATE != 0 {
+            return Err(Bme280Error::NotReady);
+        }
+
+        let mut tp = [0u8; 26];
+        let mut h = [0u8; 7];
+        self.read_registers(REG_CALIBRATION_TP, &mut tp).await?;
+        self.read_registers(REG_CALIBRATION_H, &mut h).await?;
+
+        // The humidity control is only used after the next write of the ctrl_meas
+        self.write_register(REG_CTRL_HUM, self.config.oversampling.code()).await?;
+        self.write_register(REG_CONFIG, (STANDBY_1000_MS << 5) | ((self.config.filter as u8) << 2))
+            .await?;
+        let mode = match self.config.mode {
+            MeasurementMode::Forced => 0, // Sleep until the first read
+            MeasurementMode::Normal => MODE_NORMAL,
+        };
+        self.write_register(REG_CTRL_MEAS, self.ctrl_meas(mode)).await?;
+        Ok(Calibration::parse(&tp, &h))
+    }
+
+    pub async fn read(&mut self) -> Result<Bme280Reading, Bme280Error> {
+        let calibration = match self.calibration {
+            Some(calibration) => calibration,
+            None => {
+                self.start().await?;
+                self.calibration.ok_or(Bme280Error::NotReady)?
+            }
+        };
+        if self.config.mode == MeasurementMode::Forced {
+            self.write_register(REG_CTRL_MEAS, self.ctrl_meas(MODE_FORCED)).await?;
+            Timer::after_millis(self.config.measurement_ms()).await;
+        }
+
+        let mut data = [0u8; 8];
+        self.read_registers(REG_DATA, &mut data).await?;
+        calibration.compensate(&data)
+    }
+}
+
+// This task reads the BME280 and publishes the temperature, the humidity and the pressure
+#[embassy_executor::task]
+pub async fn bme280_task(mut sensor: Bme280<I2cBusDevice>) {
+    let tx_reading = BME280_CHANNEL.sender();
+    let mut failures: u8 = 0;
+
+    if let Err(e) = sensor.start().await {
+        warn!("BME280 start failed: {}", e);
+    }
+
+    loop {
+        match sensor.read().await {
+            Ok(reading) => {
+                debug!(
+                    "BME280: {} C {} % {} hPa",
+                    reading.temperature_c, reading.humidity_pct, reading.pressure_hpa
+                );
+                tx_reading.send(reading);
+                failures = 0;
+            }
+            // The last reading stays in the channel
+            Err(e) => {
+                warn!("BME280 read failed: {}", e);
+                failures += 1;
+                if failures >= BME280_RESTART_AFTER {
+                    warn!("BME280 restart after {} failed reads", failures);
+                    if let Err(e) = sensor.start().await {
+                        warn!("BME280 start failed: {}", e);
+                    }
+                    failures = 0;
+                }
+            }
+        }
+
+        Timer::after_secs(BME280_PERIOD_S).await;
+    }
+}
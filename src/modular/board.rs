@@ -0,0 +1,290 @@
+// Board file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Board file for the modular project.
+ *  File        : board.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the pinout of the boards, it gives each role of the system its
+ *      pin or peripheral so the main.rs does not use the pins by the number. The board is chosen with
+ *      one of the cargo features below, the Pico is the default:
+ *
+ *      board-pico      Raspberry Pi Pico (RP2040), status LED in the GP25
+ *      board-pico-w    Raspberry Pi Pico W (RP2040 and CYW43), the LED of the board is in the CYW43, so
+ *                      the status LED is an external LED in the GP27 (with a resistor to the GND)
+ *      board-pico2     Raspberry Pi Pico 2 (RP2350A), status LED in the GP25 and 4 MB of flash
+ *
+ *      Another board is built with "--no-default-features --features board-pico-w". The Pico 2 also
+ *      needs "--target thumbv8m.main-none-eabihf", the build.rs picks its memory_rp2350.x.
+ *
+ *      The pinout is the same in all the boards:
+ *
+ *      GP0         telemetry UART0 TX (feature telemetry-uart)
+ *      GP2         heater PWM (Slice1 channel A)
+ *      GP3         WS2812 status LED (feature status-ws2812)
+ *      GP4 / GP5   Modbus RTU UART1 TX / RX, GP6 driver enable of the RS-485
+ *      GP7         alarm acknowledge button, GP8 alarm LED, GP9 buzzer
+ *      GP10 - GP13 SD card SPI1 SCK / MOSI / MISO and chip select
+ *      GP14        mister relay
+ *      GP15        OLED page button
+ *      GP16        DS18B20 1-Wire bus (PIO1)
+ *      GP17 / GP18 rotary encoder A / B, GP19 encoder button
+ *      GP20 / GP21 I2C0 SDA / SCL (OLED, SHT3x and BME280)
+ *      GP22        DHT22 (PIO1)
+ *      GP26        ADC0 reference temperature or setpoint knob (potentiometer)
+ *
+ *      The GP23, GP24, GP25 and GP29 of the Pico W belong to the CYW43, and its PIO0 stays free for it.
+ *      With the feature wifi the CYW43 is driven by the state machine 0 of the PIO0 and the DMA channel 4:
+ *
+ *      GP23        power of the CYW43
+ *      GP24        data of the SPI (in and out)
+ *      GP25        chip select of the SPI
+ *      GP29        clock of the SPI
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use embassy_rp::Peri;
+use embassy_rp::peripherals::*;
+
+#[cfg(not(any(feature = "board-pico", feature = "board-pico-w", feature = "board-pico2")))]
+compile_error!("No board selected, enable one of the features board-pico, board-pico-w or board-pico2");
+
+#[cfg(any(
+    all(feature = "board-pico", feature = "board-pico-w"),
+    all(feature = "board-pico", feature = "board-pico2"),
+    all(feature = "board-pico-w", feature = "board-pico2"),
+))]
+compile_error!("Only one board can be selected, build the others with --no-default-features --features board-...");
+
+#[cfg(all(feature = "wifi", not(feature = "board-pico-w")))]
+compile_error!("The Wi-Fi is the CYW43 of the Pico W, build it with --no-default-features --features board-pico-w,wifi");
+
+#[cfg(all(any(feature = "board-pico", feature = "board-pico-w"), target_abi = "eabihf"))]
+compile_error!("The RP2040 is a Cortex-M0+, build it with --target thumbv6m-none-eabi");
+
+#[cfg(all(feature = "board-pico2", not(target_abi = "eabihf")))]
+compile_error!("The RP2350 is a Cortex-M33, build it with --target thumbv8m.main-none-eabihf");
+
+#[cfg(feature = "board-pico")]
+mod pico {
+    pub const BOARD_NAME: &str = "Raspberry Pi Pico";
+    pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+    // LED of the board, Slice4 channel B
+    #[cfg(not(feature = "status-ws2812"))]
+    pub type StatusLedGpio = embassy_rp::peripherals::PIN_25;
+    #[cfg(not(feature = "status-ws2812"))]
+    pub type StatusLedSlice = embassy_rp::peripherals::PWM_SLICE4;
+
+    pub fn reset_to_bootloader() {
+        embassy_rp::rom_data::reset_to_usb_boot(0, 0);
+    }
+}
+
+#[cfg(feature = "board-pico-w")]
+mod pico_w {
+    pub const BOARD_NAME: &str = "Raspberry Pi Pico W";
+    pub const FLASH_SIZE: usize = 2 * 1024 * 1024;
+
+    // External LED, Slice5 channel B
+    #[cfg(not(feature = "status-ws2812"))]
+    pub type StatusLedGpio = embassy_rp::peripherals::PIN_27;
+    #[cfg(not(feature = "status-ws2812"))]
+    pub type StatusLedSlice = embassy_rp::peripherals::PWM_SLICE5;
+
+    pub fn reset_to_bootloader() {
+        embassy_rp::rom_data::reset_to_usb_boot(0, 0);
+    }
+}
+
+#[cfg(feature = "board-pico2")]
+mod pico2 {
+    pub const BOARD_NAME: &str = "Raspberry Pi Pico 2";
+    pub const FLASH_SIZE: usize = 4 * 1024 * 1024;
+
+    // LED of the board, Slice4 channel B
+    #[cfg(not(feature = "status-ws2812"))]
+    pub type StatusLedGpio = embassy_rp::peripherals::PIN_25;
+    #[cfg(not(feature = "status-ws2812"))]
+    pub type StatusLedSlice = embassy_rp::peripherals::PWM_SLICE4;
+
+    const REBOOT_TYPE_BOOTSEL: u32 = 0x0002;
+    const REBOOT_DELAY_MS: u32 = 10;
+
+    pub fn reset_to_bootloader() {
+        embassy_rp::rom_data::reboot(REBOOT_TYPE_BOOTSEL, REBOOT_DELAY_MS, 0, 0);
+    }
+}
+
+#[cfg(feature = "board-pico")]
+pub use pico::*;
+#[cfg(feature = "board-pico-w")]
+pub use pico_w::*;
+#[cfg(feature = "board-pico2")]
+pub use pico2::*;
+
+// Pins and peripherals of each role of the system
+pub struct Board {
+    pub flash: Peri<'static, FLASH>,
+    pub usb: Peri<'static, USB>,
+
+    // Status LED, dimmed by the PWM or a WS2812 in the PIO1
+    #[cfg(not(feature = "status-ws2812"))]
+    pub status_led: Peri<'static, StatusLedGpio>,
+    #[cfg(not(feature = "status-ws2812"))]
+    pub status_led_pwm: Peri<'static, StatusLedSlice>,
+    #[cfg(feature = "status-ws2812")]
+    pub status_ws2812: Peri<'static, PIN_3>,
+    #[cfg(feature = "status-ws2812")]
+    pub status_ws2812_dma: Peri<'static, DMA_CH3>,
+
+    // I2C0 of the OLED and the sensors
+    pub i2c: Peri<'static, I2C0>,
+    pub i2c_sda: Peri<'static, PIN_20>,
+    pub i2c_scl: Peri<'static, PIN_21>,
+
+    // ADC of the reference temperature (or the knob of the setpoint) and the temperature of the die
+    pub adc: Peri<'static, ADC>,
+    pub adc_temp_sensor: Peri<'static, ADC_TEMP_SENSOR>,
+    pub ref_temp: Peri<'static, PIN_26>,
+
+    // PIO1 of the DHT and the DS18B20
+    pub pio: Peri<'static, PIO1>,
+    pub dht: Peri<'static, PIN_22>,
+    pub ds18b20: Peri<'static, PIN_16>,
+
+    // Heater and mister
+    pub heater_pwm: Peri<'static, PWM_SLICE1>,
+    pub heater: Peri<'static, PIN_2>,
+    pub mister_relay: Peri<'static, PIN_14>,
+
+    // Buttons, encoder and alarm outputs
+    pub page_button: Peri<'static, PIN_15>,
+    pub alarm_button: Peri<'static, PIN_7>,
+    pub alarm_led: Peri<'static, PIN_8>,
+    pub buzzer: Peri<'static, PIN_9>,
+    pub encoder_a: Peri<'static, PIN_17>,
+    pub encoder_b: Peri<'static, PIN_18>,
+    pub encoder_button: Peri<'static, PIN_19>,
+
+    // Telemetry in the UART0 TX
+    #[cfg(feature = "telemetry-uart")]
+    pub telemetry_uart: Peri<'static, UART0>,
+    #[cfg(feature = "telemetry-uart")]
+    pub telemetry_tx: Peri<'static, PIN_0>,
+    #[cfg(feature = "telemetry-uart")]
+    pub telemetry_dma: Peri<'static, DMA_CH0>,
+
+    // Modbus RTU in the UART1 and the RS-485 driver enable
+    pub modbus_uart: Peri<'static, UART1>,
+    pub modbus_tx: Peri<'static, PIN_4>,
+    pub modbus_rx: Peri<'static, PIN_5>,
+    pub modbus_de: Peri<'static, PIN_6>,
+
+    // SD card in the SPI1
+    pub sd_spi: Peri<'static, SPI1>,
+    pub sd_sck: Peri<'static, PIN_10>,
+    pub sd_mosi: Peri<'static, PIN_11>,
+    pub sd_miso: Peri<'static, PIN_12>,
+    pub sd_cs: Peri<'static, PIN_13>,
+    pub sd_tx_dma: Peri<'static, DMA_CH1>,
+    pub sd_rx_dma: Peri<'static, DMA_CH2>,
+
+    // CYW43 of the Pico W in the PIO0
+    #[cfg(feature = "wifi")]
+    pub wifi_pio: Peri<'static, PIO0>,
+    #[cfg(feature = "wifi")]
+    pub wifi_power: Peri<'static, PIN_23>,
+    #[cfg(feature = "wifi")]
+    pub wifi_dio: Peri<'static, PIN_24>,
+    #[cfg(feature = "wifi")]
+    pub wifi_cs: Peri<'static, PIN_25>,
+    #[cfg(feature = "wifi")]
+    pub wifi_clk: Peri<'static, PIN_29>,
+    #[cfg(feature = "wifi")]
+    pub wifi_dma: Peri<'static, DMA_CH4>,
+}
+
+impl Board {
+    pub fn new(p: embassy_rp::Peripherals) -> Self {
+        Self {
+            flash: p.FLASH,
+            usb: p.USB,
+
+            #[cfg(all(not(feature = "status-ws2812"), any(feature = "board-pico", feature = "board-pico2")))]
+            status_led: p.PIN_25,
+            #[cfg(all(not(feature = "status-ws2812"), any(feature = "board-pico", feature = "board-pico2")))]
+            status_led_pwm: p.PWM_SLICE4,
+            #[cfg(all(not(feature = "status-ws2812"), feature = "board-pico-w"))]
+            status_led: p.PIN_27,
+            #[cfg(all(not(feature = "status-ws2812"), feature = "board-pico-w"))]
+            status_led_pwm: p.PWM_SLICE5,
+            #[cfg(feature = "status-ws2812")]
+            status_ws2812: p.PIN_3,
+            #[cfg(feature = "status-ws2812")]
+            status_ws2812_dma: p.DMA_CH3,
+
+            i2c: p.I2C0,
+            i2c_sda: p.PIN_20,
+            i2c_scl: p.PIN_21,
+
+            adc: p.ADC,
+            adc_temp_sensor: p.ADC_TEMP_SENSOR,
+            ref_temp: p.PIN_26,
+
+            pio: p.PIO1,
+            dht: p.PIN_22,
+            ds18b20: p.PIN_16,
+
+            heater_pwm: p.PWM_SLICE1,
+            heater: p.PIN_2,
+            mister_relay: p.PIN_14,
+
+            page_button: p.PIN_15,
+            alarm_button: p.PIN_7,
+            alarm_led: p.PIN_8,
+            buzzer: p.PIN_9,
+            encoder_a: p.PIN_17,
+            encoder_b: p.PIN_18,
+            encoder_button: p.PIN_19,
+
+            #[cfg(feature = "telemetry-uart")]
+            telemetry_uart: p.UART0,
+            #[cfg(feature = "telemetry-uart")]
+            telemetry_tx: p.PIN_0,
+            #[cfg(feature = "telemetry-uart")]
+            telemetry_dma: p.DMA_CH0,
+
+            modbus_uart: p.UART1,
+            modbus_tx: p.PIN_4,
+            modbus_rx: p.PIN_5,
+            modbus_de: p.PIN_6,
+
+            sd_spi: p.SPI1,
+            sd_sck: p.PIN_10,
+            sd_mosi: p.PIN_11,
+            sd_miso: p.PIN_12,
+            sd_cs: p.PIN_13,
+            sd_tx_dma: p.DMA_CH1,
+            sd_rx_dma: p.DMA_CH2,
+
+            #[cfg(feature = "wifi")]
+            wifi_pio: p.PIO0,
+            #[cfg(feature = "wifi")]
+            wifi_power: p.PIN_23,
+            #[cfg(feature = "wifi")]
+            wifi_dio: p.PIN_24,
+            #[cfg(feature = "wifi")]
+            wifi_cs: p.PIN_25,
+            #[cfg(feature = "wifi")]
+            wifi_clk: p.PIN_29,
+            #[cfg(feature = "wifi")]
+            wifi_dma: p.DMA_CH4,
+        }
+    }
+}
@@ -0,0 +1,611 @@
+// Command file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Command file for the modular project.
+ *  File        : command.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-18
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the grammar of the line commands used to configure the system in runtime,
+ *      it splits the received bytes in lines and parses each line into a command without allocation.
+ *
+ *      get <temp|sp|kp|ki|kd|out|mode|rate|rh|clock|probes|i2c|pv|env|display|trip|alarms|led|loop|knob>
+ *      get alarm <name>
+ *      set sp <°C|release>
+ *      set <kp|ki|kd> <value>
+ *      set rate <ms>
+ *      set rh [day|night] <%>
+ *      set clock <hh:mm>
+ *      set pv <die|sht3x|bme280>
+ *      set trip <low|high> <°C>
+ *      set alarm <name> <limit <°C> [°C]|hyst <°C>|delay <s>|latch <on|off>|enable <on|off>>
+ *      set led <0-100>
+ *      set loop <ms>
+ *      set knob <on|off>
+ *      mode <auto|off|manual <0-100>>
+ *      log [clear]
+ *      ack | status | save | reboot | bootsel | help
+ *
+ *      The trip limits latch the faults trip-high and trip-low, the alarms only warn the operator. The names
+ *      of the alarms are pv-high, pv-low, deviation, sensor and die-hot, the limit of the sensor alarm is a
+ *      range with the low and the high values.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::Format;
+
+use crate::modular::alarm::{ALARM_DELAY_MAX_S, ALARM_HYSTERESIS_MAX_C, AlarmId, LIMIT_MAX_C, LIMIT_MIN_C};
+use crate::modular::humidity::{HUMIDITY_MAX_PCT, HUMIDITY_MIN_PCT};
+use crate::modular::led_pattern::LED_LEVEL_MAX;
+use crate::modular::control::{CONTROL_MAX_PERIOD_MS, CONTROL_MIN_PERIOD_MS, PvSource};
+use crate::modular::setpoint::{SETPOINT_MAX_C, SETPOINT_MIN_C};
+use crate::modular::telemetry::{TELEMETRY_MAX_PERIOD_MS, TELEMETRY_MIN_PERIOD_MS};
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum CommandError {
+    LineTooLong,
+    InvalidUtf8,
+    UnknownCommand,
+    UnknownParameter,
+    MissingArgument,
+    TooManyArguments,
+    InvalidNumber,
+    OutOfRange,
+}
+
+impl CommandError {
+    pub fn message(self) -> &'static str {
+        match self {
+            CommandError::LineTooLong => "line too long",
+            CommandError::InvalidUtf8 => "invalid characters",
+            CommandError::UnknownCommand => "unknown command, type help",
+            CommandError::UnknownParameter => "unknown parameter",
+            CommandError::MissingArgument => "missing argument",
+            CommandError::TooManyArguments => "too many arguments",
+            CommandError::InvalidNumber => "invalid number",
+            CommandError::OutOfRange => "value out of range",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum Parameter {
+    Temp,
+    Setpoint,
+    Kp,
+    Ki,
+    Kd,
+    Output,
+    Mode,
+    Rate,     // Period of the telemetry
+    Humidity, // Humidity loop
+    Clock,    // Time of the day of the schedules
+    Probes,   // DS18B20 probes
+    I2c,      // Counters of the devices of the I2C bus
+    Pv,       // Sensor of the process variable
+    Env,      // Sensors of the I2C bus
+    Display,  // Health of the OLED
+    Trip,     // Trip limits of the PV faults
+    Alarm,    // Settings of one alarm
+    Alarms,   // States of the alarm engine
+    Led,      // Brightness of the status LED
+    Loop,     // Period and timing of the control loop
+    Knob,     // Potentiometer as a source of the setpoint
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum Gain {
+    Kp,
+    Ki,
+    Kd,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum TripLimit {
+    Low,
+    High,
+}
+
+#[derive(Clone, Copy, PartialEq, Format, Debug)]
+pub enum AlarmSetting {
+    Limit(f32),
+    Range(f32, f32), // Low and high, only for the sensor alarm
+    Hysteresis(f32),
+    Delay(u32),
+    Latching(bool),
+    Enabled(bool),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum Period {
+    Day,
+    Night,
+}
+
+#[derive(Clone, Copy, PartialEq, Format, Debug)]
+pub enum ModeRequest {
+    Auto,
+    Manual(f32), // Output in percent
+    Off,
+}
+
+#[derive(Clone, Copy, PartialEq, Format, Debug)]
+pub enum Command {
+    Get(Parameter),
+    SetSetpoint(f32),
+    ReleaseSetpoint,
+    SetGain(Gain, f32),
+    SetRate(u32),
+    SetHumidity(Period, f32),
+    SetClock(u32), // Seconds since 00:00
+    SetPvSource(PvSource),
+    SetTrip(TripLimit, f32),
+    GetAlarm(AlarmId),
+    SetAlarmSetting(AlarmId, AlarmSetting),
+    SetLedBrightness(u8), // Percent
+    SetLoopPeriod(u32),   // Milliseconds
+    SetKnob(bool),
+    Acknowledge,
+    Mode(ModeRequest),
+    LogExport,
+    LogClear,
+    Status,
+    Save,
+    Reboot,
+    Bootloader, // Reset into the USB bootloader of the ROM
+    Help,
+}
+
+// The gains of the PID are not negative, there is no upper limit
+pub const GAIN_MIN: f32 = 0.0;
+pub const GAIN_MAX: f32 = f32::MAX;
+
+pub const HELP_TEXT: &str = "get <temp|sp|kp|ki|kd|out|mode|rate|rh|clock|probes|i2c|pv|env|display|trip|alarms|led|loop|knob>\r\n\
+get alarm <name>\r\n\
+set sp <C|release>\r\n\
+set <kp|ki|kd> <value>\r\n\
+set rate <ms>\r\n\
+set rh [day|night] <%>\r\n\
+set clock <hh:mm>\r\n\
+set pv <die|sht3x|bme280>\r\n\
+set trip <low|high> <C>\r\n\
+set alarm <name> <limit <C> [C]|hyst <C>|delay <s>|latch <on|off>|enable <on|off>>\r\n\
+set led <0-100>\r\n\
+set loop <ms>\r\n\
+set knob <on|off>\r\n\
+mode <auto|off|manual <0-100>>\r\n\
+log [clear]\r\n\
+ack | status | save | reboot | bootsel | help";
+
+// Bounds of a number of the console, also used by the API of the HTTP
+pub fn check_number(value: f32, min: f32, max: f32) -> Result<f32, CommandError> {
+    if !value.is_finite() {
+        return Err(CommandError::InvalidNumber);
+    }
+    if value < min || value > max {
+        return Err(CommandError::OutOfRange);
+    }
+    Ok(value)
+}
+
+fn parse_number(token: Option<&str>, min: f32, max: f32) -> Result<f32, CommandError> {
+    let value: f32 = token
+        .ok_or(CommandError::MissingArgument)?
+        .parse()
+        .map_err(|_| CommandError::InvalidNumber)?;
+
+    check_number(value, min, max)
+}
+
+fn parse_integer(token: Option<&str>, min: u32, max: u32) -> Result<u32, CommandError> {
+    let value: u32 = token
+        .ok_or(CommandError::MissingArgument)?
+        .parse()
+        .map_err(|_| CommandError::InvalidNumber)?;
+
+    if value < min || value > max {
+        return Err(CommandError::OutOfRange);
+    }
+    Ok(value)
+}
+
+// Time of the day as hh:mm, returned in seconds since 00:00
+fn parse_clock(token: Option<&str>) -> Result<u32, CommandError> {
+    let (hours, minutes) = token
+        .ok_or(CommandError::MissingArgument)?
+        .split_once(':')
+        .ok_or(CommandError::InvalidNumber)?;
+    let hours = parse_integer(Some(hours), 0, 23)?;
+    let minutes = parse_integer(Some(minutes), 0, 59)?;
+    Ok(hours * 3600 + minutes * 60)
+}
+
+fn parse_pv_source(token: Option<&str>) -> Result<PvSource, CommandError> {
+    let token = token.ok_or(CommandError::MissingArgument)?;
+    PvSource::ALL
+        .iter()
+        .find(|source| token.eq_ignore_ascii_case(source.name()))
+        .copied()
+        .ok_or(CommandError::UnknownParameter)
+}
+
+fn parse_switch(token: Option<&str>) -> Result<bool, CommandError> {
+    match token.ok_or(CommandError::MissingArgument)? {
+        token if token.eq_ignore_ascii_case("on") => Ok(true),
+        token if token.eq_ignore_ascii_case("off") => Ok(false),
+        _ => Err(CommandError::UnknownParameter),
+    }
+}
+
+// Setting of one alarm, the tokens after "set alarm <name>"
+fn parse_alarm_setting<'a>(id: AlarmId, mut tokens: impl Iterator<Item = &'a str>) -> Result<AlarmSetting, CommandError> {
+    let setting = tokens.next().ok_or(CommandError::MissingArgument)?;
+    let argument = tokens.next();
+    let setting = if setting.eq_ignore_ascii_case("limit") && id == AlarmId::SensorRange {
+        let low = parse_number(argument, LIMIT_MIN_C, LIMIT_MAX_C)?;
+        AlarmSetting::Range(low, parse_number(tokens.next(), LIMIT_MIN_C, LIMIT_MAX_C)?)
+    } else if setting.eq_ignore_ascii_case("limit") {
+        AlarmSetting::Limit(parse_number(argument, LIMIT_MIN_C, LIMIT_MAX_C)?)
+    } else if setting.eq_ignore_ascii_case("hyst") {
+        AlarmSetting::Hysteresis(parse_number(argument, 0.0, ALARM_HYSTERESIS_MAX_C)?)
+    } else if setting.eq_ignore_ascii_case("delay") {
+        AlarmSetting::Delay(parse_integer(argument, 0, ALARM_DELAY_MAX_S)?)
+    } else if setting.eq_ignore_ascii_case("latch") {
+        AlarmSetting::Latching(parse_switch(argument)?)
+    } else if setting.eq_ignore_ascii_case("enable") {
+        AlarmSetting::Enabled(parse_switch(argument)?)
+    } else {
+        return Err(CommandError::UnknownParameter);
+    };
+    Ok(setting)
+}
+
+fn parse_parameter(token: &str) -> Result<Parameter, CommandError> {
+    const PARAMETERS: [(&str, Parameter); 21] = [
+        ("temp", Parameter::Temp),
+        ("sp", Parameter::Setpoint),
+        ("kp", Parameter::Kp),
+        ("ki", Parameter::Ki),
+        ("kd", Parameter::Kd),
+        ("out", Parameter::Output),
+        ("mode", Parameter::Mode),
+        ("rate", Parameter::Rate),
+        ("rh", Parameter::Humidity),
+        ("clock", Parameter::Clock),
+        ("probes", Parameter::Probes),
+        ("i2c", Parameter::I2c),
+        ("pv", Parameter::Pv),
+        ("env", Parameter::Env),
+        ("display", Parameter::Display),
+        ("trip", Parameter::Trip),
+        ("alarm", Parameter::Alarm),
+        ("alarms", Parameter::Alarms),
+        ("led", Parameter::Led),
+        ("loop", Parameter::Loop),
+        ("knob", Parameter::Knob),
+    ];
+
+    PARAMETERS
+        .iter()
+        .find(|(name, _)| token.eq_ignore_ascii_case(name))
+        .map(|&(_, parameter)| parameter)
+        .ok_or(CommandError::UnknownParameter)
+}
+
+// Parse one line, the words are separated by spaces and the case is ignored
+pub fn parse_command(line: &str) -> Result<Option<Command>, CommandError> {
+    let mut tokens = line.split_ascii_whitespace();
+    let Some(verb) = tokens.next() else {
+        return Ok(None);
+    };
+    let word = |token: Option<&str>, expected: &str| token.is_some_and(|token| token.eq_ignore_ascii_case(expected));
+
+    let command = if verb.eq_ignore_ascii_case("get") {
+        match parse_parameter(tokens.next().ok_or(CommandError::MissingArgument)?)? {
+            Parameter::Alarm => {
+                let name = tokens.next().ok_or(CommandError::MissingArgument)?;
+                Command::GetAlarm(AlarmId::from_name(name).ok_or(CommandError::UnknownParameter)?)
+            }
+            parameter => Command::Get(parameter),
+        }
+    } else if verb.eq_ignore_ascii_case("set") {
+        let parameter = parse_parameter(tokens.next().ok_or(CommandError::MissingArgument)?)?;
+        let argument = tokens.next();
+        match parameter {
+            Parameter::Setpoint if word(argument, "release") => Command::ReleaseSetpoint,
+            Parameter::Setpoint => Command::SetSetpoint(parse_number(argument, SETPOINT_MIN_C, SETPOINT_MAX_C)?),
+            Parameter::Kp => Command::SetGain(Gain::Kp, parse_number(argument, GAIN_MIN, GAIN_MAX)?),
+            Parameter::Ki => Command::SetGain(Gain::Ki, parse_number(argument, GAIN_MIN, GAIN_MAX)?),
+            Parameter::Kd => Command::SetGain(Gain::Kd, parse_number(argument, GAIN_MIN, GAIN_MAX)?),
+            Parameter::Rate => Command::SetRate(parse_integer(argument, TELEMETRY_MIN_PERIOD_MS, TELEMETRY_MAX_PERIOD_MS)?),
+            Parameter::Humidity if word(argument, "day") => {
+                Command::SetHumidity(Period::Day, parse_number(tokens.next(), HUMIDITY_MIN_PCT, HUMIDITY_MAX_PCT)?)
+            }
+            Parameter::Humidity if word(argument, "night") => {
+                Command::SetHumidity(Period::Night, parse_number(tokens.next(), HUMIDITY_MIN_PCT, HUMIDITY_MAX_PCT)?)
+            }
+            Parameter::Humidity => Command::SetHumidity(Period::Day, parse_number(argument, HUMIDITY_MIN_PCT, HUMIDITY_MAX_PCT)?),
+            Parameter::Clock => Command::SetClock(parse_clock(argument)?),
+            Parameter::Pv => Command::SetPvSource(parse_pv_source(argument)?),
+            Parameter::Loop => Command::SetLoopPeriod(parse_integer(argument, CONTROL_MIN_PERIOD_MS, CONTROL_MAX_PERIOD_MS)?),
+            Parameter::Led => Command::SetLedBrightness(parse_integer(argument, 0, LED_LEVEL_MAX as u32)? as u8),
+            Parameter::Knob => Command::SetKnob(parse_switch(argument)?),
+            Parameter::Trip if word(argument, "low") => {
+                Command::SetTrip(TripLimit::Low, parse_number(tokens.next(), LIMIT_MIN_C, LIMIT_MAX_C)?)
+            }
+            Parameter::Trip if word(argument, "high") => {
+                Command::SetTrip(TripLimit::High, parse_number(tokens.next(), LIMIT_MIN_C, LIMIT_MAX_C)?)
+            }
+            Parameter::Alarm => {
+                let name = argument.ok_or(CommandError::MissingArgument)?;
+                let id = AlarmId::from_name(name).ok_or(CommandError::UnknownParameter)?;
+                Command::SetAlarmSetting(id, parse_alarm_setting(id, &mut tokens)?)
+            }
+            _ => return Err(CommandError::UnknownParameter),
+        }
+    } else if verb.eq_ignore_ascii_case("mode") {
+        let mode = tokens.next();
+        if word(mode, "auto") {
+            Command::Mode(ModeRequest::Auto)
+        } else if word(mode, "off") {
+            Command::Mode(ModeRequest::Off)
+        } else if word(mode, "manual") {
+            Command::Mode(ModeRequest::Manual(parse_number(tokens.next(), 0.0, 100.0)?))
+        } else if mode.is_none() {
+            return Err(CommandError::MissingArgument);
+        } else {
+            return Err(CommandError::UnknownParameter);
+        }
+    } else if verb.eq_ignore_ascii_case("log") {
+        let argument = tokens.next();
+        if argument.is_none() {
+            Command::LogExport
+        } else if word(argument, "clear") {
+            Command::LogClear
+        } else {
+            return Err(CommandError::UnknownParameter);
+        }
+    } else if verb.eq_ignore_ascii_case("ack") {
+        Command::Acknowledge
+    } else if verb.eq_ignore_ascii_case("status") {
+        Command::Status
+    } else if verb.eq_ignore_ascii_case("save") {
+        Command::Save
+    } else if verb.eq_ignore_ascii_case("reboot") {
+        Command::Reboot
+    } else if verb.eq_ignore_ascii_case("bootsel") {
+        Command::Bootloader
+    } else if verb.eq_ignore_ascii_case("help") || verb == "?" {
+        Command::Help
+    } else {
+        return Err(CommandError::UnknownCommand);
+    };
+
+    if tokens.next().is_some() {
+        return Err(CommandError::TooManyArguments);
+    }
+    Ok(Some(command))
+}
+
+// Accumulate the received bytes until the end of the line, CR, LF or CRLF end the line
+// and backspace/delete remove the last character.
+pub struct LineBuffer<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    overflow: bool,
+    complete: bool,
+}
+
+impl<const N: usize> LineBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            overflow: false,
+            complete: false,
+        }
+    }
+
+    // Returns the line when the byte ends it, a line longer than the buffer is reported only once
+    pub fn push(&mut self, byte: u8) -> Option<Result<&str, CommandError>> {
+        if self.complete {
+            self.len = 0;
+            self.overflow = false;
+            self.complete = false;
+        }
+
+        match byte {
+            b'\r' | b'\n' => {
+                if self.len == 0 && !self.overflow {
+                    return None;
+                }
+                self.complete = true;
+                if self.overflow {
+                    return Some(Err(CommandError::LineTooLong));
+                }
+                Some(core::str::from_utf8(&self.buffer[..self.len]).map_err(|_| CommandError::InvalidUtf8))
+            }
+            0x08 | 0x7f => {
+                self.len = self.len.saturating_sub(1);
+                None
+            }
+            _ => {
+                if self.len < N {
+                    self.buffer[self.len] = byte;
+                    self.len += 1;
+                } else {
+                    self.overflow = true;
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_command as parse;
+    use super::*;
+
+    #[test]
+    fn empty_line_is_not_a_command() {
+        assert_eq!(parse(""), Ok(None));
+        assert_eq!(parse("   \t "), Ok(None));
+    }
+
+    #[test]
+    fn get_parameters() {
+        assert_eq!(parse("get temp"), Ok(Some(Command::Get(Parameter::Temp))));
+        assert_eq!(parse("GET Sp"), Ok(Some(Command::Get(Parameter::Setpoint))));
+        assert_eq!(parse("get loop"), Ok(Some(Command::Get(Parameter::Loop))));
+        assert_eq!(parse("get trip"), Ok(Some(Command::Get(Parameter::Trip))));
+        assert_eq!(parse("get alarm"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("get alarm die-hot"), Ok(Some(Command::GetAlarm(AlarmId::DieHot))));
+        assert_eq!(parse("get"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("get humidity"), Err(CommandError::UnknownParameter));
+        assert_eq!(parse("get alarm smoke"), Err(CommandError::UnknownParameter));
+    }
+
+    #[test]
+    fn set_setpoint() {
+        assert_eq!(parse("set sp 30.5"), Ok(Some(Command::SetSetpoint(30.5))));
+        assert_eq!(parse("set sp release"), Ok(Some(Command::ReleaseSetpoint)));
+        assert_eq!(parse("set sp"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("set sp warm"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("set sp NaN"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("set sp inf"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("set sp 80"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set sp -1"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set sp 30 31"), Err(CommandError::TooManyArguments));
+    }
+
+    #[test]
+    fn set_gains_and_periods() {
+        assert_eq!(parse("set kp 2.0"), Ok(Some(Command::SetGain(Gain::Kp, 2.0))));
+        assert_eq!(parse("set ki 0.1"), Ok(Some(Command::SetGain(Gain::Ki, 0.1))));
+        assert_eq!(parse("set kd 0"), Ok(Some(Command::SetGain(Gain::Kd, 0.0))));
+        assert_eq!(parse("set kp -2"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set rate 500"), Ok(Some(Command::SetRate(500))));
+        assert_eq!(parse("set rate 10"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set rate 1.5"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("set loop 250"), Ok(Some(Command::SetLoopPeriod(250))));
+        assert_eq!(parse("set loop 20000"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set led 40"), Ok(Some(Command::SetLedBrightness(40))));
+        assert_eq!(parse("set led 101"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set knob on"), Ok(Some(Command::SetKnob(true))));
+        assert_eq!(parse("set knob OFF"), Ok(Some(Command::SetKnob(false))));
+        assert_eq!(parse("set knob"), Err(CommandError::MissingArgument));
+    }
+
+    #[test]
+    fn set_humidity_clock_and_pv() {
+        assert_eq!(parse("set rh 70"), Ok(Some(Command::SetHumidity(Period::Day, 70.0))));
+        assert_eq!(parse("set rh night 85"), Ok(Some(Command::SetHumidity(Period::Night, 85.0))));
+        assert_eq!(parse("set rh day"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("set clock 07:30"), Ok(Some(Command::SetClock(7 * 3600 + 30 * 60))));
+        assert_eq!(parse("set clock 24:00"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set clock 0730"), Err(CommandError::InvalidNumber));
+        assert_eq!(parse("set pv SHT3X"), Ok(Some(Command::SetPvSource(PvSource::Sht3x))));
+        assert_eq!(parse("set pv dht"), Err(CommandError::UnknownParameter));
+    }
+
+    #[test]
+    fn set_alarms() {
+        assert_eq!(parse("set trip low 20"), Ok(Some(Command::SetTrip(TripLimit::Low, 20.0))));
+        assert_eq!(parse("set trip high 35"), Ok(Some(Command::SetTrip(TripLimit::High, 35.0))));
+        assert_eq!(parse("set trip low -10"), Ok(Some(Command::SetTrip(TripLimit::Low, -10.0))));
+        assert_eq!(parse("set trip high 90"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("set trip 35"), Err(CommandError::UnknownParameter));
+        assert_eq!(parse("set alarm low 20"), Err(CommandError::UnknownParameter));
+        assert_eq!(
+            parse("set alarm pv-high limit 36"),
+            Ok(Some(Command::SetAlarmSetting(AlarmId::PvHigh, AlarmSetting::Limit(36.0))))
+        );
+        assert_eq!(
+            parse("set alarm sensor limit 5 45"),
+            Ok(Some(Command::SetAlarmSetting(AlarmId::SensorRange, AlarmSetting::Range(5.0, 45.0))))
+        );
+        assert_eq!(
+            parse("set alarm deviation delay 30"),
+            Ok(Some(Command::SetAlarmSetting(AlarmId::Deviation, AlarmSetting::Delay(30))))
+        );
+        assert_eq!(
+            parse("set alarm die-hot latch on"),
+            Ok(Some(Command::SetAlarmSetting(AlarmId::DieHot, AlarmSetting::Latching(true))))
+        );
+        assert_eq!(parse("set alarm pv-low enable maybe"), Err(CommandError::UnknownParameter));
+        assert_eq!(parse("set alarm sensor limit 5"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("set alarm pv-low colour red"), Err(CommandError::UnknownParameter));
+    }
+
+    #[test]
+    fn mode_and_single_words() {
+        assert_eq!(parse("mode auto"), Ok(Some(Command::Mode(ModeRequest::Auto))));
+        assert_eq!(parse("mode off"), Ok(Some(Command::Mode(ModeRequest::Off))));
+        assert_eq!(parse("mode manual 40"), Ok(Some(Command::Mode(ModeRequest::Manual(40.0)))));
+        assert_eq!(parse("mode manual 140"), Err(CommandError::OutOfRange));
+        assert_eq!(parse("mode"), Err(CommandError::MissingArgument));
+        assert_eq!(parse("mode cruise"), Err(CommandError::UnknownParameter));
+        assert_eq!(parse("log"), Ok(Some(Command::LogExport)));
+        assert_eq!(parse("log clear"), Ok(Some(Command::LogClear)));
+        assert_eq!(parse("status"), Ok(Some(Command::Status)));
+        assert_eq!(parse("save"), Ok(Some(Command::Save)));
+        assert_eq!(parse("reboot"), Ok(Some(Command::Reboot)));
+        assert_eq!(parse("bootsel"), Ok(Some(Command::Bootloader)));
+        assert_eq!(parse("?"), Ok(Some(Command::Help)));
+        assert_eq!(parse("ack now"), Err(CommandError::TooManyArguments));
+        assert_eq!(parse("launch"), Err(CommandError::UnknownCommand));
+    }
+
+    fn push_all<'a, const N: usize>(line: &'a mut LineBuffer<N>, bytes: &[u8]) -> Option<Result<&'a str, CommandError>> {
+        let (last, rest) = bytes.split_last().unwrap();
+        for &byte in rest {
+            assert!(line.push(byte).is_none());
+        }
+        line.push(*last)
+    }
+
+    #[test]
+    fn line_buffer_splits_lines() {
+        let mut line = LineBuffer::<16>::new();
+        assert_eq!(push_all(&mut line, b"status\r"), Some(Ok("status")));
+        // The LF of the CRLF is an empty line
+        assert_eq!(line.push(b'\n'), None);
+        assert_eq!(push_all(&mut line, b"get sp\n"), Some(Ok("get sp")));
+    }
+
+    #[test]
+    fn line_buffer_backspace() {
+        let mut line = LineBuffer::<16>::new();
+        assert_eq!(push_all(&mut line, b"gex\x08t sp\x7f\x7fsp\r"), Some(Ok("get sp")));
+        // Backspace in an empty line does nothing
+        assert_eq!(push_all(&mut line, b"\x08ack\r"), Some(Ok("ack")));
+    }
+
+    #[test]
+    fn line_buffer_overflow_is_reported_once() {
+        let mut line = LineBuffer::<4>::new();
+        assert_eq!(push_all(&mut line, b"status\r"), Some(Err(CommandError::LineTooLong)));
+        assert_eq!(line.push(b'\n'), None);
+        assert_eq!(push_all(&mut line, b"ack\r"), Some(Ok("ack")));
+    }
+
+    #[test]
+    fn line_buffer_invalid_utf8() {
+        let mut line = LineBuffer::<8>::new();
+        assert_eq!(push_all(&mut line, b"s\xffp\r"), Some(Err(CommandError::InvalidUtf8)));
+    }
+
+    #[test]
+    fn help_is_streamed_in_lines_that_fit_a_reply() {
+        // The console reply is a String<256>, the whole help does not fit but each line does
+        assert!(HELP_TEXT.len() > 256);
+        for line in HELP_TEXT.split("\r\n") {
+            assert!(!line.is_empty() && line.len() < 256, "{line}");
+        }
+    }
+}
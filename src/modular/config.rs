@@ -0,0 +1,866 @@
+// Config file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Config file for the modular project.
+ *  File        : config.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the persistent configuration, saved as records of 256 bytes
+ *      in the last sectors of the flash. Every save writes the next free slot (wear levelling) with a
+ *      sequence number and a CRC-16, the load takes the valid record with the highest sequence and a
+ *      record cut by a power loss is only ignored, so the previous configuration is used.
+ *
+ *      Each field of the payload is written as tag, length and value, so a field can be added
+ *      without moving the others: a record of an older version keeps the default value of the fields
+ *      it does not have, and the fields of a newer one are skipped. The settings of each alarm of the
+ *      alarm.rs are one field, added to the version 4 in this way. The records up to the version 3
+ *      had 64 bytes and the fields in a fixed order, they are still read so the first save after the
+ *      update migrates them. Every loaded field is checked against its range and a value outside of
+ *      it is replaced by the default.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::ops::RangeInclusive;
+
+use embedded_storage::nor_flash::{NorFlash, NorFlashError, NorFlashErrorKind};
+
+use super::alarm::{
+    ALARM_COUNT, ALARM_DELAY_MAX_S, ALARM_HYSTERESIS_MAX_C, AlarmSettings, LIMIT_MAX_C, LIMIT_MIN_C, Threshold,
+};
+use super::control::{PWM_MAX_FREQUENCY_HZ, PWM_MIN_FREQUENCY_HZ, PvSource};
+use super::humidity::{HUMIDITY_MAX_PCT, HUMIDITY_MIN_PCT};
+use super::pid::PidGains;
+use super::setpoint::{SETPOINT_MAX_C, SETPOINT_MIN_C};
+use super::telemetry::{TELEMETRY_MAX_PERIOD_MS, TELEMETRY_MIN_PERIOD_MS, crc16};
+
+// Version 1: setpoint, gains, trip limits, die temperature offset, PWM frequency and telemetry period
+// Version 2: humidity targets of the day and of the night
+// Version 3: sensor of the process variable
+// Version 4: records of 256 bytes with tagged fields
+pub const CONFIG_VERSION: u16 = 4;
+// Records older than this version have a layout that can not be read anymore
+pub const CONFIG_MIN_VERSION: u16 = 1;
+// Last version with the records of 64 bytes and the fields in a fixed order
+const LEGACY_VERSION: u16 = 3;
+const LEGACY_RECORD_SIZE: usize = 64;
+
+const RECORD_MAGIC: u32 = 0x4746_4356; // "VCFG"
+pub const RECORD_SIZE: usize = 256;
+const HEADER_LEN: usize = 12; // magic, version, payload length and sequence
+const CRC_LEN: usize = 2;
+pub const PAYLOAD_MAX_LEN: usize = RECORD_SIZE - HEADER_LEN - CRC_LEN;
+
+// Tags of the fields, a tag is never reused for another field
+const TAG_SETPOINT: u8 = 1;
+const TAG_KP: u8 = 2;
+const TAG_KI: u8 = 3;
+const TAG_KD: u8 = 4;
+const TAG_TRIP_HIGH: u8 = 5;
+const TAG_TRIP_LOW: u8 = 6;
+const TAG_DIE_OFFSET: u8 = 7;
+const TAG_PWM_FREQUENCY: u8 = 8;
+const TAG_TELEMETRY_PERIOD: u8 = 9;
+const TAG_HUMIDITY_DAY: u8 = 10;
+const TAG_HUMIDITY_NIGHT: u8 = 11;
+const TAG_PV_SOURCE: u8 = 12;
+const TAG_KNOB: u8 = 13;
+// One field for each alarm, in the order of the AlarmId
+const TAG_ALARMS: [u8; ALARM_COUNT] = [14, 15, 16, 17, 18];
+
+// Value of the field of an alarm: flags, the two limits of the threshold, the hysteresis and the delay
+const ALARM_FIELD_LEN: usize = 17;
+const ALARM_ENABLED: u8 = 1 << 0;
+const ALARM_LATCHING: u8 = 1 << 1;
+const THRESHOLD_SHIFT: u8 = 2; // Kind of the threshold in the bits 2 and 3 of the flags
+const THRESHOLD_ABOVE: u8 = 0;
+const THRESHOLD_BELOW: u8 = 1;
+const THRESHOLD_OUTSIDE: u8 = 2;
+
+// Largest correction of the temperature sensor of the die
+pub const DIE_TEMP_OFFSET_MAX_C: f32 = 10.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Config {
+    pub stored_setpoint_c: f32,
+    pub gains: PidGains,
+    pub trip_high_c: f32,
+    pub trip_low_c: f32,
+    pub die_temp_offset_c: f32, // Calibration of the temperature sensor of the die
+    pub pwm_frequency_hz: u32,
+    pub telemetry_period_ms: u32,
+    pub humidity_day_pct: f32,
+    pub humidity_night_pct: f32,
+    pub pv_source: u32, // Code of the PvSource of the control.rs
+    pub knob_enabled: bool, // The potentiometer in the ADC0 is a source of the setpoint
+    pub alarms: [AlarmSettings; ALARM_COUNT], // Settings of the alarm engine, in the order of the AlarmId
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfigError {
+    NotFound,                 // No valid record, the defaults must be used
+    Flash(NorFlashErrorKind), // The flash refused the operation
+}
+
+// Tagged fields of the payload, the values are in little endian
+struct PayloadWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl PayloadWriter<'_> {
+    fn field(&mut self, tag: u8, value: &[u8]) {
+        self.buffer[self.len] = tag;
+        self.buffer[self.len + 1] = value.len() as u8;
+        self.buffer[self.len + 2..self.len + 2 + value.len()].copy_from_slice(value);
+        self.len += 2 + value.len();
+    }
+
+    fn u32(&mut self, tag: u8, value: u32) {
+        self.field(tag, &value.to_le_bytes());
+    }
+
+    fn f32(&mut self, tag: u8, value: f32) {
+        self.u32(tag, value.to_bits());
+    }
+}
+
+// Returns the tag and the value of each field, a field cut by the end of the payload ends it
+struct PayloadFields<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> Iterator for PayloadFields<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&tag, rest) = self.payload.split_first()?;
+        let (&len, rest) = rest.split_first()?;
+        let value = rest.get(..len as usize)?;
+        self.payload = &rest[len as usize..];
+        Some((tag, value))
+    }
+}
+
+fn field_u32(value: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(value.try_into().ok()?))
+}
+
+fn encode_alarm(settings: &AlarmSettings) -> [u8; ALARM_FIELD_LEN] {
+    let (kind, low, high) = match settings.threshold {
+        Threshold::Above(limit) => (THRESHOLD_ABOVE, limit, limit),
+        Threshold::Below(limit) => (THRESHOLD_BELOW, limit, limit),
+        Threshold::Outside(low, high) => (THRESHOLD_OUTSIDE, low, high),
+    };
+    let mut flags = kind << THRESHOLD_SHIFT;
+    if settings.enabled {
+        flags |= ALARM_ENABLED;
+    }
+    if settings.latching {
+        flags |= ALARM_LATCHING;
+    }
+
+    let mut value = [0u8; ALARM_FIELD_LEN];
+    value[0] = flags;
+    value[1..5].copy_from_slice(&low.to_le_bytes());
+    value[5..9].copy_from_slice(&high.to_le_bytes());
+    value[9..13].copy_from_slice(&settings.hysteresis_c.to_le_bytes());
+    value[13..17].copy_from_slice(&settings.delay_s.to_le_bytes());
+    value
+}
+
+fn decode_alarm(value: &[u8]) -> Option<AlarmSettings> {
+    if value.len() != ALARM_FIELD_LEN {
+        return None;
+    }
+    let word = |start: usize| field_u32(&value[start..start + 4]);
+    let low = f32::from_bits(word(1)?);
+    let high = f32::from_bits(word(5)?);
+    let flags = value[0];
+    let threshold = match flags >> THRESHOLD_SHIFT {
+        THRESHOLD_ABOVE => Threshold::Above(low),
+        THRESHOLD_BELOW => Threshold::Below(low),
+        THRESHOLD_OUTSIDE => Threshold::Outside(low, high),
+        _ => return None,
+    };
+    Some(AlarmSettings {
+        enabled: flags & ALARM_ENABLED != 0,
+        threshold,
+        hysteresis_c: f32::from_bits(word(9)?),
+        delay_s: word(13)?,
+        latching: flags & ALARM_LATCHING != 0,
+    })
+}
+
+// The settings must be in the ranges of the console and keep the direction of the threshold of the alarm
+fn alarm_valid(settings: &AlarmSettings, default: &AlarmSettings) -> bool {
+    let limit = |value: f32| (LIMIT_MIN_C..=LIMIT_MAX_C).contains(&value);
+    let threshold = match (settings.threshold, default.threshold) {
+        (Threshold::Above(value), Threshold::Above(_)) | (Threshold::Below(value), Threshold::Below(_)) => limit(value),
+        (Threshold::Outside(low, high), Threshold::Outside(..)) => limit(low) && limit(high) && low < high,
+        _ => false,
+    };
+    threshold && (0.0..=ALARM_HYSTERESIS_MAX_C).contains(&settings.hysteresis_c) && settings.delay_s <= ALARM_DELAY_MAX_S
+}
+
+// Positional fields of the records up to the version 3, a field after the end of the payload keeps the default
+struct LegacyReader<'a> {
+    payload: &'a [u8],
+    position: usize,
+}
+
+impl LegacyReader<'_> {
+    fn u32(&mut self, default: u32) -> u32 {
+        let Some(bytes) = self.payload.get(self.position..self.position + 4) else {
+            return default;
+        };
+        self.position += 4;
+        u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn f32(&mut self, default: f32) -> f32 {
+        f32::from_bits(self.u32(default.to_bits()))
+    }
+}
+
+// The value when it is inside the range, otherwise the default (a NaN is never inside)
+fn within<T: PartialOrd>(value: T, range: RangeInclusive<T>, default: T) -> T {
+    if range.contains(&value) { value } else { default }
+}
+
+impl Config {
+    fn encode_payload(&self, buffer: &mut [u8; PAYLOAD_MAX_LEN]) -> usize {
+        let mut writer = PayloadWriter { buffer, len: 0 };
+        writer.f32(TAG_SETPOINT, self.stored_setpoint_c);
+        writer.f32(TAG_KP, self.gains.kp);
+        writer.f32(TAG_KI, self.gains.ki);
+        writer.f32(TAG_KD, self.gains.kd);
+        writer.f32(TAG_TRIP_HIGH, self.trip_high_c);
+        writer.f32(TAG_TRIP_LOW, self.trip_low_c);
+        writer.f32(TAG_DIE_OFFSET, self.die_temp_offset_c);
+        writer.u32(TAG_PWM_FREQUENCY, self.pwm_frequency_hz);
+        writer.u32(TAG_TELEMETRY_PERIOD, self.telemetry_period_ms);
+        writer.f32(TAG_HUMIDITY_DAY, self.humidity_day_pct);
+        writer.f32(TAG_HUMIDITY_NIGHT, self.humidity_night_pct);
+        writer.u32(TAG_PV_SOURCE, self.pv_source);
+        writer.u32(TAG_KNOB, self.knob_enabled as u32);
+        for (&tag, settings) in TAG_ALARMS.iter().zip(&self.alarms) {
+            writer.field(tag, &encode_alarm(settings));
+        }
+        writer.len
+    }
+
+    // A field with an unknown tag or an unexpected length is skipped
+    fn decode_payload(payload: &[u8], defaults: &Config) -> Config {
+        let mut config = *defaults;
+        for (tag, value) in (PayloadFields { payload }) {
+            if let Some(index) = TAG_ALARMS.iter().position(|&alarm_tag| alarm_tag == tag) {
+                if let Some(settings) = decode_alarm(value) {
+                    config.alarms[index] = settings;
+                }
+                continue;
+            }
+            let Some(word) = field_u32(value) else {
+                continue;
+            };
+            let real = f32::from_bits(word);
+            match tag {
+                TAG_SETPOINT => config.stored_setpoint_c = real,
+                TAG_KP => config.gains.kp = real,
+                TAG_KI => config.gains.ki = real,
+                TAG_KD => config.gains.kd = real,
+                TAG_TRIP_HIGH => config.trip_high_c = real,
+                TAG_TRIP_LOW => config.trip_low_c = real,
+                TAG_DIE_OFFSET => config.die_temp_offset_c = real,
+                TAG_PWM_FREQUENCY => config.pwm_frequency_hz = word,
+                TAG_TELEMETRY_PERIOD => config.telemetry_period_ms = word,
+                TAG_HUMIDITY_DAY => config.humidity_day_pct = real,
+                TAG_HUMIDITY_NIGHT => config.humidity_night_pct = real,
+                TAG_PV_SOURCE => config.pv_source = word,
+                TAG_KNOB => config.knob_enabled = word != 0,
+                _ => {}
+            }
+        }
+        config
+    }
+
+    fn decode_legacy_payload(payload: &[u8], defaults: &Config) -> Config {
+        let mut reader = LegacyReader { payload, position: 0 };
+        Config {
+            stored_setpoint_c: reader.f32(defaults.stored_setpoint_c),
+            gains: PidGains {
+                kp: reader.f32(defaults.gains.kp),
+                ki: reader.f32(defaults.gains.ki),
+                kd: reader.f32(defaults.gains.kd),
+            },
+            trip_high_c: reader.f32(defaults.trip_high_c),
+            trip_low_c: reader.f32(defaults.trip_low_c),
+            die_temp_offset_c: reader.f32(defaults.die_temp_offset_c),
+            pwm_frequency_hz: reader.u32(defaults.pwm_frequency_hz),
+            telemetry_period_ms: reader.u32(defaults.telemetry_period_ms),
+            humidity_day_pct: reader.f32(defaults.humidity_day_pct),
+            humidity_night_pct: reader.f32(defaults.humidity_night_pct),
+            pv_source: reader.u32(defaults.pv_source),
+            knob_enabled: defaults.knob_enabled,
+            alarms: defaults.alarms,
+        }
+    }
+
+    // Replace every value outside of its range by the default, a bad field does not discard the others
+    pub fn validated(self, defaults: &Config) -> Config {
+        let gain = |value: f32, default: f32| within(value, 0.0..=f32::MAX, default);
+        let limit = |value: f32| (LIMIT_MIN_C..=LIMIT_MAX_C).contains(&value);
+        // The limits only make sense together, the low one below the high one
+        let (trip_low_c, trip_high_c) = if limit(self.trip_low_c) && limit(self.trip_high_c) && self.trip_low_c < self.trip_high_c {
+            (self.trip_low_c, self.trip_high_c)
+        } else {
+            (defaults.trip_low_c, defaults.trip_high_c)
+        };
+
+        let mut alarms = self.alarms;
+        for (settings, default) in alarms.iter_mut().zip(&defaults.alarms) {
+            if !alarm_valid(settings, default) {
+                *settings = *default;
+            }
+        }
+
+        Config {
+            stored_setpoint_c: within(self.stored_setpoint_c, SETPOINT_MIN_C..=SETPOINT_MAX_C, defaults.stored_setpoint_c),
+            gains: PidGains {
+                kp: gain(self.gains.kp, defaults.gains.kp),
+                ki: gain(self.gains.ki, defaults.gains.ki),
+                kd: gain(self.gains.kd, defaults.gains.kd),
+            },
+            trip_high_c,
+            trip_low_c,
+            die_temp_offset_c: within(
+                self.die_temp_offset_c,
+                -DIE_TEMP_OFFSET_MAX_C..=DIE_TEMP_OFFSET_MAX_C,
+                defaults.die_temp_offset_c,
+            ),
+            pwm_frequency_hz: within(self.pwm_frequency_hz, PWM_MIN_FREQUENCY_HZ..=PWM_MAX_FREQUENCY_HZ, defaults.pwm_frequency_hz),
+            telemetry_period_ms: within(
+                self.telemetry_period_ms,
+                TELEMETRY_MIN_PERIOD_MS..=TELEMETRY_MAX_PERIOD_MS,
+                defaults.telemetry_period_ms,
+            ),
+            humidity_day_pct: within(self.humidity_day_pct, HUMIDITY_MIN_PCT..=HUMIDITY_MAX_PCT, defaults.humidity_day_pct),
+            humidity_night_pct: within(self.humidity_night_pct, HUMIDITY_MIN_PCT..=HUMIDITY_MAX_PCT, defaults.humidity_night_pct),
+            pv_source: match PvSource::from_code(self.pv_source) {
+                Some(_) => self.pv_source,
+                None => defaults.pv_source,
+            },
+            knob_enabled: self.knob_enabled,
+            alarms,
+        }
+    }
+
+    // Complete record with the header and the CRC, the unused bytes stay erased (0xFF)
+    pub fn to_record(self, sequence: u32) -> [u8; RECORD_SIZE] {
+        let mut record = [0xFF; RECORD_SIZE];
+        let mut payload = [0u8; PAYLOAD_MAX_LEN];
+        let len = self.encode_payload(&mut payload);
+
+        record[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        record[4..6].copy_from_slice(&CONFIG_VERSION.to_le_bytes());
+        record[6..8].copy_from_slice(&(len as u16).to_le_bytes());
+        record[8..12].copy_from_slice(&sequence.to_le_bytes());
+        record[HEADER_LEN..HEADER_LEN + len].copy_from_slice(&payload[..len]);
+        let crc = crc16(&record[..HEADER_LEN + len]);
+        record[HEADER_LEN + len..HEADER_LEN + len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    // Size of the record that starts with this header, None when it is not the header of a record
+    fn record_size(header: &[u8; HEADER_LEN]) -> Option<usize> {
+        let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if magic != RECORD_MAGIC || version < CONFIG_MIN_VERSION {
+            None
+        } else if version <= LEGACY_VERSION {
+            Some(LEGACY_RECORD_SIZE)
+        } else {
+            Some(RECORD_SIZE)
+        }
+    }
+
+    // Returns the sequence and the validated configuration of a valid record of any version
+    pub fn from_record(record: &[u8], defaults: &Config) -> Option<(u32, Config)> {
+        let header: &[u8; HEADER_LEN] = record.get(..HEADER_LEN)?.try_into().ok()?;
+        let size = Config::record_size(header)?;
+        let version = u16::from_le_bytes([record[4], record[5]]);
+        let len = u16::from_le_bytes([record[6], record[7]]) as usize;
+        let sequence = u32::from_le_bytes([record[8], record[9], record[10], record[11]]);
+
+        if HEADER_LEN + len + CRC_LEN > size.min(record.len()) {
+            return None;
+        }
+        let crc = u16::from_le_bytes([record[HEADER_LEN + len], record[HEADER_LEN + len + 1]]);
+        if crc != crc16(&record[..HEADER_LEN + len]) {
+            return None;
+        }
+
+        let payload = &record[HEADER_LEN..HEADER_LEN + len];
+        let config = if version <= LEGACY_VERSION {
+            Config::decode_legacy_payload(payload, defaults)
+        } else {
+            Config::decode_payload(payload, defaults)
+        };
+        Some((sequence, config.validated(defaults)))
+    }
+}
+
+fn flash_error(error: impl NorFlashError) -> ConfigError {
+    ConfigError::Flash(error.kind())
+}
+
+// Configuration records in a region of whole sectors of the flash
+pub struct ConfigStore<F: NorFlash> {
+    flash: F,
+    offset: u32,
+    slots: u32,
+    next_slot: u32,
+    sequence: u32,
+}
+
+impl<F: NorFlash> ConfigStore<F> {
+    // The region must have at least two sectors, so the last record is never erased by the next save
+    pub fn new(flash: F, offset: u32, sectors: u32) -> Self {
+        debug_assert!(sectors >= 2 && F::ERASE_SIZE.is_multiple_of(RECORD_SIZE));
+        Self {
+            flash,
+            offset,
+            slots: sectors * (F::ERASE_SIZE / RECORD_SIZE) as u32,
+            next_slot: 0,
+            sequence: 0,
+        }
+    }
+
+    fn slot_address(&self, slot: u32) -> u32 {
+        self.offset + slot * RECORD_SIZE as u32
+    }
+
+    fn read(&mut self, address: u32, bytes: &mut [u8]) -> Result<(), ConfigError> {
+        self.flash.read(self.offset + address, bytes).map_err(flash_error)
+    }
+
+    fn read_slot(&mut self, slot: u32) -> Result<[u8; RECORD_SIZE], ConfigError> {
+        let mut record = [0u8; RECORD_SIZE];
+        self.read(slot * RECORD_SIZE as u32, &mut record)?;
+        Ok(record)
+    }
+
+    // Scan the region and return the newest valid configuration. It is read in steps of 64 bytes,
+    // the size of the records up to the version 3, so they are found before the first save migrates them.
+    pub fn load(&mut self, defaults: &Config) -> Result<Config, ConfigError> {
+        let mut newest: Option<(u32, u32, Config)> = None;
+        let region = self.slots * RECORD_SIZE as u32;
+        let mut address = 0;
+        while address < region {
+            let mut record = [0u8; RECORD_SIZE];
+            self.read(address, &mut record[..HEADER_LEN])?;
+            let size = match Config::record_size(record[..HEADER_LEN].try_into().unwrap()) {
+                // The records of 256 bytes only start in the beginning of a slot
+                Some(RECORD_SIZE) if !address.is_multiple_of(RECORD_SIZE as u32) => None,
+                size => size,
+            };
+            let Some(size) = size else {
+                address += LEGACY_RECORD_SIZE as u32;
+                continue;
+            };
+            self.read(address + HEADER_LEN as u32, &mut record[HEADER_LEN..size])?;
+            let Some((sequence, config)) = Config::from_record(&record[..size], defaults) else {
+                address += LEGACY_RECORD_SIZE as u32;
+                continue;
+            };
+            // The sequence can wrap around, the newest is the one ahead of the others
+            if newest.is_none_or(|(newest_sequence, _, _)| sequence.wrapping_sub(newest_sequence) as i32 > 0) {
+                newest = Some((sequence, address, config));
+            }
+            address += size as u32;
+        }
+
+        let (sequence, address, config) = newest.ok_or(ConfigError::NotFound)?;
+        self.sequence = sequence.wrapping_add(1);
+        // A record of 64 bytes shares its slot with other ones, the next save uses the following slot
+        self.next_slot = (address / RECORD_SIZE as u32 + 1) % self.slots;
+        Ok(config)
+    }
+
+    // Write the configuration in the next erased slot, erasing the next sector when the current one is full
+    pub fn save(&mut self, config: &Config) -> Result<(), ConfigError> {
+        let slots_per_sector = (F::ERASE_SIZE / RECORD_SIZE) as u32;
+        let mut slot = self.next_slot;
+        loop {
+            if slot.is_multiple_of(slots_per_sector) {
+                let start = self.slot_address(slot);
+                self.flash
+                    .erase(start, start + F::ERASE_SIZE as u32)
+                    .map_err(flash_error)?;
+                break;
+            }
+            // A slot written by a save cut by a power loss can not be written again until the erase
+            if self.read_slot(slot)?.iter().all(|&byte| byte == 0xFF) {
+                break;
+            }
+            slot = (slot + 1) % self.slots;
+        }
+
+        let record = config.to_record(self.sequence);
+        self.flash
+            .write(self.slot_address(slot), &record)
+            .map_err(flash_error)?;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.next_slot = (slot + 1) % self.slots;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_flash::{MemoryFlash, SECTOR_SIZE};
+    use crate::modular::alarm::{AlarmId, DEFAULT_ALARM_SETTINGS};
+
+    const DEFAULTS: Config = Config {
+        stored_setpoint_c: 28.0,
+        gains: PidGains { kp: 2.0, ki: 0.1, kd: 0.5 },
+        trip_high_c: 35.0,
+        trip_low_c: 18.0,
+        die_temp_offset_c: 0.0,
+        pwm_frequency_hz: 100_000,
+        telemetry_period_ms: 1_000,
+        humidity_day_pct: 70.0,
+        humidity_night_pct: 80.0,
+        pv_source: 0,
+        knob_enabled: false,
+        alarms: DEFAULT_ALARM_SETTINGS,
+    };
+
+    const SAVED: Config = Config {
+        stored_setpoint_c: 31.5,
+        gains: PidGains { kp: 4.0, ki: 0.25, kd: 1.0 },
+        trip_high_c: 38.0,
+        trip_low_c: 20.0,
+        die_temp_offset_c: -1.5,
+        pwm_frequency_hz: 20_000,
+        telemetry_period_ms: 500,
+        humidity_day_pct: 65.0,
+        humidity_night_pct: 90.0,
+        pv_source: 2,
+        knob_enabled: true,
+        alarms: SAVED_ALARMS,
+    };
+
+    const SAVED_ALARMS: [AlarmSettings; ALARM_COUNT] = {
+        let mut alarms = DEFAULT_ALARM_SETTINGS;
+        alarms[AlarmId::PvHigh as usize].threshold = Threshold::Above(34.5);
+        alarms[AlarmId::PvHigh as usize].delay_s = 0;
+        alarms[AlarmId::Deviation as usize].enabled = false;
+        alarms[AlarmId::SensorRange as usize].threshold = Threshold::Outside(-5.0, 45.0);
+        alarms[AlarmId::SensorRange as usize].latching = false;
+        alarms[AlarmId::DieHot as usize].hysteresis_c = 5.0;
+        alarms
+    };
+
+    fn store(flash: &mut MemoryFlash) -> ConfigStore<&mut MemoryFlash> {
+        let sectors = (flash.bytes.len() / SECTOR_SIZE) as u32;
+        ConfigStore::new(flash, 0, sectors)
+    }
+
+    // Record of the version 3, 64 bytes with the fields in a fixed order
+    fn legacy_record(sequence: u32, config: &Config) -> [u8; LEGACY_RECORD_SIZE] {
+        let mut payload = Vec::new();
+        for value in [
+            config.stored_setpoint_c.to_bits(),
+            config.gains.kp.to_bits(),
+            config.gains.ki.to_bits(),
+            config.gains.kd.to_bits(),
+            config.trip_high_c.to_bits(),
+            config.trip_low_c.to_bits(),
+            config.die_temp_offset_c.to_bits(),
+            config.pwm_frequency_hz,
+            config.telemetry_period_ms,
+            config.humidity_day_pct.to_bits(),
+            config.humidity_night_pct.to_bits(),
+            config.pv_source,
+        ] {
+            payload.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut record = [0xFF; LEGACY_RECORD_SIZE];
+        record[0..4].copy_from_slice(&RECORD_MAGIC.to_le_bytes());
+        record[4..6].copy_from_slice(&3u16.to_le_bytes());
+        record[6..8].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        record[8..12].copy_from_slice(&sequence.to_le_bytes());
+        record[HEADER_LEN..HEADER_LEN + payload.len()].copy_from_slice(&payload);
+        let crc = crc16(&record[..HEADER_LEN + payload.len()]);
+        record[HEADER_LEN + payload.len()..HEADER_LEN + payload.len() + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    // Record of the current version with the given payload
+    fn record_with_payload(payload: &[u8]) -> [u8; RECORD_SIZE] {
+        let mut record = SAVED.to_record(7);
+        record[6..8].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        record[HEADER_LEN..].fill(0xFF);
+        record[HEADER_LEN..HEADER_LEN + payload.len()].copy_from_slice(payload);
+        let crc = crc16(&record[..HEADER_LEN + payload.len()]);
+        record[HEADER_LEN + payload.len()..HEADER_LEN + payload.len() + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    #[test]
+    fn record_round_trip() {
+        let record = SAVED.to_record(42);
+        assert_eq!(Config::from_record(&record, &DEFAULTS), Some((42, SAVED)));
+    }
+
+    #[test]
+    fn payload_leaves_room_to_grow() {
+        // Space for the settings of a few more alarms and some other fields
+        let mut payload = [0u8; PAYLOAD_MAX_LEN];
+        assert!(SAVED.encode_payload(&mut payload) + 64 <= PAYLOAD_MAX_LEN);
+    }
+
+    #[test]
+    fn alarm_settings_round_trip() {
+        for settings in SAVED_ALARMS.iter().chain(&DEFAULT_ALARM_SETTINGS) {
+            assert_eq!(decode_alarm(&encode_alarm(settings)), Some(*settings));
+        }
+        // A kind of threshold that does not exist and a field of another length
+        let mut value = encode_alarm(&SAVED_ALARMS[0]);
+        value[0] |= 3 << THRESHOLD_SHIFT;
+        assert_eq!(decode_alarm(&value), None);
+        assert_eq!(decode_alarm(&value[..ALARM_FIELD_LEN - 1]), None);
+    }
+
+    #[test]
+    fn bad_alarm_settings_fall_back_to_the_defaults_of_that_alarm() {
+        let mut alarms = SAVED_ALARMS;
+        alarms[AlarmId::PvHigh as usize].threshold = Threshold::Below(30.0); // Direction changed
+        alarms[AlarmId::PvLow as usize].threshold = Threshold::Below(f32::NAN);
+        alarms[AlarmId::Deviation as usize].hysteresis_c = ALARM_HYSTERESIS_MAX_C + 1.0;
+        alarms[AlarmId::SensorRange as usize].threshold = Threshold::Outside(45.0, -5.0);
+        alarms[AlarmId::DieHot as usize].delay_s = ALARM_DELAY_MAX_S + 1;
+
+        let config = Config { alarms, ..SAVED }.validated(&DEFAULTS);
+        assert_eq!(config.alarms, DEFAULT_ALARM_SETTINGS);
+    }
+
+    #[test]
+    fn damaged_record_is_rejected() {
+        let mut record = SAVED.to_record(1);
+        record[HEADER_LEN + 3] ^= 0x01;
+        assert_eq!(Config::from_record(&record, &DEFAULTS), None);
+
+        let mut record = SAVED.to_record(1);
+        record[6..8].copy_from_slice(&(RECORD_SIZE as u16).to_le_bytes());
+        assert_eq!(Config::from_record(&record, &DEFAULTS), None);
+
+        assert_eq!(Config::from_record(&[0xFF; RECORD_SIZE], &DEFAULTS), None);
+    }
+
+    #[test]
+    fn missing_fields_keep_the_defaults_and_unknown_ones_are_skipped() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&[TAG_KP, 4]);
+        payload.extend_from_slice(&3.0f32.to_le_bytes());
+        // Field of a newer version
+        payload.extend_from_slice(&[200, 3, 1, 2, 3]);
+        // Known tag with a length it never had
+        payload.extend_from_slice(&[TAG_SETPOINT, 2, 0, 0]);
+        payload.extend_from_slice(&[TAG_PV_SOURCE, 4]);
+        payload.extend_from_slice(&1u32.to_le_bytes());
+
+        let (_, config) = Config::from_record(&record_with_payload(&payload), &DEFAULTS).unwrap();
+        let expected = Config {
+            gains: PidGains { kp: 3.0, ..DEFAULTS.gains },
+            pv_source: 1,
+            ..DEFAULTS
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn legacy_record_is_read() {
+        // The knob was always a source before the version 4, the default keeps it off
+        let record = legacy_record(9, &SAVED);
+        let expected = Config {
+            knob_enabled: DEFAULTS.knob_enabled,
+            alarms: DEFAULTS.alarms,
+            ..SAVED
+        };
+        assert_eq!(Config::from_record(&record, &DEFAULTS), Some((9, expected)));
+
+        // A version 1 record ends before the humidity and the sensor of the PV
+        let mut record = legacy_record(9, &SAVED);
+        let len = 9 * 4;
+        record[4..6].copy_from_slice(&1u16.to_le_bytes());
+        record[6..8].copy_from_slice(&(len as u16).to_le_bytes());
+        record[HEADER_LEN + len..].fill(0xFF);
+        let crc = crc16(&record[..HEADER_LEN + len]);
+        record[HEADER_LEN + len..HEADER_LEN + len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        let (_, config) = Config::from_record(&record, &DEFAULTS).unwrap();
+        assert_eq!(config.telemetry_period_ms, SAVED.telemetry_period_ms);
+        assert_eq!(config.humidity_day_pct, DEFAULTS.humidity_day_pct);
+        assert_eq!(config.pv_source, DEFAULTS.pv_source);
+    }
+
+    #[test]
+    fn values_out_of_range_fall_back_to_the_defaults() {
+        let broken = Config {
+            stored_setpoint_c: f32::NAN,
+            gains: PidGains { kp: -1.0, ki: f32::INFINITY, kd: 0.5 },
+            trip_high_c: 20.0,
+            trip_low_c: 30.0, // Above the high one
+            die_temp_offset_c: 50.0,
+            pwm_frequency_hz: 0,
+            telemetry_period_ms: 5,
+            humidity_day_pct: 150.0,
+            humidity_night_pct: 85.0,
+            pv_source: 9,
+            knob_enabled: false,
+            alarms: SAVED_ALARMS,
+        };
+        let expected = Config {
+            gains: PidGains { kd: 0.5, ..DEFAULTS.gains },
+            humidity_night_pct: 85.0,
+            alarms: SAVED_ALARMS,
+            ..DEFAULTS
+        };
+        assert_eq!(broken.validated(&DEFAULTS), expected);
+        assert_eq!(Config::from_record(&broken.to_record(3), &DEFAULTS), Some((3, expected)));
+        assert_eq!(SAVED.validated(&DEFAULTS), SAVED);
+    }
+
+    #[test]
+    fn empty_flash_has_no_configuration() {
+        let mut flash = MemoryFlash::new(2);
+        assert_eq!(store(&mut flash).load(&DEFAULTS), Err(ConfigError::NotFound));
+    }
+
+    #[test]
+    fn newest_save_is_loaded() {
+        let mut flash = MemoryFlash::new(2);
+        let mut config_store = store(&mut flash);
+        config_store.load(&DEFAULTS).unwrap_err();
+        config_store.save(&DEFAULTS).unwrap();
+        config_store.save(&SAVED).unwrap();
+
+        let mut reopened = store(&mut flash);
+        assert_eq!(reopened.load(&DEFAULTS), Ok(SAVED));
+    }
+
+    #[test]
+    fn saves_rotate_through_the_sectors() {
+        let mut flash = MemoryFlash::new(2);
+        let slots = 2 * SECTOR_SIZE / RECORD_SIZE;
+        for round in 0..3 * slots {
+            let mut config_store = store(&mut flash);
+            let _ = config_store.load(&DEFAULTS);
+            let config = Config {
+                telemetry_period_ms: 100 + round as u32,
+                ..SAVED
+            };
+            config_store.save(&config).unwrap();
+            assert_eq!(store(&mut flash).load(&DEFAULTS), Ok(config));
+        }
+        // Each sector is erased once for every pass over the region
+        assert_eq!(flash.erases, 6);
+    }
+
+    #[test]
+    fn save_cut_by_a_power_loss_keeps_the_previous_configuration() {
+        let mut flash = MemoryFlash::new(2);
+        store(&mut flash).save(&SAVED).unwrap();
+
+        // The next record was cut in the middle of the payload
+        let record = DEFAULTS.to_record(1);
+        let cut = HEADER_LEN + 20;
+        flash.bytes[RECORD_SIZE..RECORD_SIZE + cut].copy_from_slice(&record[..cut]);
+        let mut config_store = store(&mut flash);
+        assert_eq!(config_store.load(&DEFAULTS), Ok(SAVED));
+
+        // The damaged slot is not written again
+        let config = Config {
+            stored_setpoint_c: 25.0,
+            ..SAVED
+        };
+        config_store.save(&config).unwrap();
+        assert_eq!(store(&mut flash).load(&DEFAULTS), Ok(config));
+        assert_eq!(flash.bytes[RECORD_SIZE..RECORD_SIZE + cut], record[..cut]);
+    }
+
+    #[test]
+    fn legacy_records_are_migrated_by_the_next_save() {
+        let mut flash = MemoryFlash::new(2);
+        // Three saves of the version 3, the newest is in the middle of the second slot of 256 bytes
+        for (index, sequence) in [(0, 10u32), (4, 11), (5, 12)] {
+            let config = Config {
+                telemetry_period_ms: 100 * sequence,
+                ..SAVED
+            };
+            let address = index * LEGACY_RECORD_SIZE;
+            flash.bytes[address..address + LEGACY_RECORD_SIZE].copy_from_slice(&legacy_record(sequence, &config));
+        }
+
+        let mut config_store = store(&mut flash);
+        let loaded = config_store.load(&DEFAULTS).unwrap();
+        assert_eq!(loaded.telemetry_period_ms, 1_200);
+
+        let migrated = Config {
+            stored_setpoint_c: 26.0,
+            ..loaded
+        };
+        config_store.save(&migrated).unwrap();
+        // The record of the version 4 goes to the third slot, after the legacy ones
+        let header: [u8; HEADER_LEN] = flash.bytes[2 * RECORD_SIZE..2 * RECORD_SIZE + HEADER_LEN].try_into().unwrap();
+        assert_eq!(Config::record_size(&header), Some(RECORD_SIZE));
+        assert_eq!(store(&mut flash).load(&DEFAULTS), Ok(migrated));
+    }
+
+    // The three ways to set the trip limits share one range, a limit below 0 °C or above 50 °C written by
+    // the Modbus is not replaced by the default when the saved configuration is loaded
+    #[test]
+    fn trip_limits_written_over_the_modbus_survive_a_reload() {
+        use crate::modular::command::{Command, TripLimit, parse_command};
+        use crate::modular::modbus::{
+            HR_TRIP_HIGH, HR_TRIP_LOW, ModbusWrite, ModbusWrites, ProcessImage, from_x100, process_request,
+        };
+
+        let high = ((LIMIT_MAX_C * 100.0) as i16).to_be_bytes();
+        let low = ((LIMIT_MIN_C * 100.0) as i16).to_be_bytes();
+        // Write multiple registers, the high and the low limits
+        let pdu = [0x10, 0, HR_TRIP_HIGH as u8, 0, 2, 4, high[0], high[1], low[0], low[1]];
+        let mut writes = ModbusWrites::new();
+        process_request(&pdu, &ProcessImage::default(), &mut writes, &mut [0u8; 256]);
+        assert_eq!(writes.len(), 2);
+
+        let mut config = DEFAULTS;
+        for write in writes {
+            match write {
+                ModbusWrite::Register(HR_TRIP_HIGH, value) => config.trip_high_c = from_x100(value),
+                ModbusWrite::Register(HR_TRIP_LOW, value) => config.trip_low_c = from_x100(value),
+                write => panic!("unexpected write {write:?}"),
+            }
+        }
+        let mut flash = MemoryFlash::new(2);
+        store(&mut flash).save(&config).unwrap();
+        let loaded = store(&mut flash).load(&DEFAULTS).unwrap();
+        assert_eq!((loaded.trip_low_c, loaded.trip_high_c), (LIMIT_MIN_C, LIMIT_MAX_C));
+
+        // The console takes the same values
+        assert_eq!(
+            parse_command("set trip low -40"),
+            Ok(Some(Command::SetTrip(TripLimit::Low, loaded.trip_low_c)))
+        );
+        assert_eq!(
+            parse_command("set trip high 85"),
+            Ok(Some(Command::SetTrip(TripLimit::High, loaded.trip_high_c)))
+        );
+    }
+}
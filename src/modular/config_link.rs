@@ -0,0 +1,109 @@
+// Config link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Config link file for the modular project.
+ *  File        : config_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about to keep the configuration in the last 16K (4 sectors) of the
+ *      2 MB flash of the Pico, the partition is declared in the storage.rs.
+ *      The configuration is loaded in the boot, before the tasks start, and saved with the console
+ *      command "save" or the item "Save" of the menu using the values running in that moment, the
+ *      settings of the alarms included.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::mutex::Mutex;
+
+use crate::modular::adc::{die_temp_offset_c, set_die_temp_offset_c};
+use crate::modular::alarm::{
+    AlarmId, DEFAULT_ALARM_SETTINGS, DEFAULT_TRIP_LIMITS, TripLimits, alarm_settings, set_alarm_settings, set_trip_limits,
+    trip_limits,
+};
+use crate::modular::config::{Config, ConfigError, ConfigStore};
+use crate::modular::humidity::{DEFAULT_HUMIDITY_DAY_PCT, DEFAULT_HUMIDITY_NIGHT_PCT};
+use crate::modular::humidity_link::{mist_schedule, set_mist_targets};
+use crate::modular::control::{PWM_DEFAULT_FREQUENCY_HZ, PvSource};
+use crate::modular::pwm::{ControlStatus, DEFAULT_GAINS, pv_source, set_pv_source};
+use crate::modular::setpoint::DEFAULT_STORED_SETPOINT_C;
+use crate::modular::setpoint_link::knob_enabled;
+use crate::modular::storage::FlashPartition;
+use crate::modular::telemetry::TELEMETRY_DEFAULT_PERIOD_MS;
+use crate::modular::telemetry_link::{set_telemetry_period_ms, telemetry_period_ms};
+
+pub type SharedConfigStore = Mutex<ThreadModeRawMutex, ConfigStore<FlashPartition>>;
+
+pub const DEFAULT_CONFIG: Config = Config {
+    stored_setpoint_c: DEFAULT_STORED_SETPOINT_C,
+    gains: DEFAULT_GAINS,
+    trip_high_c: DEFAULT_TRIP_LIMITS.high_c,
+    trip_low_c: DEFAULT_TRIP_LIMITS.low_c,
+    die_temp_offset_c: 0.0,
+    pwm_frequency_hz: PWM_DEFAULT_FREQUENCY_HZ,
+    telemetry_period_ms: TELEMETRY_DEFAULT_PERIOD_MS,
+    humidity_day_pct: DEFAULT_HUMIDITY_DAY_PCT,
+    humidity_night_pct: DEFAULT_HUMIDITY_NIGHT_PCT,
+    pv_source: 0, // PvSource::Die
+    knob_enabled: false,
+    alarms: DEFAULT_ALARM_SETTINGS,
+};
+
+// Load the newest configuration, the defaults are used when the flash has no valid record
+pub fn load_config(store: &mut ConfigStore<FlashPartition>) -> Config {
+    match store.load(&DEFAULT_CONFIG) {
+        Ok(config) => {
+            info!("Configuration loaded from the flash");
+            config
+        }
+        Err(e) => {
+            warn!("Configuration not loaded ({}), using the defaults", Debug2Format(&e));
+            DEFAULT_CONFIG
+        }
+    }
+}
+
+// Apply the values that are not passed to the tasks when they are spawned
+pub fn apply_config(config: &Config) {
+    set_trip_limits(TripLimits {
+        high_c: config.trip_high_c,
+        low_c: config.trip_low_c,
+    });
+    set_die_temp_offset_c(config.die_temp_offset_c);
+    set_telemetry_period_ms(config.telemetry_period_ms);
+    set_mist_targets(config.humidity_day_pct, config.humidity_night_pct);
+    set_pv_source(PvSource::from_code(config.pv_source).unwrap_or(PvSource::Die));
+    for id in AlarmId::ALL {
+        set_alarm_settings(id, config.alarms[id as usize]);
+    }
+}
+
+// Save the values running now, the fields that can not be changed in runtime keep the saved value
+pub async fn save_config(store: &SharedConfigStore, status: &ControlStatus) -> Result<(), ConfigError> {
+    let mut store = store.lock().await;
+    let saved = store.load(&DEFAULT_CONFIG).unwrap_or(DEFAULT_CONFIG);
+    let limits = trip_limits();
+    let schedule = mist_schedule();
+
+    let config = Config {
+        stored_setpoint_c: status.setpoint.celsius,
+        gains: status.gains,
+        trip_high_c: limits.high_c,
+        trip_low_c: limits.low_c,
+        die_temp_offset_c: die_temp_offset_c(),
+        telemetry_period_ms: telemetry_period_ms(),
+        humidity_day_pct: schedule.day_pct,
+        humidity_night_pct: schedule.night_pct,
+        pv_source: pv_source().code(),
+        knob_enabled: knob_enabled(),
+        alarms: alarm_settings(),
+        ..saved
+    };
+    store.save(&config)
+}
@@ -0,0 +1,555 @@
+// Console file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Console file for the modular project.
+ *  File        : console.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-18
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the serial console in the USB port of the Pico (CDC-ACM),
+ *      it receives the line commands, forwards them to the setpoint and control tasks and answers
+ *      with "OK ..." or "ERR ..." so a script can check the result of each command.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::Write;
+
+use defmt::*; // For logging via RTT
+use embassy_rp::peripherals::USB;
+use embassy_rp::usb::Driver;
+use embassy_time::Timer;
+use embassy_usb::UsbDevice;
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use embassy_usb::driver::EndpointError;
+use heapless::{String, Vec};
+
+use crate::modular::bme280::Bme280Reading;
+use crate::modular::bme280_link::get_receiver_bme280;
+use crate::modular::board::reset_to_bootloader;
+use crate::modular::alarm::{
+    AlarmId, AlarmStatus, Threshold, TripLimits, acknowledge_alarms, alarm_settings, set_alarm_settings, set_trip_limits,
+    trip_limits,
+};
+use crate::modular::alarm_link::get_receiver_alarm_status;
+use crate::modular::command::{
+    AlarmSetting, Command, CommandError, Gain, HELP_TEXT, LineBuffer, ModeRequest, Parameter, Period, TripLimit, parse_command,
+};
+use crate::modular::config_link::{SharedConfigStore, save_config};
+use crate::modular::datalog::{LOG_CSV_HEADER, LogError, LogRecord};
+use crate::modular::datalog_link::{SharedDataLog, clear_data_log};
+use crate::modular::ds18b20_link::{DS18B20_MAX_PROBES, DS18B20_PROBE_NAMES, get_receiver_probe};
+use crate::modular::led::{led_brightness_pct, set_led_brightness_pct, show_bootloader};
+use crate::modular::humidity_link::{MistStatus, get_receiver_mist_status, mist_schedule, set_mist_targets, set_time_of_day_s, time_of_day_s};
+use crate::modular::i2c_bus::{I2C_DEVICES, i2c_recoveries, i2c_stats};
+use crate::modular::oled::{DisplayHealth, get_receiver_display_health};
+use crate::modular::pwm::{
+    ControlCommand, ControlMode, ControlStatus, control_period_ms, get_receiver_control_status, get_sender_control, loop_stats,
+    pv_source, set_control_period_ms, set_pv_source,
+};
+use crate::modular::setpoint::SetpointCommand;
+use crate::modular::setpoint_link::{get_sender_setpoint, knob_enabled};
+use crate::modular::sht3x::Sht3xReading;
+use crate::modular::sht3x_link::get_receiver_sht3x;
+use crate::modular::telemetry_link::{set_telemetry_period_ms, telemetry_period_ms};
+
+pub const CONSOLE_LINE_LEN: usize = 64;
+pub const CONSOLE_PACKET_SIZE: usize = 64;
+// Records copied from the log each time it is locked by the export
+const LOG_EXPORT_BATCH: usize = 16;
+
+type UsbDriver = Driver<'static, USB>;
+
+// Last values of the channels when the command arrived
+struct Snapshot {
+    status: Option<ControlStatus>,
+    mist: Option<MistStatus>,
+    probes: [Option<f32>; DS18B20_MAX_PROBES],
+    sht3x: Option<Sht3xReading>,
+    bme280: Option<Bme280Reading>,
+    display: Option<DisplayHealth>,
+    alarms: Option<AlarmStatus>,
+}
+
+// This task runs the USB stack, it must be running for any class of the device to work
+#[embassy_executor::task]
+pub async fn usb_task(mut usb: UsbDevice<'static, UsbDriver>) -> ! {
+    usb.run().await
+}
+
+// Write the text followed by CRLF, split in packets of the endpoint size
+async fn write_line(class: &mut CdcAcmClass<'static, UsbDriver>, text: &str) -> Result<(), EndpointError> {
+    let mut last_len = 0;
+    for chunk in text.as_bytes().chunks(CONSOLE_PACKET_SIZE).chain([b"\r\n".as_slice()]) {
+        class.write_packet(chunk).await?;
+        last_len = chunk.len();
+    }
+    // A full packet needs a zero length packet so the host knows the transfer ended
+    if last_len == CONSOLE_PACKET_SIZE {
+        class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
+
+fn write_status(reply: &mut String<256>, status: &ControlStatus) -> core::fmt::Result {
+    core::write!(
+        reply,
+        "OK pv={} sp={} src={} mode={} out={} p={} i={} d={} kp={} ki={} kd={}",
+        status.pv,
+        status.setpoint.celsius,
+        status.setpoint.source.name(),
+        status.mode.name(),
+        status.terms.output,
+        status.terms.p,
+        status.terms.i,
+        status.terms.d,
+        status.gains.kp,
+        status.gains.ki,
+        status.gains.kd
+    )
+}
+
+fn write_mist_status(reply: &mut String<256>, mist: &MistStatus) -> core::fmt::Result {
+    let schedule = mist_schedule();
+    core::write!(reply, "OK rh=")?;
+    match mist.humidity {
+        Some(humidity) => core::write!(reply, "{}", humidity)?,
+        None => core::write!(reply, "--")?,
+    }
+    core::write!(
+        reply,
+        " target={} day={} night={} mister={} used_s={}",
+        mist.decision.target_pct,
+        schedule.day_pct,
+        schedule.night_pct,
+        if mist.decision.on { "on" } else { "off" },
+        mist.decision.used_s
+    )?;
+    if let Some(block) = mist.decision.blocked {
+        core::write!(reply, " blocked={}", block.name())?;
+    }
+    Ok(())
+}
+
+fn write_probes(reply: &mut String<256>, probes: &[Option<f32>; DS18B20_MAX_PROBES]) -> core::fmt::Result {
+    core::write!(reply, "OK")?;
+    for (name, celsius) in DS18B20_PROBE_NAMES.iter().zip(probes) {
+        match celsius {
+            Some(celsius) => core::write!(reply, " {}={}", name, celsius)?,
+            None => core::write!(reply, " {}=--", name)?,
+        }
+    }
+    Ok(())
+}
+
+// Transfers, errors and NACKs of each device, the NACKs are also counted in the errors
+fn write_i2c_stats(reply: &mut String<256>) -> core::fmt::Result {
+    core::write!(reply, "OK")?;
+    for device in I2C_DEVICES {
+        let stats = i2c_stats(device);
+        core::write!(reply, " {}={}/{}/{}", device.name(), stats.transfers, stats.errors, stats.nacks)?;
+    }
+    Ok(())
+}
+
+// Timing of the control loop, the times in microseconds
+fn write_loop_stats(reply: &mut String<256>) -> core::fmt::Result {
+    let stats = loop_stats();
+    core::write!(
+        reply,
+        "OK period={}ms last={} min={} max={} jitter={} exec={} max_exec={} overruns={} cycles={}",
+        control_period_ms(),
+        stats.last_period_us,
+        stats.min_period_us,
+        stats.max_period_us,
+        stats.jitter_us,
+        stats.exec_us,
+        stats.max_exec_us,
+        stats.overruns,
+        stats.cycles
+    )
+}
+
+fn write_env(reply: &mut String<256>, snapshot: &Snapshot) -> core::fmt::Result {
+    match snapshot.sht3x {
+        Some(reading) => core::write!(reply, "OK sht3x={}/{}", reading.temperature_c, reading.humidity_pct)?,
+        None => core::write!(reply, "OK sht3x=--")?,
+    }
+    match snapshot.bme280 {
+        Some(reading) => core::write!(
+            reply,
+            " bme280={}/{}/{}",
+            reading.temperature_c,
+            reading.humidity_pct,
+            reading.pressure_hpa
+        ),
+        None => core::write!(reply, " bme280=--"),
+    }
+}
+
+fn write_alarms(reply: &mut String<256>, alarms: &AlarmStatus) -> core::fmt::Result {
+    core::write!(reply, "OK")?;
+    for id in AlarmId::ALL {
+        core::write!(reply, " {}={}", id.name(), alarms.state(id).name())?;
+    }
+    Ok(())
+}
+
+fn write_alarm_settings(reply: &mut String<256>, id: AlarmId) -> core::fmt::Result {
+    let settings = alarm_settings()[id as usize];
+    match settings.threshold {
+        Threshold::Above(limit) => core::write!(reply, "OK above={}", limit)?,
+        Threshold::Below(limit) => core::write!(reply, "OK below={}", limit)?,
+        Threshold::Outside(low, high) => core::write!(reply, "OK outside={}/{}", low, high)?,
+    }
+    core::write!(
+        reply,
+        " hyst={} delay={} latch={} enable={}",
+        settings.hysteresis_c,
+        settings.delay_s,
+        if settings.latching { "on" } else { "off" },
+        if settings.enabled { "on" } else { "off" }
+    )
+}
+
+// Change one setting of an alarm, the limit keeps the direction of the threshold
+fn apply_alarm_setting(id: AlarmId, setting: AlarmSetting) -> Result<(), &'static str> {
+    let mut settings = alarm_settings()[id as usize];
+    match setting {
+        AlarmSetting::Limit(limit) => {
+            settings.threshold = match settings.threshold {
+                Threshold::Above(_) => Threshold::Above(limit),
+                Threshold::Below(_) => Threshold::Below(limit),
+                Threshold::Outside(..) => return Err("the limit of this alarm is a range"),
+            }
+        }
+        AlarmSetting::Range(low, high) if low < high => settings.threshold = Threshold::Outside(low, high),
+        AlarmSetting::Range(..) => return Err("low limit must be below the high limit"),
+        AlarmSetting::Hysteresis(hysteresis_c) => settings.hysteresis_c = hysteresis_c,
+        AlarmSetting::Delay(delay_s) => settings.delay_s = delay_s,
+        AlarmSetting::Latching(latching) => settings.latching = latching,
+        AlarmSetting::Enabled(enabled) => settings.enabled = enabled,
+    }
+    set_alarm_settings(id, settings);
+    Ok(())
+}
+
+// Execute the command and write the answer in the reply
+async fn execute(
+    command: Command,
+    snapshot: &Snapshot,
+    config_store: &SharedConfigStore,
+    data_log: &SharedDataLog,
+    reply: &mut String<256>,
+) -> core::fmt::Result {
+    let status = snapshot.status;
+    match command {
+        Command::Get(parameter) => match (parameter, status) {
+            (Parameter::Rate, _) => core::write!(reply, "OK {}", telemetry_period_ms()),
+            (Parameter::Clock, _) => {
+                let time = time_of_day_s();
+                core::write!(reply, "OK {:02}:{:02}", time / 3600, time / 60 % 60)
+            }
+            (Parameter::Probes, _) => write_probes(reply, &snapshot.probes),
+            (Parameter::Env, _) => write_env(reply, snapshot),
+            (Parameter::I2c, _) => write_i2c_stats(reply),
+            (Parameter::Trip, _) => {
+                let limits = trip_limits();
+                core::write!(reply, "OK low={} high={}", limits.low_c, limits.high_c)
+            }
+            (Parameter::Alarms, _) => match snapshot.alarms {
+                Some(alarms) => write_alarms(reply, &alarms),
+                None => core::write!(reply, "ERR alarm task not running"),
+            },
+            (Parameter::Loop, _) => write_loop_stats(reply),
+            (Parameter::Led, _) => core::write!(reply, "OK {}", led_brightness_pct()),
+            (Parameter::Knob, _) => core::write!(reply, "OK {}", if knob_enabled() { "on" } else { "off" }),
+            (Parameter::Display, _) => {
+                let health = snapshot.display.map_or("--", DisplayHealth::name);
+                core::write!(reply, "OK {} recoveries={}", health, i2c_recoveries())
+            }
+            (Parameter::Humidity, _) => match snapshot.mist {
+                Some(mist) => write_mist_status(reply, &mist),
+                None => core::write!(reply, "ERR humidity loop not running"),
+            },
+            // The parser only gives the settings of an alarm with its name
+            (Parameter::Alarm, _) => core::write!(reply, "ERR {}", CommandError::MissingArgument.message()),
+            (_, None) => core::write!(reply, "ERR control loop not running"),
+            (Parameter::Temp, Some(status)) => core::write!(reply, "OK {}", status.pv),
+            (Parameter::Setpoint, Some(status)) => {
+                core::write!(reply, "OK {} {}", status.setpoint.celsius, status.setpoint.source.name())
+            }
+            (Parameter::Kp, Some(status)) => core::write!(reply, "OK {}", status.gains.kp),
+            (Parameter::Ki, Some(status)) => core::write!(reply, "OK {}", status.gains.ki),
+            (Parameter::Kd, Some(status)) => core::write!(reply, "OK {}", status.gains.kd),
+            (Parameter::Output, Some(status)) => core::write!(reply, "OK {}", status.terms.output),
+            (Parameter::Mode, Some(status)) => core::write!(reply, "OK {}", status.mode.name()),
+            // The selected sensor and the one in use, the die replaces a sensor that is not answering
+            (Parameter::Pv, Some(status)) => {
+                core::write!(reply, "OK {} {}", pv_source().name(), status.pv_source.name())
+            }
+        },
+        Command::SetSetpoint(celsius) => {
+            get_sender_setpoint().send(SetpointCommand::Remote(celsius)).await;
+            core::write!(reply, "OK")
+        }
+        Command::ReleaseSetpoint => {
+            get_sender_setpoint().send(SetpointCommand::ReleaseRemote).await;
+            core::write!(reply, "OK")
+        }
+        Command::SetGain(gain, value) => {
+            let command = match gain {
+                Gain::Kp => ControlCommand::SetKp(value),
+                Gain::Ki => ControlCommand::SetKi(value),
+                Gain::Kd => ControlCommand::SetKd(value),
+            };
+            get_sender_control().send(command).await;
+            core::write!(reply, "OK")
+        }
+        Command::SetRate(period_ms) => {
+            set_telemetry_period_ms(period_ms);
+            core::write!(reply, "OK")
+        }
+        Command::SetHumidity(period, pct) => {
+            let schedule = mist_schedule();
+            match period {
+                Period::Day => set_mist_targets(pct, schedule.night_pct),
+                Period::Night => set_mist_targets(schedule.day_pct, pct),
+            }
+            core::write!(reply, "OK")
+        }
+        Command::SetClock(time_of_day_s) => {
+            set_time_of_day_s(time_of_day_s);
+            core::write!(reply, "OK")
+        }
+        Command::SetPvSource(source) => {
+            set_pv_source(source);
+            core::write!(reply, "OK")
+        }
+        Command::SetLoopPeriod(period_ms) => {
+            set_control_period_ms(period_ms);
+            core::write!(reply, "OK")
+        }
+        Command::SetKnob(enabled) => {
+            get_sender_setpoint().send(SetpointCommand::KnobEnabled(enabled)).await;
+            core::write!(reply, "OK")
+        }
+        Command::SetLedBrightness(pct) => {
+            set_led_brightness_pct(pct);
+            core::write!(reply, "OK")
+        }
+        // The low limit must stay below the high one
+        Command::SetTrip(limit, celsius) => {
+            let limits = match limit {
+                TripLimit::Low => TripLimits {
+                    low_c: celsius,
+                    ..trip_limits()
+                },
+                TripLimit::High => TripLimits {
+                    high_c: celsius,
+                    ..trip_limits()
+                },
+            };
+            if limits.low_c < limits.high_c {
+                set_trip_limits(limits);
+                core::write!(reply, "OK")
+            } else {
+                core::write!(reply, "ERR low limit must be below the high limit")
+            }
+        }
+        Command::GetAlarm(id) => write_alarm_settings(reply, id),
+        Command::SetAlarmSetting(id, setting) => match apply_alarm_setting(id, setting) {
+            Ok(()) => core::write!(reply, "OK"),
+            Err(message) => core::write!(reply, "ERR {}", message),
+        },
+        Command::Acknowledge => {
+            acknowledge_alarms();
+            core::write!(reply, "OK")
+        }
+        Command::Mode(mode) => {
+            let mode = match mode {
+                ModeRequest::Auto => ControlMode::Auto,
+                ModeRequest::Manual(output) => ControlMode::Manual(output),
+                ModeRequest::Off => ControlMode::Off,
+            };
+            get_sender_control().send(ControlCommand::Mode(mode)).await;
+            core::write!(reply, "OK")
+        }
+        // The export is streamed by the console task, it does not fit in one reply
+        Command::LogExport => core::write!(reply, "OK"),
+        Command::LogClear => match clear_data_log(data_log).await {
+            Ok(()) => core::write!(reply, "OK log cleared"),
+            Err(e) => {
+                error!("Data log clear failed: {}", Debug2Format(&e));
+                core::write!(reply, "ERR flash erase failed")
+            }
+        },
+        Command::Status => match status {
+            Some(status) => write_status(reply, &status),
+            None => core::write!(reply, "ERR control loop not running"),
+        },
+        Command::Save => match status {
+            Some(status) => match save_config(config_store, &status).await {
+                Ok(()) => core::write!(reply, "OK saved"),
+                Err(e) => {
+                    error!("Configuration save failed: {}", Debug2Format(&e));
+                    core::write!(reply, "ERR flash write failed")
+                }
+            },
+            None => core::write!(reply, "ERR control loop not running"),
+        },
+        Command::Reboot => core::write!(reply, "OK rebooting"),
+        Command::Bootloader => core::write!(reply, "OK rebooting into the bootloader"),
+        // The help is streamed by the console task as well
+        Command::Help => core::write!(reply, "OK"),
+    }
+}
+
+// Send the help one line at a time, the whole text is longer than a reply
+async fn write_help(class: &mut CdcAcmClass<'static, UsbDriver>) -> Result<(), EndpointError> {
+    for line in HELP_TEXT.split("\r\n") {
+        write_line(class, line).await?;
+    }
+    Ok(())
+}
+
+// Send the data log as CSV, one record per line, followed by the number of records. The records are copied
+// in batches and the log is released while a batch is sent, the log task keeps appending meanwhile
+async fn export_log(class: &mut CdcAcmClass<'static, UsbDriver>, data_log: &SharedDataLog) -> Result<(), EndpointError> {
+    let mut cursor = data_log.lock().await.cursor();
+    let mut line: String<96> = String::new();
+    let mut count: u32 = 0;
+
+    write_line(class, LOG_CSV_HEADER).await?;
+    loop {
+        let mut batch: Vec<LogRecord, LOG_EXPORT_BATCH> = Vec::new();
+        let read = {
+            let mut log = data_log.lock().await;
+            let mut records = log.records_from(cursor);
+            let read: Result<(), LogError> = records.by_ref().take(LOG_EXPORT_BATCH).try_for_each(|record| {
+                unwrap!(batch.push(record?).ok());
+                Ok(())
+            });
+            cursor = records.cursor();
+            read
+        };
+        if let Err(e) = read {
+            error!("Data log read failed: {}", Debug2Format(&e));
+            return write_line(class, "ERR flash read failed").await;
+        }
+        if batch.is_empty() {
+            break;
+        }
+
+        for record in &batch {
+            line.clear();
+            if record.write_csv(&mut line).is_err() {
+                continue;
+            }
+            count += 1;
+            write_line(class, &line).await?;
+        }
+    }
+
+    line.clear();
+    let _ = core::write!(line, "OK {} records", count);
+    write_line(class, &line).await
+}
+
+// This task answers the commands received through the USB serial port
+#[embassy_executor::task]
+pub async fn console_task(
+    mut class: CdcAcmClass<'static, UsbDriver>,
+    config_store: &'static SharedConfigStore,
+    data_log: &'static SharedDataLog,
+) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let mut rx_mist = get_receiver_mist_status().unwrap();
+    let mut rx_probes: [_; DS18B20_MAX_PROBES] = core::array::from_fn(|index| get_receiver_probe(index).unwrap());
+    let mut rx_sht3x = get_receiver_sht3x().unwrap();
+    let mut rx_bme280 = get_receiver_bme280().unwrap();
+    let mut rx_display = get_receiver_display_health().unwrap();
+    let mut rx_alarms = get_receiver_alarm_status().unwrap();
+    let mut line: LineBuffer<CONSOLE_LINE_LEN> = LineBuffer::new();
+    let mut packet = [0u8; CONSOLE_PACKET_SIZE];
+
+    loop {
+        class.wait_connection().await;
+        info!("Console connected");
+
+        'connected: loop {
+            let len = match class.read_packet(&mut packet).await {
+                Ok(len) => len,
+                Err(_) => break 'connected,
+            };
+
+            for &byte in &packet[..len] {
+                let parsed = match line.push(byte) {
+                    None => continue,
+                    Some(Err(e)) => Err(e),
+                    Some(Ok(text)) => parse_command(text),
+                };
+
+                let mut reply: String<256> = String::new();
+                let mut reboot = false;
+                let mut bootloader = false;
+                let written = match parsed {
+                    Ok(None) => continue,
+                    Ok(Some(Command::LogExport)) => {
+                        info!("Console command: {}", Command::LogExport);
+                        if export_log(&mut class, data_log).await.is_err() {
+                            break 'connected;
+                        }
+                        continue;
+                    }
+                    Ok(Some(Command::Help)) => {
+                        if write_help(&mut class).await.is_err() {
+                            break 'connected;
+                        }
+                        continue;
+                    }
+                    Ok(Some(command)) => {
+                        info!("Console command: {}", command);
+                        reboot = command == Command::Reboot;
+                        bootloader = command == Command::Bootloader;
+                        let snapshot = Snapshot {
+                            status: rx_status.try_get(),
+                            mist: rx_mist.try_get(),
+                            probes: core::array::from_fn(|index| rx_probes[index].try_get()),
+                            sht3x: rx_sht3x.try_get(),
+                            bme280: rx_bme280.try_get(),
+                            display: rx_display.try_get(),
+                            alarms: rx_alarms.try_get(),
+                        };
+                        execute(command, &snapshot, config_store, data_log, &mut reply).await
+                    }
+                    Err(e) => {
+                        warn!("Console error: {}", e);
+                        core::write!(reply, "ERR {}", e.message())
+                    }
+                };
+                if written.is_err() {
+                    reply.clear();
+                    let _ = core::write!(reply, "ERR reply too long");
+                }
+
+                if write_line(&mut class, &reply).await.is_err() {
+                    break 'connected;
+                }
+                if reboot {
+                    Timer::after_millis(100).await; // Let the answer leave before the reset
+                    cortex_m::peripheral::SCB::sys_reset();
+                }
+                if bootloader {
+                    show_bootloader();
+                    Timer::after_millis(500).await; // Let the answer leave and the LED turn solid
+                    reset_to_bootloader();
+                }
+            }
+        }
+        info!("Console disconnected");
+    }
+}
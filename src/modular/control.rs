@@ -0,0 +1,59 @@
+// Control file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Control file for the modular project.
+ *  File        : control.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the settings of the control loop that do not depend on the
+ *      hardware: the limits of the period of the loop and of the frequency of the PWM, and the sensor
+ *      used as the process variable.
+ *      They are shared by the pwm.rs, the configuration and the parser of the commands.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::Format;
+
+pub const CONTROL_DEFAULT_PERIOD_MS: u32 = 1_000;
+pub const CONTROL_MIN_PERIOD_MS: u32 = 100;
+pub const CONTROL_MAX_PERIOD_MS: u32 = 10_000;
+
+// Frequency of the PWM of the heater, the top of the counter (clock / (frequency * 8) - 1) must fit
+// in 16 bits with the 125 MHz of the RP2040 and the 150 MHz of the RP2350
+pub const PWM_DEFAULT_FREQUENCY_HZ: u32 = 100_000;
+pub const PWM_MIN_FREQUENCY_HZ: u32 = 1_000;
+pub const PWM_MAX_FREQUENCY_HZ: u32 = 100_000;
+
+// Sensor used as the process variable, the die is always present and it is the fallback
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum PvSource {
+    Die,
+    Sht3x,
+    Bme280,
+}
+
+impl PvSource {
+    pub const ALL: [PvSource; 3] = [PvSource::Die, PvSource::Sht3x, PvSource::Bme280];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PvSource::Die => "die",
+            PvSource::Sht3x => "sht3x",
+            PvSource::Bme280 => "bme280",
+        }
+    }
+
+    // Code of the source in the configuration
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<PvSource> {
+        PvSource::ALL.get(code as usize).copied()
+    }
+}
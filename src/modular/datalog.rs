@@ -0,0 +1,493 @@
+// Data log file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Data log file for the modular project.
+ *  File        : datalog.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the circular log of the process in a region of whole sectors
+ *      of the flash. The records are written one after the other and, when the region is full, the
+ *      oldest sector is erased, so the log always keeps the last (sectors - 1) sectors of history.
+ *      Each record has a sequence number and a CRC-16, a record cut by a power loss is ignored.
+ *      The clear erases one sector in each step and the records are read from a cursor, so the caller
+ *      can release the log between the erases and between the batches of an export.
+ *
+ *      Record (32 bytes, little endian): sequence u32, boot u16, kind u8, mode u8, uptime_s u32,
+ *      pv f32, sp f32, output f32, faults u16, CRC-16/CCITT-FALSE of the previous bytes, 4 bytes erased.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::Write;
+
+use embedded_storage::nor_flash::{NorFlash, NorFlashError, NorFlashErrorKind};
+
+use super::telemetry::{MODE_AUTO, MODE_MANUAL, MODE_OFF, crc16};
+
+pub const LOG_RECORD_SIZE: usize = 32;
+const LOG_DATA_LEN: usize = 26; // Bytes covered by the CRC
+
+pub const LOG_KIND_BOOT: u8 = 0; // First record after the reset, the uptime starts again
+pub const LOG_KIND_SAMPLE: u8 = 1; // Average of the period of the log
+pub const LOG_KIND_FAULT: u8 = 2; // The fault flags changed
+
+pub const LOG_CSV_HEADER: &str = "sequence,boot,uptime_s,kind,mode,pv_c,sp_c,output_pct,faults";
+
+// Values written by the application, the sequence and the boot are added by the log
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct LogEntry {
+    pub kind: u8,
+    pub mode: u8, // MODE_* of the telemetry
+    pub uptime_s: u32,
+    pub pv: f32,
+    pub sp: f32,
+    pub output: f32,
+    pub faults: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LogRecord {
+    pub sequence: u32,
+    pub boot: u16, // Number of resets since the log was cleared
+    pub entry: LogEntry,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogError {
+    Flash(NorFlashErrorKind),
+}
+
+fn flash_error(error: impl NorFlashError) -> LogError {
+    LogError::Flash(error.kind())
+}
+
+fn kind_name(kind: u8) -> &'static str {
+    match kind {
+        LOG_KIND_BOOT => "boot",
+        LOG_KIND_SAMPLE => "sample",
+        LOG_KIND_FAULT => "fault",
+        _ => "unknown",
+    }
+}
+
+fn mode_name(mode: u8) -> &'static str {
+    match mode {
+        MODE_OFF => "off",
+        MODE_AUTO => "auto",
+        MODE_MANUAL => "manual",
+        _ => "unknown",
+    }
+}
+
+impl LogRecord {
+    pub fn to_bytes(self) -> [u8; LOG_RECORD_SIZE] {
+        let mut bytes = [0xFF; LOG_RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&self.sequence.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.boot.to_le_bytes());
+        bytes[6] = self.entry.kind;
+        bytes[7] = self.entry.mode;
+        bytes[8..12].copy_from_slice(&self.entry.uptime_s.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.entry.pv.to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.entry.sp.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.entry.output.to_le_bytes());
+        bytes[24..26].copy_from_slice(&self.entry.faults.to_le_bytes());
+        let crc = crc16(&bytes[..LOG_DATA_LEN]);
+        bytes[LOG_DATA_LEN..LOG_DATA_LEN + 2].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    // None for an erased slot or a record with a wrong CRC
+    pub fn from_bytes(bytes: &[u8; LOG_RECORD_SIZE]) -> Option<Self> {
+        let crc = u16::from_le_bytes([bytes[LOG_DATA_LEN], bytes[LOG_DATA_LEN + 1]]);
+        if crc != crc16(&bytes[..LOG_DATA_LEN]) {
+            return None;
+        }
+        let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+
+        Some(Self {
+            sequence: u32_at(0),
+            boot: u16::from_le_bytes([bytes[4], bytes[5]]),
+            entry: LogEntry {
+                kind: bytes[6],
+                mode: bytes[7],
+                uptime_s: u32_at(8),
+                pv: f32::from_bits(u32_at(12)),
+                sp: f32::from_bits(u32_at(16)),
+                output: f32::from_bits(u32_at(20)),
+                faults: u16::from_le_bytes([bytes[24], bytes[25]]),
+            },
+        })
+    }
+
+    // One line of the CSV, in the order of the LOG_CSV_HEADER
+    pub fn write_csv(&self, out: &mut impl Write) -> core::fmt::Result {
+        core::write!(
+            out,
+            "{},{},{},{},{},{:.2},{:.2},{:.1},{}",
+            self.sequence,
+            self.boot,
+            self.entry.uptime_s,
+            kind_name(self.entry.kind),
+            mode_name(self.entry.mode),
+            self.entry.pv,
+            self.entry.sp,
+            self.entry.output,
+            self.entry.faults
+        )
+    }
+}
+
+// Circular log of records in a region of whole sectors of the flash
+pub struct DataLog<F: NorFlash> {
+    flash: F,
+    slots: u32,
+    next_slot: u32,
+    sequence: u32,
+    boot: u16,
+}
+
+impl<F: NorFlash> DataLog<F> {
+    // The region must have at least two sectors, the sector in use is never erased by the wraparound
+    pub fn new(flash: F, sectors: u32) -> Self {
+        debug_assert!(sectors >= 2 && F::ERASE_SIZE.is_multiple_of(LOG_RECORD_SIZE));
+        Self {
+            flash,
+            slots: sectors * (F::ERASE_SIZE / LOG_RECORD_SIZE) as u32,
+            next_slot: 0,
+            sequence: 0,
+            boot: 0,
+        }
+    }
+
+    fn slots_per_sector(&self) -> u32 {
+        (F::ERASE_SIZE / LOG_RECORD_SIZE) as u32
+    }
+
+    fn read_slot(&mut self, slot: u32) -> Result<[u8; LOG_RECORD_SIZE], LogError> {
+        let mut bytes = [0u8; LOG_RECORD_SIZE];
+        self.flash
+            .read(slot * LOG_RECORD_SIZE as u32, &mut bytes)
+            .map_err(flash_error)?;
+        Ok(bytes)
+    }
+
+    // Find the end of the log after the reset, the new records belong to the next boot
+    pub fn open(&mut self) -> Result<(), LogError> {
+        let mut newest: Option<(u32, LogRecord)> = None;
+        for slot in 0..self.slots {
+            let Some(record) = LogRecord::from_bytes(&self.read_slot(slot)?) else {
+                continue;
+            };
+            // The sequence can wrap around, the newest is the one ahead of the others
+            if newest.is_none_or(|(_, newest)| record.sequence.wrapping_sub(newest.sequence) as i32 > 0) {
+                newest = Some((slot, record));
+            }
+        }
+
+        if let Some((slot, record)) = newest {
+            self.next_slot = (slot + 1) % self.slots;
+            self.sequence = record.sequence.wrapping_add(1);
+            self.boot = record.boot.wrapping_add(1);
+        }
+        Ok(())
+    }
+
+    pub fn boot(&self) -> u16 {
+        self.boot
+    }
+
+    // Write the entry in the next erased slot, erasing the oldest sector when the slot starts a sector
+    pub fn append(&mut self, entry: LogEntry) -> Result<(), LogError> {
+        let slots_per_sector = self.slots_per_sector();
+        let mut slot = self.next_slot;
+        loop {
+            if slot.is_multiple_of(slots_per_sector) {
+                let start = slot * LOG_RECORD_SIZE as u32;
+                self.flash
+                    .erase(start, start + F::ERASE_SIZE as u32)
+                    .map_err(flash_error)?;
+                break;
+            }
+            // A slot written by an append cut by a power loss can not be written again until the erase
+            if self.read_slot(slot)?.iter().all(|&byte| byte == 0xFF) {
+                break;
+            }
+            slot = (slot + 1) % self.slots;
+        }
+
+        let record = LogRecord {
+            sequence: self.sequence,
+            boot: self.boot,
+            entry,
+        };
+        self.flash
+            .write(slot * LOG_RECORD_SIZE as u32, &record.to_bytes())
+            .map_err(flash_error)?;
+        self.sequence = self.sequence.wrapping_add(1);
+        self.next_slot = (slot + 1) % self.slots;
+        Ok(())
+    }
+
+    pub fn sectors(&self) -> u32 {
+        self.slots / self.slots_per_sector()
+    }
+
+    // One step of the clear, the sectors are erased from the first one and the sequence keeps counting.
+    // The first step moves the end of the log to the first slot, a record appended during the clear goes
+    // to the first sector and it is kept.
+    pub fn clear_sector(&mut self, sector: u32) -> Result<(), LogError> {
+        let start = sector * F::ERASE_SIZE as u32;
+        self.flash
+            .erase(start, start + F::ERASE_SIZE as u32)
+            .map_err(flash_error)?;
+        if sector == 0 {
+            self.next_slot = 0;
+            self.boot = 0;
+        }
+        Ok(())
+    }
+
+    // Position of the oldest record, after the next slot come the rest of the sector (erased), the older
+    // sectors and the sector in use
+    pub fn cursor(&self) -> LogCursor {
+        LogCursor {
+            slot: self.next_slot,
+            remaining: self.slots,
+        }
+    }
+
+    // Read the records from the cursor to the newest, the erased and corrupted slots are skipped
+    pub fn records_from(&mut self, cursor: LogCursor) -> LogRecords<'_, F> {
+        LogRecords { log: self, cursor }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LogCursor {
+    slot: u32,
+    remaining: u32,
+}
+
+pub struct LogRecords<'a, F: NorFlash> {
+    log: &'a mut DataLog<F>,
+    cursor: LogCursor,
+}
+
+impl<F: NorFlash> LogRecords<'_, F> {
+    // Where the next read starts, to continue after the log was released
+    pub fn cursor(&self) -> LogCursor {
+        self.cursor
+    }
+}
+
+impl<F: NorFlash> Iterator for LogRecords<'_, F> {
+    type Item = Result<LogRecord, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.cursor.remaining > 0 {
+            let slot = self.cursor.slot;
+            self.cursor.slot = (slot + 1) % self.log.slots;
+            self.cursor.remaining -= 1;
+            match self.log.read_slot(slot) {
+                Ok(bytes) => {
+                    if let Some(record) = LogRecord::from_bytes(&bytes) {
+                        return Some(Ok(record));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_flash::{MemoryFlash, SECTOR_SIZE};
+
+    const SLOTS_PER_SECTOR: u32 = (SECTOR_SIZE / LOG_RECORD_SIZE) as u32;
+
+    fn sample(uptime_s: u32) -> LogEntry {
+        LogEntry {
+            kind: LOG_KIND_SAMPLE,
+            mode: MODE_AUTO,
+            uptime_s,
+            pv: 27.25,
+            sp: 28.0,
+            output: 42.5,
+            faults: 0,
+        }
+    }
+
+    fn opened(flash: &mut MemoryFlash) -> DataLog<&mut MemoryFlash> {
+        let sectors = (flash.bytes.len() / SECTOR_SIZE) as u32;
+        let mut log = DataLog::new(flash, sectors);
+        log.open().unwrap();
+        log
+    }
+
+    fn uptimes(log: &mut DataLog<&mut MemoryFlash>) -> Vec<u32> {
+        log.records_from(log.cursor()).map(|record| record.unwrap().entry.uptime_s).collect()
+    }
+
+    #[test]
+    fn record_round_trip_and_csv() {
+        let record = LogRecord {
+            sequence: 12,
+            boot: 3,
+            entry: sample(600),
+        };
+        let bytes = record.to_bytes();
+        assert_eq!(LogRecord::from_bytes(&bytes), Some(record));
+
+        let mut line = heapless::String::<96>::new();
+        record.write_csv(&mut line).unwrap();
+        assert_eq!(line.as_str(), "12,3,600,sample,auto,27.25,28.00,42.5,0");
+        assert_eq!(line.split(',').count(), LOG_CSV_HEADER.split(',').count());
+
+        let mut damaged = bytes;
+        damaged[14] ^= 0x01;
+        assert_eq!(LogRecord::from_bytes(&damaged), None);
+        assert_eq!(LogRecord::from_bytes(&[0xFF; LOG_RECORD_SIZE]), None);
+    }
+
+    #[test]
+    fn records_survive_the_reset() {
+        let mut flash = MemoryFlash::new(2);
+        let mut log = opened(&mut flash);
+        assert_eq!(log.boot(), 0);
+        for uptime_s in 0..3 {
+            log.append(sample(uptime_s)).unwrap();
+        }
+
+        let mut log = opened(&mut flash);
+        assert_eq!(log.boot(), 1);
+        log.append(sample(100)).unwrap();
+
+        let records: Vec<_> = log.records_from(log.cursor()).map(Result::unwrap).collect();
+        let sequences: Vec<_> = records.iter().map(|record| (record.sequence, record.boot)).collect();
+        assert_eq!(sequences, [(0, 0), (1, 0), (2, 0), (3, 1)]);
+    }
+
+    #[test]
+    fn oldest_sector_is_erased_when_the_region_is_full() {
+        let mut flash = MemoryFlash::new(3);
+        let mut log = opened(&mut flash);
+        let total = 3 * SLOTS_PER_SECTOR + 10;
+        for uptime_s in 0..total {
+            log.append(sample(uptime_s)).unwrap();
+        }
+
+        // The first sector holds the 10 newest, the oldest kept are the start of the second sector
+        let kept = uptimes(&mut log);
+        assert_eq!(kept.len() as u32, 2 * SLOTS_PER_SECTOR + 10);
+        assert_eq!(kept.first(), Some(&SLOTS_PER_SECTOR));
+        assert_eq!(kept.last(), Some(&(total - 1)));
+        assert!(kept.windows(2).all(|pair| pair[1] == pair[0] + 1));
+
+        // The next boot continues after the newest record
+        let mut log = opened(&mut flash);
+        log.append(sample(total)).unwrap();
+        assert_eq!(uptimes(&mut log).last(), Some(&total));
+    }
+
+    #[test]
+    fn record_cut_by_a_power_loss_is_skipped() {
+        let mut flash = MemoryFlash::new(2);
+        let mut log = opened(&mut flash);
+        log.append(sample(1)).unwrap();
+        log.append(sample(2)).unwrap();
+
+        // Half of the third record reached the flash
+        let cut = LogRecord {
+            sequence: 2,
+            boot: 0,
+            entry: sample(3),
+        }
+        .to_bytes();
+        let offset = 2 * LOG_RECORD_SIZE;
+        flash.bytes[offset..offset + 16].copy_from_slice(&cut[..16]);
+
+        let mut log = opened(&mut flash);
+        log.append(sample(4)).unwrap();
+        assert_eq!(uptimes(&mut log), [1, 2, 4]);
+    }
+
+    #[test]
+    fn newest_record_is_found_across_the_wrap_of_the_sequence() {
+        let mut flash = MemoryFlash::new(2);
+        let sectors = (flash.bytes.len() / SECTOR_SIZE) as u32;
+        let mut log = DataLog::new(&mut flash, sectors);
+        log.sequence = u32::MAX - 1;
+        for uptime_s in 0..4 {
+            log.append(sample(uptime_s)).unwrap();
+        }
+
+        let log = opened(&mut flash);
+        assert_eq!(log.sequence, 2);
+        assert_eq!(log.next_slot, 4);
+    }
+
+    fn clear(log: &mut DataLog<&mut MemoryFlash>) {
+        for sector in 0..log.sectors() {
+            log.clear_sector(sector).unwrap();
+        }
+    }
+
+    #[test]
+    fn clear_erases_the_region() {
+        let mut flash = MemoryFlash::new(2);
+        let mut log = opened(&mut flash);
+        log.append(sample(1)).unwrap();
+        clear(&mut log);
+        assert_eq!(log.records_from(log.cursor()).count(), 0);
+
+        log.append(sample(2)).unwrap();
+        let record = log.records_from(log.cursor()).next().unwrap().unwrap();
+        assert_eq!((record.sequence, record.boot), (1, 0));
+    }
+
+    #[test]
+    fn record_appended_during_the_clear_is_kept() {
+        let mut flash = MemoryFlash::new(3);
+        let mut log = opened(&mut flash);
+        for uptime_s in 0..2 * SLOTS_PER_SECTOR {
+            log.append(sample(uptime_s)).unwrap();
+        }
+
+        log.clear_sector(0).unwrap();
+        log.append(sample(1_000)).unwrap();
+        log.clear_sector(1).unwrap();
+        log.clear_sector(2).unwrap();
+        assert_eq!(uptimes(&mut log), [1_000]);
+    }
+
+    #[test]
+    fn export_continues_from_the_cursor() {
+        let mut flash = MemoryFlash::new(2);
+        let mut log = opened(&mut flash);
+        for uptime_s in 0..10 {
+            log.append(sample(uptime_s)).unwrap();
+        }
+
+        // Batches of 4 records, the log is free between them and a new record is not part of the export
+        let mut cursor = log.cursor();
+        let mut exported = Vec::new();
+        loop {
+            let mut records = log.records_from(cursor);
+            let batch: Vec<_> = records.by_ref().take(4).map(|record| record.unwrap().entry.uptime_s).collect();
+            cursor = records.cursor();
+            if batch.is_empty() {
+                break;
+            }
+            exported.extend(batch);
+            log.append(sample(100)).unwrap();
+        }
+        assert_eq!(exported, (0..10).collect::<Vec<_>>());
+    }
+}
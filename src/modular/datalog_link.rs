@@ -0,0 +1,106 @@
+// Data log link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Data log link file for the modular project.
+ *  File        : datalog_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about to record the history of the control loop in the data log of the
+ *      flash (256K, about 5 days with one sample per minute). The PV and the output are averaged in the
+ *      period of the log, and every change of the fault flags is recorded at once as a fault event.
+ *      The log is downloaded as CSV with the console command "log" and erased with "log clear".
+ *      The clear erases one sector at a time and releases the log between the sectors, so an erase of
+ *      the whole region never holds the log or the flash for seconds.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_futures::yield_now;
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::Instant;
+
+use crate::modular::datalog::{DataLog, LOG_KIND_BOOT, LOG_KIND_FAULT, LOG_KIND_SAMPLE, LogEntry, LogError};
+use crate::modular::pwm::{ControlStatus, get_receiver_control_status};
+use crate::modular::storage::FlashPartition;
+
+pub const LOG_PERIOD_S: u32 = 60;
+
+pub type SharedDataLog = Mutex<ThreadModeRawMutex, DataLog<FlashPartition>>;
+
+// Open the log in the boot, the history is kept even if the end of the log can not be found
+pub fn open_data_log(log: &mut DataLog<FlashPartition>) {
+    match log.open() {
+        Ok(()) => info!("Data log opened, boot {}", log.boot()),
+        Err(e) => error!("Data log not opened: {}", Debug2Format(&e)),
+    }
+}
+
+// Erase the log one sector at a time, the other tasks run and append between the sectors
+pub async fn clear_data_log(log: &SharedDataLog) -> Result<(), LogError> {
+    let sectors = log.lock().await.sectors();
+    for sector in 0..sectors {
+        log.lock().await.clear_sector(sector)?;
+        yield_now().await;
+    }
+    Ok(())
+}
+
+fn log_entry(kind: u8, status: &ControlStatus, pv: f32, output: f32) -> LogEntry {
+    LogEntry {
+        kind,
+        mode: status.mode.code(),
+        uptime_s: Instant::now().as_secs() as u32,
+        pv,
+        sp: status.setpoint.celsius,
+        output,
+        faults: status.faults,
+    }
+}
+
+async fn append(log: &SharedDataLog, entry: LogEntry) {
+    if let Err(e) = log.lock().await.append(entry) {
+        error!("Data log write failed: {}", Debug2Format(&e));
+    }
+}
+
+// This task records the control status in the data log
+#[embassy_executor::task]
+pub async fn datalog_task(log: &'static SharedDataLog) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+
+    let status = rx_status.get().await;
+    append(log, log_entry(LOG_KIND_BOOT, &status, status.pv, status.terms.output)).await;
+
+    let mut faults = status.faults;
+    let mut pv_sum = 0.0;
+    let mut output_sum = 0.0;
+    let mut samples: u32 = 0;
+    let mut period_start = Instant::now();
+
+    loop {
+        let status = rx_status.changed().await;
+        pv_sum += status.pv;
+        output_sum += status.terms.output;
+        samples += 1;
+
+        if status.faults != faults {
+            faults = status.faults;
+            append(log, log_entry(LOG_KIND_FAULT, &status, status.pv, status.terms.output)).await;
+        }
+
+        if period_start.elapsed().as_secs() >= LOG_PERIOD_S as u64 {
+            let count = samples as f32;
+            append(log, log_entry(LOG_KIND_SAMPLE, &status, pv_sum / count, output_sum / count)).await;
+            pv_sum = 0.0;
+            output_sum = 0.0;
+            samples = 0;
+            period_start = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// DHT file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : DHT file for the modular project.
+ *  File        : dht.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the frame of the DHT11 and the DHT22 (AM2302): 5 bytes with the
+ *      humidity, the temperature and the checksum (sum of the first 4 bytes). The DHT11 sends the
+ *      integer and the decimal parts, the DHT22 sends tenths, and the sign of the temperature is in
+ *      the bit 7 of the decimal part (DHT11) or in the bit 15 (DHT22). The timing of the sensors is
+ *      also here, the bus is read by the dht_link.rs.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::Format;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Format)]
+pub enum DhtKind {
+    #[allow(dead_code)] // Not chosen in the main.rs
+    Dht11,
+    Dht22,
+}
+
+impl DhtKind {
+    pub fn start_pulse_us(self) -> u32 {
+        match self {
+            DhtKind::Dht11 => 18_000,
+            DhtKind::Dht22 => 1_100,
+        }
+    }
+
+    pub fn min_interval_ms(self) -> u64 {
+        match self {
+            DhtKind::Dht11 => 1_000,
+            DhtKind::Dht22 => 2_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Format)]
+pub enum DhtError {
+    NoResponse, // Nothing received, the sensor is not connected
+    Timeout,    // The frame stopped in the middle
+    Checksum,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Format)]
+pub struct DhtReading {
+    pub temperature_c: f32,
+    pub humidity_pct: f32,
+}
+
+// Frame: humidity (2 bytes), temperature (2 bytes), checksum (sum of the 4 bytes)
+pub fn decode_frame(kind: DhtKind, frame: [u8; 5]) -> Result<DhtReading, DhtError> {
+    let sum = frame[..4].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    if sum != frame[4] {
+        return Err(DhtError::Checksum);
+    }
+
+    let (humidity_pct, temperature_c) = match kind {
+        // Integer and decimal parts, the bit 7 of the temperature decimal is the sign
+        DhtKind::Dht11 => (
+            frame[0] as f32 + frame[1] as f32 / 10.0,
+            frame[2] as f32 + (frame[3] & 0x7F) as f32 / 10.0,
+        ),
+        // Tenths, the bit 15 of the temperature is the sign
+        DhtKind::Dht22 => (
+            u16::from_be_bytes([frame[0], frame[1]]) as f32 / 10.0,
+            u16::from_be_bytes([frame[2] & 0x7F, frame[3]]) as f32 / 10.0,
+        ),
+    };
+    let negative = match kind {
+        DhtKind::Dht11 => frame[3] & 0x80 != 0,
+        DhtKind::Dht22 => frame[2] & 0x80 != 0,
+    };
+
+    Ok(DhtReading {
+        temperature_c: if negative { -temperature_c } else { temperature_c },
+        humidity_pct,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The DHT11 adds the tenths to the integer part, the result is compared to 0.001
+    fn reading(temperature_c: f32, humidity_pct: f32) -> Result<DhtReading, DhtError> {
+        Ok(DhtReading { temperature_c, humidity_pct })
+    }
+
+    fn assert_close(decoded: Result<DhtReading, DhtError>, expected: Result<DhtReading, DhtError>) {
+        let (decoded, expected) = (decoded.unwrap(), expected.unwrap());
+        assert!((decoded.temperature_c - expected.temperature_c).abs() < 0.001, "{:?}", decoded);
+        assert!((decoded.humidity_pct - expected.humidity_pct).abs() < 0.001, "{:?}", decoded);
+    }
+
+    #[test]
+    fn dht22_frames_of_the_datasheet() {
+        // 65.2 %, 35.1 °C and -10.1 °C
+        assert_eq!(decode_frame(DhtKind::Dht22, [0x02, 0x8C, 0x01, 0x5F, 0xEE]), reading(35.1, 65.2));
+        assert_eq!(decode_frame(DhtKind::Dht22, [0x02, 0x8C, 0x80, 0x65, 0x73]), reading(-10.1, 65.2));
+        assert_eq!(decode_frame(DhtKind::Dht22, [0x03, 0xE8, 0x80, 0x00, 0x6B]), reading(-0.0, 100.0));
+    }
+
+    #[test]
+    fn dht11_sign_is_in_the_decimal_byte() {
+        assert_close(decode_frame(DhtKind::Dht11, [45, 0, 25, 3, 73]), reading(25.3, 45.0));
+        assert_close(decode_frame(DhtKind::Dht11, [45, 0, 5, 0x83, 0xB5]), reading(-5.3, 45.0));
+
+        // The bit 15 of the DHT22 is a large integer part for the DHT11, not a sign
+        assert_close(decode_frame(DhtKind::Dht11, [0x02, 0x8C, 0x80, 0x65, 0x73]), reading(138.1, 16.0));
+    }
+
+    #[test]
+    fn checksum_mismatch_is_rejected() {
+        assert_eq!(decode_frame(DhtKind::Dht22, [0x02, 0x8C, 0x01, 0x5F, 0xEF]), Err(DhtError::Checksum));
+        assert_eq!(decode_frame(DhtKind::Dht11, [45, 0, 25, 3, 0]), Err(DhtError::Checksum));
+        // The sum is kept in 8 bits
+        assert_eq!(decode_frame(DhtKind::Dht11, [200, 0, 90, 0, 34]), reading(90.0, 200.0));
+    }
+}
@@ -0,0 +1,185 @@
+// DHT link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : DHT link file for the modular project.
+ *  File        : dht_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the humidity and the temperature of the air, read from a DHT11
+ *      or DHT22 (AM2302) in the GP22. The single wire protocol is timed by a state machine of the PIO1
+ *      (the PIO0 stays free for the CYW43), so the other tasks and interrupts do not corrupt the bits.
+ *
+ *      The start pulse is sent by the state machine, then it samples each bit 40 us after its rising
+ *      edge (a 0 is high for 26-28 us and a 1 for 70 us) and pushes the 5 bytes to the RX FIFO.
+ *      The frame is decoded by the dht.rs, a failed read is tried again and the minimum interval of the sensor
+ *      (1 s DHT11, 2 s DHT22) is always respected. The values go to the OLED by Watch channels.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_rp::Peri;
+use embassy_rp::clocks::clk_sys_freq;
+use embassy_rp::gpio::{Level, Pull};
+use embassy_rp::peripherals::PIO1;
+use embassy_rp::pio::{Common, Config, Direction, Pin, PioPin, ShiftConfig, ShiftDirection, StateMachine};
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+use crate::modular::dht::*;
+
+pub const DHT_PERIOD_S: u64 = 5;
+const DHT_RETRIES: u8 = 3;
+const DHT_FRAME_TIMEOUT: Duration = Duration::from_millis(40); // Start pulse and 40 bits of up to 120 us
+
+const DHT_CONSUMERS: usize = 2; // OLED and humidity loop
+static DHT_TEMPERATURE_CHANNEL: Watch<ThreadModeRawMutex, f32, DHT_CONSUMERS> = Watch::new();
+static DHT_HUMIDITY_CHANNEL: Watch<ThreadModeRawMutex, f32, DHT_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_dht_temperature() -> Option<DynReceiver<'static, f32>> {
+    DHT_TEMPERATURE_CHANNEL.dyn_receiver()
+}
+
+pub fn get_receiver_dht_humidity() -> Option<DynReceiver<'static, f32>> {
+    DHT_HUMIDITY_CHANNEL.dyn_receiver()
+}
+
+pub struct Dht {
+    sm: StateMachine<'static, PIO1, 0>,
+    pin: Pin<'static, PIO1>,
+    origin: u8,
+    kind: DhtKind,
+    last_read: Option<Instant>,
+}
+
+impl Dht {
+    pub fn new(
+        common: &mut Common<'static, PIO1>,
+        mut sm: StateMachine<'static, PIO1, 0>,
+        pin: Peri<'static, impl PioPin>,
+        kind: DhtKind,
+    ) -> Self {
+        // 1 tick per microsecond. The line is only driven low, the high comes from the pull-up.
+        let program = pio::pio_asm!(
+            r#"
+                pull block                  ; length of the start pulse in us
+                mov x, osr
+                set pindirs, 1              ; drive the line low
+            start_pulse:
+                jmp x--, start_pulse
+                set pindirs, 0              ; release the line
+                wait 1 pin 0                ; pull-up
+                wait 0 pin 0                ; answer of the sensor, 80 us low
+                wait 1 pin 0                ; 80 us high
+                wait 0 pin 0                ; low of the first bit
+            .wrap_target
+                wait 1 pin 0 [31]           ; start of the high of the bit
+                nop [7]
+                in pins, 1                  ; still high after 40 us is a 1
+                wait 0 pin 0
+            .wrap
+            "#
+        );
+        let loaded = common.load_program(&program.program);
+
+        let mut pin = common.make_pio_pin(pin);
+        pin.set_pull(Pull::Up);
+        sm.set_pins(Level::Low, &[&pin]);
+        sm.set_pin_dirs(Direction::In, &[&pin]);
+
+        let mut config = Config::default();
+        config.use_program(&loaded, &[]);
+        config.set_set_pins(&[&pin]);
+        config.set_in_pins(&[&pin]);
+        // Each byte is pushed alone, most significant bit first
+        config.shift_in = ShiftConfig {
+            auto_fill: true,
+            direction: ShiftDirection::Left,
+            threshold: 8,
+        };
+        config.clock_divider = ((clk_sys_freq() / 1_000_000) as u16).into();
+        sm.set_config(&config);
+
+        Self {
+            sm,
+            pin,
+            origin: loaded.origin,
+            kind,
+            last_read: None,
+        }
+    }
+
+    async fn read_frame(&mut self) -> Result<[u8; 5], DhtError> {
+        self.sm.set_enable(false);
+        self.sm.set_pin_dirs(Direction::In, &[&self.pin]);
+        self.sm.clear_fifos();
+        self.sm.restart();
+        unsafe {
+            self.sm.exec_jmp(self.origin);
+        }
+        self.sm.tx().push(self.kind.start_pulse_us());
+        self.sm.set_enable(true);
+
+        let mut frame = [0u8; 5];
+        let mut received = 0;
+        let result = with_timeout(DHT_FRAME_TIMEOUT, async {
+            for byte in frame.iter_mut() {
+                *byte = self.sm.rx().wait_pull().await as u8;
+                received += 1;
+            }
+        })
+        .await;
+        // The state machine waits for the next bit forever, the line stays released
+        self.sm.set_enable(false);
+
+        match (result, received) {
+            (Ok(()), _) => Ok(frame),
+            (Err(_), 0) => Err(DhtError::NoResponse),
+            (Err(_), _) => Err(DhtError::Timeout),
+        }
+    }
+
+    // Read the sensor, waiting for the minimum interval since the last read if needed
+    pub async fn read(&mut self) -> Result<DhtReading, DhtError> {
+        if let Some(last_read) = self.last_read {
+            Timer::at(last_read + Duration::from_millis(self.kind.min_interval_ms())).await;
+        }
+        let frame = self.read_frame().await;
+        self.last_read = Some(Instant::now());
+        decode_frame(self.kind, frame?)
+    }
+}
+
+// This task reads the DHT sensor and publishes the temperature and the humidity
+#[embassy_executor::task]
+pub async fn dht_task(mut dht: Dht) {
+    let tx_temperature = DHT_TEMPERATURE_CHANNEL.sender();
+    let tx_humidity = DHT_HUMIDITY_CHANNEL.sender();
+
+    loop {
+        let mut result = dht.read().await;
+        for _ in 1..DHT_RETRIES {
+            if result.is_ok() {
+                break;
+            }
+            result = dht.read().await;
+        }
+
+        match result {
+            Ok(reading) => {
+                debug!("DHT: {} C {} %", reading.temperature_c, reading.humidity_pct);
+                tx_temperature.send(reading.temperature_c);
+                tx_humidity.send(reading.humidity_pct);
+            }
+            // The last values stay in the channels
+            Err(e) => warn!("DHT read failed after {} tries: {}", DHT_RETRIES, e),
+        }
+
+        Timer::after_secs(DHT_PERIOD_S).await;
+    }
+}
@@ -0,0 +1,205 @@
+// DS18B20 file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : DS18B20 file for the modular project.
+ *  File        : ds18b20.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the commands, the CRC-8 and the scratchpad of the DS18B20
+ *      temperature probes of the 1-Wire bus. The ROM code has 8 bytes: family code (0x28), serial
+ *      number of 48 bits and the CRC-8 of the first 7 bytes. The scratchpad has 9 bytes: temperature
+ *      (2 bytes, 1/16 °C), alarm limits TH and TL, configuration (resolution), 3 reserved bytes and
+ *      the CRC-8 of the first 8 bytes.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+pub const FAMILY_DS18B20: u8 = 0x28;
+
+pub const CMD_MATCH_ROM: u8 = 0x55;
+pub const CMD_SKIP_ROM: u8 = 0xCC;
+pub const CMD_CONVERT_T: u8 = 0x44;
+pub const CMD_WRITE_SCRATCHPAD: u8 = 0x4E;
+pub const CMD_READ_SCRATCHPAD: u8 = 0xBE;
+
+pub const SCRATCHPAD_LEN: usize = 9;
+
+// Alarm limits of the probe, not used by the firmware, written with the values of the factory
+pub const ALARM_HIGH_C: i8 = 75;
+pub const ALARM_LOW_C: i8 = 70;
+
+// The temperature register starts with 85 °C, it is read when the conversion did not happen
+const POWER_ON_RAW: i16 = 0x0550;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Resolution {
+    #[allow(dead_code)] // Not chosen in the main.rs
+    Bits9,  // 0.5 °C
+    #[allow(dead_code)]
+    Bits10, // 0.25 °C
+    #[allow(dead_code)]
+    Bits11, // 0.125 °C
+    Bits12, // 0.0625 °C
+}
+
+impl Resolution {
+    fn bits(self) -> u8 {
+        match self {
+            Resolution::Bits9 => 9,
+            Resolution::Bits10 => 10,
+            Resolution::Bits11 => 11,
+            Resolution::Bits12 => 12,
+        }
+    }
+
+    // Byte of the configuration register, the resolution is in the bits 5 and 6
+    pub fn config_byte(self) -> u8 {
+        ((self.bits() - 9) << 5) | 0x1F
+    }
+
+    // Maximum time of the conversion, it doubles with each bit
+    pub fn conversion_ms(self) -> u64 {
+        match self {
+            Resolution::Bits9 => 94,
+            Resolution::Bits10 => 188,
+            Resolution::Bits11 => 375,
+            Resolution::Bits12 => 750,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Ds18b20Error {
+    Crc,
+    NotDs18b20,   // Valid ROM of another family of 1-Wire device
+    Invalid,      // Configuration register with the fixed bits wrong, the line is stuck
+    PowerOnValue, // 85 °C of the reset, the probe lost the power during the conversion
+}
+
+impl Ds18b20Error {
+    pub fn name(self) -> &'static str {
+        match self {
+            Ds18b20Error::Crc => "crc",
+            Ds18b20Error::NotDs18b20 => "not-ds18b20",
+            Ds18b20Error::Invalid => "invalid",
+            Ds18b20Error::PowerOnValue => "power-on",
+        }
+    }
+}
+
+// CRC-8 of Maxim (polynomial x^8 + x^5 + x^4 + 1, reflected), the CRC of the data with its CRC is 0
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in data {
+        let mut byte = byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+// The ROM is sent and received with the family code first, the first byte is the lowest of the u64
+pub fn check_rom(rom: u64) -> Result<(), Ds18b20Error> {
+    let bytes = rom.to_le_bytes();
+    if crc8(&bytes[..7]) != bytes[7] {
+        return Err(Ds18b20Error::Crc);
+    }
+    if bytes[0] != FAMILY_DS18B20 {
+        return Err(Ds18b20Error::NotDs18b20);
+    }
+    Ok(())
+}
+
+// Temperature of the scratchpad, the bits below the resolution are undefined and ignored
+pub fn decode_scratchpad(scratchpad: &[u8; SCRATCHPAD_LEN]) -> Result<f32, Ds18b20Error> {
+    if crc8(&scratchpad[..8]) != scratchpad[8] {
+        return Err(Ds18b20Error::Crc);
+    }
+    // A line stuck low reads zeros, and zeros have a valid CRC
+    let config = scratchpad[4];
+    if config & 0x9F != 0x1F {
+        return Err(Ds18b20Error::Invalid);
+    }
+
+    let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]);
+    if raw == POWER_ON_RAW {
+        return Err(Ds18b20Error::PowerOnValue);
+    }
+    let undefined_bits = 3 - ((config >> 5) & 0x03);
+    let raw = raw & !((1 << undefined_bits) - 1);
+    Ok(raw as f32 / 16.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Scratchpad with the reserved bytes of a real probe and its CRC
+    fn scratchpad(raw: u16, config: u8) -> [u8; SCRATCHPAD_LEN] {
+        let mut scratchpad = [raw as u8, (raw >> 8) as u8, 0x4B, 0x46, config, 0xFF, 0x0C, 0x10, 0];
+        scratchpad[8] = crc8(&scratchpad[..8]);
+        scratchpad
+    }
+
+    #[test]
+    fn crc8_of_known_vectors() {
+        // ROM of the application note 27 of Maxim, and the scratchpad of the power-on of a DS18B20
+        assert_eq!(crc8(&[0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00]), 0xA2);
+        assert_eq!(crc8(&[0x50, 0x05, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10]), 0x1C);
+        assert_eq!(crc8(&[0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xA2]), 0);
+        assert_eq!(crc8(&[]), 0);
+    }
+
+    #[test]
+    fn rom_is_checked_for_the_crc_and_the_family() {
+        assert_eq!(check_rom(u64::from_le_bytes([0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xA2])), Err(Ds18b20Error::NotDs18b20));
+        assert_eq!(check_rom(u64::from_le_bytes([0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xA3])), Err(Ds18b20Error::Crc));
+
+        let mut rom = [FAMILY_DS18B20, 0xFF, 0x4B, 0x6C, 0x71, 0x16, 0x03, 0];
+        rom[7] = crc8(&rom[..7]);
+        assert_eq!(check_rom(u64::from_le_bytes(rom)), Ok(()));
+    }
+
+    #[test]
+    fn temperatures_of_the_datasheet() {
+        assert_eq!(decode_scratchpad(&[0x91, 0x01, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10, 0x70]), Ok(25.0625));
+        assert_eq!(decode_scratchpad(&[0x5E, 0xFF, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10, 0x6A]), Ok(-10.125));
+        assert_eq!(decode_scratchpad(&scratchpad(0xFC90, 0x7F)), Ok(-55.0));
+        assert_eq!(decode_scratchpad(&scratchpad(0x07D0, 0x7F)), Ok(125.0));
+        assert_eq!(decode_scratchpad(&scratchpad(0xFFF8, 0x7F)), Ok(-0.5));
+        assert_eq!(decode_scratchpad(&scratchpad(0x0000, 0x7F)), Ok(0.0));
+    }
+
+    #[test]
+    fn bits_below_the_resolution_are_ignored() {
+        // 10.4375 °C with the undefined bits set, read with 9, 10, 11 and 12 bits
+        let configs = [Resolution::Bits9, Resolution::Bits10, Resolution::Bits11, Resolution::Bits12];
+        let expected = [10.0, 10.25, 10.375, 10.4375];
+        for (resolution, expected) in configs.into_iter().zip(expected) {
+            assert_eq!(decode_scratchpad(&scratchpad(0x00A7, resolution.config_byte())), Ok(expected), "{:?}", resolution);
+        }
+        // The negative values are rounded down
+        assert_eq!(decode_scratchpad(&scratchpad(0xFF5E, Resolution::Bits9.config_byte())), Ok(-10.5));
+        assert_eq!(decode_scratchpad(&[0xA7, 0x00, 0x4B, 0x46, 0x1F, 0xFF, 0x0C, 0x10, 0xB2]), Ok(10.0));
+    }
+
+    #[test]
+    fn bad_scratchpads_are_rejected() {
+        let mut corrupted = scratchpad(0x0191, 0x7F);
+        corrupted[0] ^= 0x01;
+        assert_eq!(decode_scratchpad(&corrupted), Err(Ds18b20Error::Crc));
+        // Line stuck low, the zeros have a valid CRC
+        assert_eq!(decode_scratchpad(&[0; SCRATCHPAD_LEN]), Err(Ds18b20Error::Invalid));
+        assert_eq!(decode_scratchpad(&[0x50, 0x05, 0x4B, 0x46, 0x7F, 0xFF, 0x0C, 0x10, 0x1C]), Err(Ds18b20Error::PowerOnValue));
+    }
+}
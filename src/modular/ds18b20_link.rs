@@ -0,0 +1,160 @@
+// DS18B20 link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : DS18B20 link file for the modular project.
+ *  File        : ds18b20_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the DS18B20 probes of the enclosure, all of them in the same
+ *      1-Wire bus in the GP16 (pull-up of 4.7k to 3.3V, the probes need the VDD wire, the parasitic
+ *      power is not supported). The 1-Wire master is the PIO program of the embassy-rp in the state
+ *      machine 1 of the PIO1, the DHT uses the state machine 0.
+ *
+ *      The ROM search finds the probes in the boot, they are sorted by the ROM code, so each probe
+ *      keeps its name after a reboot (the ROM codes are logged to label the probes). One conversion is
+ *      started in all the probes at the same time, the task sleeps during the conversion and reads the
+ *      scratchpad of each probe. The temperature of each probe is published in its own Watch channel,
+ *      like the channels of the adc.rs. The bus is searched again when no probe answers.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_rp::Peri;
+use embassy_rp::peripherals::PIO1;
+use embassy_rp::pio::{Common, PioPin, StateMachine};
+use embassy_rp::pio_programs::onewire::{PioOneWire, PioOneWireProgram, PioOneWireSearch};
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::Timer;
+use heapless::Vec;
+
+use crate::modular::ds18b20::*;
+
+pub const DS18B20_PERIOD_S: u64 = 5;
+const DS18B20_RESCAN_S: u64 = 30;
+
+// Names of the probes in the order of the ROM codes
+pub const DS18B20_MAX_PROBES: usize = 3;
+pub const DS18B20_PROBE_NAMES: [&str; DS18B20_MAX_PROBES] = ["warm", "cool", "basking"];
+
+const PROBE_CONSUMERS: usize = 1;
+static PROBE_CHANNELS: [Watch<ThreadModeRawMutex, f32, PROBE_CONSUMERS>; DS18B20_MAX_PROBES] =
+    [const { Watch::new() }; DS18B20_MAX_PROBES];
+
+pub fn get_receiver_probe(index: usize) -> Option<DynReceiver<'static, f32>> {
+    PROBE_CHANNELS.get(index)?.dyn_receiver()
+}
+
+pub struct Ds18b20Bus {
+    wire: PioOneWire<'static, PIO1, 1>,
+    resolution: Resolution,
+    roms: Vec<u64, DS18B20_MAX_PROBES>,
+}
+
+impl Ds18b20Bus {
+    pub fn new(
+        common: &mut Common<'static, PIO1>,
+        sm: StateMachine<'static, PIO1, 1>,
+        pin: Peri<'static, impl PioPin>,
+        resolution: Resolution,
+    ) -> Self {
+        let program = PioOneWireProgram::new(common);
+        Self {
+            wire: PioOneWire::new(common, sm, pin, &program),
+            resolution,
+            roms: Vec::new(),
+        }
+    }
+
+    // Find the probes of the bus and write the resolution in all of them
+    async fn scan(&mut self) {
+        self.roms.clear();
+        let mut search = PioOneWireSearch::new();
+        while let Some(rom) = search.next(&mut self.wire).await {
+            match check_rom(rom) {
+                Ok(()) if self.roms.push(rom).is_err() => {
+                    warn!("DS18B20 {:016x} ignored, only {} probes are used", rom, DS18B20_MAX_PROBES);
+                }
+                Ok(()) => {}
+                Err(e) => warn!("1-Wire device {:016x} ignored: {}", rom, e.name()),
+            }
+        }
+        self.roms.sort_unstable();
+
+        for (rom, name) in self.roms.iter().zip(DS18B20_PROBE_NAMES) {
+            info!("DS18B20 {} is {:016x}", name, rom);
+        }
+        if self.wire.reset().await {
+            self.wire
+                .write_bytes(&[
+                    CMD_SKIP_ROM,
+                    CMD_WRITE_SCRATCHPAD,
+                    ALARM_HIGH_C as u8,
+                    ALARM_LOW_C as u8,
+                    self.resolution.config_byte(),
+                ])
+                .await;
+        }
+    }
+
+    // Start the conversion in all the probes and wait for it without holding the CPU
+    async fn convert(&mut self) -> bool {
+        if !self.wire.reset().await {
+            return false;
+        }
+        self.wire.write_bytes(&[CMD_SKIP_ROM, CMD_CONVERT_T]).await;
+        Timer::after_millis(self.resolution.conversion_ms()).await;
+        true
+    }
+
+    async fn read(&mut self, rom: u64) -> Result<f32, Ds18b20Error> {
+        let mut scratchpad = [0u8; SCRATCHPAD_LEN];
+        self.wire.reset().await;
+        self.wire.write_bytes(&[CMD_MATCH_ROM]).await;
+        self.wire.write_bytes(&rom.to_le_bytes()).await;
+        self.wire.write_bytes(&[CMD_READ_SCRATCHPAD]).await;
+        self.wire.read_bytes(&mut scratchpad).await;
+        decode_scratchpad(&scratchpad)
+    }
+}
+
+// This task reads the DS18B20 probes and publishes the temperature of each one
+#[embassy_executor::task]
+pub async fn ds18b20_task(mut bus: Ds18b20Bus) {
+    loop {
+        bus.scan().await;
+        if bus.roms.is_empty() {
+            warn!("No DS18B20 found, searching again in {} s", DS18B20_RESCAN_S);
+            Timer::after_secs(DS18B20_RESCAN_S).await;
+            continue;
+        }
+
+        // Read until no probe answers, then search the bus again
+        loop {
+            let mut answered = false;
+            if bus.convert().await {
+                for index in 0..bus.roms.len() {
+                    match bus.read(bus.roms[index]).await {
+                        Ok(celsius) => {
+                            debug!("DS18B20 {}: {} C", DS18B20_PROBE_NAMES[index], celsius);
+                            PROBE_CHANNELS[index].sender().send(celsius);
+                            answered = true;
+                        }
+                        // The last value stays in the channel
+                        Err(e) => warn!("DS18B20 {} read failed: {}", DS18B20_PROBE_NAMES[index], e.name()),
+                    }
+                }
+            }
+            if !answered {
+                warn!("DS18B20 bus not answering");
+                break;
+            }
+            Timer::after_secs(DS18B20_PERIOD_S).await;
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// Encoder file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Encoder file for the modular project.
+ *  File        : encoder.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the rotary encoder with push button used by the menu of the
+ *      display. The channels A and B (GP17 and GP18) and the button (GP19) are closed to the GND,
+ *      the pull-ups of the Pico keep them high.
+ *
+ *      Each edge of A or B wakes the task by the interrupt of the GPIO, the state of the two
+ *      channels is decoded by the gray code table, so a bounce only goes one quarter back and forth.
+ *      A detent is counted when the encoder arrives in the rest state (A and B high) after at least
+ *      half of the four quarters, so a lost edge does not shift the count.
+ *
+ *      The button is debounced, a press longer than 800 ms is a long press and it is sent while the
+ *      button is still held.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_futures::select::select;
+use embassy_rp::gpio::Input;
+use embassy_time::{Duration, Timer, with_timeout};
+
+use crate::modular::menu::InputEvent;
+use crate::modular::menu_link::get_sender_input;
+
+const BUTTON_DEBOUNCE_MS: u64 = 20;
+const BUTTON_LONG_PRESS: Duration = Duration::from_millis(800);
+
+const REST_STATE: u8 = 0b11;
+// Quarter step of each transition, the index is the previous state (AB) and the new one
+const QUADRATURE_TABLE: [i8; 16] = [0, -1, 1, 0, 1, 0, 0, -1, -1, 0, 0, 1, 0, 1, -1, 0];
+
+pub struct RotaryEncoder {
+    a: Input<'static>,
+    b: Input<'static>,
+    state: u8,
+    quarters: i8,
+}
+
+impl RotaryEncoder {
+    pub fn new(a: Input<'static>, b: Input<'static>) -> Self {
+        let mut encoder = Self {
+            a,
+            b,
+            state: REST_STATE,
+            quarters: 0,
+        };
+        encoder.state = encoder.read();
+        encoder
+    }
+
+    fn read(&self) -> u8 {
+        ((self.a.is_high() as u8) << 1) | self.b.is_high() as u8
+    }
+
+    // Returns +1 or -1 when a detent is complete
+    fn update(&mut self) -> Option<i8> {
+        let state = self.read();
+        self.quarters = self
+            .quarters
+            .saturating_add(QUADRATURE_TABLE[((self.state << 2) | state) as usize]);
+        self.state = state;
+
+        if state != REST_STATE {
+            return None;
+        }
+        let detent = match self.quarters {
+            quarters if quarters >= 2 => Some(1),
+            quarters if quarters <= -2 => Some(-1),
+            _ => None,
+        };
+        self.quarters = 0;
+        detent
+    }
+}
+
+// This task decodes the turns of the encoder
+#[embassy_executor::task]
+pub async fn encoder_task(mut encoder: RotaryEncoder) {
+    let tx_input = get_sender_input();
+
+    loop {
+        select(encoder.a.wait_for_any_edge(), encoder.b.wait_for_any_edge()).await;
+        if let Some(detent) = encoder.update()
+            && tx_input.try_send(InputEvent::Turn(detent)).is_err()
+        {
+            debug!("Encoder turn dropped, the menu is busy");
+        }
+    }
+}
+
+// This task debounces the button of the encoder and detects the long press
+#[embassy_executor::task]
+pub async fn encoder_button_task(mut button: Input<'static>) {
+    let tx_input = get_sender_input();
+
+    loop {
+        button.wait_for_falling_edge().await;
+        Timer::after_millis(BUTTON_DEBOUNCE_MS).await;
+        if button.is_high() {
+            continue; // Bounce
+        }
+
+        match with_timeout(BUTTON_LONG_PRESS, button.wait_for_high()).await {
+            Ok(()) => tx_input.send(InputEvent::Press).await,
+            Err(_) => {
+                tx_input.send(InputEvent::LongPress).await;
+                button.wait_for_high().await;
+            }
+        }
+        Timer::after_millis(BUTTON_DEBOUNCE_MS).await;
+    }
+}
@@ -0,0 +1,567 @@
+// FAT file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : FAT file for the modular project.
+ *  File        : fat.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about a minimal FAT32 writer for the SD card, it only does what the
+ *      logger needs: mount the first partition (or a card without partition table), find or create a
+ *      file with a 8.3 name in the root directory and append data to it.
+ *      Both copies of the FAT are updated and the free count of the FSInfo is invalidated, so the
+ *      computer recalculates it. FAT12/16 and exFAT are not supported, the cards must be formatted
+ *      as FAT32 (default of the cards from 4 GB to 32 GB).
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+pub const BLOCK_SIZE: usize = 512;
+pub type Block = [u8; BLOCK_SIZE];
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const ATTR_DIRECTORY_OR_VOLUME: u8 = 0x18;
+const ENTRY_FREE: u8 = 0xE5;
+const ENTRY_END: u8 = 0x00;
+const DATE_1980_01_01: u16 = 0x0021; // The logger has no calendar
+
+const CLUSTER_MASK: u32 = 0x0FFF_FFFF;
+const CLUSTER_END_MIN: u32 = 0x0FFF_FFF8;
+const CLUSTER_END: u32 = 0x0FFF_FFFF;
+const MIN_FAT32_CLUSTERS: u32 = 65_525;
+
+// Storage with blocks of 512 bytes, addressed by the number of the block
+pub trait BlockDevice {
+    type Error;
+
+    async fn read(&mut self, lba: u32, block: &mut Block) -> Result<(), Self::Error>;
+    async fn write(&mut self, lba: u32, block: &Block) -> Result<(), Self::Error>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FatError<E> {
+    Device(E),
+    NoFilesystem, // No boot sector or partition
+    Unsupported,  // Not FAT32 or sector different from 512 bytes
+    Corrupt,      // Boot sector or cluster chain out of the volume
+    DiskFull,
+}
+
+impl<E> From<E> for FatError<E> {
+    fn from(error: E) -> Self {
+        FatError::Device(error)
+    }
+}
+
+fn u16_at(block: &Block, offset: usize) -> u16 {
+    u16::from_le_bytes([block[offset], block[offset + 1]])
+}
+
+fn u32_at(block: &Block, offset: usize) -> u32 {
+    u32::from_le_bytes([block[offset], block[offset + 1], block[offset + 2], block[offset + 3]])
+}
+
+// Name in the format of the directory entry, "20261019.CSV" becomes "20261019CSV"
+pub fn short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = name.split_once('.').unwrap_or((name, ""));
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 {
+        return None;
+    }
+    let mut entry = [b' '; 11];
+    for (i, byte) in base.bytes().enumerate() {
+        entry[i] = byte.to_ascii_uppercase();
+    }
+    for (i, byte) in extension.bytes().enumerate() {
+        entry[8 + i] = byte.to_ascii_uppercase();
+    }
+    let valid = |byte: &u8| byte.is_ascii_alphanumeric() || b" _-~!#$%&'()@^`{}".contains(byte);
+    entry.iter().all(valid).then_some(entry)
+}
+
+// Mounted volume, it only borrows the device so the card can be mounted again after an error
+pub struct FatVolume<'a, D: BlockDevice> {
+    device: &'a mut D,
+    fat_start: u32,
+    fat_size: u32,
+    fat_count: u32,
+    sectors_per_cluster: u32,
+    data_start: u32,
+    root_cluster: u32,
+    cluster_count: u32,
+    free_hint: u32,
+    buffer: Block, // FAT and directory sectors
+}
+
+impl<'a, D: BlockDevice> FatVolume<'a, D> {
+    pub async fn mount(device: &'a mut D) -> Result<Self, FatError<D::Error>> {
+        let mut block = [0u8; BLOCK_SIZE];
+        device.read(0, &mut block).await?;
+        if block[510..512] != [0x55, 0xAA] {
+            return Err(FatError::NoFilesystem);
+        }
+
+        // A card without partition table starts with the boot sector, otherwise the first partition is used
+        let mut volume_start = 0;
+        if block[54..57] == *b"FAT" {
+            return Err(FatError::Unsupported); // FAT12/16 boot sector
+        }
+        if block[82..87] != *b"FAT32" {
+            match block[446 + 4] {
+                0x0B | 0x0C => volume_start = u32_at(&block, 446 + 8),
+                0x00 => return Err(FatError::NoFilesystem),
+                _ => return Err(FatError::Unsupported),
+            }
+            device.read(volume_start, &mut block).await?;
+            if block[510..512] != [0x55, 0xAA] {
+                return Err(FatError::NoFilesystem);
+            }
+        }
+
+        let sectors_per_cluster = block[13] as u32;
+        let reserved = u16_at(&block, 14) as u32;
+        let fat_count = block[16] as u32;
+        let total_sectors = match u16_at(&block, 19) {
+            0 => u32_at(&block, 32),
+            total => total as u32,
+        };
+        let fat_size = u32_at(&block, 36);
+        let root_cluster = u32_at(&block, 44);
+        let fs_info = u16_at(&block, 48) as u32;
+        if u16_at(&block, 11) as usize != BLOCK_SIZE
+            || !sectors_per_cluster.is_power_of_two() // Also rejects 0
+            || reserved == 0
+            || fat_count == 0
+            || fat_size == 0
+            || u16_at(&block, 17) != 0 // Root directory of FAT12/16
+            || u16_at(&block, 22) != 0 // FAT size of FAT12/16
+        {
+            return Err(FatError::Unsupported);
+        }
+
+        let fat_start = volume_start + reserved;
+        let data_start = fat_start + fat_count * fat_size;
+        let cluster_count = (volume_start + total_sectors).saturating_sub(data_start) / sectors_per_cluster;
+        if cluster_count < MIN_FAT32_CLUSTERS {
+            return Err(FatError::Unsupported);
+        }
+        // Every cluster must have its entry in the FAT and the root directory must be one of them,
+        // otherwise the first access would read outside of the FAT or of the volume
+        let fat_entries = fat_size as u64 * (BLOCK_SIZE / 4) as u64;
+        if fat_entries < cluster_count as u64 + 2 || !(2..cluster_count + 2).contains(&root_cluster) {
+            return Err(FatError::Corrupt);
+        }
+
+        let mut volume = Self {
+            device,
+            fat_start,
+            fat_size,
+            fat_count,
+            sectors_per_cluster,
+            data_start,
+            root_cluster,
+            cluster_count,
+            free_hint: 2,
+            buffer: [0; BLOCK_SIZE],
+        };
+        // The FSInfo is optional, 0 and 0xFFFF mean that there is none
+        if (1..reserved).contains(&fs_info) {
+            volume.invalidate_free_count(volume_start + fs_info).await?;
+        }
+        Ok(volume)
+    }
+
+    // The free count of the FSInfo is not kept, the computer counts the clusters again
+    async fn invalidate_free_count(&mut self, lba: u32) -> Result<(), FatError<D::Error>> {
+        self.device.read(lba, &mut self.buffer).await?;
+        if u32_at(&self.buffer, 0) != 0x4161_5252 || u32_at(&self.buffer, 484) != 0x6141_7272 {
+            return Ok(());
+        }
+        let next_free = u32_at(&self.buffer, 492);
+        if (2..self.cluster_count + 2).contains(&next_free) {
+            self.free_hint = next_free;
+        }
+        if u32_at(&self.buffer, 488) != u32::MAX {
+            self.buffer[488..492].copy_from_slice(&u32::MAX.to_le_bytes());
+            self.device.write(lba, &self.buffer).await?;
+        }
+        Ok(())
+    }
+
+    pub fn cluster_size(&self) -> u32 {
+        self.sectors_per_cluster * BLOCK_SIZE as u32
+    }
+
+    fn cluster_lba(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2) * self.sectors_per_cluster
+    }
+
+    fn valid_cluster(&self, cluster: u32) -> bool {
+        (2..self.cluster_count + 2).contains(&cluster)
+    }
+
+    async fn read_fat(&mut self, cluster: u32) -> Result<u32, FatError<D::Error>> {
+        let offset = cluster * 4;
+        self.device
+            .read(self.fat_start + offset / BLOCK_SIZE as u32, &mut self.buffer)
+            .await?;
+        Ok(u32_at(&self.buffer, (offset as usize) % BLOCK_SIZE) & CLUSTER_MASK)
+    }
+
+    async fn write_fat(&mut self, cluster: u32, value: u32) -> Result<(), FatError<D::Error>> {
+        let offset = cluster * 4;
+        let index = (offset as usize) % BLOCK_SIZE;
+        for copy in 0..self.fat_count {
+            let lba = self.fat_start + copy * self.fat_size + offset / BLOCK_SIZE as u32;
+            self.device.read(lba, &mut self.buffer).await?;
+            // The 4 upper bits are reserved and must be kept
+            let entry = (u32_at(&self.buffer, index) & !CLUSTER_MASK) | (value & CLUSTER_MASK);
+            self.buffer[index..index + 4].copy_from_slice(&entry.to_le_bytes());
+            self.device.write(lba, &self.buffer).await?;
+        }
+        Ok(())
+    }
+
+    async fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, FatError<D::Error>> {
+        let next = self.read_fat(cluster).await?;
+        if next >= CLUSTER_END_MIN {
+            return Ok(None);
+        }
+        if !self.valid_cluster(next) {
+            return Err(FatError::Corrupt);
+        }
+        Ok(Some(next))
+    }
+
+    // Take a free cluster as the end of the chain, linked after the previous one
+    async fn allocate_cluster(&mut self, previous: Option<u32>) -> Result<u32, FatError<D::Error>> {
+        let mut cluster = self.free_hint;
+        for _ in 0..self.cluster_count {
+            if !self.valid_cluster(cluster) {
+                cluster = 2;
+            }
+            if self.read_fat(cluster).await? == 0 {
+                self.write_fat(cluster, CLUSTER_END).await?;
+                if let Some(previous) = previous {
+                    self.write_fat(previous, cluster).await?;
+                }
+                self.free_hint = cluster + 1;
+                return Ok(cluster);
+            }
+            cluster += 1;
+        }
+        Err(FatError::DiskFull)
+    }
+
+    // Find the file in the root directory or create an empty one, the data is written at the end
+    pub async fn open_append(&mut self, name: &[u8; 11]) -> Result<FatFile, FatError<D::Error>> {
+        let mut free_slot: Option<(u32, usize)> = None;
+        let mut cluster = self.root_cluster;
+
+        'clusters: loop {
+            for sector in 0..self.sectors_per_cluster {
+                let lba = self.cluster_lba(cluster) + sector;
+                self.device.read(lba, &mut self.buffer).await?;
+                for offset in (0..BLOCK_SIZE).step_by(DIR_ENTRY_SIZE) {
+                    let entry = &self.buffer[offset..offset + DIR_ENTRY_SIZE];
+                    match entry[0] {
+                        ENTRY_END => {
+                            free_slot.get_or_insert((lba, offset));
+                            break 'clusters;
+                        }
+                        ENTRY_FREE => {
+                            free_slot.get_or_insert((lba, offset));
+                        }
+                        _ if entry[11] == ATTR_LONG_NAME || entry[11] & ATTR_DIRECTORY_OR_VOLUME != 0 => {}
+                        _ if entry[..11] == name[..] => {
+                            let first_cluster = ((u16_at(&self.buffer, offset + 20) as u32) << 16)
+                                | u16_at(&self.buffer, offset + 26) as u32;
+                            let size = u32_at(&self.buffer, offset + 28);
+                            return FatFile::open(self, (lba, offset), first_cluster, size).await;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            match self.next_cluster(cluster).await? {
+                Some(next) => cluster = next,
+                None => break,
+            }
+        }
+
+        let (lba, offset) = match free_slot {
+            Some(slot) => slot,
+            None => {
+                // The directory is full, it grows one cluster filled with zeros (end of directory)
+                let new_cluster = self.allocate_cluster(Some(cluster)).await?;
+                self.buffer = [0; BLOCK_SIZE];
+                for sector in 0..self.sectors_per_cluster {
+                    self.device.write(self.cluster_lba(new_cluster) + sector, &self.buffer).await?;
+                }
+                (self.cluster_lba(new_cluster), 0)
+            }
+        };
+
+        self.device.read(lba, &mut self.buffer).await?;
+        let entry = &mut self.buffer[offset..offset + DIR_ENTRY_SIZE];
+        entry.fill(0);
+        entry[..11].copy_from_slice(name);
+        entry[11] = ATTR_ARCHIVE;
+        for date in [16, 18, 24] {
+            entry[date..date + 2].copy_from_slice(&DATE_1980_01_01.to_le_bytes());
+        }
+        self.device.write(lba, &self.buffer).await?;
+        FatFile::open(self, (lba, offset), 0, 0).await
+    }
+}
+
+// File opened to append, the last sector stays in the memory until it is full or flushed
+pub struct FatFile {
+    entry: (u32, usize), // Sector and offset of the directory entry
+    first_cluster: u32,
+    last_cluster: u32,
+    clusters: u32, // Clusters in the chain
+    size: u32,     // Including the bytes not flushed
+    synced_size: u32,
+    sector: Block,
+}
+
+impl FatFile {
+    async fn open<D: BlockDevice>(
+        volume: &mut FatVolume<'_, D>,
+        entry: (u32, usize),
+        first_cluster: u32,
+        size: u32,
+    ) -> Result<Self, FatError<D::Error>> {
+        let mut file = Self {
+            entry,
+            first_cluster,
+            last_cluster: first_cluster,
+            clusters: 0,
+            size,
+            synced_size: size,
+            sector: [0; BLOCK_SIZE],
+        };
+
+        if first_cluster != 0 {
+            if !volume.valid_cluster(first_cluster) {
+                return Err(FatError::Corrupt);
+            }
+            file.clusters = 1;
+            // Only the clusters used by the size are followed, the end of the chain is not trusted
+            let needed = size.div_ceil(volume.cluster_size()).max(1);
+            while file.clusters < needed {
+                match volume.next_cluster(file.last_cluster).await? {
+                    Some(next) => {
+                        file.last_cluster = next;
+                        file.clusters += 1;
+                    }
+                    None => return Err(FatError::Corrupt),
+                }
+            }
+        }
+
+        if !size.is_multiple_of(BLOCK_SIZE as u32) {
+            let lba = file.sector_lba(volume, size / BLOCK_SIZE as u32);
+            volume.device.read(lba, &mut file.sector).await?;
+        }
+        Ok(file)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    // The sector must be in the last cluster of the chain
+    fn sector_lba<D: BlockDevice>(&self, volume: &FatVolume<'_, D>, sector: u32) -> u32 {
+        volume.cluster_lba(self.last_cluster) + sector % volume.sectors_per_cluster
+    }
+
+    // Write the sector of the memory in its place of the file, allocating a new cluster when needed
+    async fn write_sector<D: BlockDevice>(&mut self, volume: &mut FatVolume<'_, D>, sector: u32) -> Result<(), FatError<D::Error>> {
+        if sector / volume.sectors_per_cluster >= self.clusters {
+            let previous = (self.clusters > 0).then_some(self.last_cluster);
+            self.last_cluster = volume.allocate_cluster(previous).await?;
+            if self.clusters == 0 {
+                self.first_cluster = self.last_cluster;
+            }
+            self.clusters += 1;
+        }
+        let lba = self.sector_lba(volume, sector);
+        volume.device.write(lba, &self.sector).await?;
+        Ok(())
+    }
+
+    pub async fn write<D: BlockDevice>(&mut self, volume: &mut FatVolume<'_, D>, mut data: &[u8]) -> Result<(), FatError<D::Error>> {
+        while !data.is_empty() {
+            let position = self.size as usize % BLOCK_SIZE;
+            let len = data.len().min(BLOCK_SIZE - position);
+            self.sector[position..position + len].copy_from_slice(&data[..len]);
+            data = &data[len..];
+
+            if position + len == BLOCK_SIZE {
+                self.write_sector(volume, self.size / BLOCK_SIZE as u32).await?;
+                self.sector = [0; BLOCK_SIZE];
+            }
+            self.size += len as u32;
+        }
+        Ok(())
+    }
+
+    // Write the incomplete sector and the new size in the directory
+    pub async fn flush<D: BlockDevice>(&mut self, volume: &mut FatVolume<'_, D>) -> Result<(), FatError<D::Error>> {
+        if self.size == self.synced_size {
+            return Ok(());
+        }
+        if !self.size.is_multiple_of(BLOCK_SIZE as u32) {
+            self.write_sector(volume, self.size / BLOCK_SIZE as u32).await?;
+        }
+
+        let (lba, offset) = self.entry;
+        volume.device.read(lba, &mut volume.buffer).await?;
+        let entry = &mut volume.buffer[offset..offset + DIR_ENTRY_SIZE];
+        entry[20..22].copy_from_slice(&((self.first_cluster >> 16) as u16).to_le_bytes());
+        entry[26..28].copy_from_slice(&(self.first_cluster as u16).to_le_bytes());
+        entry[28..32].copy_from_slice(&self.size.to_le_bytes());
+        volume.device.write(lba, &volume.buffer).await?;
+        self.synced_size = self.size;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_card::{CLUSTERS, FAT_SIZE, MemoryCard, PARTITION_START, RESERVED_SECTORS};
+    use embassy_futures::block_on;
+
+    const NAME: [u8; 11] = *b"0001S000CSV";
+
+    fn mount(card: &mut MemoryCard) -> Result<(), FatError<u32>> {
+        block_on(FatVolume::mount(card)).map(|_| ())
+    }
+
+    fn append(card: &mut MemoryCard, name: &[u8; 11], chunks: &[&[u8]]) -> u32 {
+        block_on(async {
+            let mut volume = FatVolume::mount(card).await.unwrap();
+            let mut file = volume.open_append(name).await.unwrap();
+            for chunk in chunks {
+                file.write(&mut volume, chunk).await.unwrap();
+            }
+            file.flush(&mut volume).await.unwrap();
+            file.size()
+        })
+    }
+
+    // Change the boot sector of the volume without the partition table
+    fn with_boot_sector(change: impl Fn(&mut Block)) -> MemoryCard {
+        let mut card = MemoryCard::fat32(false);
+        change(card.block_mut(0));
+        card
+    }
+
+    #[test]
+    fn short_names() {
+        assert_eq!(short_name("20261019.csv"), Some(*b"20261019CSV"));
+        assert_eq!(short_name("LOG"), Some(*b"LOG        "));
+        assert_eq!(short_name("toolongname.csv"), None);
+        assert_eq!(short_name("a.json"), None);
+        assert_eq!(short_name("a b*.csv"), None);
+    }
+
+    #[test]
+    fn boot_sector_is_validated() {
+        assert_eq!(mount(&mut MemoryCard::fat32(false)), Ok(()));
+        assert_eq!(mount(&mut MemoryCard::fat32(true)), Ok(()));
+
+        let mut blank = MemoryCard::fat32(false);
+        blank.blocks.clear();
+        assert_eq!(mount(&mut blank), Err(FatError::NoFilesystem));
+
+        let unsupported: [fn(&mut Block); 4] = [
+            |boot| boot[11..13].copy_from_slice(&1024u16.to_le_bytes()),
+            |boot| boot[13] = 0,
+            |boot| boot[13] = 3,
+            |boot| boot[36..40].copy_from_slice(&0u32.to_le_bytes()),
+        ];
+        for change in unsupported {
+            assert_eq!(mount(&mut with_boot_sector(change)), Err(FatError::Unsupported));
+        }
+
+        let corrupt: [fn(&mut Block); 3] = [
+            |boot| boot[44..48].copy_from_slice(&0u32.to_le_bytes()),
+            |boot| boot[44..48].copy_from_slice(&(CLUSTERS + 2).to_le_bytes()),
+            // FAT smaller than the clusters of the volume
+            |boot| {
+                boot[32..36].copy_from_slice(&(RESERVED_SECTORS + 2 * 300 + CLUSTERS).to_le_bytes());
+                boot[36..40].copy_from_slice(&300u32.to_le_bytes());
+            },
+        ];
+        for change in corrupt {
+            assert_eq!(mount(&mut with_boot_sector(change)), Err(FatError::Corrupt));
+        }
+    }
+
+    #[test]
+    fn free_count_is_invalidated() {
+        let mut card = MemoryCard::fat32(true);
+        mount(&mut card).unwrap();
+        assert_eq!(card.block(PARTITION_START + 1)[488..492], u32::MAX.to_le_bytes());
+
+        // Without the FSInfo the volume is mounted as well
+        let mut card = with_boot_sector(|boot| boot[48..50].copy_from_slice(&0xFFFFu16.to_le_bytes()));
+        assert_eq!(mount(&mut card), Ok(()));
+    }
+
+    #[test]
+    fn file_is_created_and_appended() {
+        let mut card = MemoryCard::fat32(true);
+        let first: Vec<u8> = (0..700u32).map(|i| b'a' + (i % 26) as u8).collect();
+        assert_eq!(append(&mut card, &NAME, &[&first[..300], &first[300..]]), 700);
+        assert_eq!(card.read_file(&NAME), Some(first.clone()));
+
+        // The second boot appends to the same file, in the middle of its last sector
+        let second = vec![b'z'; 1_000];
+        assert_eq!(append(&mut card, &NAME, &[&second]), 1_700);
+        assert_eq!(card.read_file(&NAME), Some([first, second].concat()));
+
+        // Both copies of the FAT have the chain of 4 clusters
+        let chain: Vec<u32> = (3..7).map(|cluster| card.fat_entry(0, cluster)).collect();
+        assert_eq!(chain, [4, 5, 6, CLUSTER_END]);
+        assert!((0..8).all(|cluster| card.fat_entry(0, cluster) == card.fat_entry(1, cluster)));
+    }
+
+    #[test]
+    fn root_directory_grows_when_it_is_full() {
+        let mut card = MemoryCard::fat32(false);
+        // One cluster of 512 bytes has 16 entries
+        for index in 0..20 {
+            let name = short_name(&format!("FILE{index:04}.CSV")).unwrap();
+            append(&mut card, &name, &[b"data\r\n"]);
+        }
+
+        let entries = card.root_entries();
+        assert_eq!(entries.len(), 20);
+        assert_ne!(card.fat_entry(0, 2), CLUSTER_END);
+        assert_eq!(card.read_file(b"FILE0019CSV"), Some(b"data\r\n".to_vec()));
+    }
+
+    #[test]
+    fn size_beyond_the_chain_is_corrupt() {
+        let mut card = MemoryCard::fat32(false);
+        append(&mut card, &NAME, &[&[b'x'; 600]]);
+
+        // The directory claims 3 clusters for a chain of 2
+        let root = card.volume_start + RESERVED_SECTORS + 2 * FAT_SIZE;
+        card.block_mut(root)[28..32].copy_from_slice(&1_500u32.to_le_bytes());
+        let result = block_on(async {
+            let mut volume = FatVolume::mount(&mut card).await?;
+            volume.open_append(&NAME).await.map(|file| file.size())
+        });
+        assert_eq!(result, Err(FatError::Corrupt));
+    }
+}
@@ -0,0 +1,460 @@
+// HTTP file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : HTTP file for the modular project.
+ *  File        : http.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the requests, the router and the JSON of the HTTP server.
+ *      Each connection carries one request and is closed after the response (Connection: close).
+ *
+ *      GET  /              status page, it reads the API every 2 seconds
+ *      GET  /api/status    {"pv_c":..,"sp_c":..,"sp_source":"..","output_pct":..,"mode":"..","faults":..,"uptime_s":..}
+ *      GET  /api/config    {"setpoint_c":..,"kp":..,"ki":..,"kd":..}
+ *      POST /api/config    same object, all the fields are optional, out of the bounds of the console is 400
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::{self, Write};
+
+use crate::modular::command::{GAIN_MAX, GAIN_MIN, check_number};
+use crate::modular::setpoint::{SETPOINT_MAX_C, SETPOINT_MIN_C};
+
+pub const HTTP_MAX_REQUEST_LEN: usize = 1024;
+
+pub const STATUS_PAGE: &str = "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+<meta name=\"viewport\" content=\"width=device-width\"><title>Vivarium</title></head>\
+<body style=\"font-family:sans-serif\"><h1>Vivarium</h1><table id=\"s\"></table><script>\
+async function r(){const s=await(await fetch('/api/status')).json();\
+document.getElementById('s').innerHTML=Object.entries(s).map(([k,v])=>`<tr><td>${k}</td><td>${v}</td></tr>`).join('');}\
+r();setInterval(r,2000);</script></body></html>";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Method {
+    Get,
+    Post,
+    Other,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Request<'a> {
+    pub method: Method,
+    pub path: &'a str,
+    pub body: &'a [u8],
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HttpStatus {
+    Ok,
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    PayloadTooLarge,
+    Unavailable, // The control loop is starting
+}
+
+impl HttpStatus {
+    pub fn line(self) -> &'static str {
+        match self {
+            HttpStatus::Ok => "200 OK",
+            HttpStatus::BadRequest => "400 Bad Request",
+            HttpStatus::NotFound => "404 Not Found",
+            HttpStatus::MethodNotAllowed => "405 Method Not Allowed",
+            HttpStatus::PayloadTooLarge => "413 Payload Too Large",
+            HttpStatus::Unavailable => "503 Service Unavailable",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Route {
+    StatusPage,
+    GetStatus,
+    GetConfig,
+    SetConfig,
+}
+
+// Values of the control loop shown by the API, filled by the link with the last status
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StatusView {
+    pub pv: f32,
+    pub sp: f32,
+    pub sp_source: &'static str,
+    pub output: f32,
+    pub mode: &'static str,
+    pub faults: u16,
+    pub uptime_s: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ConfigView {
+    pub setpoint_c: f32,
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+}
+
+// Fields of a POST /api/config, the missing ones are not changed
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ConfigUpdate {
+    pub setpoint_c: Option<f32>,
+    pub kp: Option<f32>,
+    pub ki: Option<f32>,
+    pub kd: Option<f32>,
+}
+
+impl ConfigUpdate {
+    pub fn apply(&self, config: &mut ConfigView) {
+        config.setpoint_c = self.setpoint_c.unwrap_or(config.setpoint_c);
+        config.kp = self.kp.unwrap_or(config.kp);
+        config.ki = self.ki.unwrap_or(config.ki);
+        config.kd = self.kd.unwrap_or(config.kd);
+    }
+}
+
+fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+    data.windows(pattern.len()).position(|window| window == pattern)
+}
+
+fn content_length(headers: &str) -> Result<usize, HttpStatus> {
+    for header in headers.split("\r\n") {
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            return value.trim().parse().map_err(|_| HttpStatus::BadRequest);
+        }
+    }
+    Ok(0)
+}
+
+// Parse the request of the buffer. Returns None while the headers or the body are not complete.
+pub fn parse_request(data: &[u8]) -> Result<Option<Request<'_>>, HttpStatus> {
+    let Some(end) = find(data, b"\r\n\r\n") else {
+        return if data.len() >= HTTP_MAX_REQUEST_LEN {
+            Err(HttpStatus::PayloadTooLarge)
+        } else {
+            Ok(None)
+        };
+    };
+    let head = core::str::from_utf8(&data[..end]).map_err(|_| HttpStatus::BadRequest)?;
+    let (request_line, headers) = head.split_once("\r\n").unwrap_or((head, ""));
+
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(HttpStatus::BadRequest);
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(HttpStatus::BadRequest);
+    }
+    let method = match method {
+        "GET" => Method::Get,
+        "POST" => Method::Post,
+        _ => Method::Other,
+    };
+    // The query is not used
+    let path = target.split('?').next().unwrap_or(target);
+
+    let body_start = end + 4;
+    let body_len = content_length(headers)?;
+    if body_start + body_len > HTTP_MAX_REQUEST_LEN {
+        return Err(HttpStatus::PayloadTooLarge);
+    }
+    let Some(body) = data.get(body_start..body_start + body_len) else {
+        return Ok(None);
+    };
+    Ok(Some(Request { method, path, body }))
+}
+
+pub fn route(request: &Request) -> Result<Route, HttpStatus> {
+    match (request.path, request.method) {
+        ("/" | "/index.html", Method::Get) => Ok(Route::StatusPage),
+        ("/api/status", Method::Get) => Ok(Route::GetStatus),
+        ("/api/config", Method::Get) => Ok(Route::GetConfig),
+        ("/api/config", Method::Post) => Ok(Route::SetConfig),
+        ("/" | "/index.html" | "/api/status" | "/api/config", _) => Err(HttpStatus::MethodNotAllowed),
+        _ => Err(HttpStatus::NotFound),
+    }
+}
+
+// Status line and headers, the body follows with the given length
+pub fn write_head(out: &mut impl Write, status: HttpStatus, content_type: &str, content_length: usize) -> fmt::Result {
+    core::write!(
+        out,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status.line(),
+        content_type,
+        content_length
+    )
+}
+
+// JSON has no NaN, a value that is not finite is written as null
+fn write_number(out: &mut impl Write, value: f32, decimals: usize) -> fmt::Result {
+    if value.is_finite() {
+        core::write!(out, "{:.*}", decimals, value)
+    } else {
+        out.write_str("null")
+    }
+}
+
+pub fn write_status_json(out: &mut impl Write, status: &StatusView) -> fmt::Result {
+    out.write_str("{\"pv_c\":")?;
+    write_number(out, status.pv, 2)?;
+    out.write_str(",\"sp_c\":")?;
+    write_number(out, status.sp, 2)?;
+    core::write!(out, ",\"sp_source\":\"{}\",\"output_pct\":", status.sp_source)?;
+    write_number(out, status.output, 1)?;
+    core::write!(
+        out,
+        ",\"mode\":\"{}\",\"faults\":{},\"uptime_s\":{}}}",
+        status.mode,
+        status.faults,
+        status.uptime_s
+    )
+}
+
+pub fn write_config_json(out: &mut impl Write, config: &ConfigView) -> fmt::Result {
+    out.write_str("{\"setpoint_c\":")?;
+    write_number(out, config.setpoint_c, 2)?;
+    out.write_str(",\"kp\":")?;
+    write_number(out, config.kp, 4)?;
+    out.write_str(",\"ki\":")?;
+    write_number(out, config.ki, 4)?;
+    out.write_str(",\"kd\":")?;
+    write_number(out, config.kd, 4)?;
+    out.write_str("}")
+}
+
+pub fn write_error_json(out: &mut impl Write, status: HttpStatus) -> fmt::Result {
+    core::write!(out, "{{\"error\":\"{}\"}}", status.line())
+}
+
+// Small reader of the flat JSON object of the configuration, only keys and numbers
+struct JsonReader<'a> {
+    text: &'a str,
+}
+
+impl<'a> JsonReader<'a> {
+    fn skip_spaces(&mut self) {
+        self.text = self.text.trim_start();
+    }
+
+    fn expect(&mut self, token: char) -> Option<()> {
+        self.skip_spaces();
+        self.text = self.text.strip_prefix(token)?;
+        Some(())
+    }
+
+    fn next_is(&mut self, token: char) -> bool {
+        self.skip_spaces();
+        self.text.starts_with(token)
+    }
+
+    // The keys never have escapes
+    fn key(&mut self) -> Option<&'a str> {
+        self.expect('"')?;
+        let end = self.text.find('"')?;
+        let key = &self.text[..end];
+        if key.contains('\\') {
+            return None;
+        }
+        self.text = &self.text[end + 1..];
+        Some(key)
+    }
+
+    fn number(&mut self) -> Option<f32> {
+        self.skip_spaces();
+        let end = self
+            .text
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(self.text.len());
+        let value: f32 = self.text[..end].parse().ok()?;
+        self.text = &self.text[end..];
+        value.is_finite().then_some(value)
+    }
+}
+
+// Returns None when the body is not a flat object with known keys and numbers
+pub fn parse_config_update(body: &[u8]) -> Option<ConfigUpdate> {
+    let mut reader = JsonReader {
+        text: core::str::from_utf8(body).ok()?,
+    };
+    let mut update = ConfigUpdate::default();
+
+    reader.expect('{')?;
+    if !reader.next_is('}') {
+        loop {
+            let key = reader.key()?;
+            reader.expect(':')?;
+            let value = Some(reader.number()?);
+            match key {
+                "setpoint_c" => update.setpoint_c = value,
+                "kp" => update.kp = value,
+                "ki" => update.ki = value,
+                "kd" => update.kd = value,
+                _ => return None,
+            }
+            if !reader.next_is(',') {
+                break;
+            }
+            reader.expect(',')?;
+        }
+    }
+    reader.expect('}')?;
+    reader.skip_spaces();
+    reader.text.is_empty().then_some(update)
+}
+
+// Body of a POST /api/config, checked with the same bounds of the commands of the console
+pub fn config_update(request: &Request) -> Result<ConfigUpdate, HttpStatus> {
+    let update = parse_config_update(request.body).ok_or(HttpStatus::BadRequest)?;
+    let check = |value: Option<f32>, min, max| value.map_or(Ok(()), |value| check_number(value, min, max).map(|_| ()));
+
+    check(update.setpoint_c, SETPOINT_MIN_C, SETPOINT_MAX_C)
+        .and(check(update.kp, GAIN_MIN, GAIN_MAX))
+        .and(check(update.ki, GAIN_MIN, GAIN_MAX))
+        .and(check(update.kd, GAIN_MIN, GAIN_MAX))
+        .map_err(|_| HttpStatus::BadRequest)?;
+    Ok(update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heapless::String;
+
+    const POST: &[u8] = b"POST /api/config HTTP/1.1\r\nHost: pico\r\ncontent-length: 13\r\n\r\n{\"kp\":  2.5 }";
+
+    #[test]
+    fn get_request_is_parsed_without_the_query() {
+        let request = parse_request(b"GET /api/status?x=1 HTTP/1.1\r\nHost: pico\r\n\r\n").unwrap().unwrap();
+
+        assert_eq!(request, Request { method: Method::Get, path: "/api/status", body: b"" });
+        assert_eq!(route(&request), Ok(Route::GetStatus));
+    }
+
+    #[test]
+    fn request_waits_for_the_headers_and_the_body() {
+        assert_eq!(parse_request(b"GET / HTTP/1.1\r\nHost: pico\r\n"), Ok(None));
+        assert_eq!(parse_request(&POST[..POST.len() - 1]), Ok(None));
+
+        let request = parse_request(POST).unwrap().unwrap();
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.body, b"{\"kp\":  2.5 }");
+        assert_eq!(route(&request), Ok(Route::SetConfig));
+    }
+
+    #[test]
+    fn bad_and_large_requests_are_rejected() {
+        assert_eq!(parse_request(b"GET /\r\n\r\n"), Err(HttpStatus::BadRequest));
+        assert_eq!(parse_request(b"GET / SPDY/3\r\n\r\n"), Err(HttpStatus::BadRequest));
+        assert_eq!(
+            parse_request(b"POST / HTTP/1.1\r\nContent-Length: x\r\n\r\n"),
+            Err(HttpStatus::BadRequest)
+        );
+        assert_eq!(
+            parse_request(b"POST / HTTP/1.1\r\nContent-Length: 1024\r\n\r\n"),
+            Err(HttpStatus::PayloadTooLarge)
+        );
+        assert_eq!(parse_request(&[b'a'; HTTP_MAX_REQUEST_LEN]), Err(HttpStatus::PayloadTooLarge));
+    }
+
+    #[test]
+    fn unknown_paths_and_methods_are_not_routed() {
+        let request = |method, path| Request { method, path, body: b"" };
+
+        assert_eq!(route(&request(Method::Get, "/")), Ok(Route::StatusPage));
+        assert_eq!(route(&request(Method::Get, "/api/config")), Ok(Route::GetConfig));
+        assert_eq!(route(&request(Method::Post, "/api/status")), Err(HttpStatus::MethodNotAllowed));
+        assert_eq!(route(&request(Method::Other, "/")), Err(HttpStatus::MethodNotAllowed));
+        assert_eq!(route(&request(Method::Get, "/favicon.ico")), Err(HttpStatus::NotFound));
+    }
+
+    #[test]
+    fn head_closes_the_connection() {
+        let mut head: String<160> = String::new();
+        write_head(&mut head, HttpStatus::NotFound, "application/json", 12).unwrap();
+
+        assert_eq!(
+            head.as_str(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: application/json\r\nContent-Length: 12\r\n\
+             Cache-Control: no-store\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn json_of_the_status_writes_null_for_a_value_that_is_not_finite() {
+        let status = StatusView {
+            pv: f32::NAN,
+            sp: 28.0,
+            sp_source: "stored",
+            output: 42.26,
+            mode: "auto",
+            faults: 3,
+            uptime_s: 60,
+        };
+        let mut body: String<256> = String::new();
+        write_status_json(&mut body, &status).unwrap();
+
+        assert_eq!(
+            body.as_str(),
+            "{\"pv_c\":null,\"sp_c\":28.00,\"sp_source\":\"stored\",\"output_pct\":42.3,\"mode\":\"auto\",\"faults\":3,\"uptime_s\":60}"
+        );
+    }
+
+    #[test]
+    fn config_update_changes_only_its_fields() {
+        let update = parse_config_update(b" { \"setpoint_c\" : 30 , \"kd\":1e-1 } ").unwrap();
+        assert_eq!(update, ConfigUpdate { setpoint_c: Some(30.0), kd: Some(0.1), ..Default::default() });
+
+        let mut config = ConfigView { setpoint_c: 28.0, kp: 2.0, ki: 0.1, kd: 0.5 };
+        update.apply(&mut config);
+        assert_eq!(config, ConfigView { setpoint_c: 30.0, kp: 2.0, ki: 0.1, kd: 0.1 });
+
+        let mut body: String<256> = String::new();
+        write_config_json(&mut body, &config).unwrap();
+        assert_eq!(body.as_str(), "{\"setpoint_c\":30.00,\"kp\":2.0000,\"ki\":0.1000,\"kd\":0.1000}");
+        assert_eq!(parse_config_update(b"{}"), Some(ConfigUpdate::default()));
+    }
+
+    #[test]
+    fn config_update_rejects_what_it_does_not_know() {
+        for body in [
+            &b"{\"kp\":\"2\"}"[..],
+            b"{\"mode\":1}",
+            b"{\"kp\":1,}",
+            b"{\"kp\":1} x",
+            b"{\"kp\":nan}",
+            b"{\"kp\":1e99}",
+            b"[1]",
+            b"{\"k\\\"p\":1}",
+        ] {
+            assert_eq!(parse_config_update(body), None, "{:?}", core::str::from_utf8(body));
+        }
+    }
+
+    fn post_config(body: &str) -> Result<ConfigUpdate, HttpStatus> {
+        let mut data: String<256> = String::new();
+        write!(data, "POST /api/config HTTP/1.1\r\nHost: pico\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        config_update(&parse_request(data.as_bytes()).unwrap().unwrap())
+    }
+
+    #[test]
+    fn config_request_out_of_the_console_bounds_is_a_bad_request() {
+        assert_eq!(post_config("{\"kp\":2.5,\"kd\":0}"), Ok(ConfigUpdate { kp: Some(2.5), kd: Some(0.0), ..Default::default() }));
+        assert_eq!(
+            post_config("{\"setpoint_c\":50}"),
+            Ok(ConfigUpdate { setpoint_c: Some(SETPOINT_MAX_C), ..Default::default() })
+        );
+
+        for body in ["{\"kd\":-0.1}", "{\"kp\":-2}", "{\"ki\":-1e-3,\"kp\":1}", "{\"setpoint_c\":80}", "{\"setpoint_c\":-5}"] {
+            assert_eq!(post_config(body), Err(HttpStatus::BadRequest), "{}", body);
+        }
+        assert_eq!(post_config("{\"kp\":"), Err(HttpStatus::BadRequest));
+    }
+
+}
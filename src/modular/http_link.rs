@@ -0,0 +1,204 @@
+// HTTP link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : HTTP link file for the modular project.
+ *  File        : http_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the HTTP server of the vivarium, it answers one request for each
+ *      connection with the status page or the JSON API. The setpoint and the gains written in the
+ *      /api/config go to the control loop like the commands of the console, they are stored in the
+ *      flash only with the command "save".
+ *
+ *      The request is read from any TCP stream of embedded-io-async (the TcpSocket of embassy-net in the
+ *      Pico W), the HTTP task accepts the connection in the port 80, calls it and closes the socket.
+ *      There is one socket, so the connections of the browser are answered one after the other.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_net::Stack;
+use embassy_net::tcp::TcpSocket;
+use embassy_sync::watch::DynReceiver;
+use embassy_time::{Duration, Instant, with_timeout};
+use embedded_io_async::{Read, Write};
+use heapless::String;
+
+use crate::modular::http::*;
+use crate::modular::pwm::{ControlCommand, ControlStatus, get_receiver_control_status, get_sender_control};
+use crate::modular::setpoint::SetpointCommand;
+use crate::modular::setpoint_link::get_sender_setpoint;
+
+pub const HTTP_PORT: u16 = 80;
+const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// A client that stops reading the response does not hold the only socket
+const HTTP_SOCKET_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HttpSessionError<E> {
+    Io(E),
+    Closed,  // The client closed the connection before the end of the request
+    Timeout, // The request was not complete in 5 seconds
+}
+
+fn status_view(status: &ControlStatus) -> StatusView {
+    StatusView {
+        pv: status.pv,
+        sp: status.setpoint.celsius,
+        sp_source: status.setpoint.source.name(),
+        output: status.terms.output,
+        mode: status.mode.name(),
+        faults: status.faults,
+        uptime_s: Instant::now().as_secs() as u32,
+    }
+}
+
+fn config_view(status: &ControlStatus) -> ConfigView {
+    ConfigView {
+        setpoint_c: status.setpoint.celsius,
+        kp: status.gains.kp,
+        ki: status.gains.ki,
+        kd: status.gains.kd,
+    }
+}
+
+async fn apply_update(update: &ConfigUpdate) {
+    if let Some(celsius) = update.setpoint_c {
+        get_sender_setpoint().send(SetpointCommand::Remote(celsius)).await;
+    }
+    let tx_control = get_sender_control();
+    if let Some(kp) = update.kp {
+        tx_control.send(ControlCommand::SetKp(kp)).await;
+    }
+    if let Some(ki) = update.ki {
+        tx_control.send(ControlCommand::SetKi(ki)).await;
+    }
+    if let Some(kd) = update.kd {
+        tx_control.send(ControlCommand::SetKd(kd)).await;
+    }
+}
+
+async fn read_request<S: Read>(
+    socket: &mut S,
+    buffer: &mut [u8; HTTP_MAX_REQUEST_LEN],
+) -> Result<Result<usize, HttpStatus>, HttpSessionError<S::Error>> {
+    let mut len = 0;
+    loop {
+        match parse_request(&buffer[..len]) {
+            Ok(Some(_)) => return Ok(Ok(len)),
+            Ok(None) => {}
+            Err(status) => return Ok(Err(status)),
+        }
+        let read = socket.read(&mut buffer[len..]).await.map_err(HttpSessionError::Io)?;
+        if read == 0 {
+            return Err(HttpSessionError::Closed);
+        }
+        len += read;
+    }
+}
+
+// Build the answer of the request in the body, returns the status and the type of the content
+async fn answer(
+    request: &Request<'_>,
+    status: Option<ControlStatus>,
+    body: &mut String<256>,
+) -> (HttpStatus, &'static str) {
+    const JSON: &str = "application/json";
+
+    let route = match route(request) {
+        Ok(route) => route,
+        Err(error) => {
+            let _ = write_error_json(body, error);
+            return (error, JSON);
+        }
+    };
+    match (route, status) {
+        (Route::StatusPage, _) => (HttpStatus::Ok, "text/html; charset=utf-8"),
+        // The control loop did not publish the first status yet
+        (_, None) => {
+            let _ = write_error_json(body, HttpStatus::Unavailable);
+            (HttpStatus::Unavailable, JSON)
+        }
+        (Route::GetStatus, Some(status)) => {
+            let _ = write_status_json(body, &status_view(&status));
+            (HttpStatus::Ok, JSON)
+        }
+        (Route::GetConfig, Some(status)) => {
+            let _ = write_config_json(body, &config_view(&status));
+            (HttpStatus::Ok, JSON)
+        }
+        (Route::SetConfig, Some(status)) => match config_update(request) {
+            Ok(update) => {
+                apply_update(&update).await;
+                let mut config = config_view(&status);
+                update.apply(&mut config);
+                let _ = write_config_json(body, &config);
+                (HttpStatus::Ok, JSON)
+            }
+            Err(error) => {
+                let _ = write_error_json(body, error);
+                (error, JSON)
+            }
+        },
+    }
+}
+
+// Answer the request of one connection, the caller closes the socket after it
+pub async fn http_session<S: Read + Write>(
+    socket: &mut S,
+    rx_status: &mut DynReceiver<'static, ControlStatus>,
+) -> Result<(), HttpSessionError<S::Error>> {
+    let mut buffer = [0u8; HTTP_MAX_REQUEST_LEN];
+    let mut head: String<160> = String::new();
+    let mut body: String<256> = String::new();
+
+    let parsed = with_timeout(HTTP_REQUEST_TIMEOUT, read_request(socket, &mut buffer))
+        .await
+        .map_err(|_| HttpSessionError::Timeout)??;
+
+    let request = parsed.and_then(|len| parse_request(&buffer[..len])?.ok_or(HttpStatus::BadRequest));
+    let (http_status, content_type) = match request {
+        Ok(request) => {
+            debug!("HTTP {} {}", Debug2Format(&request.method), request.path);
+            answer(&request, rx_status.try_get(), &mut body).await
+        }
+        Err(error) => {
+            let _ = write_error_json(&mut body, error);
+            (error, "application/json")
+        }
+    };
+
+    let content: &[u8] = if body.is_empty() { STATUS_PAGE.as_bytes() } else { body.as_bytes() };
+    let _ = write_head(&mut head, http_status, content_type, content.len());
+    socket.write_all(head.as_bytes()).await.map_err(HttpSessionError::Io)?;
+    socket.write_all(content).await.map_err(HttpSessionError::Io)?;
+    socket.flush().await.map_err(HttpSessionError::Io)
+}
+
+// This task answers the connections of the port 80 one at a time
+#[embassy_executor::task]
+pub async fn http_task(stack: Stack<'static>) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let mut rx_buffer = [0u8; HTTP_MAX_REQUEST_LEN];
+    let mut tx_buffer = [0u8; HTTP_MAX_REQUEST_LEN];
+
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(HTTP_SOCKET_TIMEOUT));
+        if let Err(e) = socket.accept(HTTP_PORT).await {
+            warn!("HTTP accept failed: {}", e);
+            continue;
+        }
+        if let Err(e) = http_session(&mut socket, &mut rx_status).await {
+            debug!("HTTP request dropped: {}", Debug2Format(&e));
+        }
+        // Send the rest of the response and the FIN before the socket is used again
+        socket.close();
+        let _ = socket.flush().await;
+    }
+}
@@ -0,0 +1,398 @@
+// Humidity file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Humidity file for the modular project.
+ *  File        : humidity.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the control of the humidity with the mister (or fogger), a relay
+ *      that is only on or off. There are two strategies:
+ *        - hysteresis: on below (target - band/2), off above (target + band/2);
+ *        - time proportional: a PID runs once per window and its output (0-100%) is the part of the
+ *          window with the mister on, so a slow and wet enclosure gets short pulses.
+ *
+ *      The target comes from a schedule with one value for the day and one for the night. Any decision
+ *      passes through the protections: maximum pulse, minimum interval between pulses and maximum
+ *      misting time in the last hour, so a broken sensor or a dry room can not flood the vivarium.
+ *      Without a valid humidity the mister stays off.
+ *
+ *      The time is given in seconds by the caller.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use super::pid::{Pid, PidGains};
+
+pub const HUMIDITY_MIN_PCT: f32 = 20.0;
+pub const HUMIDITY_MAX_PCT: f32 = 100.0;
+pub const DEFAULT_HUMIDITY_DAY_PCT: f32 = 70.0;
+pub const DEFAULT_HUMIDITY_NIGHT_PCT: f32 = 80.0;
+pub const SECONDS_PER_DAY: u32 = 86_400;
+
+const MINUTES_PER_HOUR: usize = 60;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MistStrategy {
+    Hysteresis { band_pct: f32 },
+    #[allow(dead_code)] // Not chosen in the main.rs
+    TimeProportional { gains: PidGains, window_s: u32 },
+}
+
+// Day and night targets, the day goes from day_start_s up to night_start_s (seconds of the day)
+#[derive(Clone, Copy, PartialEq)]
+pub struct MistSchedule {
+    pub day_start_s: u32,
+    pub night_start_s: u32,
+    pub day_pct: f32,
+    pub night_pct: f32,
+}
+
+impl MistSchedule {
+    pub fn is_day(&self, time_of_day_s: u32) -> bool {
+        let time = time_of_day_s % SECONDS_PER_DAY;
+        if self.day_start_s <= self.night_start_s {
+            time >= self.day_start_s && time < self.night_start_s
+        } else {
+            time >= self.day_start_s || time < self.night_start_s
+        }
+    }
+
+    pub fn target(&self, time_of_day_s: u32) -> f32 {
+        if self.is_day(time_of_day_s) { self.day_pct } else { self.night_pct }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct MistLimits {
+    pub max_pulse_s: u32,       // Longest time on without a pause
+    pub min_interval_s: u32,    // Shortest pause between two pulses
+    pub max_on_s_per_hour: u32, // Misting budget in the last 60 minutes
+    pub min_pulse_s: u32,       // Shorter pulses are not started, the nozzle only drips
+}
+
+pub const DEFAULT_MIST_LIMITS: MistLimits = MistLimits {
+    max_pulse_s: 30,
+    min_interval_s: 120,
+    max_on_s_per_hour: 300,
+    min_pulse_s: 2,
+};
+
+// Why the mister is off while the strategy asks for it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MistBlock {
+    NoSensor,
+    MaxPulse,
+    MinInterval,
+    HourlyBudget,
+}
+
+impl MistBlock {
+    pub fn name(self) -> &'static str {
+        match self {
+            MistBlock::NoSensor => "no-sensor",
+            MistBlock::MaxPulse => "max-pulse",
+            MistBlock::MinInterval => "min-interval",
+            MistBlock::HourlyBudget => "hourly-budget",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MistDecision {
+    pub on: bool,
+    pub target_pct: f32,
+    pub demand_pct: f32, // 0 or 100 in the hysteresis, output of the PID in the time proportional
+    pub blocked: Option<MistBlock>,
+    pub used_s: u32, // Misting time in the last hour
+}
+
+pub struct MistController {
+    strategy: MistStrategy,
+    limits: MistLimits,
+    pid: Pid,
+    demand_pct: f32,
+    window_start_s: Option<u32>,
+    on: bool,
+    on_since_s: u32,
+    off_since_s: Option<u32>, // None until the first pulse, it does not wait after the boot
+    last_step_s: Option<u32>,
+    minute: u32,
+    on_per_minute: [u16; MINUTES_PER_HOUR],
+}
+
+impl MistController {
+    pub const fn new(strategy: MistStrategy, limits: MistLimits) -> Self {
+        let gains = match strategy {
+            MistStrategy::TimeProportional { gains, .. } => gains,
+            MistStrategy::Hysteresis { .. } => PidGains { kp: 0.0, ki: 0.0, kd: 0.0 },
+        };
+        Self {
+            strategy,
+            limits,
+            pid: Pid::new(gains, 0.0, 100.0),
+            demand_pct: 0.0,
+            window_start_s: None,
+            on: false,
+            on_since_s: 0,
+            off_since_s: None,
+            last_step_s: None,
+            minute: 0,
+            on_per_minute: [0; MINUTES_PER_HOUR],
+        }
+    }
+
+    pub fn used_s(&self) -> u32 {
+        self.on_per_minute.iter().map(|&seconds| seconds as u32).sum()
+    }
+
+    // Account the time on since the last step in the buckets of one minute of the last hour
+    fn account(&mut self, now_s: u32) {
+        let minute = now_s / 60;
+        if self.last_step_s.is_none() || minute.wrapping_sub(self.minute) >= MINUTES_PER_HOUR as u32 {
+            self.on_per_minute = [0; MINUTES_PER_HOUR];
+        } else {
+            let mut clear = self.minute;
+            while clear != minute {
+                clear += 1;
+                self.on_per_minute[clear as usize % MINUTES_PER_HOUR] = 0;
+            }
+        }
+        if let Some(last_step_s) = self.last_step_s
+            && self.on
+        {
+            let bucket = &mut self.on_per_minute[minute as usize % MINUTES_PER_HOUR];
+            *bucket = bucket.saturating_add(now_s.saturating_sub(last_step_s) as u16);
+        }
+        self.minute = minute;
+        self.last_step_s = Some(now_s);
+    }
+
+    // What the strategy wants, without the protections
+    fn wanted(&mut self, now_s: u32, humidity: f32, target: f32) -> bool {
+        match self.strategy {
+            MistStrategy::Hysteresis { band_pct } => {
+                self.demand_pct = if humidity < target - band_pct / 2.0 {
+                    100.0
+                } else if humidity >= target + band_pct / 2.0 {
+                    0.0
+                } else {
+                    self.demand_pct
+                };
+                self.demand_pct > 0.0
+            }
+            MistStrategy::TimeProportional { window_s, .. } => {
+                let window_start = match self.window_start_s {
+                    Some(start) if now_s.wrapping_sub(start) < window_s => start,
+                    previous => {
+                        let dt = previous.map_or(window_s, |start| now_s.wrapping_sub(start));
+                        self.demand_pct = self.pid.update(target, humidity, dt as f32).output;
+                        self.window_start_s = Some(now_s);
+                        now_s
+                    }
+                };
+                let on_time_s = (self.demand_pct / 100.0 * window_s as f32) as u32;
+                on_time_s >= self.limits.min_pulse_s && now_s.wrapping_sub(window_start) < on_time_s
+            }
+        }
+    }
+
+    // Protections checked while the strategy wants the mister on
+    fn protect(&self, now_s: u32) -> Option<MistBlock> {
+        if self.used_s() >= self.limits.max_on_s_per_hour {
+            return Some(MistBlock::HourlyBudget);
+        }
+        if self.on {
+            (now_s.wrapping_sub(self.on_since_s) >= self.limits.max_pulse_s).then_some(MistBlock::MaxPulse)
+        } else {
+            self.off_since_s
+                .filter(|&off_since_s| now_s.wrapping_sub(off_since_s) < self.limits.min_interval_s)
+                .map(|_| MistBlock::MinInterval)
+        }
+    }
+
+    // Run one step, `humidity` is None when the sensor is not giving values
+    pub fn step(&mut self, now_s: u32, humidity: Option<f32>, target: f32) -> MistDecision {
+        self.account(now_s);
+
+        let (wanted, blocked) = match humidity {
+            Some(humidity) => {
+                let wanted = self.wanted(now_s, humidity, target);
+                (wanted, if wanted { self.protect(now_s) } else { None })
+            }
+            None => {
+                self.demand_pct = 0.0;
+                self.window_start_s = None;
+                self.pid.reset(0.0);
+                (false, Some(MistBlock::NoSensor))
+            }
+        };
+
+        let on = wanted && blocked.is_none();
+        if on && !self.on {
+            self.on_since_s = now_s;
+        } else if !on && self.on {
+            self.off_since_s = Some(now_s);
+        }
+        self.on = on;
+        MistDecision {
+            on,
+            target_pct: target,
+            demand_pct: self.demand_pct,
+            blocked,
+            used_s: self.used_s(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET_PCT: f32 = 70.0;
+    // Protections that do not get in the way of the strategy
+    const NO_LIMITS: MistLimits = MistLimits {
+        max_pulse_s: 3_600,
+        min_interval_s: 0,
+        max_on_s_per_hour: 3_600,
+        min_pulse_s: 0,
+    };
+
+    fn hysteresis(limits: MistLimits) -> MistController {
+        MistController::new(MistStrategy::Hysteresis { band_pct: 6.0 }, limits)
+    }
+
+    // One step per second from `from_s` up to `to_s` (not included), it returns the seconds on
+    fn run(controller: &mut MistController, from_s: u32, to_s: u32, humidity: f32) -> u32 {
+        (from_s..to_s).filter(|&now_s| controller.step(now_s, Some(humidity), TARGET_PCT).on).count() as u32
+    }
+
+    #[test]
+    fn hysteresis_switches_at_the_edges_of_the_band() {
+        let mut controller = hysteresis(NO_LIMITS);
+
+        // 67 up to 73 %, the mister keeps its state inside the band
+        assert!(!controller.step(0, Some(67.0), TARGET_PCT).on);
+        assert!(controller.step(1, Some(66.9), TARGET_PCT).on);
+        assert!(controller.step(2, Some(70.0), TARGET_PCT).on);
+        assert!(controller.step(3, Some(72.9), TARGET_PCT).on);
+        let decision = controller.step(4, Some(73.0), TARGET_PCT);
+        assert_eq!((decision.on, decision.demand_pct, decision.blocked), (false, 0.0, None));
+        assert!(!controller.step(5, Some(70.0), TARGET_PCT).on);
+        assert!(!controller.step(6, Some(67.0), TARGET_PCT).on);
+        assert!(controller.step(7, Some(66.0), TARGET_PCT).on);
+    }
+
+    #[test]
+    fn pulses_are_limited_and_separated() {
+        let mut controller = hysteresis(DEFAULT_MIST_LIMITS);
+
+        // The first pulse starts at once, there is no wait after the boot
+        assert_eq!(run(&mut controller, 0, 30, 50.0), 30);
+        let decision = controller.step(30, Some(50.0), TARGET_PCT);
+        assert_eq!((decision.on, decision.blocked), (false, Some(MistBlock::MaxPulse)));
+
+        // 120 s of pause since the end of the pulse
+        assert_eq!(run(&mut controller, 31, 149, 50.0), 0);
+        assert_eq!(controller.step(149, Some(50.0), TARGET_PCT).blocked, Some(MistBlock::MinInterval));
+        assert!(controller.step(150, Some(50.0), TARGET_PCT).on);
+
+        // A pulse stopped by the humidity also waits for the interval
+        assert!(!controller.step(160, Some(80.0), TARGET_PCT).on);
+        assert_eq!(controller.step(161, Some(50.0), TARGET_PCT).blocked, Some(MistBlock::MinInterval));
+        assert!(controller.step(280, Some(50.0), TARGET_PCT).on);
+    }
+
+    #[test]
+    fn hourly_budget_is_counted_in_buckets_of_one_minute() {
+        let limits = MistLimits { max_pulse_s: 30, min_interval_s: 0, max_on_s_per_hour: 300, min_pulse_s: 0 };
+        let mut controller = hysteresis(limits);
+
+        // Pulses of 30 s with a pause of 1 s, up to 300 s in the hour
+        assert_eq!(run(&mut controller, 0, 3_599, 50.0), 300);
+        let decision = controller.step(3_599, Some(50.0), TARGET_PCT);
+        assert_eq!((decision.blocked, decision.used_s), (Some(MistBlock::HourlyBudget), 300));
+
+        // The bucket of the first minute leaves the hour and its 58 s are given back
+        let decision = controller.step(3_600, Some(50.0), TARGET_PCT);
+        assert_eq!((decision.on, decision.used_s), (true, 242));
+    }
+
+    #[test]
+    fn budget_is_cleared_after_a_long_gap() {
+        let limits = MistLimits { max_on_s_per_hour: 60, ..NO_LIMITS };
+        let mut controller = hysteresis(limits);
+        assert_eq!(run(&mut controller, 0, 120, 50.0), 60);
+        assert_eq!(controller.used_s(), 60);
+
+        // No step for more than one hour
+        let decision = controller.step(7_200, Some(50.0), TARGET_PCT);
+        assert_eq!((decision.on, decision.used_s), (true, 0));
+    }
+
+    #[test]
+    fn time_proportional_pulse_is_a_part_of_the_window() {
+        let gains = PidGains { kp: 5.0, ki: 0.0, kd: 0.0 };
+        let strategy = MistStrategy::TimeProportional { gains, window_s: 60 };
+        let mut controller = MistController::new(strategy, NO_LIMITS);
+
+        // 10 % below the target is 50 % of the window
+        assert_eq!(run(&mut controller, 0, 29, 60.0), 29);
+        assert!(controller.step(29, Some(60.0), TARGET_PCT).on);
+        assert!(!controller.step(30, Some(60.0), TARGET_PCT).on);
+        assert_eq!(run(&mut controller, 31, 59, 60.0), 0);
+        assert_eq!(controller.step(59, Some(60.0), TARGET_PCT).demand_pct, 50.0);
+
+        // The next window takes the new demand, 2 % below is 10 %
+        assert_eq!(run(&mut controller, 60, 120, 68.0), 6);
+        // Above the target there is no pulse
+        assert_eq!(run(&mut controller, 120, 180, 75.0), 0);
+    }
+
+    #[test]
+    fn time_proportional_does_not_start_pulses_shorter_than_the_minimum() {
+        let gains = PidGains { kp: 1.0, ki: 0.0, kd: 0.0 };
+        let strategy = MistStrategy::TimeProportional { gains, window_s: 60 };
+        let mut controller = MistController::new(strategy, MistLimits { min_pulse_s: 2, ..NO_LIMITS });
+
+        // 3 % of 60 s is 1.8 s
+        assert_eq!(run(&mut controller, 0, 60, 67.0), 0);
+        // 5 % of 60 s is 3 s
+        assert_eq!(run(&mut controller, 60, 120, 65.0), 3);
+    }
+
+    #[test]
+    fn mister_is_off_without_the_sensor() {
+        let mut controller = hysteresis(NO_LIMITS);
+        assert!(controller.step(0, Some(50.0), TARGET_PCT).on);
+
+        let decision = controller.step(1, None, TARGET_PCT);
+        assert_eq!((decision.on, decision.demand_pct, decision.blocked), (false, 0.0, Some(MistBlock::NoSensor)));
+        // The demand starts again from the value of the sensor
+        assert!(!controller.step(2, Some(68.0), TARGET_PCT).on);
+    }
+
+    #[test]
+    fn day_of_the_schedule_may_wrap_past_midnight() {
+        let schedule = MistSchedule { day_start_s: 8 * 3600, night_start_s: 20 * 3600, day_pct: 70.0, night_pct: 80.0 };
+        assert!(!schedule.is_day(8 * 3600 - 1));
+        assert!(schedule.is_day(8 * 3600));
+        assert!(schedule.is_day(20 * 3600 - 1));
+        assert!(!schedule.is_day(20 * 3600));
+        assert_eq!(schedule.target(12 * 3600), 70.0);
+        assert_eq!(schedule.target(SECONDS_PER_DAY + 2 * 3600), 80.0);
+
+        // Nocturnal animal, the "day" of the mister goes from 20:00 up to 08:00
+        let schedule = MistSchedule { day_start_s: 20 * 3600, night_start_s: 8 * 3600, ..schedule };
+        assert!(schedule.is_day(20 * 3600));
+        assert!(schedule.is_day(23 * 3600 + 3599));
+        assert!(schedule.is_day(0));
+        assert!(schedule.is_day(8 * 3600 - 1));
+        assert!(!schedule.is_day(8 * 3600));
+        assert!(!schedule.is_day(12 * 3600));
+        assert!(schedule.is_day(SECONDS_PER_DAY + 3600));
+    }
+}
@@ -0,0 +1,125 @@
+// Humidity link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Humidity link file for the modular project.
+ *  File        : humidity_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the humidity loop of the vivarium, it reads the humidity of the
+ *      DHT, runs the controller of the humidity.rs once per second and drives the relay of the mister
+ *      in the GP14. It has its own controller, targets and relay, nothing is shared with the loop of
+ *      the temperature in the pwm.rs, only the last decision is published for the console.
+ *
+ *      The Pico has no real time clock, the time of the day is the uptime plus an offset written with
+ *      the command "set clock", so after a reboot the day starts at 00:00 until the clock is set.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::cell::Cell;
+
+use defmt::*; // For logging via RTT
+use embassy_rp::gpio::{Level, Output};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::{Duration, Instant, Timer};
+use portable_atomic::{AtomicU32, Ordering};
+
+use crate::modular::dht_link::{DHT_PERIOD_S, get_receiver_dht_humidity};
+use crate::modular::humidity::*;
+
+const HUMIDITY_PERIOD_MS: u64 = 1_000;
+// Three periods of the DHT without a valid read and the mister stops
+const HUMIDITY_STALE: Duration = Duration::from_secs(3 * DHT_PERIOD_S);
+
+pub const DEFAULT_MIST_SCHEDULE: MistSchedule = MistSchedule {
+    day_start_s: 8 * 3600,
+    night_start_s: 20 * 3600,
+    day_pct: DEFAULT_HUMIDITY_DAY_PCT,
+    night_pct: DEFAULT_HUMIDITY_NIGHT_PCT,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct MistStatus {
+    pub humidity: Option<f32>,
+    pub decision: MistDecision,
+}
+
+static MIST_SCHEDULE: Mutex<ThreadModeRawMutex, Cell<MistSchedule>> = Mutex::new(Cell::new(DEFAULT_MIST_SCHEDULE));
+static CLOCK_OFFSET_S: AtomicU32 = AtomicU32::new(0);
+
+const MIST_CONSUMERS: usize = 1;
+static MIST_STATUS_CHANNEL: Watch<ThreadModeRawMutex, MistStatus, MIST_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_mist_status() -> Option<DynReceiver<'static, MistStatus>> {
+    MIST_STATUS_CHANNEL.dyn_receiver()
+}
+
+pub fn mist_schedule() -> MistSchedule {
+    MIST_SCHEDULE.lock(|schedule| schedule.get())
+}
+
+// The targets are limited to the range of the humidity, the hours of the schedule are not changed
+pub fn set_mist_targets(day_pct: f32, night_pct: f32) {
+    MIST_SCHEDULE.lock(|cell| {
+        cell.set(MistSchedule {
+            day_pct: day_pct.clamp(HUMIDITY_MIN_PCT, HUMIDITY_MAX_PCT),
+            night_pct: night_pct.clamp(HUMIDITY_MIN_PCT, HUMIDITY_MAX_PCT),
+            ..cell.get()
+        })
+    });
+}
+
+// Seconds since 00:00
+pub fn time_of_day_s() -> u32 {
+    let uptime_s = (Instant::now().as_secs() % SECONDS_PER_DAY as u64) as u32;
+    (uptime_s + CLOCK_OFFSET_S.load(Ordering::Relaxed)) % SECONDS_PER_DAY
+}
+
+pub fn set_time_of_day_s(time_of_day_s: u32) {
+    let uptime_s = (Instant::now().as_secs() % SECONDS_PER_DAY as u64) as u32;
+    let offset_s = (time_of_day_s % SECONDS_PER_DAY + SECONDS_PER_DAY - uptime_s) % SECONDS_PER_DAY;
+    CLOCK_OFFSET_S.store(offset_s, Ordering::Relaxed);
+}
+
+// This task controls the humidity with the relay of the mister
+#[embassy_executor::task]
+pub async fn humidity_task(mut relay: Output<'static>, strategy: MistStrategy) {
+    let mut rx_humidity = get_receiver_dht_humidity().unwrap();
+    let tx_status = MIST_STATUS_CHANNEL.sender();
+    let mut controller = MistController::new(strategy, DEFAULT_MIST_LIMITS);
+    let mut last_reading: Option<(f32, Instant)> = None;
+    let mut last_block: Option<MistBlock> = None;
+
+    loop {
+        if let Some(humidity) = rx_humidity.try_changed() {
+            last_reading = Some((humidity, Instant::now()));
+        }
+        let humidity = last_reading
+            .filter(|(_, at)| at.elapsed() < HUMIDITY_STALE)
+            .map(|(humidity, _)| humidity);
+
+        let target = mist_schedule().target(time_of_day_s());
+        let decision = controller.step(Instant::now().as_secs() as u32, humidity, target);
+
+        let level = if decision.on { Level::High } else { Level::Low };
+        if relay.get_output_level() != level {
+            info!("Mister {} (humidity {} %, target {} %)", decision.on, humidity, target);
+            relay.set_level(level);
+        }
+        if decision.blocked != last_block {
+            if let Some(block) = decision.blocked {
+                debug!("Mister blocked: {}", block.name());
+            }
+            last_block = decision.blocked;
+        }
+        tx_status.send(MistStatus { humidity, decision });
+
+        Timer::after_millis(HUMIDITY_PERIOD_MS).await;
+    }
+}
@@ -0,0 +1,231 @@
+// I2C bus file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : I2C bus file for the modular project.
+ *  File        : i2c_bus.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the I2C0 bus (GP20 SDA, GP21 SCL) shared by the OLED and the
+ *      sensors. The bus is created in the main.rs inside a mutex and each driver receives its own
+ *      I2cBusDevice, that locks the bus for one transfer, so the drivers of the different tasks
+ *      never mix their transfers. A long write of the display is split in chunks by the driver, so
+ *      a sensor waits at most one chunk.
+ *
+ *      Each device keeps its own counters of transfers and errors, read by the console with
+ *      "get i2c", so a bad cable or a sensor that stopped answering is found without a debugger.
+ *
+ *      A device reset in the middle of a read can hold the SDA low forever. The recovery takes the
+ *      pins from the I2C for a moment, gives up to 9 clocks in the SCL until the device releases the
+ *      SDA, sends a STOP and gives the pins back to the I2C. The bus stays locked during it.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::cell::Cell;
+
+use defmt::*; // For logging via RTT
+use embassy_embedded_hal::shared_bus::I2cDeviceError;
+use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
+use embassy_rp::gpio::{Level, OutputOpenDrain};
+use embassy_rp::i2c::{Async, Error, I2c};
+use embassy_rp::pac;
+use embassy_rp::peripherals::{I2C0, PIN_20, PIN_21};
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_time::{Duration, block_for};
+use embedded_hal_async::i2c::{self as hal_i2c, Error as _, ErrorKind, Operation};
+use portable_atomic::{AtomicU32, Ordering};
+
+pub type I2cBus = Mutex<ThreadModeRawMutex, I2c<'static, I2C0, Async>>;
+
+// Pins given to the I2C0 in the main.rs
+const SDA_PIN: usize = 20;
+const SCL_PIN: usize = 21;
+const FUNCSEL_I2C: u8 = 3;
+const RECOVERY_CLOCKS: u8 = 9;
+const RECOVERY_HALF_PERIOD: Duration = Duration::from_micros(5); // 100 kHz
+
+// Devices of the bus, each one has its own counters
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum I2cDeviceId {
+    Oled,
+    Sht3x,
+    Bme280,
+}
+
+pub const I2C_DEVICES: [I2cDeviceId; 3] = [I2cDeviceId::Oled, I2cDeviceId::Sht3x, I2cDeviceId::Bme280];
+
+impl I2cDeviceId {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            I2cDeviceId::Oled => "oled",
+            I2cDeviceId::Sht3x => "sht3x",
+            I2cDeviceId::Bme280 => "bme280",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct I2cStats {
+    pub transfers: u32,
+    pub errors: u32,
+    pub nacks: u32,              // The device did not answer its address, it is missing or busy
+    pub consecutive_errors: u16, // Reset by the next transfer that works
+}
+
+const NO_STATS: I2cStats = I2cStats {
+    transfers: 0,
+    errors: 0,
+    nacks: 0,
+    consecutive_errors: 0,
+};
+
+static I2C_STATS: BlockingMutex<ThreadModeRawMutex, Cell<[I2cStats; I2C_DEVICES.len()]>> =
+    BlockingMutex::new(Cell::new([NO_STATS; I2C_DEVICES.len()]));
+
+static I2C_RECOVERIES: AtomicU32 = AtomicU32::new(0);
+
+// Number of times that the SDA was found stuck low and the clocks were sent
+pub fn i2c_recoveries() -> u32 {
+    I2C_RECOVERIES.load(Ordering::Relaxed)
+}
+
+pub fn i2c_stats(device: I2cDeviceId) -> I2cStats {
+    I2C_STATS.lock(|stats| stats.get()[device.index()])
+}
+
+fn account<T>(device: I2cDeviceId, result: &Result<T, I2cDeviceError<Error>>) {
+    I2C_STATS.lock(|cell| {
+        let mut all = cell.get();
+        let stats = &mut all[device.index()];
+        stats.transfers = stats.transfers.wrapping_add(1);
+        match result {
+            Ok(_) => stats.consecutive_errors = 0,
+            Err(e) => {
+                stats.errors = stats.errors.wrapping_add(1);
+                stats.consecutive_errors = stats.consecutive_errors.saturating_add(1);
+                if let ErrorKind::NoAcknowledge(_) = e.kind() {
+                    stats.nacks = stats.nacks.wrapping_add(1);
+                }
+            }
+        }
+        cell.set(all);
+    });
+}
+
+// One device of the shared bus, every transfer is counted in the statistics of the device
+pub struct I2cBusDevice {
+    device: I2cDevice<'static, ThreadModeRawMutex, I2c<'static, I2C0, Async>>,
+    id: I2cDeviceId,
+}
+
+impl I2cBusDevice {
+    pub fn new(bus: &'static I2cBus, id: I2cDeviceId) -> Self {
+        Self {
+            device: I2cDevice::new(bus),
+            id,
+        }
+    }
+}
+
+impl hal_i2c::ErrorType for I2cBusDevice {
+    type Error = I2cDeviceError<Error>;
+}
+
+impl hal_i2c::I2c for I2cBusDevice {
+    async fn read(&mut self, address: u8, read: &mut [u8]) -> Result<(), Self::Error> {
+        let result = self.device.read(address, read).await;
+        account(self.id, &result);
+        result
+    }
+
+    async fn write(&mut self, address: u8, write: &[u8]) -> Result<(), Self::Error> {
+        let result = self.device.write(address, write).await;
+        account(self.id, &result);
+        result
+    }
+
+    async fn write_read(&mut self, address: u8, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        let result = self.device.write_read(address, write, read).await;
+        account(self.id, &result);
+        result
+    }
+
+    async fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+        let result = self.device.transaction(address, operations).await;
+        account(self.id, &result);
+        result
+    }
+}
+
+// Give the pin back to the I2C with the same pads of the driver of the embassy-rp
+fn restore_i2c_pin(pin: usize) {
+    pac::PADS_BANK0.gpio(pin).write(|w| {
+        w.set_schmitt(true);
+        w.set_slewfast(false);
+        w.set_ie(true);
+        w.set_od(false);
+        w.set_pue(true);
+        w.set_pde(false);
+    });
+    pac::IO_BANK0.gpio(pin).ctrl().write(|w| w.set_funcsel(FUNCSEL_I2C));
+}
+
+// Release a SDA held low by a device, returns false when the SDA is still low after the clocks
+pub async fn recover_bus(bus: &I2cBus) -> bool {
+    let _guard = bus.lock().await;
+
+    // SAFETY: the pins belong to the I2C inside the bus, that is locked, so no transfer uses them
+    // until they are given back to the I2C in the end of this function.
+    let mut sda = OutputOpenDrain::new(unsafe { PIN_20::steal() }, Level::High);
+    let mut scl = OutputOpenDrain::new(unsafe { PIN_21::steal() }, Level::High);
+    sda.set_pullup(true);
+    scl.set_pullup(true);
+    block_for(RECOVERY_HALF_PERIOD);
+
+    let stuck = sda.is_low();
+    if stuck {
+        I2C_RECOVERIES.add(1, Ordering::Relaxed);
+        for _ in 0..RECOVERY_CLOCKS {
+            scl.set_low();
+            block_for(RECOVERY_HALF_PERIOD);
+            scl.set_high();
+            block_for(RECOVERY_HALF_PERIOD);
+            if sda.is_high() {
+                break;
+            }
+        }
+        // STOP: the SDA goes high while the SCL is high
+        scl.set_low();
+        sda.set_low();
+        block_for(RECOVERY_HALF_PERIOD);
+        scl.set_high();
+        block_for(RECOVERY_HALF_PERIOD);
+        sda.set_high();
+        block_for(RECOVERY_HALF_PERIOD);
+    }
+    let released = sda.is_high();
+
+    drop(sda);
+    drop(scl);
+    restore_i2c_pin(SDA_PIN);
+    restore_i2c_pin(SCL_PIN);
+
+    if stuck {
+        if released {
+            info!("I2C bus recovered, SDA released");
+        } else {
+            warn!("I2C bus recovery failed, SDA still low");
+        }
+    }
+    released
+}
@@ -7,7 +7,15 @@
  *  Date        : 2025-07-22
  *  * -----------------------------------------------------------------------------
  *  Description :
- *      The module is responsible about to control the LED in the GP16.
+ *      The module is responsible about to control the status LED of the board (board.rs), it follows the state of
+ *      the control loop and plays the patterns of the led_pattern.rs. The LED is driven through the
+ *      trait StatusLed, a PWM channel dims the LED for the breathing.
+ *
+ *      With the feature "status-ws2812" the status is a WS2812 RGB LED, driven by the PIO
+ *      program of the embassy-rp in the state machine 2 of the PIO1. It also shows the state by the
+ *      colour. The brightness of the dimmable LEDs is changed with the command "set led <%>".
+ *
+ *      The console command "bootsel" turns the LED solid before the reset into the USB bootloader.
  *
  *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
  *  Framework   : Embassy, no_std
@@ -15,20 +23,175 @@
  */
 
 use defmt::*; // For logging via RTT
-use embassy_rp::gpio::Output;
-use embassy_time::Timer;
+use embassy_rp::pwm::{Pwm, SetDutyCycle};
+use embassy_time::{Instant, Timer};
+use portable_atomic::{AtomicBool, AtomicU8, Ordering};
 use {defmt_rtt as _, panic_probe as _}; // RTT logging and panic handler
 
-// LED blink task - to the gpio in the board
+use crate::modular::led_pattern::{LED_LEVEL_MAX, LedSequencer, LedState, Rgb, fault_code, state_color};
+use crate::modular::pwm::{ControlMode, get_receiver_control_status};
+
+const LED_POLL_MS: u64 = 100; // Longest wait, so a new state is shown at once
+pub const LED_DEFAULT_BRIGHTNESS_PCT: u8 = 25; // A WS2812 at full power is too bright to look at
+
+static BOOTLOADER: AtomicBool = AtomicBool::new(false);
+static BRIGHTNESS_PCT: AtomicU8 = AtomicU8::new(LED_DEFAULT_BRIGHTNESS_PCT);
+
+pub fn led_brightness_pct() -> u8 {
+    BRIGHTNESS_PCT.load(Ordering::Relaxed)
+}
+
+pub fn set_led_brightness_pct(pct: u8) {
+    BRIGHTNESS_PCT.store(pct.min(LED_LEVEL_MAX), Ordering::Relaxed);
+}
+
+// The LED stays solid until the reset
+pub fn show_bootloader() {
+    BOOTLOADER.store(true, Ordering::Relaxed);
+}
+
+// Anything able to show the level of the status LED
+pub trait StatusLed {
+    // True when the levels between off and on are shown
+    fn dimmable(&self) -> bool;
+
+    // Level in percent
+    async fn set_level(&mut self, level: u8);
+
+    // Colour of the state, a LED of one colour ignores it
+    fn set_color(&mut self, _color: Rgb) {}
+}
+
+impl StatusLed for Pwm<'static> {
+    fn dimmable(&self) -> bool {
+        true
+    }
+
+    // The eye is not linear, the square of the level looks like an even ramp
+    async fn set_level(&mut self, level: u8) {
+        let level = level.min(LED_LEVEL_MAX) as u16;
+        if self.set_duty_cycle_fraction(level * level, LED_LEVEL_MAX as u16 * LED_LEVEL_MAX as u16).is_err() {
+            error!("Status LED duty cycle update failed");
+        }
+    }
+}
+
+// Pin of the status LED, chosen in the main.rs
+pub enum StatusLedPin {
+    #[cfg_attr(feature = "status-ws2812", allow(dead_code))]
+    Pwm(Pwm<'static>),
+    #[cfg(feature = "status-ws2812")]
+    Ws2812(ws2812::Ws2812Led),
+}
+
+impl StatusLed for StatusLedPin {
+    fn dimmable(&self) -> bool {
+        match self {
+            StatusLedPin::Pwm(led) => led.dimmable(),
+            #[cfg(feature = "status-ws2812")]
+            StatusLedPin::Ws2812(led) => led.dimmable(),
+        }
+    }
+
+    async fn set_level(&mut self, level: u8) {
+        match self {
+            StatusLedPin::Pwm(led) => StatusLed::set_level(led, level).await,
+            #[cfg(feature = "status-ws2812")]
+            StatusLedPin::Ws2812(led) => led.set_level(level).await,
+        }
+    }
+
+    fn set_color(&mut self, color: Rgb) {
+        match self {
+            StatusLedPin::Pwm(led) => led.set_color(color),
+            #[cfg(feature = "status-ws2812")]
+            StatusLedPin::Ws2812(led) => led.set_color(color),
+        }
+    }
+}
+
+#[cfg(feature = "status-ws2812")]
+pub mod ws2812 {
+    use embassy_rp::Peri;
+    use embassy_rp::dma::Channel;
+    use embassy_rp::peripherals::PIO1;
+    use embassy_rp::pio::{Common, PioPin, StateMachine};
+    use embassy_rp::pio_programs::ws2812::{PioWs2812, PioWs2812Program};
+    use smart_leds::RGB8;
+
+    use super::StatusLed;
+    use crate::modular::led_pattern::{COLOR_IDLE, Rgb};
+
+    // One LED in the state machine 2 of the PIO1
+    pub struct Ws2812Led {
+        driver: PioWs2812<'static, PIO1, 2, 1>,
+        color: Rgb,
+        shown: Option<Rgb>, // Last colour written, the LED keeps it
+    }
+
+    impl Ws2812Led {
+        pub fn new(
+            common: &mut Common<'static, PIO1>,
+            sm: StateMachine<'static, PIO1, 2>,
+            dma: Peri<'static, impl Channel>,
+            pin: Peri<'static, impl PioPin>,
+        ) -> Self {
+            let program = PioWs2812Program::new(common);
+            Self {
+                driver: PioWs2812::new(common, sm, dma, pin, &program),
+                color: COLOR_IDLE,
+                shown: None,
+            }
+        }
+    }
+
+    impl StatusLed for Ws2812Led {
+        fn dimmable(&self) -> bool {
+            true
+        }
+
+        async fn set_level(&mut self, level: u8) {
+            let color = self.color.scaled(level);
+            if self.shown != Some(color) {
+                self.driver.write(&[RGB8::new(color.r, color.g, color.b)]).await;
+                self.shown = Some(color);
+            }
+        }
+
+        fn set_color(&mut self, color: Rgb) {
+            self.color = color;
+        }
+    }
+}
+
+// Status LED task - plays the pattern of the state of the system
 #[embassy_executor::task]
-pub async fn toogle_led(mut led: Output<'static>) {
+pub async fn status_led_task(mut led: StatusLedPin) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let mut sequencer = LedSequencer::new(led.dimmable());
+
     loop {
-        info!("LED ON");
-        led.set_high();
-        Timer::after_millis(500).await;
+        let now_ms = Instant::now().as_millis();
+        let status = rx_status.try_get();
+        let state = match status {
+            _ if BOOTLOADER.load(Ordering::Relaxed) => LedState::Bootloader,
+            Some(status) if status.faults != 0 => LedState::Fault(fault_code(status.faults)),
+            Some(status) if status.mode == ControlMode::Auto => LedState::Regulating,
+            _ => LedState::Idle,
+        };
+        if sequencer.state() != state {
+            info!("Status LED: {}", Debug2Format(&state));
+        }
+        sequencer.set_state(now_ms, state);
+        let (pv, sp) = status.map_or((0.0, 0.0), |status| (status.pv, status.setpoint.celsius));
+        led.set_color(state_color(state, pv, sp));
 
-        info!("LED OFF");
-        led.set_low();
-        Timer::after_millis(9500).await;
+        // The brightness only scales the LEDs able to dim, a GPIO would never turn on
+        let mut level = sequencer.level(now_ms);
+        if led.dimmable() {
+            level = (level as u16 * led_brightness_pct() as u16 / LED_LEVEL_MAX as u16) as u8;
+        }
+        led.set_level(level).await;
+        Timer::after_millis(sequencer.wait_ms(now_ms).min(LED_POLL_MS)).await;
     }
 }
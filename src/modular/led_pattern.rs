@@ -0,0 +1,290 @@
+// LED pattern file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : LED pattern file for the modular project.
+ *  File        : led_pattern.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the patterns of the status LED, each state of the system has its
+ *      own pattern so the state is known only looking at the board:
+ *
+ *      idle        breathing (a short blink each 3 s when the LED can not be dimmed)
+ *      regulating  heartbeat, two beats each second
+ *      fault       N pulses followed by a pause, N is the code of the fault (bit of the FAULT_* + 1)
+ *      bootloader  solid
+ *
+ *      An RGB LED also shows the state by the colour: blue while heating up to the setpoint, cyan while
+ *      cooling down to it, green at the setpoint, red for a fault, white when idle and magenta before
+ *      the bootloader.
+ *
+ *      A pattern is a list of segments, each one ramps the level from one value to another (the same
+ *      value is a step). The sequencer repeats the pattern and starts it again when the state changes,
+ *      so a fault code is always seen from the first pulse. It only uses core and heapless.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use heapless::Vec;
+
+pub const LED_LEVEL_MAX: u8 = 100;
+pub const FAULT_CODE_MAX: u8 = 9;
+const PATTERN_SEGMENTS: usize = 2 * FAULT_CODE_MAX as usize + 1;
+
+const BREATHING_MS: u32 = 1_500; // Each half of the breath
+const FAULT_PULSE_MS: u32 = 250;
+const FAULT_PAUSE_MS: u32 = 1_500;
+const RAMP_STEP_MS: u64 = 20; // Update of the level during a ramp
+pub const AT_SETPOINT_BAND_C: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LedState {
+    Idle,       // Control loop off, manual or not running
+    Regulating, // PID in auto
+    Fault(u8), // Code 1..=FAULT_CODE_MAX
+    Bootloader,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub from: u8, // Level in percent at the start
+    pub to: u8,   // Level in percent at the end
+    pub ms: u32,
+}
+
+const fn step(level: u8, ms: u32) -> Segment {
+    Segment { from: level, to: level, ms }
+}
+
+pub type Pattern = Vec<Segment, PATTERN_SEGMENTS>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+pub const COLOR_HEATING: Rgb = Rgb { r: 0, g: 0, b: 255 };
+pub const COLOR_COOLING: Rgb = Rgb { r: 0, g: 255, b: 255 };
+pub const COLOR_AT_SETPOINT: Rgb = Rgb { r: 0, g: 255, b: 0 };
+pub const COLOR_FAULT: Rgb = Rgb { r: 255, g: 0, b: 0 };
+pub const COLOR_IDLE: Rgb = Rgb { r: 255, g: 255, b: 255 };
+pub const COLOR_BOOTLOADER: Rgb = Rgb { r: 255, g: 0, b: 255 };
+
+impl Rgb {
+    // Colour at the level in percent, squared like the PWM so the ramps look even
+    #[cfg_attr(not(feature = "status-ws2812"), allow(dead_code))] // Only the WS2812 has colours
+    pub fn scaled(self, level: u8) -> Rgb {
+        let level = level.min(LED_LEVEL_MAX) as u32;
+        let scale = |channel: u8| (channel as u32 * level * level / (LED_LEVEL_MAX as u32 * LED_LEVEL_MAX as u32)) as u8;
+        Rgb {
+            r: scale(self.r),
+            g: scale(self.g),
+            b: scale(self.b),
+        }
+    }
+}
+
+// Colour of the state, the regulation shows where the PV is from the SP
+pub fn state_color(state: LedState, pv: f32, sp: f32) -> Rgb {
+    match state {
+        LedState::Regulating if (pv - sp).abs() <= AT_SETPOINT_BAND_C => COLOR_AT_SETPOINT,
+        LedState::Regulating if pv < sp => COLOR_HEATING,
+        LedState::Regulating => COLOR_COOLING,
+        LedState::Idle => COLOR_IDLE,
+        LedState::Fault(_) => COLOR_FAULT,
+        LedState::Bootloader => COLOR_BOOTLOADER,
+    }
+}
+
+// Code of the lowest fault bit, 0 without faults
+pub fn fault_code(faults: u16) -> u8 {
+    if faults == 0 {
+        0
+    } else {
+        (faults.trailing_zeros() as u8 + 1).min(FAULT_CODE_MAX)
+    }
+}
+
+pub fn pattern(state: LedState, dimmable: bool) -> Pattern {
+    let segments: &[Segment] = match state {
+        LedState::Idle if dimmable => &[
+            Segment { from: 0, to: LED_LEVEL_MAX, ms: BREATHING_MS },
+            Segment { from: LED_LEVEL_MAX, to: 0, ms: BREATHING_MS },
+        ],
+        LedState::Idle => &[step(LED_LEVEL_MAX, 50), step(0, 2 * BREATHING_MS - 50)],
+        LedState::Regulating => &[
+            step(LED_LEVEL_MAX, 100),
+            step(0, 150),
+            step(LED_LEVEL_MAX, 100),
+            step(0, 650),
+        ],
+        LedState::Bootloader => &[step(LED_LEVEL_MAX, 1_000)],
+        LedState::Fault(code) => {
+            let mut pattern = Pattern::new();
+            for _ in 0..code.clamp(1, FAULT_CODE_MAX) {
+                let _ = pattern.push(step(LED_LEVEL_MAX, FAULT_PULSE_MS));
+                let _ = pattern.push(step(0, FAULT_PULSE_MS));
+            }
+            let _ = pattern.push(step(0, FAULT_PAUSE_MS));
+            return pattern;
+        }
+    };
+    Pattern::from_slice(segments).unwrap_or_default()
+}
+
+pub struct LedSequencer {
+    dimmable: bool,
+    state: LedState,
+    pattern: Pattern,
+    period_ms: u64,
+    started_ms: u64,
+}
+
+impl LedSequencer {
+    pub fn new(dimmable: bool) -> Self {
+        let mut sequencer = Self {
+            dimmable,
+            state: LedState::Idle,
+            pattern: Pattern::new(),
+            period_ms: 0,
+            started_ms: 0,
+        };
+        sequencer.start(0, LedState::Idle);
+        sequencer
+    }
+
+    fn start(&mut self, now_ms: u64, state: LedState) {
+        self.state = state;
+        self.pattern = pattern(state, self.dimmable);
+        self.period_ms = self.pattern.iter().map(|segment| segment.ms as u64).sum::<u64>().max(1);
+        self.started_ms = now_ms;
+    }
+
+    pub fn state(&self) -> LedState {
+        self.state
+    }
+
+    // A new state starts its pattern from the beginning, the same state keeps playing
+    pub fn set_state(&mut self, now_ms: u64, state: LedState) {
+        if state != self.state {
+            self.start(now_ms, state);
+        }
+    }
+
+    // Segment playing now and the time already spent in it
+    fn position(&self, now_ms: u64) -> (Segment, u64) {
+        let mut offset = now_ms.saturating_sub(self.started_ms) % self.period_ms;
+        for &segment in &self.pattern {
+            if offset < segment.ms as u64 {
+                return (segment, offset);
+            }
+            offset -= segment.ms as u64;
+        }
+        (step(0, 0), 0)
+    }
+
+    // Level in percent of the LED now
+    pub fn level(&self, now_ms: u64) -> u8 {
+        let (segment, offset) = self.position(now_ms);
+        let from = segment.from as i64;
+        let to = segment.to as i64;
+        (from + (to - from) * offset as i64 / segment.ms.max(1) as i64) as u8
+    }
+
+    // Time until the level changes, short steps during a ramp
+    pub fn wait_ms(&self, now_ms: u64) -> u64 {
+        let (segment, offset) = self.position(now_ms);
+        let remaining = (segment.ms as u64).saturating_sub(offset).max(1);
+        if segment.from == segment.to { remaining } else { remaining.min(RAMP_STEP_MS) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(sequencer: &LedSequencer, times_ms: &[u64]) -> std::vec::Vec<u8> {
+        times_ms.iter().map(|&now_ms| sequencer.level(now_ms)).collect()
+    }
+
+    #[test]
+    fn fault_code_is_the_lowest_bit() {
+        assert_eq!(fault_code(0), 0);
+        assert_eq!(fault_code(0b0001), 1);
+        assert_eq!(fault_code(0b1100), 3);
+        assert_eq!(fault_code(1 << 15), FAULT_CODE_MAX);
+    }
+
+    #[test]
+    fn fault_pattern_has_one_pulse_per_code() {
+        for code in 1..=FAULT_CODE_MAX {
+            let pattern = pattern(LedState::Fault(code), true);
+            let pulses = pattern.iter().filter(|segment| segment.to == LED_LEVEL_MAX).count();
+            assert_eq!(pulses, code as usize);
+            assert_eq!(pattern.last(), Some(&step(0, FAULT_PAUSE_MS)));
+        }
+        // The codes out of the range still blink
+        assert_eq!(pattern(LedState::Fault(0), false).len(), 3);
+        assert_eq!(pattern(LedState::Fault(20), false), pattern(LedState::Fault(FAULT_CODE_MAX), false));
+    }
+
+    #[test]
+    fn sequencer_repeats_the_fault_code() {
+        let mut sequencer = LedSequencer::new(false);
+        sequencer.set_state(1_000, LedState::Fault(2));
+
+        // Two pulses of 250 ms, the pause and the first pulse again
+        let times = [0, 249, 250, 500, 750, 1_000, 2_499, 2_500].map(|t| t + 1_000);
+        assert_eq!(levels(&sequencer, &times), [100, 100, 0, 100, 0, 0, 0, 100]);
+    }
+
+    #[test]
+    fn new_state_starts_its_pattern_from_the_beginning() {
+        let mut sequencer = LedSequencer::new(false);
+        sequencer.set_state(0, LedState::Regulating);
+        assert_eq!(sequencer.level(120), 0);
+
+        // The same state keeps playing, a new one restarts in its first segment
+        sequencer.set_state(120, LedState::Regulating);
+        assert_eq!(sequencer.level(120), 0);
+        sequencer.set_state(120, LedState::Fault(1));
+        assert_eq!(sequencer.state(), LedState::Fault(1));
+        assert_eq!(sequencer.level(120), LED_LEVEL_MAX);
+    }
+
+    #[test]
+    fn idle_breathes_only_when_it_can_be_dimmed() {
+        let breathing = LedSequencer::new(true);
+        assert_eq!(levels(&breathing, &[0, 750, 1_500, 2_250, 3_000]), [0, 50, 100, 50, 0]);
+        // The ramp is updated in short steps
+        assert_eq!(breathing.wait_ms(750), RAMP_STEP_MS);
+
+        let blinking = LedSequencer::new(false);
+        assert_eq!(levels(&blinking, &[0, 49, 50, 2_999, 3_000]), [100, 100, 0, 0, 100]);
+        // A step waits until its end
+        assert_eq!(blinking.wait_ms(1_000), 2_000);
+    }
+
+    #[test]
+    fn color_shows_where_the_pv_is_from_the_sp() {
+        assert_eq!(state_color(LedState::Regulating, 27.6, 28.0), COLOR_AT_SETPOINT);
+        assert_eq!(state_color(LedState::Regulating, 27.0, 28.0), COLOR_HEATING);
+        assert_eq!(state_color(LedState::Regulating, 29.0, 28.0), COLOR_COOLING);
+        assert_eq!(state_color(LedState::Fault(1), 28.0, 28.0), COLOR_FAULT);
+        assert_eq!(state_color(LedState::Idle, 20.0, 28.0), COLOR_IDLE);
+    }
+
+    #[test]
+    fn color_is_scaled_like_the_pwm() {
+        let color = Rgb { r: 255, g: 160, b: 0 };
+        assert_eq!(color.scaled(LED_LEVEL_MAX), color);
+        assert_eq!(color.scaled(200), color);
+        assert_eq!(color.scaled(50), Rgb { r: 63, g: 40, b: 0 });
+        assert_eq!(color.scaled(0), Rgb { r: 0, g: 0, b: 0 });
+    }
+}
@@ -0,0 +1,189 @@
+// Loop timing file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Loop timing file for the modular project.
+ *  File        : loop_timing.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the timing of the control loop. The loop runs on a Ticker, so
+ *      the period does not drift with the time spent in each cycle, and this module measures what the
+ *      loop really did: the period between two cycles, its jitter (the largest distance from the
+ *      nominal period), the execution time of a cycle and the overruns (a cycle longer than the
+ *      period, the next tick was lost).
+ *
+ *      The discrete PID assumes a constant Ts, so a period farther than 5% from the nominal one is
+ *      given to the PID as it was measured, limited to 4 periods after a long stall. It only uses core.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+const DT_TOLERANCE: f32 = 0.05; // Deviation of the period still given to the PID as the nominal Ts
+const DT_MAX_PERIODS: f32 = 4.0; // Longest dt given to the PID, in periods
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LoopStats {
+    pub period_us: u32,      // Nominal period
+    pub last_period_us: u32, // Measured between the last two cycles
+    pub min_period_us: u32,
+    pub max_period_us: u32,
+    pub jitter_us: u32, // Largest distance of a period from the nominal one
+    pub exec_us: u32,   // Execution time of the last cycle
+    pub max_exec_us: u32,
+    pub overruns: u32,
+    pub cycles: u32,
+}
+
+impl LoopStats {
+    pub const fn new(period_us: u32) -> Self {
+        Self {
+            period_us,
+            last_period_us: 0,
+            min_period_us: 0,
+            max_period_us: 0,
+            jitter_us: 0,
+            exec_us: 0,
+            max_exec_us: 0,
+            overruns: 0,
+            cycles: 0,
+        }
+    }
+}
+
+pub struct LoopTimer {
+    stats: LoopStats,
+    started_us: Option<u64>, // Start of the current cycle
+}
+
+impl LoopTimer {
+    pub fn new(period_us: u32) -> Self {
+        Self {
+            stats: LoopStats::new(period_us),
+            started_us: None,
+        }
+    }
+
+    pub fn stats(&self) -> LoopStats {
+        self.stats
+    }
+
+    // A new period starts the statistics again, the first cycle has no previous one
+    pub fn set_period(&mut self, period_us: u32) {
+        *self = Self::new(period_us);
+    }
+
+    // Start of a cycle, it returns the dt in seconds for the PID
+    pub fn start(&mut self, now_us: u64) -> f32 {
+        let nominal_s = self.stats.period_us as f32 / 1_000_000.0;
+        let previous = self.started_us.replace(now_us);
+        self.stats.cycles = self.stats.cycles.wrapping_add(1);
+        let Some(previous) = previous else {
+            return nominal_s;
+        };
+
+        let period_us = now_us.saturating_sub(previous).min(u32::MAX as u64) as u32;
+        let stats = &mut self.stats;
+        if stats.min_period_us == 0 || period_us < stats.min_period_us {
+            stats.min_period_us = period_us;
+        }
+        stats.max_period_us = stats.max_period_us.max(period_us);
+        stats.last_period_us = period_us;
+        stats.jitter_us = stats.jitter_us.max(period_us.abs_diff(stats.period_us));
+
+        let measured_s = period_us as f32 / 1_000_000.0;
+        if (measured_s - nominal_s).abs() > nominal_s * DT_TOLERANCE {
+            measured_s.min(nominal_s * DT_MAX_PERIODS)
+        } else {
+            nominal_s
+        }
+    }
+
+    // End of a cycle, it returns true when the cycle took longer than the period
+    pub fn finish(&mut self, now_us: u64) -> bool {
+        let Some(started) = self.started_us else {
+            return false;
+        };
+        let exec_us = now_us.saturating_sub(started).min(u32::MAX as u64) as u32;
+        self.stats.exec_us = exec_us;
+        self.stats.max_exec_us = self.stats.max_exec_us.max(exec_us);
+        let overrun = exec_us > self.stats.period_us;
+        if overrun {
+            self.stats.overruns = self.stats.overruns.wrapping_add(1);
+        }
+        overrun
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERIOD_US: u32 = 100_000;
+
+    // One cycle of the loop of the pwm.rs: start, run for exec_us, finish
+    fn cycle(timer: &mut LoopTimer, start_us: u64, exec_us: u64) -> (f32, bool) {
+        let dt = timer.start(start_us);
+        (dt, timer.finish(start_us + exec_us))
+    }
+
+    #[test]
+    fn nominal_ticks_give_the_nominal_dt() {
+        let mut timer = LoopTimer::new(PERIOD_US);
+        for (index, start_us) in [0, 100_000, 204_000, 300_000, 396_000].into_iter().enumerate() {
+            assert_eq!(cycle(&mut timer, start_us, 2_000), (0.1, false), "cycle {}", index);
+        }
+
+        let stats = timer.stats();
+        assert_eq!((stats.cycles, stats.overruns), (5, 0));
+        assert_eq!((stats.min_period_us, stats.max_period_us, stats.last_period_us), (96_000, 104_000, 96_000));
+        assert_eq!((stats.jitter_us, stats.exec_us, stats.max_exec_us), (4_000, 2_000, 2_000));
+    }
+
+    #[test]
+    fn late_tick_gives_the_measured_dt_to_the_pid() {
+        let mut timer = LoopTimer::new(PERIOD_US);
+        cycle(&mut timer, 0, 1_000);
+
+        assert_eq!(cycle(&mut timer, 112_000, 1_000), (0.112, false));
+        assert_eq!(cycle(&mut timer, 200_000, 1_000), (0.088, false));
+        // A stall of one second is limited to 4 periods
+        assert_eq!(cycle(&mut timer, 1_200_000, 1_000), (0.4, false));
+        assert_eq!(timer.stats().jitter_us, 900_000);
+    }
+
+    #[test]
+    fn overruns_are_counted_and_the_ticker_starts_again_after_them() {
+        let mut timer = LoopTimer::new(PERIOD_US);
+        cycle(&mut timer, 0, 1_000);
+
+        // The cycle of 100 ms runs for 150 ms, the ticker is reset at its end and ticks one period later
+        assert_eq!(cycle(&mut timer, 100_000, 150_000), (0.1, true));
+        assert_eq!(cycle(&mut timer, 350_000, 1_000), (0.25, false));
+        assert_eq!(cycle(&mut timer, 450_000, 1_000), (0.1, false));
+        // A cycle of exactly one period is not an overrun
+        assert_eq!(cycle(&mut timer, 550_000, 100_000), (0.1, false));
+        assert_eq!(cycle(&mut timer, 650_000, 100_001), (0.1, true));
+
+        let stats = timer.stats();
+        assert_eq!((stats.cycles, stats.overruns, stats.max_exec_us), (6, 2, 150_000));
+        assert_eq!(stats.max_period_us, 250_000);
+    }
+
+    #[test]
+    fn new_period_starts_the_statistics_again() {
+        let mut timer = LoopTimer::new(PERIOD_US);
+        cycle(&mut timer, 0, 150_000);
+        assert_eq!(timer.stats().overruns, 1);
+
+        timer.set_period(50_000);
+        assert_eq!(timer.stats(), LoopStats::new(50_000));
+        // The first cycle after the change has no previous one
+        assert!(!timer.finish(400_000));
+        assert_eq!(cycle(&mut timer, 1_000_000, 1_000), (0.05, false));
+        assert_eq!(cycle(&mut timer, 1_050_000, 1_000), (0.05, false));
+        assert_eq!(timer.stats().last_period_us, 50_000);
+    }
+}
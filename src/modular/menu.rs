@@ -0,0 +1,552 @@
+// Menu file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Menu file for the modular project.
+ *  File        : menu.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the menu of the display, driven by the rotary encoder:
+ *        - closed: turn changes the page, press opens the menu;
+ *        - browse: turn selects the item, press edits it, long press closes the menu;
+ *        - edit: turn changes the value, press confirms it, long press cancels it;
+ *        - save: turn chooses yes or no, press confirms, long press cancels.
+ *
+ *      A confirmed value is returned as a Command of the console, so it is applied in the same way,
+ *      and it is only written in the flash by the item "Save".
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::iso_8859_1::FONT_6X10;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle};
+use embedded_graphics::text::{Baseline, Text};
+use heapless::String;
+
+use super::alarm::{LIMIT_MAX_C, LIMIT_MIN_C};
+use super::command::{Command, Gain, ModeRequest, TripLimit};
+use super::screen::NavEvent;
+use super::setpoint::{SETPOINT_MAX_C, SETPOINT_MIN_C};
+
+const GAIN_MAX: f32 = 1000.0;
+const TRIP_GAP_C: f32 = 0.5; // The low limit stays below the high one
+const MENU_LINES: usize = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputEvent {
+    Turn(i8), // Detents, positive is clockwise
+    Press,
+    LongPress,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuItem {
+    Setpoint,
+    Kp,
+    Ki,
+    Kd,
+    Mode,
+    Output,
+    TripLow,
+    TripHigh,
+    Save,
+    Exit,
+}
+
+impl MenuItem {
+    pub const ALL: [MenuItem; 10] = [
+        MenuItem::Setpoint,
+        MenuItem::Kp,
+        MenuItem::Ki,
+        MenuItem::Kd,
+        MenuItem::Mode,
+        MenuItem::Output,
+        MenuItem::TripLow,
+        MenuItem::TripHigh,
+        MenuItem::Save,
+        MenuItem::Exit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MenuItem::Setpoint => "Setpoint",
+            MenuItem::Kp => "Kp",
+            MenuItem::Ki => "Ki",
+            MenuItem::Kd => "Kd",
+            MenuItem::Mode => "Mode",
+            MenuItem::Output => "Manual out",
+            MenuItem::TripLow => "Trip low",
+            MenuItem::TripHigh => "Trip high",
+            MenuItem::Save => "Save",
+            MenuItem::Exit => "Exit",
+        }
+    }
+
+    // Change of the value for each detent
+    fn step(self) -> f32 {
+        match self {
+            MenuItem::Setpoint => 0.1,
+            MenuItem::Ki => 0.01,
+            MenuItem::TripLow | MenuItem::TripHigh => 0.5,
+            MenuItem::Output => 1.0,
+            _ => 0.1,
+        }
+    }
+
+    fn index(self) -> usize {
+        MenuItem::ALL.iter().position(|&item| item == self).unwrap_or(0)
+    }
+}
+
+// Values of the system when the menu is drawn or an edit starts
+#[derive(Clone, Copy, PartialEq)]
+pub struct MenuValues {
+    pub setpoint_c: f32,
+    pub kp: f32,
+    pub ki: f32,
+    pub kd: f32,
+    pub mode: ModeRequest,
+    pub output: f32,
+    pub trip_low_c: f32,
+    pub trip_high_c: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditValue {
+    Number(f32),
+    Mode(ModeRequest),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuState {
+    Closed,
+    Browse { selected: MenuItem },
+    Edit { item: MenuItem, value: EditValue },
+    ConfirmSave { yes: bool },
+}
+
+// What the caller must do after an event
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MenuOutput {
+    Nothing,
+    Navigate(NavEvent),
+    Command(Command),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Menu {
+    state: MenuState,
+    notice: Option<&'static str>, // Result of the last command, shown until the next event
+}
+
+fn mode_name(mode: ModeRequest) -> &'static str {
+    match mode {
+        ModeRequest::Auto => "auto",
+        ModeRequest::Manual(_) => "manual",
+        ModeRequest::Off => "off",
+    }
+}
+
+// Auto, off and manual in a circle, the manual starts with the current output so it is bumpless
+fn turn_mode(mode: ModeRequest, detents: i8, output: f32) -> ModeRequest {
+    const MODES: usize = 3;
+    let index: i32 = match mode {
+        ModeRequest::Auto => 0,
+        ModeRequest::Off => 1,
+        ModeRequest::Manual(_) => 2,
+    };
+    match (index + detents as i32).rem_euclid(MODES as i32) {
+        0 => ModeRequest::Auto,
+        1 => ModeRequest::Off,
+        _ => ModeRequest::Manual(output),
+    }
+}
+
+impl Menu {
+    pub const fn new() -> Self {
+        Self {
+            state: MenuState::Closed,
+            notice: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state != MenuState::Closed
+    }
+
+    pub fn close(&mut self) {
+        self.state = MenuState::Closed;
+        self.notice = None;
+    }
+
+    pub fn notify(&mut self, notice: &'static str) {
+        self.notice = Some(notice);
+    }
+
+    fn limits(item: MenuItem, values: &MenuValues) -> (f32, f32) {
+        match item {
+            MenuItem::Setpoint => (SETPOINT_MIN_C, SETPOINT_MAX_C),
+            MenuItem::Kp | MenuItem::Ki | MenuItem::Kd => (0.0, GAIN_MAX),
+            MenuItem::Output => (0.0, 100.0),
+            MenuItem::TripLow => (LIMIT_MIN_C, values.trip_high_c - TRIP_GAP_C),
+            MenuItem::TripHigh => (values.trip_low_c + TRIP_GAP_C, LIMIT_MAX_C),
+            MenuItem::Mode | MenuItem::Save | MenuItem::Exit => (0.0, 0.0),
+        }
+    }
+
+    fn current(item: MenuItem, values: &MenuValues) -> EditValue {
+        match item {
+            MenuItem::Setpoint => EditValue::Number(values.setpoint_c),
+            MenuItem::Kp => EditValue::Number(values.kp),
+            MenuItem::Ki => EditValue::Number(values.ki),
+            MenuItem::Kd => EditValue::Number(values.kd),
+            MenuItem::Mode => EditValue::Mode(values.mode),
+            MenuItem::Output => EditValue::Number(values.output),
+            MenuItem::TripLow => EditValue::Number(values.trip_low_c),
+            MenuItem::TripHigh => EditValue::Number(values.trip_high_c),
+            MenuItem::Save | MenuItem::Exit => EditValue::Number(0.0),
+        }
+    }
+
+    fn command(item: MenuItem, value: EditValue) -> Option<Command> {
+        match (item, value) {
+            (MenuItem::Setpoint, EditValue::Number(celsius)) => Some(Command::SetSetpoint(celsius)),
+            (MenuItem::Kp, EditValue::Number(kp)) => Some(Command::SetGain(Gain::Kp, kp)),
+            (MenuItem::Ki, EditValue::Number(ki)) => Some(Command::SetGain(Gain::Ki, ki)),
+            (MenuItem::Kd, EditValue::Number(kd)) => Some(Command::SetGain(Gain::Kd, kd)),
+            (MenuItem::Mode, EditValue::Mode(mode)) => Some(Command::Mode(mode)),
+            (MenuItem::Output, EditValue::Number(output)) => Some(Command::Mode(ModeRequest::Manual(output))),
+            (MenuItem::TripLow, EditValue::Number(celsius)) => Some(Command::SetTrip(TripLimit::Low, celsius)),
+            (MenuItem::TripHigh, EditValue::Number(celsius)) => Some(Command::SetTrip(TripLimit::High, celsius)),
+            _ => None,
+        }
+    }
+
+    pub fn handle(&mut self, event: InputEvent, values: &MenuValues) -> MenuOutput {
+        self.notice = None;
+        let (state, output) = match (self.state, event) {
+            (MenuState::Closed, InputEvent::Turn(detents)) => {
+                let event = if detents > 0 { NavEvent::Next } else { NavEvent::Previous };
+                (MenuState::Closed, MenuOutput::Navigate(event))
+            }
+            (MenuState::Closed, InputEvent::Press) => (
+                MenuState::Browse {
+                    selected: MenuItem::Setpoint,
+                },
+                MenuOutput::Nothing,
+            ),
+            (MenuState::Closed, InputEvent::LongPress) => (MenuState::Closed, MenuOutput::Nothing),
+
+            (MenuState::Browse { selected }, InputEvent::Turn(detents)) => {
+                let count = MenuItem::ALL.len() as i32;
+                let index = (selected.index() as i32 + detents as i32).rem_euclid(count);
+                let selected = MenuItem::ALL[index as usize];
+                (MenuState::Browse { selected }, MenuOutput::Nothing)
+            }
+            (MenuState::Browse { selected }, InputEvent::Press) => match selected {
+                MenuItem::Exit => (MenuState::Closed, MenuOutput::Nothing),
+                MenuItem::Save => (MenuState::ConfirmSave { yes: false }, MenuOutput::Nothing),
+                item => (
+                    MenuState::Edit {
+                        item,
+                        value: Self::current(item, values),
+                    },
+                    MenuOutput::Nothing,
+                ),
+            },
+            (MenuState::Browse { .. }, InputEvent::LongPress) => (MenuState::Closed, MenuOutput::Nothing),
+
+            (MenuState::Edit { item, value }, InputEvent::Turn(detents)) => {
+                let value = match value {
+                    EditValue::Number(number) => {
+                        let (min, max) = Self::limits(item, values);
+                        EditValue::Number((number + detents as f32 * item.step()).clamp(min, max))
+                    }
+                    EditValue::Mode(mode) => EditValue::Mode(turn_mode(mode, detents, values.output)),
+                };
+                (MenuState::Edit { item, value }, MenuOutput::Nothing)
+            }
+            (MenuState::Edit { item, value }, InputEvent::Press) => {
+                let output = Self::command(item, value).map_or(MenuOutput::Nothing, MenuOutput::Command);
+                (MenuState::Browse { selected: item }, output)
+            }
+            (MenuState::Edit { item, .. }, InputEvent::LongPress) => {
+                self.notice = Some("Cancelled");
+                (MenuState::Browse { selected: item }, MenuOutput::Nothing)
+            }
+
+            (MenuState::ConfirmSave { yes }, InputEvent::Turn(detents)) => {
+                let yes = if detents % 2 != 0 { !yes } else { yes };
+                (MenuState::ConfirmSave { yes }, MenuOutput::Nothing)
+            }
+            (MenuState::ConfirmSave { yes }, InputEvent::Press) => {
+                let output = if yes { MenuOutput::Command(Command::Save) } else { MenuOutput::Nothing };
+                (MenuState::Browse { selected: MenuItem::Save }, output)
+            }
+            (MenuState::ConfirmSave { .. }, InputEvent::LongPress) => {
+                (MenuState::Browse { selected: MenuItem::Save }, MenuOutput::Nothing)
+            }
+        };
+        self.state = state;
+        output
+    }
+}
+
+fn write_value(out: &mut impl Write, item: MenuItem, value: EditValue) -> fmt::Result {
+    match (item, value) {
+        (_, EditValue::Mode(mode)) => out.write_str(mode_name(mode)),
+        (MenuItem::Setpoint | MenuItem::TripLow | MenuItem::TripHigh, EditValue::Number(celsius)) => {
+            core::write!(out, "{:.1} °C", celsius)
+        }
+        (MenuItem::Output, EditValue::Number(output)) => core::write!(out, "{:.0} %", output),
+        (MenuItem::Kp | MenuItem::Ki | MenuItem::Kd, EditValue::Number(gain)) => core::write!(out, "{:.2}", gain),
+        _ => Ok(()),
+    }
+}
+
+fn text<D: DrawTarget<Color = BinaryColor>>(target: &mut D, text: &str, x: i32, y: i32) -> Result<(), D::Error> {
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    Text::with_baseline(text, Point::new(x, y), style, Baseline::Top).draw(target)?;
+    Ok(())
+}
+
+// Draw the menu in all the display, nothing is drawn while it is closed
+pub fn draw_menu<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    menu: &Menu,
+    values: &MenuValues,
+) -> Result<(), D::Error> {
+    let title = match menu.state {
+        MenuState::Closed => return Ok(()),
+        MenuState::Browse { .. } => "Menu",
+        MenuState::Edit { item, .. } => item.name(),
+        MenuState::ConfirmSave { .. } => "Save",
+    };
+    text(target, title, 0, 0)?;
+    if let Some(notice) = menu.notice {
+        text(target, notice, 128 - notice.len() as i32 * 6, 0)?;
+    }
+    Line::new(Point::new(0, 11), Point::new(127, 11))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)?;
+
+    match menu.state {
+        MenuState::Closed => {}
+        // A window of 5 items that follows the selection
+        MenuState::Browse { selected } => {
+            let first = selected.index().saturating_sub(MENU_LINES - 1);
+            for (row, &item) in MenuItem::ALL.iter().skip(first).take(MENU_LINES).enumerate() {
+                let y = 14 + row as i32 * 10;
+                let mut line: String<24> = String::new();
+                let marker = if item == selected { '>' } else { ' ' };
+                let _ = core::write!(line, "{}{:<10} ", marker, item.name());
+                let _ = write_value(&mut line, item, Menu::current(item, values));
+                text(target, &line, 0, y)?;
+            }
+        }
+        MenuState::Edit { value, item } => {
+            let mut line: String<24> = String::new();
+            let _ = write_value(&mut line, item, value);
+            text(target, &line, (128 - line.chars().count() as i32 * 6) / 2, 28)?;
+            text(target, "press: ok", 0, 44)?;
+            text(target, "hold: cancel", 0, 54)?;
+        }
+        MenuState::ConfirmSave { yes } => {
+            text(target, "Write to the flash?", 0, 20)?;
+            text(target, if yes { "> yes    no" } else { "  yes  > no" }, 28, 38)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_display::FrameBuffer;
+
+    const VALUES: MenuValues = MenuValues {
+        setpoint_c: 28.0,
+        kp: 2.0,
+        ki: 0.1,
+        kd: 0.0,
+        mode: ModeRequest::Auto,
+        output: 35.0,
+        trip_low_c: 20.0,
+        trip_high_c: 32.0,
+    };
+
+    // Run the events and return the output of the last one
+    fn script(menu: &mut Menu, events: &[InputEvent]) -> MenuOutput {
+        events
+            .iter()
+            .map(|&event| menu.handle(event, &VALUES))
+            .last()
+            .unwrap_or(MenuOutput::Nothing)
+    }
+
+    // Open the menu and start to edit the item
+    fn edit(item: MenuItem) -> Menu {
+        let mut menu = Menu::new();
+        script(&mut menu, &[InputEvent::Press, InputEvent::Turn(item.index() as i8), InputEvent::Press]);
+        assert!(matches!(menu.state, MenuState::Edit { item: edited, .. } if edited == item));
+        menu
+    }
+
+    fn number(menu: &Menu) -> f32 {
+        match menu.state {
+            MenuState::Edit { value: EditValue::Number(number), .. } => number,
+            state => panic!("not editing a number: {state:?}"),
+        }
+    }
+
+    #[test]
+    fn closed_menu_turns_the_pages() {
+        let mut menu = Menu::new();
+
+        assert_eq!(menu.handle(InputEvent::Turn(1), &VALUES), MenuOutput::Navigate(NavEvent::Next));
+        assert_eq!(menu.handle(InputEvent::Turn(-2), &VALUES), MenuOutput::Navigate(NavEvent::Previous));
+        assert_eq!(menu.handle(InputEvent::LongPress, &VALUES), MenuOutput::Nothing);
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn browse_wraps_around_and_exit_closes() {
+        let mut menu = Menu::new();
+        script(&mut menu, &[InputEvent::Press, InputEvent::Turn(-1)]);
+        assert_eq!(menu.state, MenuState::Browse { selected: MenuItem::Exit });
+
+        script(&mut menu, &[InputEvent::Turn(1)]);
+        assert_eq!(menu.state, MenuState::Browse { selected: MenuItem::Setpoint });
+
+        script(&mut menu, &[InputEvent::Turn(-1), InputEvent::Press]);
+        assert!(!menu.is_open());
+        script(&mut menu, &[InputEvent::Press, InputEvent::LongPress]);
+        assert!(!menu.is_open());
+    }
+
+    #[test]
+    fn edited_gain_is_sent_on_press() {
+        let mut menu = edit(MenuItem::Kp);
+        assert_eq!(number(&menu), 2.0);
+
+        let output = script(&mut menu, &[InputEvent::Turn(2), InputEvent::Turn(1), InputEvent::Press]);
+        let MenuOutput::Command(Command::SetGain(Gain::Kp, kp)) = output else {
+            panic!("no gain command: {output:?}");
+        };
+        assert!((kp - 2.3).abs() < 1e-5);
+        assert_eq!(menu.state, MenuState::Browse { selected: MenuItem::Kp });
+    }
+
+    #[test]
+    fn long_press_cancels_the_edit() {
+        let mut menu = edit(MenuItem::Setpoint);
+
+        assert_eq!(script(&mut menu, &[InputEvent::Turn(5), InputEvent::LongPress]), MenuOutput::Nothing);
+        assert_eq!(menu.state, MenuState::Browse { selected: MenuItem::Setpoint });
+        assert_eq!(menu.notice, Some("Cancelled"));
+        // The notice is only shown until the next event
+        script(&mut menu, &[InputEvent::Turn(0)]);
+        assert_eq!(menu.notice, None);
+    }
+
+    #[test]
+    fn values_are_clamped_to_their_limits() {
+        let mut menu = edit(MenuItem::Setpoint);
+        script(&mut menu, &[InputEvent::Turn(127); 4]);
+        assert_eq!(number(&menu), SETPOINT_MAX_C);
+        assert_eq!(script(&mut menu, &[InputEvent::Press]), MenuOutput::Command(Command::SetSetpoint(SETPOINT_MAX_C)));
+
+        let mut menu = edit(MenuItem::Output);
+        script(&mut menu, &[InputEvent::Turn(-128)]);
+        assert_eq!(number(&menu), 0.0);
+    }
+
+    #[test]
+    fn trip_limits_do_not_cross() {
+        let mut menu = edit(MenuItem::TripLow);
+        let output = script(&mut menu, &[InputEvent::Turn(127), InputEvent::Press]);
+        assert_eq!(output, MenuOutput::Command(Command::SetTrip(TripLimit::Low, 31.5)));
+
+        let mut menu = edit(MenuItem::TripHigh);
+        let output = script(&mut menu, &[InputEvent::Turn(-128), InputEvent::Press]);
+        assert_eq!(output, MenuOutput::Command(Command::SetTrip(TripLimit::High, 20.5)));
+    }
+
+    #[test]
+    fn mode_turns_in_a_circle_and_manual_keeps_the_output() {
+        let mut menu = edit(MenuItem::Mode);
+        let mode = |menu: &Menu| match menu.state {
+            MenuState::Edit { value: EditValue::Mode(mode), .. } => mode,
+            state => panic!("not editing the mode: {state:?}"),
+        };
+
+        script(&mut menu, &[InputEvent::Turn(1)]);
+        assert_eq!(mode(&menu), ModeRequest::Off);
+        script(&mut menu, &[InputEvent::Turn(1)]);
+        assert_eq!(mode(&menu), ModeRequest::Manual(35.0));
+        script(&mut menu, &[InputEvent::Turn(1)]);
+        assert_eq!(mode(&menu), ModeRequest::Auto);
+
+        let output = script(&mut menu, &[InputEvent::Turn(-1), InputEvent::Press]);
+        assert_eq!(output, MenuOutput::Command(Command::Mode(ModeRequest::Manual(35.0))));
+    }
+
+    #[test]
+    fn save_needs_the_confirmation() {
+        let mut menu = Menu::new();
+        script(&mut menu, &[InputEvent::Press, InputEvent::Turn(-2), InputEvent::Press]);
+        assert_eq!(menu.state, MenuState::ConfirmSave { yes: false });
+
+        // "no" is the default and an even number of detents keeps the choice
+        assert_eq!(script(&mut menu, &[InputEvent::Turn(2), InputEvent::Press]), MenuOutput::Nothing);
+        assert_eq!(menu.state, MenuState::Browse { selected: MenuItem::Save });
+
+        let output = script(&mut menu, &[InputEvent::Press, InputEvent::Turn(-1), InputEvent::Press]);
+        assert_eq!(output, MenuOutput::Command(Command::Save));
+        assert_eq!(menu.state, MenuState::Browse { selected: MenuItem::Save });
+    }
+
+    #[test]
+    fn closed_menu_draws_nothing() {
+        let mut frame = FrameBuffer::new();
+        draw_menu(&mut frame, &Menu::new(), &VALUES).unwrap();
+
+        assert_eq!(frame.count(0, 0, 127, 63), 0);
+    }
+
+    #[test]
+    fn browse_draws_the_marker_in_the_selected_line() {
+        let mut menu = Menu::new();
+        script(&mut menu, &[InputEvent::Press, InputEvent::Turn(2)]);
+        let mut frame = FrameBuffer::new();
+        draw_menu(&mut frame, &menu, &VALUES).unwrap();
+
+        assert_eq!(frame.count(0, 11, 127, 11), 128, "{frame:?}");
+        // The marker is the first character of the third line (Ki), the others start with a space
+        for row in 0..MENU_LINES as i32 {
+            let y = 14 + row * 10;
+            let marker = frame.count(0, y, 5, y + 9);
+            assert_eq!(marker > 0, row == 2, "{frame:?}");
+            assert!(frame.count(6, y, 127, y + 9) > 0, "{frame:?}");
+        }
+    }
+
+    #[test]
+    fn edit_draws_the_value_in_the_middle() {
+        let menu = edit(MenuItem::Kd);
+        let mut frame = FrameBuffer::new();
+        draw_menu(&mut frame, &menu, &VALUES).unwrap();
+
+        // "0.00" is 24 pixels wide, centered in the 128
+        assert!(frame.count(52, 28, 75, 37) > 0, "{frame:?}");
+        assert_eq!(frame.count(0, 28, 51, 37) + frame.count(76, 28, 127, 37), 0, "{frame:?}");
+    }
+}
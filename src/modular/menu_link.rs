@@ -0,0 +1,160 @@
+// Menu link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Menu link file for the modular project.
+ *  File        : menu_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the menu of the display, it receives the events of the encoder,
+ *      runs the menu.rs and applies the confirmed values like the commands of the console: the setpoint
+ *      is a remote one, the gains and the mode go to the control loop and the trip limits are changed
+ *      in the alarm.rs. The item "Save" writes the configuration in the flash.
+ *
+ *      The state of the menu is published for the oled.rs, which draws it instead of the page. The
+ *      menu closes by itself after 30 seconds without events.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::channel::{Channel, DynamicSender};
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::{Duration, with_timeout};
+
+use crate::modular::alarm::{TripLimits, set_trip_limits, trip_limits};
+use crate::modular::command::{Command, Gain, ModeRequest, TripLimit};
+use crate::modular::config_link::{SharedConfigStore, save_config};
+use crate::modular::menu::{InputEvent, Menu, MenuOutput, MenuValues};
+use crate::modular::oled::get_sender_page;
+use crate::modular::pwm::{ControlCommand, ControlMode, ControlStatus, get_receiver_control_status, get_sender_control};
+use crate::modular::setpoint::SetpointCommand;
+use crate::modular::setpoint_link::get_sender_setpoint;
+
+const MENU_TIMEOUT: Duration = Duration::from_secs(30);
+
+const INPUT_EVENTS_DEPTH: usize = 8;
+static INPUT_EVENTS: Channel<ThreadModeRawMutex, InputEvent, INPUT_EVENTS_DEPTH> = Channel::new();
+
+pub fn get_sender_input() -> DynamicSender<'static, InputEvent> {
+    INPUT_EVENTS.dyn_sender()
+}
+
+const MENU_CONSUMERS: usize = 1;
+static MENU_CHANNEL: Watch<ThreadModeRawMutex, Menu, MENU_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_menu() -> Option<DynReceiver<'static, Menu>> {
+    MENU_CHANNEL.dyn_receiver()
+}
+
+pub fn menu_values(status: &ControlStatus) -> MenuValues {
+    let limits = trip_limits();
+    MenuValues {
+        setpoint_c: status.setpoint.celsius,
+        kp: status.gains.kp,
+        ki: status.gains.ki,
+        kd: status.gains.kd,
+        mode: match status.mode {
+            ControlMode::Auto => ModeRequest::Auto,
+            ControlMode::Manual(output) => ModeRequest::Manual(output),
+            ControlMode::Off => ModeRequest::Off,
+        },
+        output: status.terms.output,
+        trip_low_c: limits.low_c,
+        trip_high_c: limits.high_c,
+    }
+}
+
+// Apply a confirmed value, the menu only makes these commands
+async fn execute(command: Command, status: &ControlStatus, config_store: &SharedConfigStore) -> &'static str {
+    match command {
+        Command::SetSetpoint(celsius) => get_sender_setpoint().send(SetpointCommand::Remote(celsius)).await,
+        Command::SetGain(gain, value) => {
+            let command = match gain {
+                Gain::Kp => ControlCommand::SetKp(value),
+                Gain::Ki => ControlCommand::SetKi(value),
+                Gain::Kd => ControlCommand::SetKd(value),
+            };
+            get_sender_control().send(command).await;
+        }
+        Command::Mode(mode) => {
+            let mode = match mode {
+                ModeRequest::Auto => ControlMode::Auto,
+                ModeRequest::Manual(output) => ControlMode::Manual(output),
+                ModeRequest::Off => ControlMode::Off,
+            };
+            get_sender_control().send(ControlCommand::Mode(mode)).await;
+        }
+        Command::SetTrip(limit, celsius) => {
+            let limits = trip_limits();
+            set_trip_limits(match limit {
+                TripLimit::Low => TripLimits {
+                    low_c: celsius,
+                    ..limits
+                },
+                TripLimit::High => TripLimits {
+                    high_c: celsius,
+                    ..limits
+                },
+            });
+        }
+        Command::Save => {
+            return match save_config(config_store, status).await {
+                Ok(()) => "Saved",
+                Err(e) => {
+                    error!("Configuration save failed: {}", Debug2Format(&e));
+                    "Save failed"
+                }
+            };
+        }
+        _ => {}
+    }
+    "Applied"
+}
+
+// This task runs the menu with the events of the encoder
+#[embassy_executor::task]
+pub async fn menu_task(config_store: &'static SharedConfigStore) {
+    let rx_input = INPUT_EVENTS.receiver();
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let tx_menu = MENU_CHANNEL.sender();
+    let tx_page = get_sender_page();
+    let mut menu = Menu::new();
+    tx_menu.send(menu);
+
+    loop {
+        let event = if menu.is_open() {
+            match with_timeout(MENU_TIMEOUT, rx_input.receive()).await {
+                Ok(event) => event,
+                Err(_) => {
+                    info!("Menu closed after {} s without events", MENU_TIMEOUT.as_secs());
+                    menu.close();
+                    tx_menu.send(menu);
+                    continue;
+                }
+            }
+        } else {
+            rx_input.receive().await
+        };
+        // The values of the menu come from the control loop
+        let Some(status) = rx_status.try_get() else {
+            warn!("Menu ignored, the control loop is not running");
+            continue;
+        };
+
+        match menu.handle(event, &menu_values(&status)) {
+            MenuOutput::Nothing => {}
+            MenuOutput::Navigate(event) => tx_page.send(event).await,
+            MenuOutput::Command(command) => {
+                info!("Menu command: {}", command);
+                let notice = execute(command, &status, config_store).await;
+                menu.notify(notice);
+            }
+        }
+        tx_menu.send(menu);
+    }
+}
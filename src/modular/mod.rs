@@ -17,17 +17,97 @@
 // Import required crates and modules
 
 mod adc;
+mod alarm;
+mod alarm_link;
+mod bme280;
+mod bme280_link;
+mod board;
 mod channel_adc_0;
-mod channel_temp;
-//mod dht;
+mod command;
+mod config;
+mod config_link;
+mod console;
+mod control;
+mod datalog;
+mod datalog_link;
+mod dht;
+mod dht_link;
+mod encoder;
+mod ds18b20;
+mod ds18b20_link;
+mod fat;
+#[cfg(feature = "wifi")]
+mod http;
+#[cfg(feature = "wifi")]
+mod http_link;
+mod i2c_bus;
+mod humidity;
+mod humidity_link;
 mod led;
+mod led_pattern;
+mod loop_timing;
+mod menu;
+mod menu_link;
+mod modbus;
+mod modbus_link;
+#[cfg(feature = "wifi")]
+mod mqtt;
+#[cfg(feature = "wifi")]
+mod mqtt_link;
 mod oled;
+mod pid;
 mod pwm;
+mod screen;
+mod sd_link;
+mod sdcard;
+mod sdlog;
+mod setpoint;
+mod setpoint_link;
+mod sht3x;
+mod sht3x_link;
+mod storage;
+mod telemetry;
+mod telemetry_link;
+mod trend;
+#[cfg(feature = "wifi")]
+mod wifi_link;
 
 pub(crate) use adc::*;
+pub(crate) use alarm_link::*;
+pub(crate) use bme280::{BME280_ADDRESS, Bme280Config, Filter, MeasurementMode, Oversampling};
+pub(crate) use bme280_link::*;
+pub(crate) use board::*;
 pub(crate) use channel_adc_0::*;
-pub(crate) use channel_temp::*;
-//pub(crate) use dht::*;
+pub(crate) use config::ConfigStore;
+pub(crate) use config_link::*;
+pub(crate) use console::*;
+pub(crate) use datalog::DataLog;
+pub(crate) use datalog_link::*;
+pub(crate) use dht::DhtKind;
+pub(crate) use dht_link::*;
+pub(crate) use encoder::*;
+pub(crate) use ds18b20::Resolution;
+pub(crate) use ds18b20_link::*;
+#[cfg(feature = "wifi")]
+pub(crate) use http_link::*;
+pub(crate) use humidity::MistStrategy;
+pub(crate) use humidity_link::*;
+pub(crate) use i2c_bus::*;
 pub(crate) use led::*;
+pub(crate) use menu_link::*;
+pub(crate) use modbus_link::*;
+#[cfg(feature = "wifi")]
+pub(crate) use mqtt_link::*;
 pub(crate) use oled::*;
 pub(crate) use pwm::*;
+pub(crate) use screen::Navigation;
+pub(crate) use sd_link::*;
+pub(crate) use sdcard::SdCard;
+pub(crate) use setpoint_link::*;
+pub(crate) use sht3x::{Repeatability, SHT3X_ADDRESS};
+pub(crate) use sht3x_link::*;
+pub(crate) use storage::*;
+pub(crate) use telemetry_link::*;
+pub(crate) use trend::TrendWindow;
+#[cfg(feature = "wifi")]
+pub(crate) use wifi_link::*;
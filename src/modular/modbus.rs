@@ -0,0 +1,589 @@
+// Modbus file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Modbus file for the modular project.
+ *  File        : modbus.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the Modbus RTU slave: the CRC, the framing by the silence
+ *      in the line (t3.5 between frames), the register map and the answer of each request. It does
+ *      not touch any peripheral, the UART is in the modbus_link.rs.
+ *
+ *      The UART keeps its FIFO, so the bytes are handed over in chunks and the silence of t1.5 between
+ *      the characters is not checked, a frame broken by a gap fails the CRC. The end of the frame comes
+ *      from the receive timeout of the UART, that hands over the last bytes after 32 bits of silence.
+ *
+ *      Input registers (0x04)             Holding registers (0x03, 0x06, 0x10)   Coils (0x01, 0x05, 0x0F)
+ *      0 die temperature  °C x100         0 setpoint      °C x100               0 run (1) / stop (0)
+ *      1 ADC0 temperature °C x100         1 Kp            x100                  1 fault reset (write 1)
+ *      2 output           % x10           2 Ki            x1000
+ *      3 setpoint         °C x100         3 Kd            x100
+ *      4 setpoint source                  4 mode          0 off, 1 auto, 2 manual
+ *      5 fault flags                      5 manual output % x10
+ *                                         6 trip high     °C x100
+ *                                         7 trip low      °C x100
+ *      The temperatures are signed (i16). The setpoint source is 0 remote, 2 knob, 3 stored.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use heapless::Vec;
+
+use super::alarm::{LIMIT_MAX_C, LIMIT_MIN_C};
+
+pub const INPUT_REGISTER_COUNT: usize = 6;
+pub const HOLDING_REGISTER_COUNT: usize = 8;
+pub const COIL_COUNT: usize = 2;
+
+pub const IR_DIE_TEMP: u16 = 0;
+pub const IR_REF_TEMP: u16 = 1;
+pub const IR_OUTPUT: u16 = 2;
+pub const IR_SETPOINT: u16 = 3;
+pub const IR_SETPOINT_SOURCE: u16 = 4;
+pub const IR_FAULTS: u16 = 5;
+
+pub const HR_SETPOINT: u16 = 0;
+pub const HR_KP: u16 = 1;
+pub const HR_KI: u16 = 2;
+pub const HR_KD: u16 = 3;
+pub const HR_MODE: u16 = 4;
+pub const HR_MANUAL_OUTPUT: u16 = 5;
+pub const HR_TRIP_HIGH: u16 = 6;
+pub const HR_TRIP_LOW: u16 = 7;
+
+pub const COIL_RUN: u16 = 0;
+pub const COIL_FAULT_RESET: u16 = 1;
+
+pub const MODE_OFF: u16 = 0;
+pub const MODE_AUTO: u16 = 1;
+pub const MODE_MANUAL: u16 = 2;
+
+// Same codes of the telemetry, the 1 is not used
+pub const SOURCE_REMOTE: u16 = 0;
+pub const SOURCE_KNOB: u16 = 2;
+pub const SOURCE_STORED: u16 = 3;
+
+// Limits of the holding registers, a write outside of them is answered with the exception 3,
+// as a write that leaves the trip low at or above the trip high
+pub const SETPOINT_MAX_X100: i16 = 5_000;
+pub const OUTPUT_MAX_X10: u16 = 1_000;
+pub const TRIP_MIN_X100: i16 = (LIMIT_MIN_C * 100.0) as i16;
+pub const TRIP_MAX_X100: i16 = (LIMIT_MAX_C * 100.0) as i16;
+
+pub const BROADCAST_ADDRESS: u8 = 0;
+// Largest RTU frame: address, 253 bytes of PDU and the CRC
+pub const MAX_FRAME_LEN: usize = 256;
+
+const READ_COILS: u8 = 0x01;
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_COIL: u8 = 0x05;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[allow(clippy::enum_variant_names)] // Names of the Modbus specification
+pub enum Exception {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameError {
+    TooShort,
+    Crc,
+    Overflow, // More than MAX_FRAME_LEN bytes without a silence
+}
+
+// Value written by the master, already validated
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ModbusWrite {
+    Register(u16, u16),
+    Coil(u16, bool),
+}
+
+pub type ModbusWrites = Vec<ModbusWrite, HOLDING_REGISTER_COUNT>;
+
+// Values answered to the master, refreshed by the link before every request
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ProcessImage {
+    pub input_registers: [u16; INPUT_REGISTER_COUNT],
+    pub holding_registers: [u16; HOLDING_REGISTER_COUNT],
+    pub coils: [bool; COIL_COUNT],
+}
+
+// Fixed point helpers used by the map
+pub fn to_x100(value: f32) -> u16 {
+    ((value * 100.0) as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16 as u16
+}
+
+pub fn from_x100(value: u16) -> f32 {
+    value as i16 as f32 / 100.0
+}
+
+// CRC-16/MODBUS, polynomial 0xA001 (reflected) and initial value 0xFFFF, sent low byte first
+pub fn crc16_modbus(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+// Check the CRC of the frame and return the address of the slave and the PDU
+pub fn split_frame(frame: &[u8]) -> Result<(u8, &[u8]), FrameError> {
+    if frame.len() < 4 {
+        return Err(FrameError::TooShort);
+    }
+    let (body, crc) = frame.split_at(frame.len() - 2);
+    if crc16_modbus(body) != u16::from_le_bytes([crc[0], crc[1]]) {
+        return Err(FrameError::Crc);
+    }
+    Ok((body[0], &body[1..]))
+}
+
+// Build the frame around the PDU already written in frame[1..1 + pdu_len], returns the frame length
+pub fn finish_frame(frame: &mut [u8; MAX_FRAME_LEN], address: u8, pdu_len: usize) -> usize {
+    frame[0] = address;
+    let crc = crc16_modbus(&frame[..1 + pdu_len]);
+    frame[1 + pdu_len..3 + pdu_len].copy_from_slice(&crc.to_le_bytes());
+    pdu_len + 3
+}
+
+// The trip limits are checked together after the write, the low one must stay below the high one
+fn validate_trip_limits(registers: &[u16; HOLDING_REGISTER_COUNT]) -> bool {
+    (registers[HR_TRIP_LOW as usize] as i16) < (registers[HR_TRIP_HIGH as usize] as i16)
+}
+
+fn validate_register(address: u16, value: u16) -> bool {
+    match address {
+        HR_SETPOINT => (0..=SETPOINT_MAX_X100).contains(&(value as i16)),
+        HR_KP | HR_KI | HR_KD => true,
+        HR_MODE => value <= MODE_MANUAL,
+        HR_MANUAL_OUTPUT => value <= OUTPUT_MAX_X10,
+        HR_TRIP_HIGH | HR_TRIP_LOW => (TRIP_MIN_X100..=TRIP_MAX_X100).contains(&(value as i16)),
+        _ => false,
+    }
+}
+
+fn word(pdu: &[u8], index: usize) -> u16 {
+    u16::from_be_bytes([pdu[index], pdu[index + 1]])
+}
+
+// Check that [start, start + quantity) is inside a table with `count` entries
+fn check_range(start: u16, quantity: u16, max_quantity: u16, count: usize) -> Result<(), Exception> {
+    if quantity == 0 || quantity > max_quantity {
+        return Err(Exception::IllegalDataValue);
+    }
+    if start as usize + quantity as usize > count {
+        return Err(Exception::IllegalDataAddress);
+    }
+    Ok(())
+}
+
+fn read_registers(table: &[u16], pdu: &[u8], response: &mut [u8]) -> Result<usize, Exception> {
+    if pdu.len() != 5 {
+        return Err(Exception::IllegalDataValue);
+    }
+    let (start, quantity) = (word(pdu, 1), word(pdu, 3));
+    check_range(start, quantity, 125, table.len())?;
+
+    response[1] = (quantity * 2) as u8;
+    for (index, value) in table[start as usize..][..quantity as usize].iter().enumerate() {
+        response[2 + index * 2..4 + index * 2].copy_from_slice(&value.to_be_bytes());
+    }
+    Ok(2 + quantity as usize * 2)
+}
+
+fn read_coils(coils: &[bool], pdu: &[u8], response: &mut [u8]) -> Result<usize, Exception> {
+    if pdu.len() != 5 {
+        return Err(Exception::IllegalDataValue);
+    }
+    let (start, quantity) = (word(pdu, 1), word(pdu, 3));
+    check_range(start, quantity, 2_000, coils.len())?;
+
+    let byte_count = (quantity as usize).div_ceil(8);
+    response[1] = byte_count as u8;
+    response[2..2 + byte_count].fill(0);
+    for (index, &coil) in coils[start as usize..][..quantity as usize].iter().enumerate() {
+        if coil {
+            response[2 + index / 8] |= 1 << (index % 8);
+        }
+    }
+    Ok(2 + byte_count)
+}
+
+fn write_registers(pdu: &[u8], image: &ProcessImage, writes: &mut ModbusWrites, response: &mut [u8]) -> Result<usize, Exception> {
+    if pdu.len() < 6 {
+        return Err(Exception::IllegalDataValue);
+    }
+    let (start, quantity, byte_count) = (word(pdu, 1), word(pdu, 3), pdu[5] as usize);
+    if byte_count != quantity as usize * 2 || pdu.len() != 6 + byte_count {
+        return Err(Exception::IllegalDataValue);
+    }
+    check_range(start, quantity, 123, HOLDING_REGISTER_COUNT)?;
+
+    // All the values are checked before any of them is applied
+    let mut registers = image.holding_registers;
+    for index in 0..quantity {
+        let value = word(pdu, 6 + index as usize * 2);
+        if !validate_register(start + index, value) {
+            return Err(Exception::IllegalDataValue);
+        }
+        registers[(start + index) as usize] = value;
+    }
+    if !validate_trip_limits(&registers) {
+        return Err(Exception::IllegalDataValue);
+    }
+    for index in 0..quantity {
+        let _ = writes.push(ModbusWrite::Register(start + index, word(pdu, 6 + index as usize * 2)));
+    }
+    response[1..5].copy_from_slice(&pdu[1..5]);
+    Ok(5)
+}
+
+fn write_coils(pdu: &[u8], writes: &mut ModbusWrites, response: &mut [u8]) -> Result<usize, Exception> {
+    if pdu.len() < 6 {
+        return Err(Exception::IllegalDataValue);
+    }
+    let (start, quantity, byte_count) = (word(pdu, 1), word(pdu, 3), pdu[5] as usize);
+    if byte_count != (quantity as usize).div_ceil(8) || pdu.len() != 6 + byte_count {
+        return Err(Exception::IllegalDataValue);
+    }
+    check_range(start, quantity, 1_968, COIL_COUNT)?;
+
+    for index in 0..quantity as usize {
+        let value = pdu[6 + index / 8] & (1 << (index % 8)) != 0;
+        let _ = writes.push(ModbusWrite::Coil(start + index as u16, value));
+    }
+    response[1..5].copy_from_slice(&pdu[1..5]);
+    Ok(5)
+}
+
+// Answer one request, the PDU of the response is written in `response` and its length returned.
+// The writes accepted are appended to `writes`, they must be applied by the caller.
+pub fn process_request(pdu: &[u8], image: &ProcessImage, writes: &mut ModbusWrites, response: &mut [u8]) -> usize {
+    let function = pdu.first().copied().unwrap_or(0);
+    response[0] = function;
+
+    let result = match function {
+        READ_COILS => read_coils(&image.coils, pdu, response),
+        READ_HOLDING_REGISTERS => read_registers(&image.holding_registers, pdu, response),
+        READ_INPUT_REGISTERS => read_registers(&image.input_registers, pdu, response),
+        WRITE_SINGLE_COIL if pdu.len() != 5 => Err(Exception::IllegalDataValue),
+        WRITE_SINGLE_COIL => {
+            let (address, value) = (word(pdu, 1), word(pdu, 3));
+            if value != 0xFF00 && value != 0x0000 {
+                Err(Exception::IllegalDataValue)
+            } else if address as usize >= COIL_COUNT {
+                Err(Exception::IllegalDataAddress)
+            } else {
+                let _ = writes.push(ModbusWrite::Coil(address, value == 0xFF00));
+                response[1..5].copy_from_slice(&pdu[1..5]);
+                Ok(5)
+            }
+        }
+        WRITE_SINGLE_REGISTER if pdu.len() != 5 => Err(Exception::IllegalDataValue),
+        WRITE_SINGLE_REGISTER => {
+            let (address, value) = (word(pdu, 1), word(pdu, 3));
+            if address as usize >= HOLDING_REGISTER_COUNT {
+                Err(Exception::IllegalDataAddress)
+            } else if !validate_register(address, value) {
+                Err(Exception::IllegalDataValue)
+            } else {
+                let mut registers = image.holding_registers;
+                registers[address as usize] = value;
+                if validate_trip_limits(&registers) {
+                    let _ = writes.push(ModbusWrite::Register(address, value));
+                    response[1..5].copy_from_slice(&pdu[1..5]);
+                    Ok(5)
+                } else {
+                    Err(Exception::IllegalDataValue)
+                }
+            }
+        }
+        WRITE_MULTIPLE_COILS => write_coils(pdu, writes, response),
+        WRITE_MULTIPLE_REGISTERS => write_registers(pdu, image, writes, response),
+        _ => Err(Exception::IllegalFunction),
+    };
+
+    match result {
+        Ok(len) => len,
+        Err(exception) => {
+            writes.clear();
+            response[0] = function | 0x80;
+            response[1] = exception as u8;
+            2
+        }
+    }
+}
+
+// The UART of the RP2040 hands over the received bytes when its FIFO has 28 of its 32 entries, or when
+// there are bytes left in it after a silence of 32 bits (receive timeout)
+pub const UART_RX_FIFO_LEVEL: usize = 28;
+const UART_RX_TIMEOUT_BITS: u64 = 32;
+
+// Times of the RTU, above 19200 bit/s the specification fixes t3.5 = 1750 us
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RtuTiming {
+    pub char_us: u64,
+    pub t3_5_us: u64,
+    pub rx_timeout_us: u64,
+}
+
+impl RtuTiming {
+    pub const fn for_baudrate(baudrate: u32) -> Self {
+        // 11 bits per character (start, 8 data, parity or second stop, stop)
+        let char_us = 11_000_000 / baudrate as u64;
+        Self {
+            char_us,
+            t3_5_us: if baudrate > 19_200 { 1_750 } else { char_us * 7 / 2 },
+            rx_timeout_us: UART_RX_TIMEOUT_BITS * 1_000_000 / baudrate as u64,
+        }
+    }
+}
+
+// Join the chunks of the UART in frames, using the time (us) each chunk arrived. A chunk shorter than the
+// level of the FIFO came with the receive timeout, the line is already silent and the frame ends when the
+// silence reaches t3.5. After a full FIFO more bytes are coming, the frame only ends if nothing arrives in
+// the time the FIFO takes to fill again.
+pub struct RtuReceiver {
+    timing: RtuTiming,
+    buffer: [u8; MAX_FRAME_LEN],
+    len: usize,
+    end_us: u64,
+    error: Option<FrameError>,
+}
+
+impl RtuReceiver {
+    pub const fn new(timing: RtuTiming) -> Self {
+        Self {
+            timing,
+            buffer: [0; MAX_FRAME_LEN],
+            len: 0,
+            end_us: 0,
+            error: None,
+        }
+    }
+
+    pub fn push(&mut self, bytes: &[u8], now_us: u64) {
+        if self.deadline_us().is_some_and(|deadline| now_us >= deadline) {
+            // The previous frame was not polled in time, it is dropped
+            self.len = 0;
+            self.error = None;
+        }
+
+        for &byte in bytes {
+            if self.len < MAX_FRAME_LEN {
+                self.buffer[self.len] = byte;
+                self.len += 1;
+            } else {
+                self.error = Some(FrameError::Overflow);
+            }
+        }
+        let silence_us = if bytes.len() < UART_RX_FIFO_LEVEL {
+            self.timing.t3_5_us.saturating_sub(self.timing.rx_timeout_us)
+        } else {
+            UART_RX_FIFO_LEVEL as u64 * self.timing.char_us + self.timing.rx_timeout_us
+        };
+        self.end_us = now_us + silence_us;
+    }
+
+    // Time when the frame in progress ends if no other chunk arrives
+    pub fn deadline_us(&self) -> Option<u64> {
+        (self.len > 0).then_some(self.end_us)
+    }
+
+    // Returns the frame after the silence of t3.5
+    pub fn poll(&mut self, now_us: u64) -> Option<Result<&[u8], FrameError>> {
+        if self.deadline_us().is_none_or(|deadline| now_us < deadline) {
+            return None;
+        }
+        let len = core::mem::take(&mut self.len);
+        match self.error.take() {
+            Some(error) => Some(Err(error)),
+            None => Some(Ok(&self.buffer[..len])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> ProcessImage {
+        let mut image = ProcessImage {
+            input_registers: [2_750, 2_800, 425, 2_800, 3, 0],
+            ..ProcessImage::default()
+        };
+        image.holding_registers[HR_SETPOINT as usize] = 2_800;
+        image.holding_registers[HR_TRIP_HIGH as usize] = 3_500;
+        image.holding_registers[HR_TRIP_LOW as usize] = 1_800;
+        image.coils[COIL_RUN as usize] = true;
+        image
+    }
+
+    // Answer to the PDU, the writes accepted and the response PDU
+    fn request(pdu: &[u8]) -> (ModbusWrites, std::vec::Vec<u8>) {
+        let mut writes = ModbusWrites::new();
+        let mut response = [0u8; MAX_FRAME_LEN];
+        let len = process_request(pdu, &image(), &mut writes, &mut response);
+        (writes, response[..len].to_vec())
+    }
+
+    fn write_trips(low: i16, high: i16) -> std::vec::Vec<u8> {
+        let mut pdu = std::vec![WRITE_MULTIPLE_REGISTERS, 0, HR_TRIP_HIGH as u8, 0, 2, 4];
+        pdu.extend_from_slice(&high.to_be_bytes());
+        pdu.extend_from_slice(&low.to_be_bytes());
+        pdu
+    }
+
+    #[test]
+    fn crc_matches_the_specification() {
+        assert_eq!(crc16_modbus(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]).to_le_bytes(), [0xC5, 0xCD]);
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let mut frame = [0u8; MAX_FRAME_LEN];
+        frame[1..6].copy_from_slice(&[0x04, 0x00, 0x00, 0x00, 0x02]);
+        let len = finish_frame(&mut frame, 7, 5);
+
+        assert_eq!(split_frame(&frame[..len]), Ok((7, &[0x04, 0x00, 0x00, 0x00, 0x02][..])));
+        frame[3] ^= 0x10;
+        assert_eq!(split_frame(&frame[..len]), Err(FrameError::Crc));
+        assert_eq!(split_frame(&frame[..3]), Err(FrameError::TooShort));
+    }
+
+    #[test]
+    fn read_registers_and_coils() {
+        let (writes, response) = request(&[READ_INPUT_REGISTERS, 0, 0, 0, 2]);
+        assert!(writes.is_empty());
+        assert_eq!(response, [READ_INPUT_REGISTERS, 4, 0x0A, 0xBE, 0x0A, 0xF0]);
+
+        let (_, response) = request(&[READ_HOLDING_REGISTERS, 0, HR_TRIP_HIGH as u8, 0, 2]);
+        assert_eq!(response, [READ_HOLDING_REGISTERS, 4, 0x0D, 0xAC, 0x07, 0x08]);
+
+        let (_, response) = request(&[READ_COILS, 0, 0, 0, 2]);
+        assert_eq!(response, [READ_COILS, 1, 0b01]);
+    }
+
+    #[test]
+    fn malformed_requests_get_an_exception() {
+        assert_eq!(request(&[0x2B, 0, 0]).1, [0xAB, Exception::IllegalFunction as u8]);
+        assert_eq!(request(&[]).1, [0x80, Exception::IllegalFunction as u8]);
+        assert_eq!(request(&[READ_INPUT_REGISTERS, 0, 5, 0, 2]).1, [0x84, Exception::IllegalDataAddress as u8]);
+        assert_eq!(request(&[READ_INPUT_REGISTERS, 0, 0, 0, 0]).1, [0x84, Exception::IllegalDataValue as u8]);
+        assert_eq!(request(&[READ_INPUT_REGISTERS, 0, 0, 0]).1, [0x84, Exception::IllegalDataValue as u8]);
+        // Byte count that does not match the quantity
+        assert_eq!(
+            request(&[WRITE_MULTIPLE_REGISTERS, 0, 0, 0, 1, 4, 0, 1, 0, 2]).1,
+            [0x90, Exception::IllegalDataValue as u8]
+        );
+        assert_eq!(request(&[WRITE_SINGLE_COIL, 0, 0, 0x12, 0x34]).1, [0x85, Exception::IllegalDataValue as u8]);
+    }
+
+    #[test]
+    fn writes_are_validated_before_they_are_applied() {
+        let (writes, response) = request(&[WRITE_SINGLE_REGISTER, 0, HR_SETPOINT as u8, 0x0B, 0xB8]);
+        assert_eq!(writes.as_slice(), [ModbusWrite::Register(HR_SETPOINT, 3_000)]);
+        assert_eq!(response, [WRITE_SINGLE_REGISTER, 0, HR_SETPOINT as u8, 0x0B, 0xB8]);
+
+        // Mode valid but the manual output above 100 %, nothing of the request is applied
+        let (writes, response) = request(&[WRITE_MULTIPLE_REGISTERS, 0, HR_MODE as u8, 0, 2, 4, 0, 1, 0x27, 0x10]);
+        assert!(writes.is_empty());
+        assert_eq!(response, [0x90, Exception::IllegalDataValue as u8]);
+
+        let (writes, _) = request(&[WRITE_MULTIPLE_COILS, 0, 0, 0, 2, 1, 0b10]);
+        assert_eq!(writes.as_slice(), [ModbusWrite::Coil(COIL_RUN, false), ModbusWrite::Coil(COIL_FAULT_RESET, true)]);
+    }
+
+    #[test]
+    fn inverted_trip_limits_are_rejected() {
+        // High below the low one already set
+        let (writes, response) = request(&[WRITE_SINGLE_REGISTER, 0, HR_TRIP_HIGH as u8, 0x06, 0x40]);
+        assert!(writes.is_empty());
+        assert_eq!(response, [0x86, Exception::IllegalDataValue as u8]);
+        // Low equal to the high one
+        let (_, response) = request(&[WRITE_SINGLE_REGISTER, 0, HR_TRIP_LOW as u8, 0x0D, 0xAC]);
+        assert_eq!(response, [0x86, Exception::IllegalDataValue as u8]);
+
+        assert_eq!(request(&write_trips(3_000, 2_000)).1, [0x90, Exception::IllegalDataValue as u8]);
+        // Both limits moved together above the old high one
+        let (writes, _) = request(&write_trips(3_600, 4_000));
+        assert_eq!(
+            writes.as_slice(),
+            [ModbusWrite::Register(HR_TRIP_HIGH, 4_000), ModbusWrite::Register(HR_TRIP_LOW, 3_600)]
+        );
+    }
+
+    #[test]
+    fn timing_of_the_specification() {
+        assert_eq!(
+            RtuTiming::for_baudrate(9_600),
+            RtuTiming { char_us: 1_145, t3_5_us: 4_007, rx_timeout_us: 3_333 }
+        );
+        assert_eq!(
+            RtuTiming::for_baudrate(115_200),
+            RtuTiming { char_us: 95, t3_5_us: 1_750, rx_timeout_us: 277 }
+        );
+    }
+
+    #[test]
+    fn receiver_ends_the_frame_after_the_receive_timeout() {
+        let timing = RtuTiming::for_baudrate(19_200);
+        let mut receiver = RtuReceiver::new(timing);
+
+        // The chunk arrives 32 bits after its last byte, the rest of t3.5 is waited
+        receiver.push(&[1, 2, 3], 10_000);
+        let deadline = receiver.deadline_us().unwrap();
+        assert_eq!(deadline, 10_000 + timing.t3_5_us - timing.rx_timeout_us);
+        assert_eq!(receiver.poll(deadline - 1), None);
+        assert_eq!(receiver.poll(deadline), Some(Ok(&[1u8, 2, 3][..])));
+        assert_eq!(receiver.poll(deadline + 10_000), None);
+        assert_eq!(receiver.deadline_us(), None);
+    }
+
+    #[test]
+    fn receiver_waits_the_rest_after_a_full_fifo() {
+        let timing = RtuTiming::for_baudrate(19_200);
+        let mut receiver = RtuReceiver::new(timing);
+
+        receiver.push(&[0x11; UART_RX_FIFO_LEVEL], 0);
+        // A silence of t3.5 after the chunk does not end the frame, the UART is still receiving
+        assert_eq!(receiver.poll(timing.t3_5_us), None);
+        receiver.push(&[0x22; 4], 6_000);
+        let frame = receiver.poll(6_000 + timing.t3_5_us).unwrap().unwrap();
+        assert_eq!(frame.len(), UART_RX_FIFO_LEVEL + 4);
+        assert_eq!(frame[UART_RX_FIFO_LEVEL], 0x22);
+
+        // A frame that filled the FIFO to its end gets no receive timeout, the guard ends it
+        receiver.push(&[0x33; UART_RX_FIFO_LEVEL], 100_000);
+        let guard = UART_RX_FIFO_LEVEL as u64 * timing.char_us + timing.rx_timeout_us;
+        assert_eq!(receiver.poll(100_000 + guard - 1), None);
+        assert_eq!(receiver.poll(100_000 + guard).map(|frame| frame.map(<[u8]>::len)), Some(Ok(UART_RX_FIFO_LEVEL)));
+    }
+
+    #[test]
+    fn receiver_drops_a_frame_too_long_or_not_polled() {
+        let mut receiver = RtuReceiver::new(RtuTiming::for_baudrate(115_200));
+        for index in 0..10 {
+            receiver.push(&[0x55; 30], index * 2_000);
+        }
+        assert_eq!(receiver.poll(1_000_000), Some(Err(FrameError::Overflow)));
+
+        // A frame not polled before the next one starts is replaced by it
+        receiver.push(&[1], 2_000_000);
+        receiver.push(&[2], 2_010_000);
+        assert_eq!(receiver.poll(2_020_000), Some(Ok(&[2u8][..])));
+    }
+}
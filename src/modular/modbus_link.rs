@@ -0,0 +1,226 @@
+// Modbus link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Modbus link file for the modular project.
+ *  File        : modbus_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the Modbus RTU slave in the UART1 (TX GP4, RX GP5, 19200 8E1),
+ *      the GP6 enables the driver of the RS-485 transceiver while the answer is sent.
+ *      The receiver runs in the executor of the control, above the tasks of the thread mode, so the
+ *      chunks of the UART are timed when its interrupt hands them over and the silence of t3.5 that
+ *      ends the frame is not stretched by a busy task. The frames go to the task of the thread mode,
+ *      that answers them. It refreshes the register map with the last values of the control loop before every request
+ *      and forwards the writes of the master to the setpoint, control and alarm modules.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_futures::select::{Either, select};
+use embassy_rp::gpio::Output;
+use embassy_rp::uart::{BufferedUartRx, BufferedUartTx};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Instant, Timer};
+use embedded_io_async::{Read, Write};
+use heapless::Vec;
+
+use crate::modular::adc::get_receiver_adc0;
+use crate::modular::alarm::{TripLimits, reset_faults, set_trip_limits, trip_limits};
+use crate::modular::modbus::*;
+use crate::modular::pwm::{ControlCommand, ControlMode, ControlStatus, get_receiver_control_status, get_sender_control};
+use crate::modular::setpoint::{SetpointCommand, SetpointSource, knob_raw_to_celsius};
+use crate::modular::setpoint_link::get_sender_setpoint;
+
+pub const MODBUS_ADDRESS: u8 = 1;
+pub const MODBUS_BAUDRATE: u32 = 19_200;
+
+// Frames received, from the executor of the control to the thread mode
+static MODBUS_FRAMES: Channel<CriticalSectionRawMutex, Vec<u8, MAX_FRAME_LEN>, 1> = Channel::new();
+
+fn mode_register(mode: ControlMode) -> u16 {
+    match mode {
+        ControlMode::Off => MODE_OFF,
+        ControlMode::Auto => MODE_AUTO,
+        ControlMode::Manual(_) => MODE_MANUAL,
+    }
+}
+
+fn source_register(source: SetpointSource) -> u16 {
+    match source {
+        SetpointSource::Remote => SOURCE_REMOTE,
+        SetpointSource::Knob => SOURCE_KNOB,
+        SetpointSource::Stored => SOURCE_STORED,
+    }
+}
+
+fn refresh_image(image: &mut ProcessImage, status: Option<ControlStatus>, ref_temp: Option<u16>, manual_output: f32) {
+    let limits = trip_limits();
+
+    if let Some(status) = status {
+        image.input_registers[IR_DIE_TEMP as usize] = to_x100(status.die_c);
+        image.input_registers[IR_OUTPUT as usize] = (status.terms.output * 10.0) as u16;
+        image.input_registers[IR_SETPOINT as usize] = to_x100(status.setpoint.celsius);
+        image.input_registers[IR_SETPOINT_SOURCE as usize] = source_register(status.setpoint.source);
+        image.input_registers[IR_FAULTS as usize] = status.faults;
+
+        image.holding_registers[HR_SETPOINT as usize] = to_x100(status.setpoint.celsius);
+        image.holding_registers[HR_KP as usize] = (status.gains.kp * 100.0) as u16;
+        image.holding_registers[HR_KI as usize] = (status.gains.ki * 1_000.0) as u16;
+        image.holding_registers[HR_KD as usize] = (status.gains.kd * 100.0) as u16;
+        image.holding_registers[HR_MODE as usize] = mode_register(status.mode);
+        image.coils[COIL_RUN as usize] = status.mode != ControlMode::Off;
+    }
+    if let Some(raw) = ref_temp {
+        image.input_registers[IR_REF_TEMP as usize] = to_x100(knob_raw_to_celsius(raw));
+    }
+    image.holding_registers[HR_MANUAL_OUTPUT as usize] = (manual_output * 10.0) as u16;
+    image.holding_registers[HR_TRIP_HIGH as usize] = to_x100(limits.high_c);
+    image.holding_registers[HR_TRIP_LOW as usize] = to_x100(limits.low_c);
+    image.coils[COIL_FAULT_RESET as usize] = false;
+}
+
+async fn apply_write(write: ModbusWrite, mode: ControlMode, manual_output: &mut f32) {
+    let tx_control = get_sender_control();
+    match write {
+        ModbusWrite::Register(HR_SETPOINT, value) => {
+            get_sender_setpoint().send(SetpointCommand::Remote(from_x100(value))).await;
+        }
+        ModbusWrite::Register(HR_KP, value) => tx_control.send(ControlCommand::SetKp(value as f32 / 100.0)).await,
+        ModbusWrite::Register(HR_KI, value) => tx_control.send(ControlCommand::SetKi(value as f32 / 1_000.0)).await,
+        ModbusWrite::Register(HR_KD, value) => tx_control.send(ControlCommand::SetKd(value as f32 / 100.0)).await,
+        ModbusWrite::Register(HR_MODE, value) => {
+            let mode = match value {
+                MODE_OFF => ControlMode::Off,
+                MODE_AUTO => ControlMode::Auto,
+                _ => ControlMode::Manual(*manual_output),
+            };
+            tx_control.send(ControlCommand::Mode(mode)).await;
+        }
+        ModbusWrite::Register(HR_MANUAL_OUTPUT, value) => {
+            *manual_output = value as f32 / 10.0;
+            if let ControlMode::Manual(_) = mode {
+                tx_control.send(ControlCommand::Mode(ControlMode::Manual(*manual_output))).await;
+            }
+        }
+        ModbusWrite::Register(HR_TRIP_HIGH, value) => {
+            set_trip_limits(TripLimits {
+                high_c: from_x100(value),
+                ..trip_limits()
+            });
+        }
+        ModbusWrite::Register(HR_TRIP_LOW, value) => {
+            set_trip_limits(TripLimits {
+                low_c: from_x100(value),
+                ..trip_limits()
+            });
+        }
+        ModbusWrite::Coil(COIL_RUN, run) => {
+            if !run {
+                tx_control.send(ControlCommand::Mode(ControlMode::Off)).await;
+            } else if mode == ControlMode::Off {
+                tx_control.send(ControlCommand::Mode(ControlMode::Auto)).await;
+            }
+        }
+        ModbusWrite::Coil(COIL_FAULT_RESET, true) => reset_faults(),
+        _ => {}
+    }
+}
+
+// This task joins the bytes of the UART in frames, it runs in the executor of the control
+#[embassy_executor::task]
+pub async fn modbus_rx_task(mut uart: BufferedUartRx) {
+    let mut receiver = RtuReceiver::new(RtuTiming::for_baudrate(MODBUS_BAUDRATE));
+    let mut chunk = [0u8; MAX_FRAME_LEN];
+
+    loop {
+        // Wait for more bytes, or for the silence that ends the frame in progress
+        let silence = async {
+            match receiver.deadline_us() {
+                Some(deadline) => Timer::at(Instant::from_micros(deadline)).await,
+                None => core::future::pending().await,
+            }
+        };
+        match select(uart.read(&mut chunk), silence).await {
+            Either::First(Ok(len)) => {
+                receiver.push(&chunk[..len], Instant::now().as_micros());
+                continue;
+            }
+            Either::First(Err(e)) => {
+                warn!("Modbus UART error: {}", e);
+                continue;
+            }
+            Either::Second(()) => {}
+        }
+
+        match receiver.poll(Instant::now().as_micros()) {
+            None => {}
+            Some(Ok(frame)) => {
+                let frame = unwrap!(Vec::from_slice(frame).ok());
+                // The answer of the previous request is still being sent, the master waits for it
+                if MODBUS_FRAMES.try_send(frame).is_err() {
+                    debug!("Modbus frame dropped while busy");
+                }
+            }
+            Some(Err(e)) => debug!("Modbus frame dropped: {}", defmt::Debug2Format(&e)),
+        }
+    }
+}
+
+// This task answers the requests of the Modbus master
+#[embassy_executor::task]
+pub async fn modbus_task(mut uart: BufferedUartTx, mut driver_enable: Output<'static>) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let mut rx_ref_temp = get_receiver_adc0().unwrap();
+
+    let mut image = ProcessImage::default();
+    let mut manual_output = 0.0;
+    let mut response = [0u8; MAX_FRAME_LEN];
+
+    driver_enable.set_low();
+    loop {
+        let frame = MODBUS_FRAMES.receive().await;
+        let (address, pdu) = match split_frame(&frame) {
+            Ok(request) => request,
+            Err(e) => {
+                debug!("Modbus frame dropped: {}", defmt::Debug2Format(&e));
+                continue;
+            }
+        };
+        if address != MODBUS_ADDRESS && address != BROADCAST_ADDRESS {
+            continue;
+        }
+
+        let status = rx_status.try_get();
+        refresh_image(&mut image, status, rx_ref_temp.try_get(), manual_output);
+        let mut writes = ModbusWrites::new();
+        let pdu_len = process_request(pdu, &image, &mut writes, &mut response[1..]);
+
+        let mode = status.map_or(ControlMode::Auto, |status| status.mode);
+        for write in writes {
+            apply_write(write, mode, &mut manual_output).await;
+        }
+
+        // The broadcast is never answered
+        if address == BROADCAST_ADDRESS {
+            continue;
+        }
+        let len = finish_frame(&mut response, MODBUS_ADDRESS, pdu_len);
+        driver_enable.set_high();
+        let sent = uart.write_all(&response[..len]).await;
+        let flushed = uart.flush().await;
+        // The flush returns with the last byte still in the shift register
+        while uart.busy() {
+            Timer::after_micros(100).await;
+        }
+        driver_enable.set_low();
+        if sent.and(flushed).is_err() {
+            warn!("Modbus answer not sent");
+        }
+    }
+}
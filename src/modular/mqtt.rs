@@ -0,0 +1,364 @@
+// MQTT file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : MQTT file for the modular project.
+ *  File        : mqtt.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the packets of the MQTT 3.1.1 client and the topics of the
+ *      vivarium. Only QoS 0 is used, the values are published again in every period, so a lost
+ *      message is replaced by the next one and the client does not need to keep any session.
+ *
+ *      vivarium/<client>/status          online | offline (retained, offline is the last will)
+ *      vivarium/<client>/pv|sp|output    value with 2 decimals
+ *      vivarium/<client>/mode            off | auto | manual
+ *      vivarium/<client>/cmd/setpoint    <°C> | release
+ *      vivarium/<client>/cmd/mode        auto | off | manual <0-100>
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::Write;
+
+use heapless::String;
+
+pub const MQTT_MAX_PACKET_LEN: usize = 256;
+pub const MQTT_KEEP_ALIVE_S: u16 = 60;
+pub const MQTT_TOPIC_ROOT: &str = "vivarium";
+pub const MQTT_ONLINE: &[u8] = b"online";
+pub const MQTT_OFFLINE: &[u8] = b"offline";
+
+const PACKET_CONNECT: u8 = 1;
+const PACKET_CONNACK: u8 = 2;
+const PACKET_PUBLISH: u8 = 3;
+const PACKET_SUBSCRIBE: u8 = 8;
+const PACKET_SUBACK: u8 = 9;
+const PACKET_PINGREQ: u8 = 12;
+const PACKET_PINGRESP: u8 = 13;
+
+const CONNECT_CLEAN_SESSION: u8 = 0x02;
+const CONNECT_WILL: u8 = 0x04;
+const CONNECT_WILL_RETAIN: u8 = 0x20;
+const PUBLISH_RETAIN: u8 = 0x01;
+const PROTOCOL_LEVEL_3_1_1: u8 = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MqttError {
+    BufferTooSmall,
+    Malformed,
+    Refused(u8),    // Return code of the CONNACK
+    Unexpected(u8), // Type of a packet that the client does not expect
+}
+
+// Packets sent by the broker, the topic and the payload point to the receive buffer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Packet<'a> {
+    ConnAck { session_present: bool, code: u8 },
+    SubAck { packet_id: u16, granted: u8 },
+    Publish { topic: &'a str, payload: &'a [u8] },
+    PingResp,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CommandTopic {
+    Setpoint,
+    Mode,
+}
+
+struct Encoder<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Encoder<'a> {
+    // The fixed header has up to 5 bytes, the body is written after it and moved in the end
+    fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 5 }
+    }
+
+    fn bytes(&mut self, data: &[u8]) -> Result<(), MqttError> {
+        let end = self.len + data.len();
+        if end > self.buffer.len() {
+            return Err(MqttError::BufferTooSmall);
+        }
+        self.buffer[self.len..end].copy_from_slice(data);
+        self.len = end;
+        Ok(())
+    }
+
+    fn u16(&mut self, value: u16) -> Result<(), MqttError> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    // Strings and binary fields have a length of 2 bytes in front
+    fn field(&mut self, data: &[u8]) -> Result<(), MqttError> {
+        let len = u16::try_from(data.len()).map_err(|_| MqttError::BufferTooSmall)?;
+        self.u16(len)?;
+        self.bytes(data)
+    }
+
+    fn finish(self, header: u8) -> Result<usize, MqttError> {
+        let mut remaining = self.len - 5;
+        let mut length = [0u8; 4];
+        let mut digits = 0;
+        loop {
+            let mut digit = (remaining % 128) as u8;
+            remaining /= 128;
+            if remaining > 0 {
+                digit |= 0x80;
+            }
+            length[digits] = digit;
+            digits += 1;
+            if remaining == 0 {
+                break;
+            }
+        }
+        let start = 5 - 1 - digits;
+        self.buffer[start] = header;
+        self.buffer[start + 1..5].copy_from_slice(&length[..digits]);
+        self.buffer.copy_within(start..self.len, 0);
+        Ok(self.len - start)
+    }
+}
+
+// CONNECT with clean session and the last will, that the broker publishes when the link is lost
+pub fn encode_connect(
+    buffer: &mut [u8],
+    client_id: &str,
+    keep_alive_s: u16,
+    will_topic: &str,
+    will_payload: &[u8],
+) -> Result<usize, MqttError> {
+    let mut encoder = Encoder::new(buffer);
+    encoder.field(b"MQTT")?;
+    encoder.bytes(&[PROTOCOL_LEVEL_3_1_1, CONNECT_CLEAN_SESSION | CONNECT_WILL | CONNECT_WILL_RETAIN])?;
+    encoder.u16(keep_alive_s)?;
+    encoder.field(client_id.as_bytes())?;
+    encoder.field(will_topic.as_bytes())?;
+    encoder.field(will_payload)?;
+    encoder.finish(PACKET_CONNECT << 4)
+}
+
+pub fn encode_publish(buffer: &mut [u8], topic: &str, payload: &[u8], retain: bool) -> Result<usize, MqttError> {
+    let mut encoder = Encoder::new(buffer);
+    encoder.field(topic.as_bytes())?;
+    encoder.bytes(payload)?;
+    encoder.finish(PACKET_PUBLISH << 4 | if retain { PUBLISH_RETAIN } else { 0 })
+}
+
+pub fn encode_subscribe(buffer: &mut [u8], packet_id: u16, filter: &str) -> Result<usize, MqttError> {
+    let mut encoder = Encoder::new(buffer);
+    encoder.u16(packet_id)?;
+    encoder.field(filter.as_bytes())?;
+    encoder.bytes(&[0])?; // QoS 0
+    encoder.finish(PACKET_SUBSCRIBE << 4 | 0x02)
+}
+
+pub fn encode_pingreq(buffer: &mut [u8]) -> Result<usize, MqttError> {
+    Encoder::new(buffer).finish(PACKET_PINGREQ << 4)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, MqttError> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or(MqttError::Malformed)
+}
+
+// Decode the first packet of the buffer. Returns None while the packet is not complete,
+// otherwise the packet and the number of bytes used.
+pub fn decode_packet(data: &[u8]) -> Result<Option<(Packet<'_>, usize)>, MqttError> {
+    let Some(&header) = data.first() else {
+        return Ok(None);
+    };
+
+    let mut remaining: usize = 0;
+    let mut offset = 1;
+    loop {
+        let Some(&digit) = data.get(offset) else {
+            return Ok(None);
+        };
+        remaining |= ((digit & 0x7F) as usize) << (7 * (offset - 1));
+        offset += 1;
+        if digit & 0x80 == 0 {
+            break;
+        }
+        if offset > 4 {
+            return Err(MqttError::Malformed);
+        }
+    }
+    let len = offset + remaining;
+    if len > MQTT_MAX_PACKET_LEN {
+        return Err(MqttError::BufferTooSmall);
+    }
+    let Some(body) = data.get(offset..len) else {
+        return Ok(None);
+    };
+
+    let packet = match header >> 4 {
+        PACKET_CONNACK if body.len() == 2 => Packet::ConnAck {
+            session_present: body[0] & 0x01 != 0,
+            code: body[1],
+        },
+        PACKET_SUBACK if body.len() >= 3 => Packet::SubAck {
+            packet_id: read_u16(body, 0)?,
+            granted: body[2],
+        },
+        PACKET_PINGRESP => Packet::PingResp,
+        PACKET_PUBLISH => {
+            let topic_len = read_u16(body, 0)? as usize;
+            let topic = body.get(2..2 + topic_len).ok_or(MqttError::Malformed)?;
+            let topic = core::str::from_utf8(topic).map_err(|_| MqttError::Malformed)?;
+            // The packet identifier is only present with QoS 1 and 2
+            let qos = (header >> 1) & 0x03;
+            let payload_start = 2 + topic_len + if qos > 0 { 2 } else { 0 };
+            let payload = body.get(payload_start..).ok_or(MqttError::Malformed)?;
+            Packet::Publish { topic, payload }
+        }
+        PACKET_CONNACK | PACKET_SUBACK => return Err(MqttError::Malformed),
+        other => return Err(MqttError::Unexpected(other)),
+    };
+    Ok(Some((packet, len)))
+}
+
+// Topics of one vivarium, all of them start with "vivarium/<client>/"
+pub struct Topics {
+    base: String<48>,
+}
+
+impl Topics {
+    pub fn new(client_id: &str) -> Self {
+        let mut base = String::new();
+        let _ = core::write!(base, "{}/{}/", MQTT_TOPIC_ROOT, client_id);
+        Self { base }
+    }
+
+    pub fn topic(&self, leaf: &str) -> String<64> {
+        let mut topic = String::new();
+        let _ = core::write!(topic, "{}{}", self.base, leaf);
+        topic
+    }
+
+    // Filter of the subscription with all the commands
+    pub fn command_filter(&self) -> String<64> {
+        self.topic("cmd/+")
+    }
+
+    pub fn route(&self, topic: &str) -> Option<CommandTopic> {
+        match topic.strip_prefix(self.base.as_str())? {
+            "cmd/setpoint" => Some(CommandTopic::Setpoint),
+            "cmd/mode" => Some(CommandTopic::Mode),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_has_the_clean_session_and_the_retained_last_will() {
+        let mut buffer = [0u8; MQTT_MAX_PACKET_LEN];
+        let len = encode_connect(&mut buffer, "v1", 60, "w", b"off").unwrap();
+
+        let expected = [
+            0x10, 22, // CONNECT and the remaining length
+            0, 4, b'M', b'Q', b'T', b'T', 4, 0x26, 0, 60, // Protocol, level, flags and keep alive
+            0, 2, b'v', b'1', // Client
+            0, 1, b'w', // Topic of the will
+            0, 3, b'o', b'f', b'f', // Payload of the will
+        ];
+        assert_eq!(&buffer[..len], &expected);
+    }
+
+    #[test]
+    fn publish_is_decoded_back_with_its_topic_and_payload() {
+        let mut buffer = [0u8; MQTT_MAX_PACKET_LEN];
+        let len = encode_publish(&mut buffer, "vivarium/v1/pv", b"27.50", true).unwrap();
+        assert_eq!(buffer[0], 0x31);
+
+        let (packet, used) = decode_packet(&buffer[..len]).unwrap().unwrap();
+        assert_eq!(packet, Packet::Publish { topic: "vivarium/v1/pv", payload: b"27.50" });
+        assert_eq!(used, len);
+    }
+
+    #[test]
+    fn remaining_length_above_127_takes_two_bytes() {
+        let mut buffer = [0u8; MQTT_MAX_PACKET_LEN];
+        let payload = [b'x'; 200];
+        let len = encode_publish(&mut buffer, "t", &payload, false).unwrap();
+
+        assert_eq!(&buffer[..3], &[0x30, 0xCB, 0x01]); // 203 = 0x4B + 1 * 128, the 0x80 tells that a digit follows
+        assert_eq!(len, 3 + 203);
+        let (packet, _) = decode_packet(&buffer[..len]).unwrap().unwrap();
+        assert_eq!(packet, Packet::Publish { topic: "t", payload: &payload });
+    }
+
+    #[test]
+    fn subscribe_and_ping_have_their_fixed_headers() {
+        let mut buffer = [0u8; MQTT_MAX_PACKET_LEN];
+        let len = encode_subscribe(&mut buffer, 1, "a/+").unwrap();
+        assert_eq!(&buffer[..len], &[0x82, 8, 0, 1, 0, 3, b'a', b'/', b'+', 0]);
+
+        let len = encode_pingreq(&mut buffer).unwrap();
+        assert_eq!(&buffer[..len], &[0xC0, 0]);
+    }
+
+    #[test]
+    fn small_buffer_is_reported() {
+        let mut buffer = [0u8; 16];
+        assert_eq!(encode_publish(&mut buffer, "vivarium/v1/pv", b"27.50", false), Err(MqttError::BufferTooSmall));
+    }
+
+    #[test]
+    fn packets_of_the_broker_are_decoded_one_at_a_time() {
+        let data = [0x20, 2, 0, 0, 0x90, 3, 0, 1, 0x80, 0xD0, 0];
+
+        let (packet, len) = decode_packet(&data).unwrap().unwrap();
+        assert_eq!(packet, Packet::ConnAck { session_present: false, code: 0 });
+        let (packet, len2) = decode_packet(&data[len..]).unwrap().unwrap();
+        assert_eq!(packet, Packet::SubAck { packet_id: 1, granted: 0x80 });
+        let (packet, _) = decode_packet(&data[len + len2..]).unwrap().unwrap();
+        assert_eq!(packet, Packet::PingResp);
+    }
+
+    #[test]
+    fn incomplete_packet_waits_for_more_bytes() {
+        assert_eq!(decode_packet(&[]), Ok(None));
+        assert_eq!(decode_packet(&[0x30]), Ok(None));
+        assert_eq!(decode_packet(&[0x30, 0x81]), Ok(None));
+        assert_eq!(decode_packet(&[0x20, 2, 0]), Ok(None));
+    }
+
+    #[test]
+    fn bad_packets_are_rejected() {
+        assert_eq!(decode_packet(&[0x20, 1, 0]), Err(MqttError::Malformed));
+        assert_eq!(decode_packet(&[0x30, 0xFF, 0xFF, 0xFF, 0xFF]), Err(MqttError::Malformed));
+        assert_eq!(decode_packet(&[0x30, 0x80, 0x02]), Err(MqttError::BufferTooSmall));
+        assert_eq!(decode_packet(&[0x30, 2, 0, 5]), Err(MqttError::Malformed));
+        assert_eq!(decode_packet(&[0xB0, 2, 0, 1]), Err(MqttError::Unexpected(11)));
+    }
+
+    #[test]
+    fn publish_with_qos_1_skips_the_packet_identifier() {
+        let data = [0x32, 7, 0, 1, b't', 0, 9, b'o', b'n'];
+        let (packet, _) = decode_packet(&data).unwrap().unwrap();
+        assert_eq!(packet, Packet::Publish { topic: "t", payload: b"on" });
+    }
+
+    #[test]
+    fn only_the_commands_of_this_client_are_routed() {
+        let topics = Topics::new("v1");
+
+        assert_eq!(topics.topic("pv").as_str(), "vivarium/v1/pv");
+        assert_eq!(topics.command_filter().as_str(), "vivarium/v1/cmd/+");
+        assert_eq!(topics.route("vivarium/v1/cmd/setpoint"), Some(CommandTopic::Setpoint));
+        assert_eq!(topics.route("vivarium/v1/cmd/mode"), Some(CommandTopic::Mode));
+        assert_eq!(topics.route("vivarium/v1/cmd/other"), None);
+        assert_eq!(topics.route("vivarium/v2/cmd/mode"), None);
+    }
+}
@@ -0,0 +1,268 @@
+// MQTT link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : MQTT link file for the modular project.
+ *  File        : mqtt_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the MQTT session of the vivarium. It connects to the broker,
+ *      publishes the PV, SP, output and mode every 10 seconds and forwards the setpoint and mode
+ *      commands of the subscribe topic to the control loop, with the same grammar of the console.
+ *
+ *      The session runs over any TCP stream of embedded-io-async (the TcpSocket of embassy-net in the
+ *      Pico W) and returns when the link is lost, the MQTT task opens a new socket and calls it again.
+ *      The receiver of the control status belongs to the task, so it is not lost between sessions.
+ *      The broker and the client are set when the firmware is built (wifi_link.rs).
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::Write as _;
+
+use defmt::*; // For logging via RTT
+use embassy_futures::select::{Either, select};
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Ipv4Address, Stack};
+use embassy_sync::watch::DynReceiver;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+use embedded_io_async::{Read, Write};
+use heapless::String;
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::modular::command::{Command, ModeRequest, parse_command};
+use crate::modular::mqtt::*;
+use crate::modular::pwm::{ControlCommand, ControlMode, ControlStatus, get_receiver_control_status, get_sender_control};
+use crate::modular::setpoint::SetpointCommand;
+use crate::modular::setpoint_link::get_sender_setpoint;
+use crate::modular::wifi_link::{MQTT_BROKER, MQTT_CLIENT_ID};
+
+pub const MQTT_PUBLISH_PERIOD_S: u64 = 10;
+pub const MQTT_PORT: u16 = 1883;
+const MQTT_RETRY_S: u64 = 10;
+const MQTT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MQTT_SUBSCRIBE_ID: u16 = 1;
+
+static MQTT_CONNECTED: AtomicBool = AtomicBool::new(false);
+
+// The broker accepted the session and it is not lost yet
+pub fn mqtt_connected() -> bool {
+    MQTT_CONNECTED.load(Ordering::Relaxed)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SessionError<E> {
+    Io(E),
+    Mqtt(MqttError),
+    Closed,  // The broker closed the connection
+    Timeout, // No answer of the broker in 1.5 keep alive
+}
+
+impl<E> From<MqttError> for SessionError<E> {
+    fn from(error: MqttError) -> Self {
+        SessionError::Mqtt(error)
+    }
+}
+
+struct Session<'a, S: Read + Write> {
+    socket: &'a mut S,
+    tx: [u8; MQTT_MAX_PACKET_LEN],
+    rx: [u8; MQTT_MAX_PACKET_LEN],
+    rx_len: usize,
+}
+
+impl<S: Read + Write> Session<'_, S> {
+    async fn send(&mut self, len: usize) -> Result<(), SessionError<S::Error>> {
+        self.socket.write_all(&self.tx[..len]).await.map_err(SessionError::Io)?;
+        self.socket.flush().await.map_err(SessionError::Io)
+    }
+
+    async fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> Result<(), SessionError<S::Error>> {
+        let len = encode_publish(&mut self.tx, topic, payload, retain)?;
+        self.send(len).await
+    }
+
+    async fn receive(&mut self) -> Result<(), SessionError<S::Error>> {
+        let len = self.socket.read(&mut self.rx[self.rx_len..]).await.map_err(SessionError::Io)?;
+        if len == 0 {
+            return Err(SessionError::Closed);
+        }
+        self.rx_len += len;
+        Ok(())
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.rx.copy_within(len..self.rx_len, 0);
+        self.rx_len -= len;
+    }
+
+    async fn wait_connack(&mut self) -> Result<(), SessionError<S::Error>> {
+        loop {
+            if let Some((packet, len)) = decode_packet(&self.rx[..self.rx_len])? {
+                let result = match packet {
+                    Packet::ConnAck { code: 0, .. } => Ok(()),
+                    Packet::ConnAck { code, .. } => Err(MqttError::Refused(code).into()),
+                    // Any other packet before the CONNACK is a protocol error
+                    _ => Err(MqttError::Malformed.into()),
+                };
+                self.consume(len);
+                return result;
+            }
+            self.receive().await?;
+        }
+    }
+}
+
+async fn execute(command: Command) {
+    match command {
+        Command::SetSetpoint(celsius) => get_sender_setpoint().send(SetpointCommand::Remote(celsius)).await,
+        Command::ReleaseSetpoint => get_sender_setpoint().send(SetpointCommand::ReleaseRemote).await,
+        Command::Mode(mode) => {
+            let mode = match mode {
+                ModeRequest::Auto => ControlMode::Auto,
+                ModeRequest::Manual(output) => ControlMode::Manual(output),
+                ModeRequest::Off => ControlMode::Off,
+            };
+            get_sender_control().send(ControlCommand::Mode(mode)).await;
+        }
+        _ => {}
+    }
+}
+
+// The payload is the argument of the same command in the console
+fn parse_payload(topic: CommandTopic, payload: &[u8]) -> Option<Command> {
+    let payload = core::str::from_utf8(payload).ok()?;
+    let mut line: String<48> = String::new();
+    match topic {
+        CommandTopic::Setpoint => core::write!(line, "set sp {}", payload).ok()?,
+        CommandTopic::Mode => core::write!(line, "mode {}", payload).ok()?,
+    }
+    match parse_command(&line) {
+        Ok(command) => command,
+        Err(e) => {
+            warn!("MQTT command {} rejected: {}", line.as_str(), e.message());
+            None
+        }
+    }
+}
+
+async fn publish_status<S: Read + Write>(
+    session: &mut Session<'_, S>,
+    topics: &Topics,
+    status: &ControlStatus,
+) -> Result<(), SessionError<S::Error>> {
+    let values = [("pv", status.pv), ("sp", status.setpoint.celsius), ("output", status.terms.output)];
+    for (leaf, value) in values {
+        let mut payload: String<16> = String::new();
+        let _ = core::write!(payload, "{:.2}", value);
+        session.publish(&topics.topic(leaf), payload.as_bytes(), false).await?;
+    }
+    session.publish(&topics.topic("mode"), status.mode.name().as_bytes(), false).await
+}
+
+// Run one MQTT session over a connected socket, returns only when the link is lost
+pub async fn mqtt_session<S: Read + Write>(
+    socket: &mut S,
+    client_id: &str,
+    rx_status: &mut DynReceiver<'static, ControlStatus>,
+) -> Result<(), SessionError<S::Error>> {
+    let topics = Topics::new(client_id);
+    let status_topic = topics.topic("status");
+    let mut session = Session {
+        socket,
+        tx: [0; MQTT_MAX_PACKET_LEN],
+        rx: [0; MQTT_MAX_PACKET_LEN],
+        rx_len: 0,
+    };
+
+    let len = encode_connect(&mut session.tx, client_id, MQTT_KEEP_ALIVE_S, &status_topic, MQTT_OFFLINE)?;
+    session.send(len).await?;
+    with_timeout(MQTT_CONNECT_TIMEOUT, session.wait_connack())
+        .await
+        .map_err(|_| SessionError::Timeout)??;
+    session.publish(&status_topic, MQTT_ONLINE, true).await?;
+    let len = encode_subscribe(&mut session.tx, MQTT_SUBSCRIBE_ID, &topics.command_filter())?;
+    session.send(len).await?;
+    info!("MQTT connected as {}", client_id);
+    MQTT_CONNECTED.store(true, Ordering::Relaxed);
+
+    // A ping in the half of the keep alive, the broker is lost after 1.5 keep alive without answer
+    let keep_alive = Duration::from_secs(MQTT_KEEP_ALIVE_S as u64);
+    let mut next_publish = Instant::now();
+    let mut next_ping = Instant::now() + keep_alive / 2;
+    let mut last_received = Instant::now();
+
+    loop {
+        let deadline = next_publish.min(next_ping);
+        match select(session.receive(), Timer::at(deadline)).await {
+            Either::First(result) => {
+                result?;
+                last_received = Instant::now();
+            }
+            Either::Second(()) => {
+                let now = Instant::now();
+                if now >= next_publish {
+                    if let Some(status) = rx_status.try_get() {
+                        publish_status(&mut session, &topics, &status).await?;
+                    }
+                    next_publish = now + Duration::from_secs(MQTT_PUBLISH_PERIOD_S);
+                }
+                if now >= next_ping {
+                    if now - last_received > keep_alive + keep_alive / 2 {
+                        return Err(SessionError::Timeout);
+                    }
+                    let len = encode_pingreq(&mut session.tx)?;
+                    session.send(len).await?;
+                    next_ping = now + keep_alive / 2;
+                }
+                continue;
+            }
+        }
+
+        // All the complete packets of the buffer, a packet larger than the buffer ends the session
+        while let Some((packet, len)) = decode_packet(&session.rx[..session.rx_len])? {
+            let command = match packet {
+                Packet::Publish { topic, payload } => topics.route(topic).and_then(|topic| parse_payload(topic, payload)),
+                Packet::SubAck { granted, .. } if granted & 0x80 != 0 => {
+                    warn!("MQTT subscription refused");
+                    None
+                }
+                _ => None,
+            };
+            session.consume(len);
+            if let Some(command) = command {
+                info!("MQTT command: {}", command);
+                execute(command).await;
+            }
+        }
+    }
+}
+
+// This task keeps a session with the broker while the Wi-Fi has an address
+#[embassy_executor::task]
+pub async fn mqtt_task(stack: Stack<'static>) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let broker: Ipv4Address = unwrap!(MQTT_BROKER.parse().ok(), "MQTT_BROKER is not an IPv4 address");
+    let mut rx_buffer = [0u8; 2 * MQTT_MAX_PACKET_LEN];
+    let mut tx_buffer = [0u8; 2 * MQTT_MAX_PACKET_LEN];
+
+    loop {
+        stack.wait_config_up().await;
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        match socket.connect((broker, MQTT_PORT)).await {
+            Ok(()) => {
+                if let Err(e) = mqtt_session(&mut socket, MQTT_CLIENT_ID, &mut rx_status).await {
+                    warn!("MQTT session ended: {}", defmt::Debug2Format(&e));
+                }
+                MQTT_CONNECTED.store(false, Ordering::Relaxed);
+            }
+            Err(e) => warn!("MQTT broker not reached: {}", e),
+        }
+        socket.abort();
+        let _ = socket.flush().await;
+        Timer::after_secs(MQTT_RETRY_S).await;
+    }
+}
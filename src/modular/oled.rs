@@ -10,182 +10,265 @@
  *      The module is responsible about to acquire and send the information to the display OLED,
  *      regarding the values about the temperature, humidity and luminosity.
  *
+ *      The pages are made and drawn by the screen.rs, this task copies the last values of the channels
+ *      once per second and adds the PV, SP and output to the history of the trend page. The button in the GP15 (to the GND) changes the page: a short press shows the
+ *      next one and a long press the previous one. The encoder also turns the pages, and its menu is
+ *      drawn instead of the page while it is open.
+ *
+ *      The display is optional, the control does not depend on it. A failed start is tried again with
+ *      a growing delay (1 s up to 30 s) and a recovery of the I2C bus, after 5 failures the task runs
+ *      headless and only tries again every minute, so a display plugged later is found. Some failed
+ *      flushes in a row start the display again. The health of the display is published in a Watch.
+ *
  *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
  *  Framework   : Embassy, no_std
  *
  */
 
-#![allow(clippy::write_literal, clippy::uninlined_format_args)] // To be possible to use core::write!()
-
 // Crate regarding I2C Oled Display
-use core::fmt::Write;
-
-use defmt::info;
-use embassy_rp::i2c::{Async, I2c};
-// Crate regarding I2C Oled Display
-use embassy_rp::peripherals::I2C0;
-use embassy_time::Timer;
-use embedded_graphics::geometry::Point;
-use embedded_graphics::mono_font::MonoTextStyle;
-use embedded_graphics::mono_font::ascii::FONT_6X10;
-use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics::prelude::*;
-use embedded_graphics::primitives::{Circle, PrimitiveStyle};
-use embedded_graphics::text::Text;
-use heapless::String;
+use defmt::{Format, info, warn};
+use embassy_futures::select::{Either3, select3};
+use embassy_rp::gpio::Input;
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::channel::{Channel, DynamicSender};
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::{Duration, Instant, Timer};
 use micromath::F32Ext;
+use ssd1306::mode::BufferedGraphicsModeAsync;
 use ssd1306::prelude::*;
-use ssd1306::{I2CDisplayInterface, Ssd1306};
+use ssd1306::{I2CDisplayInterface, Ssd1306Async};
 
 use crate::modular::adc::{get_receiver_adc0, get_receiver_adctemp};
-//use crate::modular::{get_receiver_dht_humidity, get_receiver_dht_temperature};
+use crate::modular::alarm::{AlarmStatus, trip_limits};
+use crate::modular::alarm_link::get_receiver_alarm_status;
+use crate::modular::dht_link::{get_receiver_dht_humidity, get_receiver_dht_temperature};
+use crate::modular::i2c_bus::{I2cBus, I2cBusDevice, I2cDeviceId, i2c_recoveries, recover_bus};
+use crate::modular::menu::draw_menu;
+use crate::modular::menu_link::{get_receiver_menu, menu_values};
+use crate::modular::pwm::get_receiver_control_status;
+#[cfg(not(feature = "wifi"))]
+use crate::modular::screen::NetworkView;
+use crate::modular::screen::{ControlView, NavEvent, Navigation, Navigator, ScreenModel, render};
+use crate::modular::setpoint_link::get_receiver_setpoint;
+use crate::modular::trend::{Trend, TrendPoint, TrendWindow};
+#[cfg(feature = "wifi")]
+use crate::modular::wifi_link::network_view;
+
+const OLED_INIT_BACKOFF_MIN_MS: u64 = 1_000;
+const OLED_INIT_BACKOFF_MAX_MS: u64 = 30_000;
+const OLED_INIT_ATTEMPTS: u8 = 5; // Failed starts before the headless mode
+const OLED_HEADLESS_RETRY_MS: u64 = 60_000;
+const OLED_FLUSH_FAILURES: u8 = 3; // Failed flushes in a row before the display is started again
+const BUTTON_DEBOUNCE_MS: u64 = 20;
+const BUTTON_LONG_PRESS_MS: u64 = 1_000;
+
+const PAGE_EVENTS_DEPTH: usize = 4;
+static PAGE_EVENTS: Channel<ThreadModeRawMutex, NavEvent, PAGE_EVENTS_DEPTH> = Channel::new();
+
+pub fn get_sender_page() -> DynamicSender<'static, NavEvent> {
+    PAGE_EVENTS.dyn_sender()
+}
+
+type Display = Ssd1306Async<
+    I2CInterface<I2cBusDevice>,
+    DisplaySize128x64,
+    BufferedGraphicsModeAsync<DisplaySize128x64>,
+>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Format)]
+pub enum DisplayHealth {
+    Starting,
+    Ready,
+    Retrying, // The start failed, it is tried again with a growing delay
+    Headless, // No display, the system runs without it
+}
+
+impl DisplayHealth {
+    pub fn name(self) -> &'static str {
+        match self {
+            DisplayHealth::Starting => "starting",
+            DisplayHealth::Ready => "ready",
+            DisplayHealth::Retrying => "retrying",
+            DisplayHealth::Headless => "headless",
+        }
+    }
+}
+
+const DISPLAY_HEALTH_CONSUMERS: usize = 1;
+static DISPLAY_HEALTH_CHANNEL: Watch<ThreadModeRawMutex, DisplayHealth, DISPLAY_HEALTH_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_display_health() -> Option<DynReceiver<'static, DisplayHealth>> {
+    DISPLAY_HEALTH_CHANNEL.dyn_receiver()
+}
+
+// Start the display, it returns only when the display answered
+async fn start_display(display: &mut Display, bus: &'static I2cBus) {
+    let tx_health = DISPLAY_HEALTH_CHANNEL.sender();
+    let mut backoff_ms = OLED_INIT_BACKOFF_MIN_MS;
+    let mut attempts: u8 = 0;
+
+    loop {
+        if display.init().await.is_ok() {
+            info!("OLED display ready");
+            tx_health.send(DisplayHealth::Ready);
+            return;
+        }
+        // A device reset in the middle of a transfer can hold the bus
+        recover_bus(bus).await;
+
+        attempts = attempts.saturating_add(1);
+        let delay_ms = if attempts < OLED_INIT_ATTEMPTS {
+            warn!("OLED init failed (attempt {}), trying again in {} ms", attempts, backoff_ms);
+            tx_health.send(DisplayHealth::Retrying);
+            let delay_ms = backoff_ms;
+            backoff_ms = (backoff_ms * 2).min(OLED_INIT_BACKOFF_MAX_MS);
+            delay_ms
+        } else {
+            if attempts == OLED_INIT_ATTEMPTS {
+                warn!("OLED not found, running headless");
+            }
+            tx_health.send(DisplayHealth::Headless);
+            OLED_HEADLESS_RETRY_MS
+        };
+        Timer::after_millis(delay_ms).await;
+    }
+}
 
 #[embassy_executor::task]
-pub async fn oled_task(i2c: I2c<'static, I2C0, Async>) {
-    let interface = I2CDisplayInterface::new(i2c);
+pub async fn oled_task(bus: &'static I2cBus, navigation: Navigation, trend_window: TrendWindow) {
+    let interface = I2CDisplayInterface::new(I2cBusDevice::new(bus, I2cDeviceId::Oled));
     let mut display =
-        Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0).into_buffered_graphics_mode();
+        Ssd1306Async::new(interface, DisplaySize128x64, DisplayRotation::Rotate0).into_buffered_graphics_mode();
+    DISPLAY_HEALTH_CHANNEL.sender().send(DisplayHealth::Starting);
 
     Timer::after_millis(2_000).await;
-    if display.init().is_err() {
-        defmt::error!("Display init failed defmt");
-        core::panic!("Display init failed core");
-    }
-
-    let header_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
-    let temp_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    start_display(&mut display, bus).await;
+    let mut flush_failures: u8 = 0;
 
-    // Header text
-    let header_text = "Smart Vivarium";
-    let header_x = (128 - header_text.len() as i32 * 6) / 2;
-    let header_y = 12;
+    let mut navigator = Navigator::new(navigation);
+    let mut trend = Trend::new(trend_window);
+    let rx_page = PAGE_EVENTS.receiver();
+    let mut rx_menu = get_receiver_menu().unwrap();
 
     // acquiring the value of the Die Temperature
     let mut rx_temp = get_receiver_adctemp().unwrap();
     let mut rx_ref_temp_resistor = get_receiver_adc0().unwrap();
-    //let mut rx_dht_temperature = get_receiver_dht_temperature().unwrap();
-    //let mut rx_dht_humidity = get_receiver_dht_humidity().unwrap();
+    let mut rx_setpoint = get_receiver_setpoint().unwrap();
+    let mut rx_dht_temperature = get_receiver_dht_temperature().unwrap();
+    let mut rx_dht_humidity = get_receiver_dht_humidity().unwrap();
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let mut rx_alarms = get_receiver_alarm_status().unwrap();
 
     loop {
         info!("Updating OLED display");
         let adctemp = rx_temp.get().await; // Get the value of the sensor
         let adc_ref_res_temp = rx_ref_temp_resistor.get().await; // Get the value of the sensor
-        info!("RefTempRes OLED: {}", adc_ref_res_temp);
         let adc_ref_res_temp_float: f32 = (adc_ref_res_temp as f32 + 1.0) / 128.0 as f32;
-        info!("RefTempResAvg OLED: {}", adc_ref_res_temp_float);
-        let adc_ref_res_temp_trunk = (adc_ref_res_temp_float * 100.0).trunc() / 100.0;
-        info!("RefTempResTrunk OLED: {}", adc_ref_res_temp_trunk);
-        //let dht_temperature = rx_dht_temperature.get().await; // Get the value of dht temperature
-        //let dht_humidity = rx_dht_humidity.get().await; // Get the value of the dht humidity
+        let setpoint = rx_setpoint.get().await; // Get the effective setpoint
+        // The DHT may be missing, the display does not wait for it
+        let dht_temperature = rx_dht_temperature.try_get(); // Get the value of dht temperature
+        let dht_humidity = rx_dht_humidity.try_get(); // Get the value of the dht humidity
 
         // Convert the ADC value to temperature in Celsius
         // The formula is based on the RP2040 datasheet, where the temperature die is calculated as:
         // Temp = 27 - (V - 0.706) / 0.001721
         // where V is the voltage measured by the ADC, and 0.706 and 0.001721 are constants derived from the RP2040's temperature
         // sensor characteristics.
-        // The ADC value is scaled to a voltage between 0 and 3.3V
         // The ADC value is 12-bit, so it ranges from 0 to 4095.
         // The voltage is calculated as: V = ADC_value * 3.3 / 4096.0
-        // The temperature is then calculated using the formula above.
-        // The temperature is then truncated to two decimal places for display.
-        // The temperature is then converted to a string for display.
-        // The temperature is then displayed on the OLED display.
         let voltage = adctemp as f32 * 3.3 / 4096.0;
         let temp = 27.0 - (voltage - 0.706) / 0.001721;
-        let temp_trunk = (temp * 100.0).trunc() / 100.0;
-        let mut buffer_temp: String<32> = String::new(); // Create a buffer to store the text
-        core::write!(buffer_temp, "Temp Die: {}  C", temp_trunk).unwrap();
-        let buffer_temp_x = (128 - buffer_temp.len() as i32 * 6) / 2; // Calculate the x-coordinate for the text
-        let buffer_temp_y = 30; // Set the y-coordinate for the text
-
-        let mut buffer_ref_res_temp: String<32> = String::new(); // Create a buffer to store the text
-        //let lumens = adc_ref_res_temp as f32;  // Convert the value of ADC into Lux
-        //let lumens_trunk = (lumens * 100.0).trunc() / 100.0;
-        core::write!(buffer_ref_res_temp, "Ref Temp: {}  C", adc_ref_res_temp_trunk).unwrap();
-        let buffer_ref_res_temp_x = (128 - buffer_ref_res_temp.len() as i32 * 6) / 2; // Calculate the x-coordinate for the text
-        let buffer_ref_res_temp_y = 41;
-
-        /* 
-        let mut buffer_dht_temp: String<32> = String::new(); // Create a buffer to store the text
-        core::write!(buffer_dht_temp, "DHT Temp: {}  C", dht_temperature).unwrap();
-        let buffer_dht_temp_x = (128 - buffer_dht_temp.len() as i32 * 6) / 2; // Calculate the x-coordinate for the text
-        let buffer_dht_temp_y = 52; // Set the y-coordinate for the text    
-
-        let mut buffer_dht_hum: String<32> = String::new(); // Create a buffer to store the text
-        core::write!(buffer_dht_hum, "DHT Hum: {} %", dht_humidity).unwrap();
-        let buffer_dht_hum_x = (128 - buffer_dht_hum.len() as i32 * 6) / 2; // Calculate the x-coordinate for the text
-        let buffer_dht_hum_y = 63; // Set the y-coordinate for the text 
-        */
-        // Clear the display
-
-        if display.clear(BinaryColor::Off).is_err() {
-            defmt::error!("Clear failed");
+
+        let status = rx_status.try_get();
+        if let Some(status) = status {
+            let sample = TrendPoint {
+                pv: status.pv,
+                sp: status.setpoint.celsius,
+                output: status.terms.output,
+            };
+            trend.add(Instant::now().as_millis(), sample);
+        }
+
+        let limits = trip_limits();
+        let model = ScreenModel {
+            die_c: (temp * 100.0).trunc() / 100.0,
+            ref_c: (adc_ref_res_temp_float * 100.0).trunc() / 100.0,
+            setpoint_c: (setpoint.celsius * 100.0).trunc() / 100.0,
+            setpoint_source: setpoint.source.name(),
+            air: dht_temperature.zip(dht_humidity),
+            control: status.map(|status| ControlView {
+                pv: status.pv,
+                pv_source: status.pv_source.name(),
+                p: status.terms.p,
+                i: status.terms.i,
+                d: status.terms.d,
+                output: status.terms.output,
+                mode: status.mode.name(),
+                faults: status.faults,
+            }),
+            trip_limits: (limits.low_c, limits.high_c),
+            alarms: rx_alarms.try_get().unwrap_or(AlarmStatus::new()),
+            #[cfg(feature = "wifi")]
+            network: network_view(),
+            #[cfg(not(feature = "wifi"))]
+            network: NetworkView::NotFitted,
+            display: DisplayHealth::Ready.name(),
+            i2c_recoveries: i2c_recoveries(),
+            uptime_s: Instant::now().as_secs() as u32,
+        };
+
+        // Clear the display and draw the menu while it is open, otherwise the page
+        let page = navigator.tick(model.uptime_s);
+        display.clear_buffer();
+        let drawn = match (rx_menu.try_get(), status) {
+            (Some(menu), Some(status)) if menu.is_open() => draw_menu(&mut display, &menu, &menu_values(&status)),
+            _ => render(&mut display, page, &model, &trend),
+        };
+        if drawn.is_err() {
+            defmt::error!("Draw failed");
         }
-        // Display the text in the OLED Display
-        // Display first line
-        Text::new(header_text, Point::new(header_x, header_y), header_style)
-            .draw(&mut display)
-            .unwrap();
-        // Display second line
-        Text::new(&buffer_temp, Point::new(buffer_temp_x, buffer_temp_y), temp_style)
-            .draw(&mut display)
-            .unwrap();
-        // Display third line
-        Text::new(&buffer_ref_res_temp, Point::new(buffer_ref_res_temp_x, buffer_ref_res_temp_y), temp_style)
-            .draw(&mut display)
-            .unwrap();
-        // Display the fourth line
-        /*
-        Text::new(
-            &buffer_dht_temp,
-            Point::new(buffer_dht_temp_x, buffer_dht_temp_y),
-            temp_style,
-        )
-        .draw(&mut display)
-        .unwrap();
-        // Display the fifth line
-        Text::new(
-            &buffer_dht_hum,
-            Point::new(buffer_dht_hum_x, buffer_dht_hum_y),
-            temp_style,
-        )
-        .draw(&mut display)
-        .unwrap();
-        */
-        
-        // Draw the temperature indicator for temperature of the die
-        let x_circle = buffer_temp_x + (buffer_temp.len() as i32 * 6) - 12;
-        let y_circle = buffer_temp_y - 6;
-        let degree_pos = Point::new(x_circle, y_circle); // adjust these values as needed
-        Circle::new(degree_pos, 4) // a small filled circle
-            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
-            .draw(&mut display)
-            .unwrap();
-
-                // Draw the temperature indicator for temperature of the die
-        let x_circle_ref = buffer_ref_res_temp_x + (buffer_ref_res_temp.len() as i32 * 6) - 12;
-        let y_circle_ref = buffer_ref_res_temp_y - 6;
-        let degree_pos_ref = Point::new(x_circle_ref, y_circle_ref); // adjust these values as needed
-        Circle::new(degree_pos_ref, 4) // a small filled circle
-            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
-            .draw(&mut display)
-            .unwrap();
-
-        /*
-        // Draw the temperature indicator for DHT temperature
-        let x_circle_dht = buffer_dht_temp_x + (buffer_dht_temp.len() as i32 * 6) - 12;
-        let y_circle_dht = buffer_dht_temp_y - 6;
-        let degree_pos_dht = Point::new(x_circle_dht, y_circle_dht); // adjust these values as needed
-        Circle::new(degree_pos_dht, 4) // a small filled circle
-            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
-            .draw(&mut display)
-            .unwrap();
-        */
+
         // Flush the display to show the changes
-        if display.flush().is_err() {
+        if display.flush().await.is_err() {
             defmt::error!("Flush failed");
+            flush_failures += 1;
+            if flush_failures >= OLED_FLUSH_FAILURES {
+                warn!("OLED not answering, starting it again");
+                start_display(&mut display, bus).await;
+                flush_failures = 0;
+            }
+        } else {
+            flush_failures = 0;
         }
 
-        Timer::after_millis(1_000).await; // Update the display every 305 seconds
+        // Update the display every second, a press of the button or a change of the menu is drawn at once
+        match select3(Timer::after_millis(1_000), rx_page.receive(), rx_menu.changed()).await {
+            Either3::Second(event) => navigator.event(Instant::now().as_secs() as u32, event),
+            Either3::First(()) | Either3::Third(_) => {}
+        }
+    }
+}
+
+// This task reads the button of the pages in the GP15, a short press shows the next page and a long
+// press the previous one
+#[embassy_executor::task]
+pub async fn page_button_task(mut button: Input<'static>) {
+    let tx_page = PAGE_EVENTS.sender();
+
+    loop {
+        button.wait_for_falling_edge().await;
+        Timer::after_millis(BUTTON_DEBOUNCE_MS).await;
+        if button.is_high() {
+            continue; // Bounce
+        }
+        let pressed_at = Instant::now();
+        button.wait_for_high().await;
+        let event = if pressed_at.elapsed() >= Duration::from_millis(BUTTON_LONG_PRESS_MS) {
+            NavEvent::Previous
+        } else {
+            NavEvent::Next
+        };
+        tx_page.send(event).await;
+        Timer::after_millis(BUTTON_DEBOUNCE_MS).await;
     }
 }
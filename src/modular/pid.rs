@@ -0,0 +1,202 @@
+// PID file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : PID file for the modular project.
+ *  File        : pid.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-18
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the discrete PID controller, it only uses core so the same code
+ *      can run in the firmware and in any tool running in the host.
+ *      The derivative is calculated over the measurement to avoid the kick when the setpoint changes,
+ *      and the integral is only accumulated while the output is not saturated (anti-windup).
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PidGains {
+    pub kp: f32,
+    pub ki: f32, // 1/s
+    pub kd: f32, // s
+}
+
+// Contribution of each term and the final output, all of them in the unit of the output
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct PidTerms {
+    pub p: f32,
+    pub i: f32,
+    pub d: f32,
+    pub output: f32,
+}
+
+pub struct Pid {
+    gains: PidGains,
+    out_min: f32,
+    out_max: f32,
+    integral: f32,        // Already multiplied by ki, so changing the gain does not bump the output
+    prev_pv: Option<f32>, // Last measurement used by the derivative
+}
+
+impl Pid {
+    pub const fn new(gains: PidGains, out_min: f32, out_max: f32) -> Self {
+        Self {
+            gains,
+            out_min,
+            out_max,
+            integral: 0.0,
+            prev_pv: None,
+        }
+    }
+
+    pub fn gains(&self) -> PidGains {
+        self.gains
+    }
+
+    pub fn set_gains(&mut self, gains: PidGains) {
+        self.gains = gains;
+    }
+
+    // Prepare the controller to take over from `output` without a bump, used when leaving the manual mode
+    pub fn reset(&mut self, output: f32) {
+        self.integral = output.clamp(self.out_min, self.out_max);
+        self.prev_pv = None;
+    }
+
+    // Run one step of the controller, `dt` is the time in seconds since the last step
+    pub fn update(&mut self, setpoint: f32, pv: f32, dt: f32) -> PidTerms {
+        let error = setpoint - pv;
+
+        let p = self.gains.kp * error;
+        let d = match self.prev_pv {
+            Some(prev_pv) if dt > 0.0 => -self.gains.kd * (pv - prev_pv) / dt,
+            _ => 0.0,
+        };
+        self.prev_pv = Some(pv);
+
+        // Conditional integration, the integral only grows while it helps to leave the saturation
+        let integral = self.integral + self.gains.ki * error * dt;
+        let unsaturated = p + integral + d;
+        let winding_up = (unsaturated > self.out_max && error > 0.0) || (unsaturated < self.out_min && error < 0.0);
+        if !winding_up {
+            self.integral = integral.clamp(self.out_min, self.out_max);
+        }
+
+        PidTerms {
+            p,
+            i: self.integral,
+            d,
+            output: (p + self.integral + d).clamp(self.out_min, self.out_max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAINS: PidGains = PidGains { kp: 10.0, ki: 0.5, kd: 2.0 };
+
+    #[test]
+    fn proportional_term_follows_the_error() {
+        let mut pid = Pid::new(PidGains { kp: 10.0, ki: 0.0, kd: 0.0 }, 0.0, 100.0);
+        let terms = pid.update(30.0, 27.5, 1.0);
+        assert_eq!(terms, PidTerms { p: 25.0, i: 0.0, d: 0.0, output: 25.0 });
+
+        // Above the setpoint the output is held at the low limit
+        let terms = pid.update(30.0, 31.0, 1.0);
+        assert_eq!((terms.p, terms.output), (-10.0, 0.0));
+    }
+
+    #[test]
+    fn integral_term_accumulates_with_dt() {
+        let mut pid = Pid::new(PidGains { kp: 0.0, ki: 0.5, kd: 0.0 }, 0.0, 100.0);
+        assert_eq!(pid.update(30.0, 28.0, 1.0).i, 1.0);
+        assert_eq!(pid.update(30.0, 28.0, 0.5).i, 1.5);
+        assert_eq!(pid.update(30.0, 28.0, 2.0).output, 3.5);
+    }
+
+    #[test]
+    fn derivative_acts_on_the_measurement() {
+        let mut pid = Pid::new(PidGains { kp: 0.0, ki: 0.0, kd: 2.0 }, -100.0, 100.0);
+        // No derivative in the first step, there is no previous measurement
+        assert_eq!(pid.update(30.0, 25.0, 0.5).d, 0.0);
+        // The PV rose 1 °C in 0.5 s
+        assert_eq!(pid.update(30.0, 26.0, 0.5).d, -4.0);
+        // A step of the setpoint gives no kick
+        assert_eq!(pid.update(40.0, 26.0, 0.5).d, 0.0);
+        // A step without time is ignored
+        assert_eq!(pid.update(40.0, 27.0, 0.0).d, 0.0);
+    }
+
+    #[test]
+    fn output_stays_in_its_limits() {
+        let mut pid = Pid::new(GAINS, 0.0, 100.0);
+        assert_eq!(pid.update(50.0, 0.0, 1.0).output, 100.0);
+
+        let mut pid = Pid::new(GAINS, 0.0, 100.0);
+        assert_eq!(pid.update(0.0, 50.0, 1.0).output, 0.0);
+
+        // The takeover from the manual output is clamped as well
+        let mut pid = Pid::new(GAINS, 0.0, 100.0);
+        pid.reset(150.0);
+        assert_eq!(pid.update(30.0, 30.0, 1.0), PidTerms { p: 0.0, i: 100.0, d: 0.0, output: 100.0 });
+    }
+
+    #[test]
+    fn integral_does_not_wind_up_in_the_saturation() {
+        let mut pid = Pid::new(PidGains { kp: 10.0, ki: 1.0, kd: 0.0 }, 0.0, 100.0);
+        // A cold start far below the setpoint saturates the output for a long time
+        for _ in 0..1_000 {
+            assert_eq!(pid.update(30.0, 10.0, 1.0).output, 100.0);
+        }
+        let integral = pid.update(30.0, 10.0, 1.0).i;
+        assert_eq!(integral, 0.0);
+
+        // Just above the setpoint the output leaves the saturation at once
+        let terms = pid.update(30.0, 30.5, 1.0);
+        assert_eq!(terms.output, 0.0);
+        assert!(terms.i <= 0.0);
+    }
+
+    #[test]
+    fn integral_unwinds_inside_the_limits() {
+        let mut pid = Pid::new(PidGains { kp: 1.0, ki: 1.0, kd: 0.0 }, 0.0, 100.0);
+        pid.reset(50.0);
+        // An error of -2 takes 2 per second from the integral
+        let terms = pid.update(30.0, 32.0, 1.0);
+        assert_eq!(terms, PidTerms { p: -2.0, i: 48.0, d: 0.0, output: 46.0 });
+    }
+
+    // First order enclosure: 100 % of the heater holds it 20 °C above the room, with 60 s of time constant
+    #[test]
+    fn closed_loop_settles_on_the_setpoint() {
+        let mut pid = Pid::new(PidGains { kp: 10.0, ki: 0.2, kd: 0.0 }, 0.0, 100.0);
+        let (room, dt) = (20.0, 1.0);
+        let mut pv = room;
+        for _ in 0..1_200 {
+            let output = pid.update(30.0, pv, dt).output;
+            pv += (room + output * 0.2 - pv) * dt / 60.0;
+        }
+        assert!((pv - 30.0).abs() < 0.05, "pv {pv}");
+        // The integral holds the 50 % that keeps the enclosure 10 °C above the room
+        assert!((pid.update(30.0, pv, dt).i - 50.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn reset_takes_over_without_a_bump() {
+        let mut pid = Pid::new(GAINS, 0.0, 100.0);
+        pid.update(30.0, 20.0, 1.0);
+        pid.reset(40.0);
+        // At the setpoint the output is the manual one, the old measurement does not kick the derivative
+        assert_eq!(pid.update(30.0, 30.0, 1.0).output, 40.0);
+
+        // Changing the gains keeps the integral
+        pid.set_gains(PidGains { ki: 5.0, ..GAINS });
+        assert_eq!(pid.update(30.0, 30.0, 1.0).output, 40.0);
+        assert_eq!(pid.gains().ki, 5.0);
+    }
+}
@@ -11,37 +11,274 @@
  *      and update the output through the PWM to control the temperature of the system and  the info to the display OLED,
  *      regarding the values about the output to control the system.
  *
+ *      The loop runs on a Ticker at the period set with "set loop <ms>" (1 s by default), so the time
+ *      spent in a cycle does not shift the next one. The period, jitter, execution time and overruns
+ *      are measured by the loop_timing.rs and read with "get loop".
+ *
+ *      The task runs in the interrupt executor of the control (main.rs), above the tasks of the thread
+ *      mode, so its channels and shared values are locked with a critical section.
+ *
  *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
  *  Framework   : Embassy, no_std
  *
  */
 
- use defmt::info;
- use embassy_rp::pwm::{Pwm, SetDutyCycle};
- use embassy_time::{Timer};
+use core::cell::Cell;
+
+use defmt::*; // For logging via RTT
+use embassy_rp::pwm::{Pwm, SetDutyCycle};
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::{Channel, DynamicSender};
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::{Duration, Instant, Ticker};
+use portable_atomic::{AtomicU32, Ordering};
+
+use crate::modular::adc::{die_raw_to_celsius, get_receiver_adctemp};
+use crate::modular::alarm::{evaluate_faults, interlock_output};
+use crate::modular::bme280_link::get_receiver_bme280;
+use crate::modular::control::{CONTROL_DEFAULT_PERIOD_MS, CONTROL_MAX_PERIOD_MS, CONTROL_MIN_PERIOD_MS, PvSource};
+use crate::modular::loop_timing::{LoopStats, LoopTimer};
+use crate::modular::pid::{Pid, PidGains, PidTerms};
+use crate::modular::setpoint::Setpoint;
+use crate::modular::setpoint_link::get_receiver_setpoint;
+use crate::modular::sht3x_link::get_receiver_sht3x;
+use crate::modular::telemetry::{MODE_AUTO, MODE_MANUAL, MODE_OFF};
+
+pub const OUTPUT_MIN_PERCENT: f32 = 0.0;
+pub const OUTPUT_MAX_PERCENT: f32 = 100.0;
+pub const DEFAULT_GAINS: PidGains = PidGains {
+    kp: 10.0,
+    ki: 0.1,
+    kd: 0.0,
+};
+// A sensor of the I2C without a new value in this time is not used as the process variable
+const PV_STALE: Duration = Duration::from_secs(10);
+
+static PV_SOURCE: BlockingMutex<CriticalSectionRawMutex, Cell<PvSource>> = BlockingMutex::new(Cell::new(PvSource::Die));
+
+pub fn pv_source() -> PvSource {
+    PV_SOURCE.lock(|source| source.get())
+}
+
+pub fn set_pv_source(source: PvSource) {
+    PV_SOURCE.lock(|cell| cell.set(source));
+}
 
+static CONTROL_PERIOD_MS: AtomicU32 = AtomicU32::new(CONTROL_DEFAULT_PERIOD_MS);
+
+pub fn control_period_ms() -> u32 {
+    CONTROL_PERIOD_MS.load(Ordering::Relaxed)
+}
+
+pub fn set_control_period_ms(period_ms: u32) {
+    CONTROL_PERIOD_MS.store(period_ms.clamp(CONTROL_MIN_PERIOD_MS, CONTROL_MAX_PERIOD_MS), Ordering::Relaxed);
+}
+
+static LOOP_STATS: BlockingMutex<CriticalSectionRawMutex, Cell<LoopStats>> = BlockingMutex::new(Cell::new(LoopStats::new(CONTROL_DEFAULT_PERIOD_MS * 1_000)));
+
+// Timing of the control loop measured in the last cycle
+pub fn loop_stats() -> LoopStats {
+    LOOP_STATS.lock(|stats| stats.get())
+}
+
+#[derive(Clone, Copy, PartialEq, Format)]
+pub enum ControlMode {
+    Auto,        // The PID drives the output
+    Manual(f32), // Fixed output in percent
+    Off,         // Output forced to 0%
+}
+
+impl ControlMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            ControlMode::Auto => "auto",
+            ControlMode::Manual(_) => "manual",
+            ControlMode::Off => "off",
+        }
+    }
 
-/// Demonstrate PWM by setting duty cycle
-///
-/// Using GP4 in Slice2, make sure to use an appropriate resistor.
+    // Code of the mode in the telemetry, data log and SD card
+    pub fn code(self) -> u8 {
+        match self {
+            ControlMode::Off => MODE_OFF,
+            ControlMode::Auto => MODE_AUTO,
+            ControlMode::Manual(_) => MODE_MANUAL,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Format)]
+pub enum ControlCommand {
+    SetKp(f32),
+    SetKi(f32),
+    SetKd(f32),
+    Mode(ControlMode),
+}
+
+// Snapshot of the control loop published after every step
+#[derive(Clone, Copy, PartialEq)]
+pub struct ControlStatus {
+    pub pv: f32,
+    pub pv_source: PvSource, // Sensor really used, the die when the selected one is not answering
+    pub die_c: f32,          // Temperature of the die, whatever the sensor of the process variable
+    pub setpoint: Setpoint,
+    pub gains: PidGains,
+    pub terms: PidTerms,
+    pub mode: ControlMode,
+    pub faults: u16, // Bits FAULT_* of the alarm.rs
+}
+
+const CONTROL_COMMANDS_DEPTH: usize = 4;
+static CONTROL_COMMANDS: Channel<CriticalSectionRawMutex, ControlCommand, CONTROL_COMMANDS_DEPTH> = Channel::new();
+
+pub fn get_sender_control() -> DynamicSender<'static, ControlCommand> {
+    CONTROL_COMMANDS.dyn_sender()
+}
+
+const CONTROL_STATUS_CONSUMERS: usize = 11; // The last two are the MQTT and HTTP of the feature wifi
+static CONTROL_STATUS_CHANNEL: Watch<CriticalSectionRawMutex, ControlStatus, CONTROL_STATUS_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_control_status() -> Option<DynReceiver<'static, ControlStatus>> {
+    CONTROL_STATUS_CHANNEL.dyn_receiver()
+}
+
+fn apply_command(pid: &mut Pid, mode: &mut ControlMode, command: ControlCommand) {
+    let mut gains = pid.gains();
+    match command {
+        ControlCommand::SetKp(kp) => gains.kp = kp,
+        ControlCommand::SetKi(ki) => gains.ki = ki,
+        ControlCommand::SetKd(kd) => gains.kd = kd,
+        ControlCommand::Mode(new_mode) => *mode = new_mode,
+    }
+    pid.set_gains(gains);
+}
+
+// Last value of a sensor and when it arrived
+fn fresh(reading: Option<(f32, Instant)>) -> Option<f32> {
+    reading
+        .filter(|(_, at)| at.elapsed() < PV_STALE)
+        .map(|(celsius, _)| celsius)
+}
+
+/// Discrete PID loop, the process variable is the temperature of the sensor selected with
+/// set_pv_source (the die by default) and the output is the duty cycle of the PWM in the
+/// GP2 (Slice1 channel A). The loop starts with the gains loaded from the configuration and
+/// runs on a Ticker, the dt of the PID is the measured period when it is far from the nominal one.
 #[embassy_executor::task]
-pub async fn pwm_set_dutycycle(mut pwm: Pwm<'static>){
+pub async fn pwm_set_dutycycle(mut pwm: Pwm<'static>, gains: PidGains) {
+    let mut rx_temp = get_receiver_adctemp().unwrap();
+    let mut rx_sht3x = get_receiver_sht3x().unwrap();
+    let mut rx_bme280 = get_receiver_bme280().unwrap();
+    let mut rx_setpoint = get_receiver_setpoint().unwrap();
+    let rx_commands = CONTROL_COMMANDS.receiver();
+    let tx_status = CONTROL_STATUS_CHANNEL.sender();
+
+    let mut pid = Pid::new(gains, OUTPUT_MIN_PERCENT, OUTPUT_MAX_PERCENT);
+    let mut mode = ControlMode::Auto;
+    let mut period_ms = control_period_ms();
+    let mut timer = LoopTimer::new(period_ms * 1_000);
+    let mut ticker = Ticker::every(Duration::from_millis(period_ms as u64));
+    let mut sht3x: Option<(f32, Instant)> = None;
+    let mut bme280: Option<(f32, Instant)> = None;
+    let mut used_source = PvSource::Die;
 
     loop {
-        // 100% duty cycle, fully on
-        pwm.set_duty_cycle_fully_on().unwrap();
-        Timer::after_secs(1).await;
-        info!("PWM FULLY ON");
+        let dt = timer.start(Instant::now().as_micros());
+        while let Ok(command) = rx_commands.try_receive() {
+            info!("Control command: {}", command);
+            apply_command(&mut pid, &mut mode, command);
+        }
+
+        if let Some(reading) = rx_sht3x.try_changed() {
+            sht3x = Some((reading.temperature_c, Instant::now()));
+        }
+        if let Some(reading) = rx_bme280.try_changed() {
+            bme280 = Some((reading.temperature_c, Instant::now()));
+        }
+        let die = die_raw_to_celsius(rx_temp.get().await);
+        let selected = pv_source();
+        let (pv, source) = match selected {
+            PvSource::Die => None,
+            PvSource::Sht3x => fresh(sht3x),
+            PvSource::Bme280 => fresh(bme280),
+        }
+        .map_or((die, PvSource::Die), |celsius| (celsius, selected));
+        if source != used_source {
+            if source == selected {
+                info!("Process variable from the {}", source.name());
+            } else {
+                warn!("No value from the {}, process variable from the die", selected.name());
+            }
+            used_source = source;
+        }
+        let setpoint = rx_setpoint.get().await;
+
+        let terms = match mode {
+            ControlMode::Auto => pid.update(setpoint.celsius, pv, dt),
+            ControlMode::Manual(output) => {
+                pid.reset(output);
+                PidTerms { output, ..PidTerms::default() }
+            }
+            ControlMode::Off => {
+                pid.reset(OUTPUT_MIN_PERCENT);
+                PidTerms::default()
+            }
+        };
 
-        // 66% duty cycle. Expressed as simple percentage.
-        //let now = Instant::now();
-        pwm.set_duty_cycle_percent(50).unwrap(); // 115 micro seconds to configure the new output for the pwm
-        //let elapsed = now.elapsed();
-        //info!("PWM 50% - Elapsed: {} us", elapsed.as_micros());
-        Timer::after_secs(1).await;
+        let saturated = mode == ControlMode::Auto
+            && (terms.output <= OUTPUT_MIN_PERCENT || terms.output >= OUTPUT_MAX_PERCENT);
+        let faults = evaluate_faults(pv, die, saturated);
+        // Interlock of the heater, the PID starts again from 0 % when the faults are acknowledged
+        let output = interlock_output(terms.output, faults);
+        let terms = if output == terms.output {
+            terms
+        } else {
+            pid.reset(output);
+            PidTerms { output, ..PidTerms::default() }
+        };
 
+        // Duty cycle with 0.1% of resolution
+        let duty = (terms.output * 10.0) as u16;
+        if pwm.set_duty_cycle_fraction(duty, 1_000).is_err() {
+            error!("PWM duty cycle update failed");
+        }
+        info!(
+            "PID {}: PV {} SP {} P {} I {} D {} OUT {}%",
+            mode.name(),
+            pv,
+            setpoint.celsius,
+            terms.p,
+            terms.i,
+            terms.d,
+            terms.output
+        );
 
+        tx_status.send(ControlStatus {
+            pv,
+            pv_source: source,
+            die_c: die,
+            setpoint,
+            gains: pid.gains(),
+            terms,
+            mode,
+            faults,
+        });
 
+        let overrun = timer.finish(Instant::now().as_micros());
+        let stats = timer.stats();
+        LOOP_STATS.lock(|cell| cell.set(stats));
+        if overrun {
+            warn!("Control loop overrun: cycle of {} us in a period of {} us", stats.exec_us, stats.period_us);
+            ticker.reset(); // The lost ticks are not run in a burst
+        }
+        if control_period_ms() != period_ms {
+            period_ms = control_period_ms();
+            info!("Control loop period {} ms", period_ms);
+            timer.set_period(period_ms * 1_000);
+            ticker = Ticker::every(Duration::from_millis(period_ms as u64));
+        }
 
+        ticker.next().await;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,483 @@
+// Screen file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Screen file for the modular project.
+ *  File        : screen.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the pages of the display and the navigation between them:
+ *      overview, trend, PID, alarms, setpoint, network and device. Each page is a title and up to 5
+ *      lines of text made from the ScreenModel, the values are copied by the oled.rs from the channels.
+ *      The trend page is a graph of the history of the trend.rs.
+ *
+ *      The navigation rotates the pages by itself or only moves with the button. A press in the
+ *      auto rotate holds the page shown for 30 seconds before the rotation continues.
+ *
+ *      The pages are drawn in any DrawTarget of embedded-graphics, so the same code draws in the
+ *      SSD1306 and in a frame buffer of the host.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::iso_8859_1::FONT_6X10;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle};
+use embedded_graphics::text::{Baseline, Text};
+use heapless::{String, Vec};
+
+use super::alarm::{AlarmId, AlarmState, AlarmStatus, FAULT_NAMES};
+use super::trend::{Trend, draw_trend};
+
+pub const SCREEN_WIDTH: i32 = 128;
+pub const PAGE_LINES: usize = 5;
+pub const LINE_LEN: usize = 24; // Bytes, 21 characters of 6 pixels fit in the width and ° takes 2 bytes
+pub const PAGE_HOLD_S: u32 = 30; // Pause of the auto rotate after a press
+
+const CHAR_WIDTH: i32 = 6;
+const LINE_HEIGHT: i32 = 10;
+const FIRST_LINE_Y: i32 = 14; // Below the title and its separator
+
+pub type PageLine = String<LINE_LEN>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Page {
+    Overview,
+    Trend,
+    Pid,
+    Alarms,
+    Setpoint,
+    Network,
+    Device,
+}
+
+impl Page {
+    pub const ALL: [Page; 7] = [
+        Page::Overview,
+        Page::Trend,
+        Page::Pid,
+        Page::Alarms,
+        Page::Setpoint,
+        Page::Network,
+        Page::Device,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Page::Overview => "Smart Vivarium",
+            Page::Trend => "Trend",
+            Page::Pid => "PID",
+            Page::Alarms => "Alarms",
+            Page::Setpoint => "Setpoint",
+            Page::Network => "Network",
+            Page::Device => "Device",
+        }
+    }
+
+    fn index(self) -> usize {
+        Page::ALL.iter().position(|&page| page == self).unwrap_or(0)
+    }
+
+    pub fn next(self) -> Page {
+        Page::ALL[(self.index() + 1) % Page::ALL.len()]
+    }
+
+    pub fn previous(self) -> Page {
+        Page::ALL[(self.index() + Page::ALL.len() - 1) % Page::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Navigation {
+    AutoRotate { dwell_s: u32 }, // Next page after dwell_s, the button still works
+    #[allow(dead_code)] // Not chosen in the main.rs
+    Buttons,                     // The page only changes with the button
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NavEvent {
+    Next,
+    Previous,
+    Show(Page),
+}
+
+pub struct Navigator {
+    navigation: Navigation,
+    page: Page,
+    shown_at_s: u32,
+    held_until_s: Option<u32>,
+}
+
+impl Navigator {
+    pub const fn new(navigation: Navigation) -> Self {
+        Self {
+            navigation,
+            page: Page::Overview,
+            shown_at_s: 0,
+            held_until_s: None,
+        }
+    }
+
+    fn show(&mut self, now_s: u32, page: Page) {
+        self.page = page;
+        self.shown_at_s = now_s;
+    }
+
+    pub fn event(&mut self, now_s: u32, event: NavEvent) {
+        let page = match event {
+            NavEvent::Next => self.page.next(),
+            NavEvent::Previous => self.page.previous(),
+            NavEvent::Show(page) => page,
+        };
+        self.show(now_s, page);
+        self.held_until_s = Some(now_s.wrapping_add(PAGE_HOLD_S));
+    }
+
+    // Page to draw now, the auto rotate moves to the next one when its time is over
+    pub fn tick(&mut self, now_s: u32) -> Page {
+        if let Navigation::AutoRotate { dwell_s } = self.navigation {
+            match self.held_until_s {
+                Some(held_until_s) if (now_s.wrapping_sub(held_until_s) as i32) < 0 => {}
+                _ => {
+                    self.held_until_s = None;
+                    if now_s.wrapping_sub(self.shown_at_s) >= dwell_s {
+                        self.show(now_s, self.page.next());
+                    }
+                }
+            }
+        }
+        self.page
+    }
+}
+
+// Values of the control loop shown in the pages
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ControlView {
+    pub pv: f32,
+    pub pv_source: &'static str,
+    pub p: f32,
+    pub i: f32,
+    pub d: f32,
+    pub output: f32,
+    pub mode: &'static str,
+    pub faults: u16,
+}
+
+// State of the Wi-Fi of the feature wifi (wifi_link.rs), the other builds have no network
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NetworkView {
+    #[cfg_attr(feature = "wifi", allow(dead_code))]
+    NotFitted,
+    #[cfg_attr(not(feature = "wifi"), allow(dead_code))]
+    Connecting,
+    #[cfg_attr(not(feature = "wifi"), allow(dead_code))]
+    Connected { ip: [u8; 4], mqtt: bool },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScreenModel {
+    pub die_c: f32,
+    pub ref_c: f32,
+    pub setpoint_c: f32,
+    pub setpoint_source: &'static str,
+    pub air: Option<(f32, f32)>, // Temperature and humidity of the DHT
+    pub control: Option<ControlView>,
+    pub trip_limits: (f32, f32), // Low and high trip limits of the PV
+    pub alarms: AlarmStatus,
+    pub network: NetworkView,
+    pub display: &'static str,
+    pub i2c_recoveries: u32,
+    pub uptime_s: u32,
+}
+
+// The line is cut when it does not fit in the width of the display
+fn line(args: fmt::Arguments) -> PageLine {
+    let mut text = PageLine::new();
+    let _ = text.write_fmt(args);
+    text
+}
+
+fn push(lines: &mut Vec<PageLine, PAGE_LINES>, args: fmt::Arguments) {
+    let _ = lines.push(line(args));
+}
+
+// Lines of the page, without the title. The trend page has no lines, it is a graph.
+pub fn page_lines(page: Page, model: &ScreenModel) -> Vec<PageLine, PAGE_LINES> {
+    let mut lines = Vec::new();
+    match page {
+        Page::Trend => {}
+        Page::Overview => {
+            push(&mut lines, format_args!("Temp Die: {:.2} °C", model.die_c));
+            push(&mut lines, format_args!("Ref Temp: {:.2} °C", model.ref_c));
+            push(&mut lines, format_args!("Set: {:.2} °C {}", model.setpoint_c, model.setpoint_source));
+            match model.air {
+                Some((temperature, humidity)) => {
+                    push(&mut lines, format_args!("Air: {:.1} °C {:.1} %", temperature, humidity))
+                }
+                None => push(&mut lines, format_args!("Air: --")),
+            }
+        }
+        Page::Pid => match model.control {
+            Some(control) => {
+                push(&mut lines, format_args!("PV: {:.2} °C {}", control.pv, control.pv_source));
+                push(&mut lines, format_args!("P: {:.2}", control.p));
+                push(&mut lines, format_args!("I: {:.2}", control.i));
+                push(&mut lines, format_args!("D: {:.2}", control.d));
+                push(&mut lines, format_args!("Out: {:.1} % {}", control.output, control.mode));
+            }
+            None => push(&mut lines, format_args!("Loop not running")),
+        },
+        Page::Alarms => {
+            let (low_c, high_c) = model.trip_limits;
+            push(&mut lines, format_args!("Trips: {:.0}-{:.0} °C", low_c, high_c));
+            let faults = model.control.map_or(0, |control| control.faults);
+            let alarms = AlarmId::ALL.iter().filter(|&&id| model.alarms.state(id) != AlarmState::Normal);
+            if faults == 0 && alarms.clone().next().is_none() {
+                push(&mut lines, format_args!("No faults or alarms"));
+            }
+            for (bit, name) in FAULT_NAMES {
+                if faults & bit != 0 {
+                    push(&mut lines, format_args!("! {}", name));
+                }
+            }
+            for &id in alarms {
+                push(&mut lines, format_args!("* {} {}", id.name(), model.alarms.state(id).name()));
+            }
+        }
+        Page::Setpoint => {
+            push(&mut lines, format_args!("Target: {:.2} °C", model.setpoint_c));
+            push(&mut lines, format_args!("Source: {}", model.setpoint_source));
+        }
+        Page::Network => match model.network {
+            NetworkView::NotFitted => {
+                push(&mut lines, format_args!("Wi-Fi: not fitted"));
+                push(&mut lines, format_args!("USB console on"));
+            }
+            NetworkView::Connecting => push(&mut lines, format_args!("Wi-Fi: connecting")),
+            NetworkView::Connected { ip, mqtt } => {
+                push(&mut lines, format_args!("Wi-Fi: up"));
+                push(&mut lines, format_args!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]));
+                push(&mut lines, format_args!("MQTT: {}", if mqtt { "on" } else { "off" }));
+            }
+        },
+        Page::Device => {
+            let uptime_s = model.uptime_s;
+            push(&mut lines, format_args!("FW {}", env!("CARGO_PKG_VERSION")));
+            push(
+                &mut lines,
+                format_args!(
+                    "Up {}d {:02}:{:02}:{:02}",
+                    uptime_s / 86_400,
+                    uptime_s / 3600 % 24,
+                    uptime_s / 60 % 60,
+                    uptime_s % 60
+                ),
+            );
+            push(&mut lines, format_args!("Display: {}", model.display));
+            push(&mut lines, format_args!("I2C recoveries: {}", model.i2c_recoveries));
+        }
+    }
+    lines
+}
+
+fn centered_x(text: &str) -> i32 {
+    (SCREEN_WIDTH - text.chars().count() as i32 * CHAR_WIDTH).max(0) / 2
+}
+
+// Draw the title, the number of the page and the lines. The target is not cleared.
+pub fn render<D: DrawTarget<Color = BinaryColor>>(
+    target: &mut D,
+    page: Page,
+    model: &ScreenModel,
+    trend: &Trend,
+) -> Result<(), D::Error> {
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+
+    let title = match page {
+        Page::Trend => line(format_args!("{} {}", page.title(), trend.window().name())),
+        _ => line(format_args!("{}", page.title())),
+    };
+    Text::with_baseline(&title, Point::new(0, 0), style, Baseline::Top).draw(target)?;
+    let number = line(format_args!("{}/{}", page.index() + 1, Page::ALL.len()));
+    let number_x = SCREEN_WIDTH - number.len() as i32 * CHAR_WIDTH;
+    Text::with_baseline(&number, Point::new(number_x, 0), style, Baseline::Top).draw(target)?;
+    Line::new(Point::new(0, 11), Point::new(SCREEN_WIDTH - 1, 11))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)?;
+
+    if page == Page::Trend {
+        return draw_trend(target, trend);
+    }
+    for (row, text) in page_lines(page, model).iter().enumerate() {
+        let y = FIRST_LINE_Y + row as i32 * LINE_HEIGHT;
+        Text::with_baseline(text, Point::new(centered_x(text), y), style, Baseline::Top).draw(target)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_display::FrameBuffer;
+    use crate::modular::alarm::FAULT_OVER_TEMP;
+    use crate::modular::trend::TrendWindow;
+
+    fn model() -> ScreenModel {
+        ScreenModel {
+            die_c: 25.5,
+            ref_c: 24.0,
+            setpoint_c: 28.0,
+            setpoint_source: "stored",
+            air: None,
+            control: None,
+            trip_limits: (18.0, 35.0),
+            alarms: AlarmStatus::new(),
+            network: NetworkView::NotFitted,
+            display: "ok",
+            i2c_recoveries: 0,
+            uptime_s: 0,
+        }
+    }
+
+    fn control(faults: u16) -> ControlView {
+        ControlView {
+            pv: 27.25,
+            pv_source: "die",
+            p: 1.0,
+            i: 0.5,
+            d: 0.0,
+            output: 42.0,
+            mode: "auto",
+            faults,
+        }
+    }
+
+    #[test]
+    fn pages_wrap_around() {
+        assert_eq!(Page::Overview.previous(), Page::Device);
+        assert_eq!(Page::Device.next(), Page::Overview);
+        assert_eq!(Page::Trend.next().previous(), Page::Trend);
+    }
+
+    #[test]
+    fn auto_rotate_moves_after_the_dwell() {
+        let mut navigator = Navigator::new(Navigation::AutoRotate { dwell_s: 5 });
+
+        assert_eq!(navigator.tick(4), Page::Overview);
+        assert_eq!(navigator.tick(5), Page::Trend);
+        assert_eq!(navigator.tick(9), Page::Trend);
+        assert_eq!(navigator.tick(10), Page::Pid);
+    }
+
+    #[test]
+    fn press_holds_the_auto_rotate() {
+        let mut navigator = Navigator::new(Navigation::AutoRotate { dwell_s: 5 });
+        navigator.event(3, NavEvent::Next);
+
+        assert_eq!(navigator.tick(32), Page::Trend);
+        assert_eq!(navigator.tick(3 + PAGE_HOLD_S), Page::Pid);
+    }
+
+    #[test]
+    fn buttons_only_move_with_the_events() {
+        let mut navigator = Navigator::new(Navigation::Buttons);
+
+        assert_eq!(navigator.tick(1_000), Page::Overview);
+        navigator.event(1_000, NavEvent::Previous);
+        assert_eq!(navigator.tick(5_000), Page::Device);
+        navigator.event(5_000, NavEvent::Show(Page::Alarms));
+        assert_eq!(navigator.tick(5_001), Page::Alarms);
+    }
+
+    #[test]
+    fn overview_and_pid_lines() {
+        let mut model = model();
+        assert_eq!(
+            page_lines(Page::Overview, &model),
+            ["Temp Die: 25.50 °C", "Ref Temp: 24.00 °C", "Set: 28.00 °C stored", "Air: --"]
+        );
+        assert_eq!(page_lines(Page::Pid, &model), ["Loop not running"]);
+
+        model.air = Some((26.04, 71.26));
+        model.control = Some(control(0));
+        assert_eq!(page_lines(Page::Overview, &model)[3], "Air: 26.0 °C 71.3 %");
+        assert_eq!(
+            page_lines(Page::Pid, &model),
+            ["PV: 27.25 °C die", "P: 1.00", "I: 0.50", "D: 0.00", "Out: 42.0 % auto"]
+        );
+        assert!(page_lines(Page::Trend, &model).is_empty());
+    }
+
+    #[test]
+    fn alarms_page_lists_the_faults_and_the_alarms() {
+        let mut model = model();
+        assert_eq!(page_lines(Page::Alarms, &model), ["Trips: 18-35 °C", "No faults or alarms"]);
+
+        model.control = Some(control(FAULT_OVER_TEMP));
+        model.alarms.states[AlarmId::PvHigh as usize] = AlarmState::Active;
+        model.alarms.states[AlarmId::DieHot as usize] = AlarmState::Latched;
+        assert_eq!(
+            page_lines(Page::Alarms, &model),
+            ["Trips: 18-35 °C", "! over-temp", "* pv-high active", "* die-hot latched"]
+        );
+    }
+
+    #[test]
+    fn network_and_device_lines() {
+        let mut model = model();
+        assert_eq!(page_lines(Page::Network, &model), ["Wi-Fi: not fitted", "USB console on"]);
+        model.network = NetworkView::Connected { ip: [192, 168, 1, 20], mqtt: true };
+        assert_eq!(page_lines(Page::Network, &model), ["Wi-Fi: up", "192.168.1.20", "MQTT: on"]);
+
+        model.uptime_s = 86_400 + 3_600 + 60 + 1;
+        model.i2c_recoveries = 2;
+        let device = page_lines(Page::Device, &model);
+        assert_eq!(device[1..], ["Up 1d 01:01:01", "Display: ok", "I2C recoveries: 2"]);
+    }
+
+    #[test]
+    fn long_lines_are_cut() {
+        let mut model = model();
+        model.setpoint_source = "a source with a very long name";
+
+        let lines = page_lines(Page::Setpoint, &model);
+        assert!(lines[1].starts_with("Source:"));
+        assert!(lines[1].len() <= LINE_LEN);
+    }
+
+    #[test]
+    fn render_draws_the_title_the_separator_and_the_centered_lines() {
+        let mut frame = FrameBuffer::new();
+        render(&mut frame, Page::Setpoint, &model(), &Trend::new(TrendWindow::TwoMinutes)).unwrap();
+
+        // Separator in all the width, nothing between it and the first line
+        assert_eq!(frame.count(0, 11, 127, 11), 128, "{frame:?}");
+        assert_eq!(frame.count(0, 12, 127, 13), 0, "{frame:?}");
+        // Title in the left and the number of the page ("5/7", 18 pixels) in the right
+        assert!(frame.count(0, 0, 60, 10) > 0, "{frame:?}");
+        assert!(frame.count(110, 0, 127, 10) > 0, "{frame:?}");
+        assert_eq!(frame.count(84, 0, 109, 10), 0, "{frame:?}");
+        // "Source: stored" has 14 characters, 84 pixels in the middle of the 128
+        assert!(frame.count(22, 24, 105, 33) > 0, "{frame:?}");
+        assert_eq!(frame.count(0, 24, 21, 33) + frame.count(106, 24, 127, 33), 0, "{frame:?}");
+        // Only two lines in the page
+        assert_eq!(frame.count(0, 34, 127, 63), 0, "{frame:?}");
+    }
+
+    #[test]
+    fn render_of_the_trend_page_is_the_graph() {
+        let mut frame = FrameBuffer::new();
+        render(&mut frame, Page::Trend, &model(), &Trend::new(TrendWindow::TwoMinutes)).unwrap();
+
+        // No samples yet, the text in the middle instead of the graph
+        assert!(frame.count(34, 30, 93, 39) > 0, "{frame:?}");
+        assert_eq!(frame.count(0, 12, 33, 63), 0, "{frame:?}");
+    }
+}
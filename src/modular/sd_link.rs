@@ -0,0 +1,133 @@
+// SD link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : SD link file for the modular project.
+ *  File        : sd_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the logging of the control loop in the SD card. One task takes a
+ *      sample of the control status every 10 seconds and puts it in a queue without waiting, the other
+ *      task formats the samples as CSV and writes them in the file of the boot and day once a minute.
+ *      When the card is not present the samples stay in the batch until it is inserted again, and the
+ *      samples that do not fit are dropped and counted.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{Duration, Instant, Timer};
+use heapless::String;
+
+use crate::modular::datalog::{LOG_KIND_SAMPLE, LogEntry, LogRecord};
+use crate::modular::humidity_link::time_of_day_s;
+use crate::modular::pwm::get_receiver_control_status;
+use crate::modular::sdcard::SdCard;
+use crate::modular::sdlog::{CsvBatch, log_day};
+
+pub const SD_SAMPLE_PERIOD_S: u64 = 10;
+pub const SD_WRITE_PERIOD_S: u64 = 60;
+
+const SD_QUEUE_DEPTH: usize = 16;
+// Day of the file and the sample, the day is taken with the sample so a full queue does not move it
+static SD_SAMPLES: Channel<ThreadModeRawMutex, (u32, LogRecord), SD_QUEUE_DEPTH> = Channel::new();
+
+// This task samples the control status for the SD card, it never waits for the card
+#[embassy_executor::task]
+pub async fn sd_sample_task(boot: u16) {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let mut sequence: u32 = 0;
+    let mut dropped: u32 = 0;
+
+    loop {
+        Timer::after_secs(SD_SAMPLE_PERIOD_S).await;
+
+        let Some(status) = rx_status.try_get() else {
+            continue;
+        };
+        let uptime_s = Instant::now().as_secs() as u32;
+        let day = log_day(uptime_s, time_of_day_s());
+        let record = LogRecord {
+            sequence,
+            boot,
+            entry: LogEntry {
+                kind: LOG_KIND_SAMPLE,
+                mode: status.mode.code(),
+                uptime_s,
+                pv: status.pv,
+                sp: status.setpoint.celsius,
+                output: status.terms.output,
+                faults: status.faults,
+            },
+        };
+        sequence = sequence.wrapping_add(1);
+
+        if SD_SAMPLES.try_send((day, record)).is_err() {
+            dropped += 1;
+            warn!("SD queue full, {} samples dropped", dropped);
+        }
+    }
+}
+
+// Write the batch in the card, initialising it first when it was inserted or had an error
+async fn write_batch(card: &mut SdCard, batch: &mut CsvBatch, boot: u16) {
+    if batch.is_empty() {
+        return;
+    }
+    if !card.is_initialised() {
+        match card.init().await {
+            Ok(()) => info!("SD card initialised"),
+            Err(e) => {
+                debug!("SD card not available: {}", Debug2Format(&e));
+                return;
+            }
+        }
+    }
+    match batch.write_to(card, boot).await {
+        Ok(size) => {
+            debug!("SD log day {}: {} bytes written, file with {} bytes", batch.day(), batch.len(), size);
+            batch.clear();
+        }
+        Err(e) => warn!("SD log not written: {}", Debug2Format(&e)),
+    }
+}
+
+// This task writes the samples in the CSV files of the SD card
+#[embassy_executor::task]
+pub async fn sd_card_task(mut card: SdCard, boot: u16) {
+    let mut batch = CsvBatch::new();
+    let mut line: String<96> = String::new();
+    let mut next_write = Instant::now() + Duration::from_secs(SD_WRITE_PERIOD_S);
+    let mut dropped: u32 = 0;
+
+    loop {
+        let (day, record) = match select(SD_SAMPLES.receive(), Timer::at(next_write)).await {
+            Either::First(record) => record,
+            Either::Second(()) => {
+                write_batch(&mut card, &mut batch, boot).await;
+                next_write = Instant::now() + Duration::from_secs(SD_WRITE_PERIOD_S);
+                continue;
+            }
+        };
+
+        line.clear();
+        if record.write_csv(&mut line).is_err() {
+            continue;
+        }
+        if batch.push(day, &line) {
+            continue;
+        }
+        // New day or full batch, the old lines go to the card first
+        write_batch(&mut card, &mut batch, boot).await;
+        if !batch.push(day, &line) {
+            dropped += 1;
+            warn!("SD card not available, {} samples dropped", dropped);
+        }
+    }
+}
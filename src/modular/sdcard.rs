@@ -0,0 +1,270 @@
+// SD card file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : SD card file for the modular project.
+ *  File        : sdcard.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the SD card in the SPI mode, connected to the SPI1
+ *      (SCK GP10, MOSI GP11, MISO GP12) with the chip select in the GP13.
+ *      The card is initialised at 400 kHz and used at 12 MHz, the blocks are read and written one by one
+ *      with DMA, so the other tasks keep running during the transfers. Any error marks the card as not
+ *      initialised, so a removed card is initialised again when it is inserted.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use embassy_rp::gpio::Output;
+use embassy_rp::peripherals::SPI1;
+use embassy_rp::spi::{self, Async, Spi};
+use embassy_time::{Duration, Instant, Timer};
+
+use crate::modular::fat::{BLOCK_SIZE, Block, BlockDevice};
+
+const INIT_FREQUENCY_HZ: u32 = 400_000;
+const DATA_FREQUENCY_HZ: u32 = 12_000_000;
+
+const CMD_GO_IDLE_STATE: u8 = 0;
+const CMD_SEND_IF_COND: u8 = 8;
+const CMD_SET_BLOCKLEN: u8 = 16;
+const CMD_READ_SINGLE_BLOCK: u8 = 17;
+const CMD_WRITE_BLOCK: u8 = 24;
+const CMD_APP_CMD: u8 = 55;
+const CMD_READ_OCR: u8 = 58;
+const ACMD_SD_SEND_OP_COND: u8 = 41;
+
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+const TOKEN_START_BLOCK: u8 = 0xFE;
+const DATA_ACCEPTED: u8 = 0x05;
+
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(100);
+const INIT_TIMEOUT: Duration = Duration::from_millis(1_000);
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SdError {
+    Spi(spi::Error),
+    NotInitialised,
+    NoCard,          // No answer to the reset, the card is not in the slot
+    Timeout,         // The card stopped answering
+    Command(u8, u8), // Command and R1 with the error
+    Unsupported,     // Card of the version 1 without the 512 bytes block or MMC
+    WriteRejected(u8),
+}
+
+pub struct SdCard {
+    spi: Spi<'static, SPI1, Async>,
+    cs: Output<'static>,
+    initialised: bool,
+    block_addressing: bool, // SDHC/SDXC use the number of the block, SDSC the address in bytes
+}
+
+impl SdCard {
+    pub fn new(spi: Spi<'static, SPI1, Async>, mut cs: Output<'static>) -> Self {
+        cs.set_high();
+        Self {
+            spi,
+            cs,
+            initialised: false,
+            block_addressing: false,
+        }
+    }
+
+    pub fn is_initialised(&self) -> bool {
+        self.initialised
+    }
+
+    async fn exchange(&mut self, data: &mut [u8]) -> Result<(), SdError> {
+        self.spi.transfer_in_place(data).await.map_err(SdError::Spi)
+    }
+
+    async fn read_byte(&mut self) -> Result<u8, SdError> {
+        let mut byte = [0xFF];
+        self.exchange(&mut byte).await?;
+        Ok(byte[0])
+    }
+
+    // The card holds MISO low while it is busy
+    async fn wait_ready(&mut self, timeout: Duration) -> Result<(), SdError> {
+        let start = Instant::now();
+        while self.read_byte().await? != 0xFF {
+            if start.elapsed() > timeout {
+                return Err(SdError::Timeout);
+            }
+        }
+        Ok(())
+    }
+
+    async fn deselect(&mut self) {
+        self.cs.set_high();
+        // The card only releases the MISO after one more byte
+        let _ = self.read_byte().await;
+    }
+
+    // Send the command and return the R1, the CRC is only checked by the card in the CMD0 and CMD8
+    async fn command(&mut self, command: u8, argument: u32) -> Result<u8, SdError> {
+        if command != CMD_GO_IDLE_STATE {
+            self.wait_ready(COMMAND_TIMEOUT).await?;
+        }
+        let crc = match command {
+            CMD_GO_IDLE_STATE => 0x95,
+            CMD_SEND_IF_COND => 0x87,
+            _ => 0x01,
+        };
+        let argument = argument.to_be_bytes();
+        let mut frame = [0x40 | command, argument[0], argument[1], argument[2], argument[3], crc];
+        self.exchange(&mut frame).await?;
+
+        // The R1 comes in up to 8 bytes, the first bit is zero
+        for _ in 0..8 {
+            let r1 = self.read_byte().await?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(SdError::Timeout)
+    }
+
+    async fn app_command(&mut self, command: u8, argument: u32) -> Result<u8, SdError> {
+        self.command(CMD_APP_CMD, 0).await?;
+        self.command(command, argument).await
+    }
+
+    // Put the card in the SPI mode and read the type of addressing
+    pub async fn init(&mut self) -> Result<(), SdError> {
+        self.initialised = false;
+        self.spi.set_frequency(INIT_FREQUENCY_HZ);
+        self.cs.set_high();
+        // At least 74 clocks with the chip select high
+        let mut clocks = [0xFF; 10];
+        self.exchange(&mut clocks).await?;
+
+        self.cs.set_low();
+        let result = self.init_selected().await;
+        self.deselect().await;
+        result?;
+
+        self.spi.set_frequency(DATA_FREQUENCY_HZ);
+        self.initialised = true;
+        Ok(())
+    }
+
+    async fn init_selected(&mut self) -> Result<(), SdError> {
+        if self.command(CMD_GO_IDLE_STATE, 0).await.map_err(|_| SdError::NoCard)? != R1_IDLE {
+            return Err(SdError::NoCard);
+        }
+
+        // Only the version 2 answers the CMD8, with the echo of the pattern 0xAA
+        let r1 = self.command(CMD_SEND_IF_COND, 0x1AA).await?;
+        let version_2 = r1 & R1_ILLEGAL_COMMAND == 0;
+        if version_2 {
+            let mut r7 = [0xFF; 4];
+            self.exchange(&mut r7).await?;
+            if r7[3] != 0xAA {
+                return Err(SdError::Unsupported);
+            }
+        }
+
+        let start = Instant::now();
+        let high_capacity = if version_2 { 1 << 30 } else { 0 };
+        loop {
+            match self.app_command(ACMD_SD_SEND_OP_COND, high_capacity).await? {
+                0 => break,
+                R1_IDLE => {}
+                r1 => return Err(SdError::Command(ACMD_SD_SEND_OP_COND, r1)),
+            }
+            if start.elapsed() > INIT_TIMEOUT {
+                return Err(SdError::Timeout);
+            }
+            Timer::after_millis(10).await;
+        }
+
+        self.block_addressing = false;
+        if version_2 {
+            let r1 = self.command(CMD_READ_OCR, 0).await?;
+            if r1 != 0 {
+                return Err(SdError::Command(CMD_READ_OCR, r1));
+            }
+            let mut ocr = [0xFF; 4];
+            self.exchange(&mut ocr).await?;
+            self.block_addressing = ocr[0] & 0x40 != 0;
+        }
+        if !self.block_addressing {
+            let r1 = self.command(CMD_SET_BLOCKLEN, BLOCK_SIZE as u32).await?;
+            if r1 != 0 {
+                return Err(SdError::Unsupported);
+            }
+        }
+        Ok(())
+    }
+
+    fn address(&self, lba: u32) -> u32 {
+        if self.block_addressing { lba } else { lba * BLOCK_SIZE as u32 }
+    }
+
+    async fn read_selected(&mut self, lba: u32, block: &mut Block) -> Result<(), SdError> {
+        let r1 = self.command(CMD_READ_SINGLE_BLOCK, self.address(lba)).await?;
+        if r1 != 0 {
+            return Err(SdError::Command(CMD_READ_SINGLE_BLOCK, r1));
+        }
+        let start = Instant::now();
+        loop {
+            match self.read_byte().await? {
+                TOKEN_START_BLOCK => break,
+                0xFF if start.elapsed() < COMMAND_TIMEOUT => {}
+                _ => return Err(SdError::Timeout),
+            }
+        }
+        block.fill(0xFF);
+        self.exchange(block).await?;
+        let mut crc = [0xFF; 2];
+        self.exchange(&mut crc).await
+    }
+
+    async fn write_selected(&mut self, lba: u32, block: &Block) -> Result<(), SdError> {
+        let r1 = self.command(CMD_WRITE_BLOCK, self.address(lba)).await?;
+        if r1 != 0 {
+            return Err(SdError::Command(CMD_WRITE_BLOCK, r1));
+        }
+        self.spi.write(&[0xFF, TOKEN_START_BLOCK]).await.map_err(SdError::Spi)?;
+        self.spi.write(block).await.map_err(SdError::Spi)?;
+        self.spi.write(&[0xFF, 0xFF]).await.map_err(SdError::Spi)?; // CRC not checked
+
+        let response = self.read_byte().await? & 0x1F;
+        if response != DATA_ACCEPTED {
+            return Err(SdError::WriteRejected(response));
+        }
+        self.wait_ready(WRITE_TIMEOUT).await
+    }
+}
+
+impl BlockDevice for SdCard {
+    type Error = SdError;
+
+    async fn read(&mut self, lba: u32, block: &mut Block) -> Result<(), SdError> {
+        if !self.initialised {
+            return Err(SdError::NotInitialised);
+        }
+        self.cs.set_low();
+        let result = self.read_selected(lba, block).await;
+        self.deselect().await;
+        self.initialised = result.is_ok();
+        result
+    }
+
+    async fn write(&mut self, lba: u32, block: &Block) -> Result<(), SdError> {
+        if !self.initialised {
+            return Err(SdError::NotInitialised);
+        }
+        self.cs.set_low();
+        let result = self.write_selected(lba, block).await;
+        self.deselect().await;
+        self.initialised = result.is_ok();
+        result
+    }
+}
@@ -0,0 +1,213 @@
+// SD log file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : SD log file for the modular project.
+ *  File        : sdlog.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the CSV files of the SD card. The lines are kept in a batch
+ *      in the memory and the batch is appended to the file of its day in a single operation, that mounts
+ *      the card, writes the data and updates the directory. Nothing stays open between two batches, so
+ *      the card can be removed and inserted at any moment, the batch is only cleared after it was written.
+ *
+ *      The Pico has no calendar, the day is counted from the clock of the "set clock" (humidity_link.rs):
+ *      every boot starts a new file and a new one follows at every midnight. The name has the number of
+ *      the boot (data log) and the index of the day since the boot: "BBBBDNNN.CSV", for example
+ *      0012D003.CSV is the day after the third midnight of the 12th boot. Setting the clock moves the
+ *      next midnight, the following lines may go to the file of the day before or after.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+
+use super::datalog::LOG_CSV_HEADER;
+use super::fat::{BlockDevice, FatError, FatVolume, short_name};
+use super::humidity::SECONDS_PER_DAY;
+
+pub const SD_BATCH_LEN: usize = 2048;
+
+// Midnights since the boot, from the uptime and the time of the day at the same moment
+pub fn log_day(uptime_s: u32, time_of_day_s: u32) -> u32 {
+    let boot_time_s = (time_of_day_s % SECONDS_PER_DAY + SECONDS_PER_DAY - uptime_s % SECONDS_PER_DAY) % SECONDS_PER_DAY;
+    ((boot_time_s as u64 + uptime_s as u64) / SECONDS_PER_DAY as u64) as u32
+}
+
+// File of the day, the boot is limited to 4 digits and the day to 3
+pub fn day_file_name(boot: u16, day: u32) -> [u8; 11] {
+    let mut name: String<12> = String::new();
+    let _ = core::write!(name, "{:04}D{:03}.CSV", boot % 10_000, day % 1_000);
+    short_name(&name).unwrap_or(*b"LOG     CSV")
+}
+
+// Lines of the same day waiting to be written in the card
+pub struct CsvBatch {
+    day: u32,
+    data: Vec<u8, SD_BATCH_LEN>,
+}
+
+impl CsvBatch {
+    pub const fn new() -> Self {
+        Self {
+            day: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    // Returns false when the line is from another day or does not fit, the batch must be written before
+    pub fn push(&mut self, day: u32, line: &str) -> bool {
+        if !self.data.is_empty() && day != self.day {
+            return false;
+        }
+        if self.data.len() + line.len() + 2 > SD_BATCH_LEN {
+            return false;
+        }
+        self.day = day;
+        let _ = self.data.extend_from_slice(line.as_bytes());
+        let _ = self.data.extend_from_slice(b"\r\n");
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    // Append the batch to the file of its day, a new file starts with the header of the CSV.
+    // Returns the size of the file after the write.
+    pub async fn write_to<D: BlockDevice>(&self, device: &mut D, boot: u16) -> Result<u32, FatError<D::Error>> {
+        let mut volume = FatVolume::mount(device).await?;
+        let mut file = volume.open_append(&day_file_name(boot, self.day)).await?;
+        if file.size() == 0 {
+            file.write(&mut volume, LOG_CSV_HEADER.as_bytes()).await?;
+            file.write(&mut volume, b"\r\n").await?;
+        }
+        file.write(&mut volume, &self.data).await?;
+        file.flush(&mut volume).await?;
+        Ok(file.size())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_card::MemoryCard;
+    use embassy_futures::block_on;
+
+    #[test]
+    fn file_names_have_the_boot_and_the_day() {
+        assert_eq!(&day_file_name(12, 3), b"0012D003CSV");
+        assert_eq!(&day_file_name(12_345, 1_001), b"2345D001CSV");
+    }
+
+    #[test]
+    fn day_changes_at_midnight() {
+        // Clock not set, the boot is at 00:00
+        assert_eq!(log_day(0, 0), 0);
+        assert_eq!(log_day(86_399, 86_399), 0);
+        assert_eq!(log_day(86_400, 0), 1);
+        assert_eq!(log_day(3 * 86_400 + 10, 10), 3);
+
+        // Boot at 23:00, the first midnight comes after one hour
+        assert_eq!(log_day(0, 23 * 3600), 0);
+        assert_eq!(log_day(3599, 86_399), 0);
+        assert_eq!(log_day(3600, 0), 1);
+        assert_eq!(log_day(3600 + 86_400, 0), 2);
+
+        // Boot at 00:30, midnight comes after 23 h 30 min and not after 24 h of uptime
+        assert_eq!(log_day(84_599, 86_399), 0);
+        assert_eq!(log_day(84_600, 0), 1);
+        assert_eq!(log_day(u32::MAX, 86_399), u32::MAX / 86_400);
+    }
+
+    #[test]
+    fn batch_keeps_one_day() {
+        let mut batch = CsvBatch::new();
+        assert!(batch.push(2, "a,b"));
+        assert!(batch.push(2, "c,d"));
+        assert_eq!((batch.day(), batch.len()), (2, 10));
+        assert!(!batch.push(3, "e,f"));
+
+        batch.clear();
+        assert!(batch.push(3, "e,f"));
+        assert_eq!(batch.day(), 3);
+    }
+
+    #[test]
+    fn batch_does_not_grow_beyond_its_size() {
+        let mut batch = CsvBatch::new();
+        let line = "x".repeat(98);
+        for _ in 0..SD_BATCH_LEN / 100 {
+            assert!(batch.push(0, &line));
+        }
+        assert!(!batch.push(0, &line));
+        assert_eq!(batch.len(), SD_BATCH_LEN / 100 * 100);
+    }
+
+    #[test]
+    fn batches_are_appended_under_one_header() {
+        let mut card = MemoryCard::fat32(true);
+        let mut batch = CsvBatch::new();
+        batch.push(0, "1,7,10,sample,auto,27.00,28.00,40.0,0");
+        block_on(batch.write_to(&mut card, 7)).unwrap();
+        batch.clear();
+        batch.push(0, "2,7,20,sample,auto,27.50,28.00,35.0,0");
+        block_on(batch.write_to(&mut card, 7)).unwrap();
+
+        let data = card.read_file(b"0007D000CSV").unwrap();
+        let lines: std::vec::Vec<&str> = core::str::from_utf8(&data).unwrap().split_terminator("\r\n").collect();
+        assert_eq!(lines, [LOG_CSV_HEADER, "1,7,10,sample,auto,27.00,28.00,40.0,0", "2,7,20,sample,auto,27.50,28.00,35.0,0"]);
+
+        // The next day goes to its own file
+        let line = "3,7,86410,sample,auto,27.50,28.00,35.0,0";
+        batch.clear();
+        batch.push(1, line);
+        assert_eq!(block_on(batch.write_to(&mut card, 7)), Ok((LOG_CSV_HEADER.len() + line.len() + 4) as u32));
+        assert_eq!(card.root_entries().len(), 2);
+    }
+
+    #[test]
+    fn files_roll_over_at_midnight() {
+        // Boot at 23:59:00, one sample every 10 seconds, the batch is written when the day changes
+        let mut card = MemoryCard::fat32(true);
+        let mut batch = CsvBatch::new();
+        let boot_time_s = 86_400 - 60;
+        for uptime_s in (0..120).step_by(10) {
+            let day = log_day(uptime_s, (boot_time_s + uptime_s) % 86_400);
+            let line = std::format!("{},7,{},sample,auto,27.00,28.00,40.0,0", uptime_s / 10, uptime_s);
+            if !batch.push(day, &line) {
+                block_on(batch.write_to(&mut card, 7)).unwrap();
+                batch.clear();
+                assert!(batch.push(day, &line));
+            }
+        }
+        block_on(batch.write_to(&mut card, 7)).unwrap();
+
+        let lines = |name: &[u8; 11]| {
+            let data = card.read_file(name).unwrap();
+            std::string::String::from_utf8(data).unwrap().split_terminator("\r\n").count() - 1
+        };
+        assert_eq!(card.root_entries().len(), 2);
+        assert_eq!(lines(b"0007D000CSV"), 6);
+        assert_eq!(lines(b"0007D001CSV"), 6);
+        assert!(card.read_file(b"0007D001CSV").unwrap().starts_with(LOG_CSV_HEADER.as_bytes()));
+    }
+
+}
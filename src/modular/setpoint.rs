@@ -0,0 +1,266 @@
+// Setpoint file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Setpoint file for the modular project.
+ *  File        : setpoint.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-18
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about to decide where the target temperature of the controller comes from.
+ *      The sources are the analog knob in the ADC0, a stored fixed value and the remote commands, the first
+ *      source in the priority list that has a value wins. The knob is only a source after it is enabled
+ *      (set knob on), the ADC0 is the reference temperature of the display in the boards without a
+ *      potentiometer. The task that publishes it through a watch is in the setpoint_link.rs.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::Format;
+
+// Limits of the setpoint, any value outside of this range is clamped before being published
+pub const SETPOINT_MIN_C: f32 = 0.0;
+pub const SETPOINT_MAX_C: f32 = 50.0;
+// Value used by the stored source until a persisted one is loaded
+pub const DEFAULT_STORED_SETPOINT_C: f32 = 28.0;
+// The knob only moves when the ADC0 average changes more than the deadband (raw counts),
+// and a change of direction needs the hysteresis on top of it.
+pub const KNOB_DEADBAND_COUNTS: u16 = 8;
+pub const KNOB_HYSTERESIS_COUNTS: u16 = 16;
+
+// Convert the raw average of the ADC0 into °C, the same scale used by the "Ref Temp" in the display
+pub fn knob_raw_to_celsius(raw: u16) -> f32 {
+    (raw as f32 + 1.0) / 128.0
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Format, Debug)]
+pub enum SetpointSource {
+    Remote, // Serial console, Modbus, MQTT or HTTP
+    Knob,   // Potentiometer in the ADC0, when it is enabled
+    Stored, // Fixed value persisted in the configuration
+}
+
+impl SetpointSource {
+    pub fn name(self) -> &'static str {
+        match self {
+            SetpointSource::Remote => "remote",
+            SetpointSource::Knob => "knob",
+            SetpointSource::Stored => "stored",
+        }
+    }
+}
+
+// Priority list used to arbitrate between the sources, the first source with a value wins.
+// Putting a source in the first position forces it whenever it has a value.
+#[derive(Clone, Copy, PartialEq, Eq, Format)]
+pub struct SetpointPolicy {
+    pub priority: [SetpointSource; 3],
+}
+
+impl SetpointPolicy {
+    pub const DEFAULT: Self = Self {
+        priority: [SetpointSource::Remote, SetpointSource::Knob, SetpointSource::Stored],
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Format, Debug)]
+pub struct Setpoint {
+    pub celsius: f32,
+    pub source: SetpointSource,
+}
+
+#[derive(Clone, Copy, PartialEq, Format)]
+pub enum SetpointCommand {
+    Remote(f32),       // Override the setpoint until it is released
+    ReleaseRemote,     // Give the control back to the next source
+    KnobEnabled(bool), // Enable or disable the potentiometer as a source
+}
+
+// Filter for the potentiometer, so the ADC noise does not wiggle the target.
+pub struct KnobFilter {
+    deadband: u16,
+    hysteresis: u16,
+    held: Option<u16>,
+    rising: bool,
+}
+
+impl KnobFilter {
+    pub const fn new(deadband: u16, hysteresis: u16) -> Self {
+        Self {
+            deadband,
+            hysteresis,
+            held: None,
+            rising: true,
+        }
+    }
+
+    // Returns the new held value when the reading moved enough, otherwise None
+    pub fn update(&mut self, raw: u16) -> Option<u16> {
+        let Some(held) = self.held else {
+            self.held = Some(raw);
+            return Some(raw);
+        };
+
+        let rising = raw > held;
+        let diff = raw.abs_diff(held);
+        let threshold = if rising == self.rising {
+            self.deadband
+        } else {
+            self.deadband.saturating_add(self.hysteresis)
+        };
+
+        if diff > threshold {
+            self.held = Some(raw);
+            self.rising = rising;
+            Some(raw)
+        } else {
+            None
+        }
+    }
+
+    pub fn held(&self) -> Option<u16> {
+        self.held
+    }
+}
+
+pub struct SetpointManager {
+    policy: SetpointPolicy,
+    knob: KnobFilter,
+    knob_enabled: bool,
+    stored: f32,
+    remote: Option<f32>,
+}
+
+impl SetpointManager {
+    pub const fn new(policy: SetpointPolicy, stored: f32, knob_enabled: bool) -> Self {
+        Self {
+            policy,
+            knob: KnobFilter::new(KNOB_DEADBAND_COUNTS, KNOB_HYSTERESIS_COUNTS),
+            knob_enabled,
+            stored,
+            remote: None,
+        }
+    }
+
+    pub fn update_knob(&mut self, raw: u16) {
+        self.knob.update(raw);
+    }
+
+    pub fn apply(&mut self, command: SetpointCommand) {
+        match command {
+            SetpointCommand::Remote(value) => self.remote = Some(value),
+            SetpointCommand::ReleaseRemote => self.remote = None,
+            SetpointCommand::KnobEnabled(enabled) => self.knob_enabled = enabled,
+        }
+    }
+
+    fn value_of(&self, source: SetpointSource) -> Option<f32> {
+        match source {
+            SetpointSource::Remote => self.remote,
+            SetpointSource::Knob if self.knob_enabled => self.knob.held().map(knob_raw_to_celsius),
+            SetpointSource::Knob => None,
+            SetpointSource::Stored => Some(self.stored),
+        }
+    }
+
+    // The effective setpoint is the first source of the policy with a value, the stored value is the fallback
+    pub fn effective(&self) -> Setpoint {
+        let (source, celsius) = self
+            .policy
+            .priority
+            .iter()
+            .find_map(|&source| self.value_of(source).map(|value| (source, value)))
+            .unwrap_or((SetpointSource::Stored, self.stored));
+
+        Setpoint {
+            celsius: celsius.clamp(SETPOINT_MIN_C, SETPOINT_MAX_C),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(knob_enabled: bool) -> SetpointManager {
+        SetpointManager::new(SetpointPolicy::DEFAULT, 26.0, knob_enabled)
+    }
+
+    #[test]
+    fn knob_filter_ignores_the_noise_inside_the_deadband() {
+        let mut filter = KnobFilter::new(8, 16);
+
+        assert_eq!(filter.update(1000), Some(1000));
+        assert_eq!(filter.update(1008), None);
+        assert_eq!(filter.update(992), None);
+        assert_eq!(filter.update(1009), Some(1009));
+        assert_eq!(filter.held(), Some(1009));
+    }
+
+    #[test]
+    fn knob_filter_needs_the_hysteresis_to_change_direction() {
+        let mut filter = KnobFilter::new(8, 16);
+        filter.update(1000);
+        filter.update(1100);
+
+        // Going down needs more than deadband + hysteresis
+        assert_eq!(filter.update(1076), None);
+        assert_eq!(filter.update(1075), Some(1075));
+        // Keeps going down with the deadband only
+        assert_eq!(filter.update(1066), Some(1066));
+    }
+
+    #[test]
+    fn stored_setpoint_wins_while_the_knob_is_disabled() {
+        let mut manager = manager(false);
+        manager.update_knob(3200);
+
+        let setpoint = manager.effective();
+        assert_eq!(setpoint.source, SetpointSource::Stored);
+        assert_eq!(setpoint.celsius, 26.0);
+    }
+
+    #[test]
+    fn knob_wins_over_the_stored_setpoint_after_it_is_enabled() {
+        let mut manager = manager(false);
+        manager.update_knob(3199);
+        manager.apply(SetpointCommand::KnobEnabled(true));
+
+        let setpoint = manager.effective();
+        assert_eq!(setpoint.source, SetpointSource::Knob);
+        assert_eq!(setpoint.celsius, knob_raw_to_celsius(3199));
+
+        manager.apply(SetpointCommand::KnobEnabled(false));
+        assert_eq!(manager.effective().source, SetpointSource::Stored);
+    }
+
+    #[test]
+    fn enabled_knob_without_a_reading_falls_back_to_the_stored_setpoint() {
+        assert_eq!(manager(true).effective().source, SetpointSource::Stored);
+    }
+
+    #[test]
+    fn remote_overrides_until_it_is_released() {
+        let mut manager = manager(true);
+        manager.update_knob(3199);
+        manager.apply(SetpointCommand::Remote(30.5));
+
+        assert_eq!(manager.effective(), Setpoint { celsius: 30.5, source: SetpointSource::Remote });
+
+        manager.apply(SetpointCommand::ReleaseRemote);
+        assert_eq!(manager.effective().source, SetpointSource::Knob);
+    }
+
+    #[test]
+    fn setpoint_is_clamped_to_the_limits() {
+        let mut manager = manager(false);
+
+        manager.apply(SetpointCommand::Remote(80.0));
+        assert_eq!(manager.effective().celsius, SETPOINT_MAX_C);
+        manager.apply(SetpointCommand::Remote(-5.0));
+        assert_eq!(manager.effective().celsius, SETPOINT_MIN_C);
+    }
+}
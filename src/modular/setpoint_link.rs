@@ -0,0 +1,82 @@
+// Setpoint link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Setpoint link file for the modular project.
+ *  File        : setpoint_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about to run the setpoint manager of the setpoint.rs, it receives the
+ *      knob and the commands and publishes the effective setpoint through a watch.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, ThreadModeRawMutex};
+use embassy_sync::channel::{Channel, DynamicSender};
+use embassy_sync::watch::{DynReceiver, Watch};
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::modular::adc::get_receiver_adc0;
+use crate::modular::setpoint::{Setpoint, SetpointCommand, SetpointManager, SetpointPolicy};
+
+const SETPOINT_COMMANDS_DEPTH: usize = 4;
+static SETPOINT_COMMANDS: Channel<ThreadModeRawMutex, SetpointCommand, SETPOINT_COMMANDS_DEPTH> = Channel::new();
+
+pub fn get_sender_setpoint() -> DynamicSender<'static, SetpointCommand> {
+    SETPOINT_COMMANDS.dyn_sender()
+}
+
+// Read by the control loop in the interrupt executor
+const SETPOINT_CONSUMERS: usize = 2;
+static SETPOINT_CHANNEL: Watch<CriticalSectionRawMutex, Setpoint, SETPOINT_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_setpoint() -> Option<DynReceiver<'static, Setpoint>> {
+    SETPOINT_CHANNEL.dyn_receiver()
+}
+
+// The potentiometer is only a source of the setpoint after it is enabled, saved in the configuration
+static KNOB_ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn knob_enabled() -> bool {
+    KNOB_ENABLED.load(Ordering::Relaxed)
+}
+
+// This task arbitrates the setpoint sources and publishes the effective setpoint every time it changes.
+// The stored setpoint and the knob enable come from the configuration saved in the flash.
+#[embassy_executor::task]
+pub async fn setpoint_task(stored_setpoint_c: f32, knob_enabled: bool) {
+    let mut rx_knob = get_receiver_adc0().unwrap();
+    let rx_commands = SETPOINT_COMMANDS.receiver();
+    let tx_setpoint = SETPOINT_CHANNEL.sender();
+
+    KNOB_ENABLED.store(knob_enabled, Ordering::Relaxed);
+    let mut manager = SetpointManager::new(SetpointPolicy::DEFAULT, stored_setpoint_c, knob_enabled);
+    let mut setpoint = manager.effective();
+    tx_setpoint.send(setpoint);
+
+    loop {
+        match select(rx_knob.changed(), rx_commands.receive()).await {
+            Either::First(raw) => manager.update_knob(raw),
+            Either::Second(command) => {
+                info!("Setpoint command: {}", command);
+                if let SetpointCommand::KnobEnabled(enabled) = command {
+                    KNOB_ENABLED.store(enabled, Ordering::Relaxed);
+                }
+                manager.apply(command);
+            }
+        }
+
+        let effective = manager.effective();
+        if effective != setpoint {
+            setpoint = effective;
+            info!("Setpoint: {} °C from {}", setpoint.celsius, setpoint.source.name());
+            tx_setpoint.send(setpoint);
+        }
+    }
+}
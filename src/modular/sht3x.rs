@@ -0,0 +1,121 @@
+// SHT3x file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : SHT3x file for the modular project.
+ *  File        : sht3x.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the commands and the data of the SHT31 (temperature and
+ *      humidity), address 0x44 (ADDR low). A single shot measurement returns 6 bytes: temperature,
+ *      CRC, humidity, CRC, and the CRC-8 of each word is checked. The sensor is read by the
+ *      sht3x_link.rs.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::Format;
+
+pub const SHT3X_ADDRESS: u8 = 0x44;
+
+pub const CMD_SOFT_RESET: u16 = 0x30A2;
+pub const SOFT_RESET_MS: u64 = 2;
+
+// Repeatability of the single shot measurement, a higher one is less noisy and takes longer
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Format)]
+pub enum Repeatability {
+    #[allow(dead_code)] // Not chosen in the main.rs
+    Low,
+    #[allow(dead_code)]
+    Medium,
+    High,
+}
+
+impl Repeatability {
+    // Commands without clock stretching, the sensor does not hold the shared bus
+    pub fn command(self) -> u16 {
+        match self {
+            Repeatability::Low => 0x2416,
+            Repeatability::Medium => 0x240B,
+            Repeatability::High => 0x2400,
+        }
+    }
+
+    pub fn duration_ms(self) -> u64 {
+        match self {
+            Repeatability::Low => 5,
+            Repeatability::Medium => 7,
+            Repeatability::High => 16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Format)]
+pub enum Sht3xError {
+    Bus, // NACK or another error of the I2C
+    Crc,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Format)]
+pub struct Sht3xReading {
+    pub temperature_c: f32,
+    pub humidity_pct: f32,
+}
+
+// CRC-8 of Sensirion: polynomial 0x31, initial value 0xFF, no reflection
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x31 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+pub fn decode(data: &[u8; 6]) -> Result<Sht3xReading, Sht3xError> {
+    if crc8(&data[0..2]) != data[2] || crc8(&data[3..5]) != data[5] {
+        return Err(Sht3xError::Crc);
+    }
+    let raw_temperature = u16::from_be_bytes([data[0], data[1]]) as f32;
+    let raw_humidity = u16::from_be_bytes([data[3], data[4]]) as f32;
+    Ok(Sht3xReading {
+        temperature_c: -45.0 + 175.0 * raw_temperature / 65_535.0,
+        humidity_pct: 100.0 * raw_humidity / 65_535.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc8_of_the_datasheet() {
+        assert_eq!(crc8(&[0xBE, 0xEF]), 0x92);
+        assert_eq!(crc8(&[0x00, 0x00]), 0x81);
+        assert_eq!(crc8(&[]), 0xFF);
+    }
+
+    #[test]
+    fn words_are_converted_with_the_formulas_of_the_datasheet() {
+        // 0x6666 is 40 % of the range: 25 °C, 0x8000 is 50 %
+        let reading = decode(&[0x66, 0x66, 0x93, 0x80, 0x00, 0xA2]).unwrap();
+        assert!((reading.temperature_c - 25.0).abs() < 0.001, "{:?}", reading);
+        assert!((reading.humidity_pct - 50.0).abs() < 0.001, "{:?}", reading);
+
+        assert_eq!(decode(&[0x00, 0x00, 0x81, 0x00, 0x00, 0x81]), Ok(Sht3xReading { temperature_c: -45.0, humidity_pct: 0.0 }));
+        assert_eq!(decode(&[0xFF, 0xFF, 0xAC, 0xFF, 0xFF, 0xAC]), Ok(Sht3xReading { temperature_c: 130.0, humidity_pct: 100.0 }));
+    }
+
+    #[test]
+    fn each_word_has_its_crc() {
+        assert_eq!(decode(&[0x66, 0x66, 0x92, 0x80, 0x00, 0xA2]), Err(Sht3xError::Crc));
+        assert_eq!(decode(&[0x66, 0x66, 0x93, 0x80, 0x01, 0xA2]), Err(Sht3xError::Crc));
+        // A bus stuck high reads 0xFF everywhere, the CRC of 0xFFFF is not 0xFF
+        assert_eq!(decode(&[0xFF; 6]), Err(Sht3xError::Crc));
+    }
+}
@@ -0,0 +1,111 @@
+// SHT3x link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : SHT3x link file for the modular project.
+ *  File        : sht3x_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the SHT31 (temperature and humidity) in the shared I2C0 bus,
+ *      address 0x44 (ADDR low). Each read is a single shot measurement without clock stretching, the
+ *      task sleeps during the measurement and reads the 6 bytes: temperature, CRC, humidity, CRC.
+ *      The data is decoded by the sht3x.rs, after some failed reads in a row the sensor receives a soft
+ *      reset. The reading is published in a Watch channel, the control loop can use it as the
+ *      process variable.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::{DynReceiver, Watch};
+use embassy_time::Timer;
+use embedded_hal_async::i2c::I2c;
+
+use crate::modular::i2c_bus::I2cBusDevice;
+use crate::modular::sht3x::*;
+
+pub const SHT3X_PERIOD_S: u64 = 2;
+const SHT3X_RESET_AFTER: u8 = 3; // Failed reads in a row before the soft reset
+
+const SHT3X_CONSUMERS: usize = 2; // Control loop and console
+static SHT3X_CHANNEL: Watch<CriticalSectionRawMutex, Sht3xReading, SHT3X_CONSUMERS> = Watch::new();
+
+pub fn get_receiver_sht3x() -> Option<DynReceiver<'static, Sht3xReading>> {
+    SHT3X_CHANNEL.dyn_receiver()
+}
+
+pub struct Sht3x<I> {
+    i2c: I,
+    address: u8,
+    repeatability: Repeatability,
+}
+
+impl<I: I2c> Sht3x<I> {
+    pub fn new(i2c: I, address: u8, repeatability: Repeatability) -> Self {
+        Self {
+            i2c,
+            address,
+            repeatability,
+        }
+    }
+
+    async fn command(&mut self, command: u16) -> Result<(), Sht3xError> {
+        self.i2c
+            .write(self.address, &command.to_be_bytes())
+            .await
+            .map_err(|_| Sht3xError::Bus)
+    }
+
+    // The sensor reloads its calibration and goes to the idle state
+    pub async fn soft_reset(&mut self) -> Result<(), Sht3xError> {
+        self.command(CMD_SOFT_RESET).await?;
+        Timer::after_millis(SOFT_RESET_MS).await;
+        Ok(())
+    }
+
+    pub async fn read(&mut self) -> Result<Sht3xReading, Sht3xError> {
+        self.command(self.repeatability.command()).await?;
+        Timer::after_millis(self.repeatability.duration_ms()).await;
+        let mut data = [0u8; 6];
+        self.i2c
+            .read(self.address, &mut data)
+            .await
+            .map_err(|_| Sht3xError::Bus)?;
+        decode(&data)
+    }
+}
+
+// This task reads the SHT3x and publishes the temperature and the humidity
+#[embassy_executor::task]
+pub async fn sht3x_task(mut sensor: Sht3x<I2cBusDevice>) {
+    let tx_reading = SHT3X_CHANNEL.sender();
+    let mut failures: u8 = 0;
+
+    loop {
+        match sensor.read().await {
+            Ok(reading) => {
+                debug!("SHT3x: {} C {} %", reading.temperature_c, reading.humidity_pct);
+                tx_reading.send(reading);
+                failures = 0;
+            }
+            // The last reading stays in the channel
+            Err(e) => {
+                warn!("SHT3x read failed: {}", e);
+                failures += 1;
+                if failures >= SHT3X_RESET_AFTER {
+                    warn!("SHT3x soft reset after {} failed reads", failures);
+                    if let Err(e) = sensor.soft_reset().await {
+                        warn!("SHT3x soft reset failed: {}", e);
+                    }
+                    failures = 0;
+                }
+            }
+        }
+
+        Timer::after_secs(SHT3X_PERIOD_S).await;
+    }
+}
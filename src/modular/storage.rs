@@ -0,0 +1,49 @@
+// Storage file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Storage file for the modular project.
+ *  File        : storage.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the layout of the flash of the board (2 MB in the Pico and the
+ *      Pico W, 4 MB in the Pico 2). The program uses the beginning of the flash and the last 272K are
+ *      split in partitions, each one used by a single module (offsets of the 2 MB flash):
+ *
+ *      0x1BC000 - 0x1FBFFF  data log (256K)
+ *      0x1FC000 - 0x1FFFFF  configuration (16K)
+ *
+ *      The FLASH of the memory_rp2040.x (memory_rp2350.x) must end before the first partition.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::cell::RefCell;
+
+use embassy_embedded_hal::flash::partition::BlockingPartition;
+use embassy_rp::flash::{Blocking, ERASE_SIZE, Flash};
+use embassy_rp::peripherals::FLASH;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+
+use crate::modular::board::FLASH_SIZE;
+
+pub const CONFIG_SECTORS: u32 = 4;
+pub const CONFIG_OFFSET: u32 = FLASH_SIZE as u32 - CONFIG_SECTORS * ERASE_SIZE as u32;
+
+pub const LOG_SECTORS: u32 = 64;
+pub const LOG_OFFSET: u32 = CONFIG_OFFSET - LOG_SECTORS * ERASE_SIZE as u32;
+
+pub type SharedFlash = Mutex<ThreadModeRawMutex, RefCell<Flash<'static, FLASH, Blocking, FLASH_SIZE>>>;
+pub type FlashPartition = BlockingPartition<'static, ThreadModeRawMutex, Flash<'static, FLASH, Blocking, FLASH_SIZE>>;
+
+pub fn config_partition(flash: &'static SharedFlash) -> FlashPartition {
+    FlashPartition::new(flash, CONFIG_OFFSET, CONFIG_SECTORS * ERASE_SIZE as u32)
+}
+
+pub fn log_partition(flash: &'static SharedFlash) -> FlashPartition {
+    FlashPartition::new(flash, LOG_OFFSET, LOG_SECTORS * ERASE_SIZE as u32)
+}
@@ -0,0 +1,430 @@
+// Telemetry file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Telemetry file for the modular project.
+ *  File        : telemetry.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the binary telemetry frame sent to the host, it only uses core so the
+ *      same encoder/decoder is used by the firmware and by the tools running in the host.
+ *
+ *      Frame (little endian): version u8, sequence u16, timestamp_ms u32, pv f32, sp f32, p f32, i f32, d f32,
+ *      output f32, mode u8, setpoint source u8, faults u16, alarms u16, unacknowledged alarms u16, followed
+ *      by the CRC-16/CCITT-FALSE of all the previous bytes. The frame is COBS encoded and terminated by 0x00, so the host can resynchronise
+ *      in the next zero after any lost byte.
+ *
+ *      The frames of the version 1 end after the faults, they are still decoded (without alarms) so the
+ *      tools read the captures made before the version 2.
+ *
+ *      The decoder is only built with the feature "decode" of the host tools, the firmware only encodes.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+// Version 2: active and unacknowledged alarms
+pub const TELEMETRY_VERSION: u8 = 2;
+// Bytes of the frame before the CRC
+pub const FRAME_LEN: usize = 39;
+// Version 1 without the alarms, only decoded
+#[cfg(feature = "decode")]
+const TELEMETRY_V1: u8 = 1;
+#[cfg(feature = "decode")]
+const FRAME_V1_LEN: usize = 35;
+// Frame + CRC, COBS overhead of one byte every 254 and the delimiter
+pub const WIRE_MAX_LEN: usize = FRAME_LEN + 2 + (FRAME_LEN + 2).div_ceil(254) + 1;
+
+// Period of the frames
+pub const TELEMETRY_DEFAULT_PERIOD_MS: u32 = 1_000;
+pub const TELEMETRY_MIN_PERIOD_MS: u32 = 100;
+pub const TELEMETRY_MAX_PERIOD_MS: u32 = 60_000;
+
+pub const MODE_OFF: u8 = 0;
+pub const MODE_AUTO: u8 = 1;
+pub const MODE_MANUAL: u8 = 2;
+
+// The 1 is not used
+pub const SOURCE_REMOTE: u8 = 0;
+pub const SOURCE_KNOB: u8 = 2;
+pub const SOURCE_STORED: u8 = 3;
+
+// Errors of the decoder of the host tools
+#[cfg(feature = "decode")]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DecodeError {
+    Empty,
+    Cobs,            // Zero inside the packet or a code pointing after the end
+    Length(usize),   // Decoded length different from the expected frame
+    Crc(u16, u16),   // Received, calculated
+    Version(u8),     // Frame from an unknown version of the firmware
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TelemetryFrame {
+    pub sequence: u16,
+    pub timestamp_ms: u32,
+    pub pv: f32,
+    pub sp: f32,
+    pub p: f32,
+    pub i: f32,
+    pub d: f32,
+    pub output: f32,
+    pub mode: u8,
+    pub source: u8,
+    pub faults: u16,  // Bits FAULT_* of the alarm.rs
+    pub alarms: u16,  // Bit of each AlarmId of the alarm.rs with the condition present
+    pub unacked: u16, // Alarms waiting the acknowledge
+}
+
+// CRC-16/CCITT-FALSE, polynomial 0x1021 and initial value 0xFFFF
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+// COBS encoding without the delimiter, the output must have input.len() + input.len() / 254 + 1 bytes
+pub fn cobs_encode(input: &[u8], output: &mut [u8]) -> usize {
+    let mut code_index = 0;
+    let mut out = 1;
+    let mut code: u8 = 1;
+
+    for &byte in input {
+        if byte == 0 {
+            output[code_index] = code;
+            code_index = out;
+            out += 1;
+            code = 1;
+        } else {
+            output[out] = byte;
+            out += 1;
+            code += 1;
+            if code == 0xFF {
+                output[code_index] = code;
+                code_index = out;
+                out += 1;
+                code = 1;
+            }
+        }
+    }
+    output[code_index] = code;
+    out
+}
+
+// COBS decoding of one packet without the delimiter, returns the number of bytes written in the output
+#[cfg(feature = "decode")]
+pub fn cobs_decode(input: &[u8], output: &mut [u8]) -> Result<usize, DecodeError> {
+    let mut index = 0;
+    let mut out = 0;
+
+    while index < input.len() {
+        let code = input[index] as usize;
+        if code == 0 || index + code > input.len() {
+            return Err(DecodeError::Cobs);
+        }
+        index += 1;
+        for &byte in &input[index..index + code - 1] {
+            if byte == 0 {
+                return Err(DecodeError::Cobs);
+            }
+            *output.get_mut(out).ok_or(DecodeError::Length(out + 1))? = byte;
+            out += 1;
+        }
+        index += code - 1;
+        // The zero is implicit after every group, except after a full group or the last one
+        if code < 0xFF && index < input.len() {
+            *output.get_mut(out).ok_or(DecodeError::Length(out + 1))? = 0;
+            out += 1;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(feature = "decode")]
+struct Reader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+#[cfg(feature = "decode")]
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut value = [0; N];
+        value.copy_from_slice(&self.bytes[self.index..self.index + N]);
+        self.index += N;
+        value
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take::<1>()[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take())
+    }
+}
+
+impl TelemetryFrame {
+    pub fn to_bytes(self) -> [u8; FRAME_LEN] {
+        let mut bytes = [0; FRAME_LEN];
+        let mut index = 0;
+        let mut put = |value: &[u8]| {
+            bytes[index..index + value.len()].copy_from_slice(value);
+            index += value.len();
+        };
+
+        put(&[TELEMETRY_VERSION]);
+        put(&self.sequence.to_le_bytes());
+        put(&self.timestamp_ms.to_le_bytes());
+        for value in [self.pv, self.sp, self.p, self.i, self.d, self.output] {
+            put(&value.to_le_bytes());
+        }
+        put(&[self.mode, self.source]);
+        put(&self.faults.to_le_bytes());
+        put(&self.alarms.to_le_bytes());
+        put(&self.unacked.to_le_bytes());
+        bytes
+    }
+
+    // Frame of the current version or of the version 1, whose alarms are zero
+    #[cfg(feature = "decode")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let &version = bytes.first().ok_or(DecodeError::Length(0))?;
+        let len = match version {
+            TELEMETRY_VERSION => FRAME_LEN,
+            TELEMETRY_V1 => FRAME_V1_LEN,
+            _ => return Err(DecodeError::Version(version)),
+        };
+        if bytes.len() != len {
+            return Err(DecodeError::Length(bytes.len()));
+        }
+        let mut reader = Reader { bytes, index: 1 };
+        let alarms = |reader: &mut Reader| if version == TELEMETRY_V1 { 0 } else { reader.u16() };
+
+        Ok(Self {
+            sequence: reader.u16(),
+            timestamp_ms: reader.u32(),
+            pv: reader.f32(),
+            sp: reader.f32(),
+            p: reader.f32(),
+            i: reader.f32(),
+            d: reader.f32(),
+            output: reader.f32(),
+            mode: reader.u8(),
+            source: reader.u8(),
+            faults: reader.u16(),
+            alarms: alarms(&mut reader),
+            unacked: alarms(&mut reader),
+        })
+    }
+
+    // Frame + CRC, COBS encoded and terminated with the delimiter, returns the length written in the output
+    pub fn encode(self, output: &mut [u8; WIRE_MAX_LEN]) -> usize {
+        let mut raw = [0; FRAME_LEN + 2];
+        raw[..FRAME_LEN].copy_from_slice(&self.to_bytes());
+        let crc = crc16(&raw[..FRAME_LEN]);
+        raw[FRAME_LEN..].copy_from_slice(&crc.to_le_bytes());
+
+        let len = cobs_encode(&raw, output);
+        output[len] = 0;
+        len + 1
+    }
+
+    // Decode one packet received between two delimiters (the delimiter itself is not included)
+    #[cfg(feature = "decode")]
+    pub fn decode(packet: &[u8]) -> Result<Self, DecodeError> {
+        if packet.is_empty() {
+            return Err(DecodeError::Empty);
+        }
+        let mut raw = [0; FRAME_LEN + 2];
+        let len = cobs_decode(packet, &mut raw)?;
+        if len != FRAME_LEN + 2 && len != FRAME_V1_LEN + 2 {
+            return Err(DecodeError::Length(len));
+        }
+
+        let frame_len = len - 2;
+        let received = u16::from_le_bytes([raw[frame_len], raw[frame_len + 1]]);
+        let calculated = crc16(&raw[..frame_len]);
+        if received != calculated {
+            return Err(DecodeError::Crc(received, calculated));
+        }
+        Self::from_bytes(&raw[..frame_len])
+    }
+}
+
+// Split a stream of bytes in packets using the 0x00 delimiter, a packet larger than N is dropped
+#[cfg(feature = "decode")]
+pub struct PacketSplitter<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+    overflow: bool,
+}
+
+#[cfg(feature = "decode")]
+impl<const N: usize> PacketSplitter<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    // Returns the packet when the byte is the delimiter
+    pub fn push(&mut self, byte: u8) -> Option<&[u8]> {
+        if byte == 0 {
+            let len = core::mem::take(&mut self.len);
+            let overflow = core::mem::take(&mut self.overflow);
+            return if overflow || len == 0 { None } else { Some(&self.buffer[..len]) };
+        }
+        if self.len < N {
+            self.buffer[self.len] = byte;
+            self.len += 1;
+        } else {
+            self.overflow = true;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: TelemetryFrame = TelemetryFrame {
+        sequence: 513,
+        timestamp_ms: 86_400_000,
+        pv: 27.5,
+        sp: 28.0,
+        p: 1.0,
+        i: -0.25,
+        d: 0.0, // Zero bytes inside the frame
+        output: 42.5,
+        mode: MODE_AUTO,
+        source: SOURCE_STORED,
+        faults: 0,
+        alarms: 0b101,
+        unacked: 0b100,
+    };
+
+    fn encoded(frame: TelemetryFrame) -> Vec<u8> {
+        let mut wire = [0; WIRE_MAX_LEN];
+        let len = frame.encode(&mut wire);
+        wire[..len].to_vec()
+    }
+
+    #[test]
+    fn crc_matches_the_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        let wire = encoded(FRAME);
+        assert!(wire.len() <= WIRE_MAX_LEN);
+        assert_eq!(wire.last(), Some(&0));
+        assert!(!wire[..wire.len() - 1].contains(&0));
+        assert_eq!(TelemetryFrame::decode(&wire[..wire.len() - 1]), Ok(FRAME));
+        assert_eq!(TelemetryFrame::from_bytes(&FRAME.to_bytes()), Ok(FRAME));
+    }
+
+    #[test]
+    fn cobs_round_trip() {
+        let inputs: [&[u8]; 5] = [&[], &[0], &[0, 0, 1], &[0x11; 254], &[0x22; 600]];
+        for input in inputs {
+            let mut packet = vec![0; input.len() + input.len() / 254 + 1];
+            let len = cobs_encode(input, &mut packet);
+            assert!(!packet[..len].contains(&0));
+
+            let mut output = vec![0; input.len()];
+            assert_eq!(cobs_decode(&packet[..len], &mut output), Ok(input.len()));
+            assert_eq!(output, input);
+        }
+    }
+
+    #[test]
+    fn damaged_packets_are_rejected() {
+        let mut wire = encoded(FRAME);
+        wire.pop();
+        assert_eq!(TelemetryFrame::decode(&[]), Err(DecodeError::Empty));
+
+        let mut flipped = wire.clone();
+        flipped[10] ^= 0x04;
+        assert!(matches!(TelemetryFrame::decode(&flipped), Err(DecodeError::Crc(_, _))));
+
+        // Code pointing after the end of the packet and a zero inside of it
+        assert_eq!(TelemetryFrame::decode(&[5, 1, 2]), Err(DecodeError::Cobs));
+        assert_eq!(TelemetryFrame::decode(&[3, 0, 2]), Err(DecodeError::Cobs));
+        assert!(matches!(TelemetryFrame::decode(&wire[..20]), Err(DecodeError::Length(_))));
+    }
+
+    #[test]
+    fn version_1_frames_are_decoded_without_alarms() {
+        let mut raw = FRAME.to_bytes()[..FRAME_V1_LEN].to_vec();
+        raw[0] = TELEMETRY_V1;
+        let crc = crc16(&raw);
+        raw.extend_from_slice(&crc.to_le_bytes());
+        let mut packet = [0; WIRE_MAX_LEN];
+        let len = cobs_encode(&raw, &mut packet);
+
+        let expected = TelemetryFrame { alarms: 0, unacked: 0, ..FRAME };
+        assert_eq!(TelemetryFrame::decode(&packet[..len]), Ok(expected));
+        // The length must be the one of its version
+        assert_eq!(TelemetryFrame::from_bytes(&FRAME.to_bytes()[..FRAME_V1_LEN]), Err(DecodeError::Length(FRAME_V1_LEN)));
+        let mut long = FRAME.to_bytes();
+        long[0] = TELEMETRY_V1;
+        assert_eq!(TelemetryFrame::from_bytes(&long), Err(DecodeError::Length(FRAME_LEN)));
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let mut bytes = FRAME.to_bytes();
+        bytes[0] = TELEMETRY_VERSION + 1;
+        assert_eq!(TelemetryFrame::from_bytes(&bytes), Err(DecodeError::Version(TELEMETRY_VERSION + 1)));
+        assert_eq!(TelemetryFrame::from_bytes(&FRAME.to_bytes()[..10]), Err(DecodeError::Length(10)));
+        assert_eq!(TelemetryFrame::from_bytes(&[]), Err(DecodeError::Length(0)));
+    }
+
+    #[test]
+    fn splitter_resynchronises_after_lost_bytes() {
+        let mut splitter = PacketSplitter::<WIRE_MAX_LEN>::new();
+        let wire = encoded(FRAME);
+        let mut frames = Vec::new();
+
+        // Tail of a frame lost in the middle, then a complete one
+        for &byte in wire[15..].iter().chain(&wire) {
+            if let Some(packet) = splitter.push(byte) {
+                frames.push(TelemetryFrame::decode(packet));
+            }
+        }
+        assert!(frames[0].is_err());
+        assert_eq!(frames[1], Ok(FRAME));
+    }
+
+    #[test]
+    fn splitter_drops_a_packet_too_long() {
+        let mut splitter = PacketSplitter::<8>::new();
+        assert!((1..=20).all(|byte| splitter.push(byte).is_none()));
+        assert_eq!(splitter.push(0), None);
+
+        splitter.push(7);
+        assert_eq!(splitter.push(0), Some(&[7u8][..]));
+        assert_eq!(splitter.push(0), None);
+    }
+}
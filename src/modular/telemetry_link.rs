@@ -0,0 +1,143 @@
+// Telemetry link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Telemetry link file for the modular project.
+ *  File        : telemetry_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about to stream the telemetry frames to the host, through the second
+ *      USB serial port (CDC-ACM) or, with the feature "telemetry-uart", through the UART0 in the GP0.
+ *      The period can be changed in runtime with the console command "set rate <ms>".
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use defmt::*; // For logging via RTT
+use embassy_time::{Instant, Timer};
+use portable_atomic::{AtomicU32, Ordering};
+
+use crate::modular::alarm::AlarmStatus;
+use crate::modular::alarm_link::get_receiver_alarm_status;
+use crate::modular::pwm::{ControlStatus, get_receiver_control_status};
+use crate::modular::setpoint::SetpointSource;
+use crate::modular::telemetry::*;
+
+static TELEMETRY_PERIOD_MS: AtomicU32 = AtomicU32::new(TELEMETRY_DEFAULT_PERIOD_MS);
+
+pub fn telemetry_period_ms() -> u32 {
+    TELEMETRY_PERIOD_MS.load(Ordering::Relaxed)
+}
+
+pub fn set_telemetry_period_ms(period_ms: u32) {
+    TELEMETRY_PERIOD_MS.store(period_ms.clamp(TELEMETRY_MIN_PERIOD_MS, TELEMETRY_MAX_PERIOD_MS), Ordering::Relaxed);
+}
+
+pub fn telemetry_frame(status: &ControlStatus, alarms: &AlarmStatus, sequence: u16) -> TelemetryFrame {
+    let source = match status.setpoint.source {
+        SetpointSource::Remote => SOURCE_REMOTE,
+        SetpointSource::Knob => SOURCE_KNOB,
+        SetpointSource::Stored => SOURCE_STORED,
+    };
+
+    TelemetryFrame {
+        sequence,
+        timestamp_ms: Instant::now().as_millis() as u32,
+        pv: status.pv,
+        sp: status.setpoint.celsius,
+        p: status.terms.p,
+        i: status.terms.i,
+        d: status.terms.d,
+        output: status.terms.output,
+        mode: status.mode.code(),
+        source,
+        faults: status.faults,
+        alarms: alarms.active(),
+        unacked: alarms.unacknowledged(),
+    }
+}
+
+// Anything able to send one encoded frame, returns false when the frame was not sent
+trait FrameWriter {
+    async fn write_frame(&mut self, frame: &[u8]) -> bool;
+}
+
+async fn stream_telemetry(writer: &mut impl FrameWriter) -> ! {
+    let mut rx_status = get_receiver_control_status().unwrap();
+    let mut rx_alarms = get_receiver_alarm_status().unwrap();
+    let mut sequence: u16 = 0;
+    let mut wire = [0u8; WIRE_MAX_LEN];
+
+    loop {
+        Timer::after_millis(telemetry_period_ms() as u64).await;
+
+        let Some(status) = rx_status.try_get() else {
+            continue;
+        };
+        // The sequence always moves, so the host can count the frames that were lost
+        let alarms = rx_alarms.try_get().unwrap_or(AlarmStatus::new());
+        let len = telemetry_frame(&status, &alarms, sequence).encode(&mut wire);
+        sequence = sequence.wrapping_add(1);
+        if !writer.write_frame(&wire[..len]).await {
+            debug!("Telemetry frame dropped");
+        }
+    }
+}
+
+#[cfg(not(feature = "telemetry-uart"))]
+mod usb {
+    use embassy_rp::peripherals::USB;
+    use embassy_rp::usb::Driver;
+    use embassy_usb::class::cdc_acm::CdcAcmClass;
+
+    use super::{FrameWriter, stream_telemetry};
+
+    impl FrameWriter for CdcAcmClass<'static, Driver<'static, USB>> {
+        async fn write_frame(&mut self, frame: &[u8]) -> bool {
+            // Nobody is listening while the port is closed in the host
+            if !self.dtr() {
+                return false;
+            }
+            for chunk in frame.chunks(self.max_packet_size() as usize) {
+                if self.write_packet(chunk).await.is_err() {
+                    return false;
+                }
+            }
+            true
+        }
+    }
+
+    // This task streams the telemetry through the second USB serial port
+    #[embassy_executor::task]
+    pub async fn telemetry_task(mut class: CdcAcmClass<'static, Driver<'static, USB>>) {
+        stream_telemetry(&mut class).await
+    }
+}
+
+#[cfg(feature = "telemetry-uart")]
+mod uart {
+    use embassy_rp::uart::{Async, UartTx};
+
+    use super::{FrameWriter, stream_telemetry};
+
+    impl FrameWriter for UartTx<'static, Async> {
+        async fn write_frame(&mut self, frame: &[u8]) -> bool {
+            self.write(frame).await.is_ok()
+        }
+    }
+
+    // This task streams the telemetry through the UART0
+    #[embassy_executor::task]
+    pub async fn telemetry_task(mut uart: UartTx<'static, Async>) {
+        stream_telemetry(&mut uart).await
+    }
+}
+
+#[cfg(not(feature = "telemetry-uart"))]
+pub use usb::telemetry_task;
+
+#[cfg(feature = "telemetry-uart")]
+pub use uart::telemetry_task;
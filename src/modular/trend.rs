@@ -0,0 +1,319 @@
+// Trend file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Trend file for the modular project.
+ *  File        : trend.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the history of the PV, SP and output shown in the graph page
+ *      of the display. The window (2 min, 10 min or 1 h) is split in one point for each column of the
+ *      graph, the samples of the same point are averaged, so the history has always the same size.
+ *      A point without samples (display restarting) is kept as a gap and is not drawn.
+ *
+ *      The graph scales the Y axis to the values of the window, with a span of at least 1 °C so the
+ *      noise of a stable loop does not fill the screen. The PV is a line, the SP is dotted and the
+ *      output is a bar in the bottom of each column.
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::fmt::Write;
+
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::mono_font::iso_8859_1::FONT_6X10;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use heapless::{HistoryBuf, String};
+
+pub const TREND_POINTS: usize = 96; // One point for each column of the graph
+const TREND_MIN_SPAN_C: f32 = 1.0;
+
+// Area of the graph in the display, below the title
+const GRAPH_LEFT: i32 = 32;
+const GRAPH_TOP: i32 = 13;
+const GRAPH_BOTTOM: i32 = 54;
+const BAR_TOP: i32 = 57;
+const BAR_BOTTOM: i32 = 63;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrendWindow {
+    TwoMinutes,
+    #[allow(dead_code)] // Not chosen in the main.rs
+    TenMinutes,
+    #[allow(dead_code)]
+    OneHour,
+}
+
+impl TrendWindow {
+    pub fn seconds(self) -> u32 {
+        match self {
+            TrendWindow::TwoMinutes => 120,
+            TrendWindow::TenMinutes => 600,
+            TrendWindow::OneHour => 3600,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TrendWindow::TwoMinutes => "2 min",
+            TrendWindow::TenMinutes => "10 min",
+            TrendWindow::OneHour => "1 h",
+        }
+    }
+
+    fn point_ms(self) -> u64 {
+        self.seconds() as u64 * 1000 / TREND_POINTS as u64
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TrendPoint {
+    pub pv: f32,
+    pub sp: f32,
+    pub output: f32, // 0-100 %
+}
+
+pub struct Trend {
+    window: TrendWindow,
+    points: HistoryBuf<Option<TrendPoint>, TREND_POINTS>,
+    point: Option<u64>, // Number of the point being averaged, from the time
+    sum: TrendPoint,
+    count: u32,
+}
+
+impl Trend {
+    pub const fn new(window: TrendWindow) -> Self {
+        Self {
+            window,
+            points: HistoryBuf::new(),
+            point: None,
+            sum: TrendPoint { pv: 0.0, sp: 0.0, output: 0.0 },
+            count: 0,
+        }
+    }
+
+    pub fn window(&self) -> TrendWindow {
+        self.window
+    }
+
+    // Add a sample, the time is in milliseconds
+    pub fn add(&mut self, now_ms: u64, sample: TrendPoint) {
+        let point = now_ms / self.window.point_ms();
+        if let Some(current) = self.point
+            && point != current
+        {
+            self.points.write(Some(self.average()));
+            let missing = point.saturating_sub(current + 1).min(TREND_POINTS as u64);
+            for _ in 0..missing {
+                self.points.write(None);
+            }
+            self.count = 0;
+            self.sum = TrendPoint { pv: 0.0, sp: 0.0, output: 0.0 };
+        }
+        self.point = Some(point);
+        self.sum.pv += sample.pv;
+        self.sum.sp += sample.sp;
+        self.sum.output += sample.output;
+        self.count += 1;
+    }
+
+    fn average(&self) -> TrendPoint {
+        let count = self.count.max(1) as f32;
+        TrendPoint {
+            pv: self.sum.pv / count,
+            sp: self.sum.sp / count,
+            output: self.sum.output / count,
+        }
+    }
+
+    // Oldest first, the point being averaged is the last one
+    fn iter(&self) -> impl Iterator<Item = Option<TrendPoint>> + '_ {
+        let current = (self.count > 0).then(|| Some(self.average()));
+        let extra = (self.points.len() + current.iter().len()).saturating_sub(TREND_POINTS);
+        self.points
+            .oldest_ordered()
+            .copied()
+            .chain(current)
+            .skip(extra)
+            .skip_while(Option::is_none)
+    }
+
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    // Lowest and highest values of the PV and SP, with the minimum span around the middle
+    pub fn range(&self) -> Option<(f32, f32)> {
+        let (low, high) = self.iter().flatten().fold((f32::MAX, f32::MIN), |(low, high), point| {
+            (low.min(point.pv).min(point.sp), high.max(point.pv).max(point.sp))
+        });
+        if low > high {
+            return None;
+        }
+        let middle = (low + high) / 2.0;
+        let half = ((high - low) / 2.0).max(TREND_MIN_SPAN_C / 2.0);
+        Some((middle - half, middle + half))
+    }
+}
+
+fn value_y(value: f32, low: f32, high: f32) -> i32 {
+    let scale = (GRAPH_BOTTOM - GRAPH_TOP) as f32 / (high - low);
+    (GRAPH_BOTTOM - ((value - low) * scale) as i32).clamp(GRAPH_TOP, GRAPH_BOTTOM)
+}
+
+// Draw the graph below the title, the newest point is in the right side
+pub fn draw_trend<D: DrawTarget<Color = BinaryColor>>(target: &mut D, trend: &Trend) -> Result<(), D::Error> {
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let Some((low, high)) = trend.range() else {
+        Text::with_baseline("No samples", Point::new(34, 30), style, Baseline::Top).draw(target)?;
+        return Ok(());
+    };
+
+    // Limits of the Y axis and the axis itself
+    for (value, y, baseline) in [(high, GRAPH_TOP, Baseline::Top), (low, GRAPH_BOTTOM, Baseline::Bottom)] {
+        let mut label: String<8> = String::new();
+        let _ = core::write!(label, "{:.1}", value);
+        Text::with_baseline(&label, Point::new(0, y), style, baseline).draw(target)?;
+    }
+    Line::new(Point::new(GRAPH_LEFT - 2, GRAPH_TOP), Point::new(GRAPH_LEFT - 2, BAR_BOTTOM))
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)?;
+
+    let first_x = GRAPH_LEFT + (TREND_POINTS - trend.len()) as i32;
+    let mut last_pv: Option<Point> = None;
+    for (index, point) in trend.iter().enumerate() {
+        let x = first_x + index as i32;
+        let Some(point) = point else {
+            last_pv = None;
+            continue;
+        };
+
+        let pv = Point::new(x, value_y(point.pv, low, high));
+        Line::new(last_pv.unwrap_or(pv), pv)
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(target)?;
+        last_pv = Some(pv);
+
+        if x % 2 == 0 {
+            Pixel(Point::new(x, value_y(point.sp, low, high)), BinaryColor::On).draw(target)?;
+        }
+
+        let bar = ((point.output.clamp(0.0, 100.0) / 100.0) * (BAR_BOTTOM - BAR_TOP + 1) as f32 + 0.5) as u32;
+        if bar > 0 {
+            Rectangle::new(Point::new(x, BAR_BOTTOM + 1 - bar as i32), Size::new(1, bar))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_display::FrameBuffer;
+
+    const POINT_MS: u64 = 1_250; // 2 minutes in 96 points
+
+    fn sample(pv: f32, sp: f32, output: f32) -> TrendPoint {
+        TrendPoint { pv, sp, output }
+    }
+
+    fn points(trend: &Trend) -> Vec<Option<TrendPoint>> {
+        trend.iter().collect()
+    }
+
+    #[test]
+    fn samples_of_a_point_are_averaged() {
+        let mut trend = Trend::new(TrendWindow::TwoMinutes);
+        trend.add(0, sample(20.0, 25.0, 10.0));
+        trend.add(POINT_MS - 1, sample(22.0, 25.0, 30.0));
+
+        assert_eq!(points(&trend), [Some(sample(21.0, 25.0, 20.0))]);
+
+        trend.add(POINT_MS, sample(23.0, 25.0, 40.0));
+        assert_eq!(points(&trend), [Some(sample(21.0, 25.0, 20.0)), Some(sample(23.0, 25.0, 40.0))]);
+    }
+
+    #[test]
+    fn missing_points_are_gaps() {
+        let mut trend = Trend::new(TrendWindow::TwoMinutes);
+        trend.add(0, sample(20.0, 25.0, 0.0));
+        trend.add(3 * POINT_MS, sample(21.0, 25.0, 0.0));
+
+        assert_eq!(
+            points(&trend),
+            [Some(sample(20.0, 25.0, 0.0)), None, None, Some(sample(21.0, 25.0, 0.0))]
+        );
+    }
+
+    #[test]
+    fn history_keeps_the_newest_points() {
+        let mut trend = Trend::new(TrendWindow::TwoMinutes);
+        for point in 0..200 {
+            trend.add(point * POINT_MS, sample(point as f32, 0.0, 0.0));
+        }
+
+        let points = points(&trend);
+        assert_eq!(points.len(), TREND_POINTS);
+        assert_eq!(points[0].unwrap().pv, (200 - TREND_POINTS) as f32);
+        assert_eq!(points[TREND_POINTS - 1].unwrap().pv, 199.0);
+
+        // A pause longer than the window only leaves the new point
+        trend.add(1_000 * POINT_MS, sample(5.0, 0.0, 0.0));
+        assert_eq!(trend.iter().flatten().count(), 1);
+    }
+
+    #[test]
+    fn range_has_a_minimum_span() {
+        let mut trend = Trend::new(TrendWindow::TenMinutes);
+        assert_eq!(trend.range(), None);
+
+        trend.add(0, sample(25.0, 25.0, 0.0));
+        assert_eq!(trend.range(), Some((24.5, 25.5)));
+        trend.add(10_000, sample(22.0, 28.0, 0.0));
+        assert_eq!(trend.range(), Some((22.0, 28.0)));
+    }
+
+    #[test]
+    fn graph_draws_the_newest_point_in_the_right_side() {
+        let mut trend = Trend::new(TrendWindow::TwoMinutes);
+        trend.add(0, sample(20.0, 20.0, 0.0));
+        trend.add(POINT_MS, sample(21.0, 20.0, 100.0));
+        let mut frame = FrameBuffer::new();
+        draw_trend(&mut frame, &trend).unwrap();
+
+        // Axis in the left of the graph, from the top to the bar of the output
+        assert_eq!(frame.column(GRAPH_LEFT - 2), (GRAPH_TOP..=BAR_BOTTOM).collect::<Vec<_>>(), "{frame:?}");
+        // Older point in the bottom with the SP on it and no output, the newest in the top with the full bar
+        let older = frame.column(126);
+        let newest = frame.column(127);
+        assert_eq!(older.last(), Some(&GRAPH_BOTTOM), "{frame:?}");
+        assert_eq!(newest.first(), Some(&GRAPH_TOP), "{frame:?}");
+        // The line between them has no holes
+        let line: Vec<i32> = newest.iter().chain(&older).copied().filter(|&y| y <= GRAPH_BOTTOM).collect();
+        assert_eq!(line, (GRAPH_TOP..=GRAPH_BOTTOM).collect::<Vec<_>>(), "{frame:?}");
+        assert!(newest.ends_with(&[57, 58, 59, 60, 61, 62, 63]), "{frame:?}");
+        assert!(!newest.contains(&(BAR_TOP - 1)), "{frame:?}");
+        // Nothing in the columns without points
+        assert_eq!(frame.count(GRAPH_LEFT, GRAPH_TOP, 125, BAR_BOTTOM), 0, "{frame:?}");
+        // Labels of the limits of the axis
+        assert!(frame.count(0, GRAPH_TOP, GRAPH_LEFT - 3, GRAPH_TOP + 9) > 0, "{frame:?}");
+        assert!(frame.count(0, GRAPH_BOTTOM - 9, GRAPH_LEFT - 3, GRAPH_BOTTOM) > 0, "{frame:?}");
+    }
+
+    #[test]
+    fn graph_without_samples_is_a_message() {
+        let mut frame = FrameBuffer::new();
+        draw_trend(&mut frame, &Trend::new(TrendWindow::OneHour)).unwrap();
+
+        assert!(frame.count(34, 30, 93, 39) > 0, "{frame:?}");
+        assert_eq!(frame.count(0, 0, 127, 29) + frame.count(0, 40, 127, 63), 0, "{frame:?}");
+    }
+}
@@ -0,0 +1,126 @@
+// Wi-Fi link file for the modular project.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Wi-Fi link file for the modular project.
+ *  File        : wifi_link.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      The module is responsible about the Wi-Fi of the CYW43 of the Pico W (feature wifi). It loads
+ *      the firmware of the chip, runs the driver and the network stack of the embassy-net, joins the
+ *      network and waits for the address of the DHCP. The driver does not tell when the access point is
+ *      lost, the DHCP drops the address when it cannot renew it, then the task leaves and joins again.
+ *      The MQTT task waits for the address before every connection to the broker, the HTTP task listens
+ *      in the port 80. The state of the link is shown in the network page of the OLED.
+ *
+ *      The network is set when the firmware is built, with the environment variables:
+ *
+ *      WIFI_SSID       name of the network
+ *      WIFI_PASSWORD   passphrase of the WPA2
+ *      MQTT_BROKER     IPv4 address of the broker, port 1883
+ *      MQTT_CLIENT_ID  client of the MQTT and the level of its topics (optional, pico-w)
+ *
+ *  Target MCU  : Raspberry Pi Pico W (RP2040 and CYW43)
+ *  Framework   : Embassy, no_std
+ *
+ */
+
+use core::cell::Cell;
+
+use cyw43::{Control, JoinOptions, NetDriver, PowerManagementMode, State};
+use cyw43_pio::PioSpi;
+use defmt::*; // For logging via RTT
+use embassy_executor::Spawner;
+use embassy_net::{Config as NetConfig, Stack, StackResources};
+use embassy_rp::clocks::RoscRng;
+use embassy_rp::gpio::Output;
+use embassy_rp::peripherals::{DMA_CH4, PIO0};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_time::Timer;
+use static_cell::StaticCell;
+
+use crate::modular::mqtt_link::mqtt_connected;
+use crate::modular::screen::NetworkView;
+
+pub const WIFI_SSID: &str = env!("WIFI_SSID", "Set the WIFI_SSID of the network to build the feature wifi");
+pub const WIFI_PASSWORD: &str = env!("WIFI_PASSWORD", "Set the WIFI_PASSWORD of the network to build the feature wifi");
+pub const MQTT_BROKER: &str = env!("MQTT_BROKER", "Set the IPv4 address of the MQTT_BROKER to build the feature wifi");
+pub const MQTT_CLIENT_ID: &str = match option_env!("MQTT_CLIENT_ID") {
+    Some(client_id) => client_id,
+    None => "pico-w",
+};
+
+const WIFI_RETRY_S: u64 = 10;
+// DHCP and the sockets of the MQTT and the HTTP
+const NET_SOCKETS: usize = 3;
+
+// Address given by the DHCP, None while the network is joined
+static WIFI_ADDRESS: Mutex<CriticalSectionRawMutex, Cell<Option<[u8; 4]>>> = Mutex::new(Cell::new(None));
+
+pub fn network_view() -> NetworkView {
+    match WIFI_ADDRESS.lock(|address| address.get()) {
+        Some(ip) => NetworkView::Connected { ip, mqtt: mqtt_connected() },
+        None => NetworkView::Connecting,
+    }
+}
+
+pub type WifiSpi = PioSpi<'static, PIO0, 0, DMA_CH4>;
+
+#[embassy_executor::task]
+async fn cyw43_task(runner: cyw43::Runner<'static, Output<'static>, WifiSpi>) -> ! {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, NetDriver<'static>>) -> ! {
+    runner.run().await
+}
+
+// This task joins the network again every time the address is lost
+#[embassy_executor::task]
+async fn wifi_task(mut control: Control<'static>, stack: Stack<'static>) {
+    control.init(cyw43_firmware::CYW43_43439A0_CLM).await;
+    control.set_power_management(PowerManagementMode::PowerSave).await;
+
+    loop {
+        info!("Joining the Wi-Fi {}", WIFI_SSID);
+        if let Err(e) = control.join(WIFI_SSID, JoinOptions::new(WIFI_PASSWORD.as_bytes())).await {
+            warn!("Wi-Fi join failed with the status {}", e.status);
+            Timer::after_secs(WIFI_RETRY_S).await;
+            continue;
+        }
+
+        stack.wait_config_up().await;
+        if let Some(config) = stack.config_v4() {
+            info!("Wi-Fi connected, address {}", config.address);
+            WIFI_ADDRESS.lock(|address| address.set(Some(config.address.address().octets())));
+        }
+
+        stack.wait_config_down().await;
+        warn!("Wi-Fi address lost");
+        WIFI_ADDRESS.lock(|address| address.set(None));
+        control.leave().await;
+    }
+}
+
+// Start the driver of the CYW43 and the network stack, the returned stack opens the sockets
+pub async fn start_wifi(spawner: Spawner, power: Output<'static>, spi: WifiSpi) -> Stack<'static> {
+    static STATE: StaticCell<State> = StaticCell::new();
+    let (driver, control, runner) = cyw43::new(STATE.init(State::new()), power, spi, cyw43_firmware::CYW43_43439A0).await;
+    unwrap!(spawner.spawn(cyw43_task(runner)));
+
+    // The seed of the ports and the sequence numbers of the TCP, the ring oscillator gives a random bit
+    let seed = u64::from_le_bytes(core::array::from_fn(|_| RoscRng::next_u8()));
+    static RESOURCES: StaticCell<StackResources<NET_SOCKETS>> = StaticCell::new();
+    let (stack, runner) = embassy_net::new(
+        driver,
+        NetConfig::dhcpv4(Default::default()),
+        RESOURCES.init(StackResources::new()),
+        seed,
+    );
+    unwrap!(spawner.spawn(net_task(runner)));
+    unwrap!(spawner.spawn(wifi_task(control, stack)));
+    stack
+}
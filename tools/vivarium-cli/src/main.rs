@@ -0,0 +1,258 @@
+// Host companion of the Smart Vivarium firmware.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Host companion of the Smart Vivarium firmware.
+ *  File        : main.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      Decodes the telemetry frames sent by the firmware, from the serial port (or any pseudo-terminal)
+ *      or from a captured file, and:
+ *
+ *      record  <input> [--raw <file>] [--csv <file>] [--jsonl <file>]   Export the frames (CSV in stdout by default)
+ *      metrics <input> [--band <°C>]                                    IAE, ISE, overshoot and settling time
+ *      replay  <input> --kp <v> --ki <v> --kd <v> [--csv <file>]        Run the recorded PV through the PID
+ *
+ *      The decoder and the PID are the same files used by the firmware (src/modular).
+ *      A real UART must be configured before, e.g. `stty -F /dev/ttyUSB0 115200 raw`,
+ *      the USB serial port of the Pico does not need it.
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+#[path = "../../../src/modular/pid.rs"]
+#[allow(dead_code)] // The replay does not change the gains
+mod pid;
+#[path = "../../../src/modular/telemetry.rs"]
+#[allow(dead_code)] // The encoding side is only used by the firmware
+mod telemetry;
+
+mod metrics;
+mod output;
+mod replay;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::process::ExitCode;
+
+use telemetry::{PacketSplitter, TelemetryFrame, WIRE_MAX_LEN};
+
+const USAGE: &str = "usage:
+  vivarium-cli record  <input> [--raw <file>] [--csv <file>] [--jsonl <file>]
+  vivarium-cli metrics <input> [--band <C>]
+  vivarium-cli replay  <input> --kp <v> --ki <v> --kd <v> [--csv <file>]
+
+<input> is a serial port, a pseudo-terminal or a raw capture, '-' reads the stdin";
+
+// Options after the input, all of them are `--name value`
+struct Options {
+    pairs: Vec<(String, String)>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut pairs = Vec::new();
+        let mut args = args.iter();
+        while let Some(name) = args.next() {
+            let Some(name) = name.strip_prefix("--") else {
+                return Err(format!("unexpected argument '{name}'"));
+            };
+            let value = args.next().ok_or(format!("missing value for --{name}"))?;
+            pairs.push((name.to_string(), value.clone()));
+        }
+        Ok(Self { pairs })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.pairs.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn number(&self, name: &str) -> Result<Option<f32>, String> {
+        self.get(name)
+            .map(|value| value.parse().map_err(|_| format!("invalid number for --{name}: '{value}'")))
+            .transpose()
+    }
+
+    fn check(&self, allowed: &[&str]) -> Result<(), String> {
+        match self.pairs.iter().find(|(key, _)| !allowed.contains(&key.as_str())) {
+            Some((key, _)) => Err(format!("unknown option --{key}")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn open_input(path: &str) -> io::Result<Box<dyn Read>> {
+    if path == "-" {
+        Ok(Box::new(io::stdin()))
+    } else {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+fn create_output(path: &str) -> io::Result<Box<dyn Write>> {
+    if path == "-" {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+// Statistics about the stream, the sequence shows the frames lost in the way
+#[derive(Default)]
+struct StreamStats {
+    frames: u64,
+    bad_packets: u64,
+    lost_frames: u64,
+    last_sequence: Option<u16>,
+}
+
+// Read the input until its end, calling `on_frame` for every valid frame and `on_bytes` with the raw bytes
+fn read_frames(
+    input: &mut dyn Read,
+    mut on_bytes: impl FnMut(&[u8]) -> io::Result<()>,
+    mut on_frame: impl FnMut(&TelemetryFrame) -> io::Result<()>,
+) -> io::Result<StreamStats> {
+    let mut splitter: PacketSplitter<WIRE_MAX_LEN> = PacketSplitter::new();
+    let mut stats = StreamStats::default();
+    let mut buffer = [0u8; 4096];
+
+    loop {
+        let len = match input.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // A pseudo-terminal reports EIO when the other side is closed
+            Err(e) if e.raw_os_error() == Some(5) => break,
+            Err(e) => return Err(e),
+        };
+        on_bytes(&buffer[..len])?;
+
+        for &byte in &buffer[..len] {
+            let Some(packet) = splitter.push(byte) else {
+                continue;
+            };
+            match TelemetryFrame::decode(packet) {
+                Ok(frame) => {
+                    if let Some(last) = stats.last_sequence {
+                        stats.lost_frames += frame.sequence.wrapping_sub(last).wrapping_sub(1) as u64;
+                    }
+                    stats.last_sequence = Some(frame.sequence);
+                    stats.frames += 1;
+                    on_frame(&frame)?;
+                }
+                Err(e) => {
+                    stats.bad_packets += 1;
+                    eprintln!("bad packet: {e:?}");
+                }
+            }
+        }
+    }
+    Ok(stats)
+}
+
+fn print_stats(stats: &StreamStats) {
+    eprintln!(
+        "{} frames, {} lost, {} bad packets",
+        stats.frames, stats.lost_frames, stats.bad_packets
+    );
+}
+
+fn record(input: &str, options: &Options) -> Result<(), String> {
+    options.check(&["raw", "csv", "jsonl"])?;
+    let mut raw = options.get("raw").map(create_output).transpose().map_err(|e| e.to_string())?;
+    let mut jsonl = options.get("jsonl").map(create_output).transpose().map_err(|e| e.to_string())?;
+    // Without any output the CSV goes to the stdout
+    let csv_path = options.get("csv").or(match (&raw, &jsonl) {
+        (None, None) => Some("-"),
+        _ => None,
+    });
+    let mut csv = csv_path.map(create_output).transpose().map_err(|e| e.to_string())?;
+
+    if let Some(csv) = csv.as_mut() {
+        output::write_csv_header(csv).map_err(|e| e.to_string())?;
+    }
+
+    let mut reader = open_input(input).map_err(|e| format!("{input}: {e}"))?;
+    let stats = read_frames(
+        &mut *reader,
+        |bytes| match raw.as_mut() {
+            Some(raw) => raw.write_all(bytes),
+            None => Ok(()),
+        },
+        |frame| {
+            if let Some(csv) = csv.as_mut() {
+                output::write_csv(csv, frame)?;
+                csv.flush()?;
+            }
+            if let Some(jsonl) = jsonl.as_mut() {
+                output::write_jsonl(jsonl, frame)?;
+                jsonl.flush()?;
+            }
+            Ok(())
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    for output in [raw.as_mut(), csv.as_mut(), jsonl.as_mut()].into_iter().flatten() {
+        output.flush().map_err(|e| e.to_string())?;
+    }
+    print_stats(&stats);
+    Ok(())
+}
+
+fn collect_frames(input: &str) -> Result<Vec<TelemetryFrame>, String> {
+    let mut reader = open_input(input).map_err(|e| format!("{input}: {e}"))?;
+    let mut frames = Vec::new();
+    let stats = read_frames(&mut *reader, |_| Ok(()), |frame| {
+        frames.push(*frame);
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    print_stats(&stats);
+    Ok(frames)
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let [command, input, rest @ ..] = args else {
+        return Err(USAGE.to_string());
+    };
+    let options = Options::parse(rest)?;
+
+    match command.as_str() {
+        "record" => record(input, &options),
+        "metrics" => {
+            options.check(&["band"])?;
+            let band = options.number("band")?.unwrap_or(metrics::DEFAULT_SETTLING_BAND_C);
+            let frames = collect_frames(input)?;
+            metrics::print_report(&metrics::analyse(&frames, band));
+            Ok(())
+        }
+        "replay" => {
+            options.check(&["kp", "ki", "kd", "csv"])?;
+            let gains = pid::PidGains {
+                kp: options.number("kp")?.ok_or("missing --kp")?,
+                ki: options.number("ki")?.ok_or("missing --ki")?,
+                kd: options.number("kd")?.ok_or("missing --kd")?,
+            };
+            let frames = collect_frames(input)?;
+            let mut csv = options.get("csv").map(create_output).transpose().map_err(|e| e.to_string())?;
+            let summary = replay::replay(&frames, gains, csv.as_deref_mut().map(|csv| csv as &mut dyn Write)).map_err(|e| e.to_string())?;
+            replay::print_summary(&summary);
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
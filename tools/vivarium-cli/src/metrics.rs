@@ -0,0 +1,178 @@
+// Metrics file of the host companion.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Host companion of the Smart Vivarium firmware.
+ *  File        : metrics.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      Performance of the loop for every segment with a constant setpoint:
+ *      IAE and ISE (integral of the absolute and of the squared error, in °C·s and °C²·s),
+ *      overshoot in percent of the step and the settling time inside a band around the setpoint.
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+use crate::telemetry::TelemetryFrame;
+
+pub const DEFAULT_SETTLING_BAND_C: f32 = 0.5;
+
+pub struct SegmentMetrics {
+    pub start_ms: u64,
+    pub duration_s: f32,
+    pub sp: f32,
+    pub iae: f32,
+    pub ise: f32,
+    pub overshoot_percent: Option<f32>, // None when the segment does not start with a step
+    pub settling_s: Option<f32>,        // None when the PV never stays inside the band
+}
+
+// Time in ms since the first frame, the u32 timestamp of the firmware wraps after 49 days
+fn timeline(frames: &[TelemetryFrame]) -> Vec<u64> {
+    let mut elapsed = 0u64;
+    let mut previous = frames.first().map(|frame| frame.timestamp_ms);
+    frames
+        .iter()
+        .map(|frame| {
+            if let Some(previous) = previous {
+                elapsed += frame.timestamp_ms.wrapping_sub(previous) as u64;
+            }
+            previous = Some(frame.timestamp_ms);
+            elapsed
+        })
+        .collect()
+}
+
+fn segment(frames: &[TelemetryFrame], times: &[u64], band: f32) -> SegmentMetrics {
+    let sp = frames[0].sp;
+    let start = times[0];
+
+    let mut iae = 0.0;
+    let mut ise = 0.0;
+    for window in frames.windows(2).zip(times.windows(2)) {
+        let ([frame, _], [t0, t1]) = window else { continue };
+        let dt = (t1 - t0) as f32 / 1_000.0;
+        let error = frame.sp - frame.pv;
+        iae += error.abs() * dt;
+        ise += error * error * dt;
+    }
+
+    let step = sp - frames[0].pv;
+    let overshoot_percent = (step.abs() > band).then(|| {
+        let peak = frames.iter().map(|frame| (frame.pv - sp) * step.signum()).fold(0.0f32, f32::max);
+        peak / step.abs() * 100.0
+    });
+
+    // The settling time is the last time the PV entered the band and stayed there until the end
+    let last_outside = frames.iter().rposition(|frame| (frame.pv - sp).abs() > band);
+    let settling_s = match last_outside {
+        None => Some(0.0),
+        Some(index) if index + 1 < frames.len() => Some((times[index + 1] - start) as f32 / 1_000.0),
+        Some(_) => None,
+    };
+
+    SegmentMetrics {
+        start_ms: start,
+        duration_s: (times[times.len() - 1] - start) as f32 / 1_000.0,
+        sp,
+        iae,
+        ise,
+        overshoot_percent,
+        settling_s,
+    }
+}
+
+pub fn analyse(frames: &[TelemetryFrame], band: f32) -> Vec<SegmentMetrics> {
+    let times = timeline(frames);
+    let mut segments = Vec::new();
+    let mut start = 0;
+
+    for index in 1..=frames.len() {
+        if index == frames.len() || frames[index].sp != frames[start].sp {
+            segments.push(segment(&frames[start..index], &times[start..index], band));
+            start = index;
+        }
+    }
+    segments
+}
+
+fn optional(value: Option<f32>) -> String {
+    value.map_or("-".to_string(), |value| format!("{value:.2}"))
+}
+
+pub fn print_report(segments: &[SegmentMetrics]) {
+    println!("start_s,duration_s,sp,iae,ise,overshoot_percent,settling_s");
+    for segment in segments {
+        println!(
+            "{:.3},{:.3},{:.2},{:.3},{:.3},{},{}",
+            segment.start_ms as f32 / 1_000.0,
+            segment.duration_s,
+            segment.sp,
+            segment.iae,
+            segment.ise,
+            optional(segment.overshoot_percent),
+            optional(segment.settling_s)
+        );
+    }
+
+    let iae: f32 = segments.iter().map(|segment| segment.iae).sum();
+    let ise: f32 = segments.iter().map(|segment| segment.ise).sum();
+    eprintln!("total: iae {iae:.3} °C·s, ise {ise:.3} °C²·s in {} segments", segments.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(timestamp_ms: u32, sp: f32, pv: f32) -> TelemetryFrame {
+        TelemetryFrame {
+            timestamp_ms,
+            sp,
+            pv,
+            ..TelemetryFrame::default()
+        }
+    }
+
+    #[test]
+    fn step_response_metrics() {
+        // Step from 20 to 30 °C, peak of 31 °C and inside the band from the fourth second
+        let pvs = [20.0, 25.0, 29.0, 31.0, 30.2, 30.0];
+        let frames: Vec<_> = pvs.iter().enumerate().map(|(index, &pv)| frame(index as u32 * 1_000, 30.0, pv)).collect();
+
+        let segments = analyse(&frames, DEFAULT_SETTLING_BAND_C);
+        assert_eq!(segments.len(), 1);
+        let segment = &segments[0];
+        assert_eq!(segment.duration_s, 5.0);
+        // The last frame has no interval after it
+        assert!((segment.iae - (10.0 + 5.0 + 1.0 + 1.0 + 0.2)).abs() < 1e-4);
+        assert!((segment.ise - (100.0 + 25.0 + 1.0 + 1.0 + 0.04)).abs() < 1e-3);
+        assert!((segment.overshoot_percent.unwrap() - 10.0).abs() < 1e-4);
+        assert_eq!(segment.settling_s, Some(4.0));
+    }
+
+    #[test]
+    fn segments_split_on_setpoint_changes() {
+        let frames = [
+            frame(0, 28.0, 28.0),
+            frame(1_000, 28.0, 28.1),
+            frame(2_000, 30.0, 28.1),
+            frame(3_000, 30.0, 29.0),
+        ];
+
+        let segments = analyse(&frames, DEFAULT_SETTLING_BAND_C);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].overshoot_percent, None);
+        assert_eq!(segments[0].settling_s, Some(0.0));
+        assert_eq!(segments[1].start_ms, 2_000);
+        // Still outside of the band in the last frame
+        assert_eq!(segments[1].settling_s, None);
+    }
+
+    #[test]
+    fn timeline_survives_the_wrap_of_the_timestamp() {
+        let frames = [frame(u32::MAX - 499, 28.0, 28.0), frame(500, 28.0, 28.0)];
+        assert_eq!(timeline(&frames), vec![0, 1_000]);
+    }
+}
@@ -0,0 +1,142 @@
+// Output file of the host companion.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Host companion of the Smart Vivarium firmware.
+ *  File        : output.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      Writes the decoded frames as CSV (one header line) or as JSON Lines (one object per frame).
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+use std::io::{self, Write};
+
+use crate::telemetry::*;
+
+pub fn mode_name(mode: u8) -> &'static str {
+    match mode {
+        MODE_OFF => "off",
+        MODE_AUTO => "auto",
+        MODE_MANUAL => "manual",
+        _ => "unknown",
+    }
+}
+
+pub fn source_name(source: u8) -> &'static str {
+    match source {
+        SOURCE_REMOTE => "remote",
+        SOURCE_KNOB => "knob",
+        SOURCE_STORED => "stored",
+        _ => "unknown",
+    }
+}
+
+pub fn write_csv_header(output: &mut dyn Write) -> io::Result<()> {
+    writeln!(output, "sequence,timestamp_ms,pv,sp,p,i,d,output,mode,source,faults,alarms,unacked")
+}
+
+pub fn write_csv(output: &mut dyn Write, frame: &TelemetryFrame) -> io::Result<()> {
+    writeln!(
+        output,
+        "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+        frame.sequence,
+        frame.timestamp_ms,
+        frame.pv,
+        frame.sp,
+        frame.p,
+        frame.i,
+        frame.d,
+        frame.output,
+        mode_name(frame.mode),
+        source_name(frame.source),
+        frame.faults,
+        frame.alarms,
+        frame.unacked
+    )
+}
+
+// JSON has no NaN or infinity, those values are written as null
+struct JsonNumber(f32);
+
+impl std::fmt::Display for JsonNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_finite() { write!(f, "{}", self.0) } else { write!(f, "null") }
+    }
+}
+
+pub fn write_jsonl(output: &mut dyn Write, frame: &TelemetryFrame) -> io::Result<()> {
+    writeln!(
+        output,
+        "{{\"sequence\":{},\"timestamp_ms\":{},\"pv\":{},\"sp\":{},\"p\":{},\"i\":{},\"d\":{},\"output\":{},\"mode\":\"{}\",\"source\":\"{}\",\"faults\":{},\"alarms\":{},\"unacked\":{}}}",
+        frame.sequence,
+        frame.timestamp_ms,
+        JsonNumber(frame.pv),
+        JsonNumber(frame.sp),
+        JsonNumber(frame.p),
+        JsonNumber(frame.i),
+        JsonNumber(frame.d),
+        JsonNumber(frame.output),
+        mode_name(frame.mode),
+        source_name(frame.source),
+        frame.faults,
+        frame.alarms,
+        frame.unacked
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: TelemetryFrame = TelemetryFrame {
+        sequence: 7,
+        timestamp_ms: 1_500,
+        pv: 27.5,
+        sp: 28.0,
+        p: 1.0,
+        i: 0.5,
+        d: f32::NAN,
+        output: 40.0,
+        mode: MODE_MANUAL,
+        source: SOURCE_KNOB,
+        faults: 2,
+        alarms: 1,
+        unacked: 0,
+    };
+
+    fn written(write: fn(&mut dyn Write, &TelemetryFrame) -> io::Result<()>) -> String {
+        let mut output = Vec::new();
+        write(&mut output, &FRAME).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn csv_line_matches_the_header() {
+        let mut header = Vec::new();
+        write_csv_header(&mut header).unwrap();
+        let header = String::from_utf8(header).unwrap();
+        let line = written(write_csv);
+
+        assert_eq!(line, "7,1500,27.5,28,1,0.5,NaN,40,manual,knob,2,1,0\n");
+        assert_eq!(header.split(',').count(), line.split(',').count());
+    }
+
+    #[test]
+    fn jsonl_writes_null_for_nan() {
+        assert_eq!(
+            written(write_jsonl),
+            "{\"sequence\":7,\"timestamp_ms\":1500,\"pv\":27.5,\"sp\":28,\"p\":1,\"i\":0.5,\"d\":null,\"output\":40,\"mode\":\"manual\",\"source\":\"knob\",\"faults\":2,\"alarms\":1,\"unacked\":0}\n"
+        );
+    }
+
+    #[test]
+    fn unknown_codes_have_a_name() {
+        assert_eq!(mode_name(9), "unknown");
+        assert_eq!(source_name(1), "unknown");
+        assert_eq!(source_name(9), "unknown");
+    }
+}
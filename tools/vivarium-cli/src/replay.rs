@@ -0,0 +1,146 @@
+// Replay file of the host companion.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Host companion of the Smart Vivarium firmware.
+ *  File        : replay.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      Runs the recorded PV and SP through the same PID of the firmware with new gains, so the output
+ *      of the new tuning can be compared with the recorded output before flashing it.
+ *      The replay is open loop, the recorded PV does not react to the new output.
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+use std::io::{self, Write};
+
+use crate::pid::{Pid, PidGains};
+use crate::telemetry::{MODE_AUTO, TelemetryFrame};
+
+// Same limits of the output in the firmware (pwm.rs)
+const OUTPUT_MIN_PERCENT: f32 = 0.0;
+const OUTPUT_MAX_PERCENT: f32 = 100.0;
+
+#[derive(Default)]
+pub struct ReplaySummary {
+    pub samples: usize,
+    pub mean_abs_difference: f32, // Mean of |replay - recorded| in percent
+    pub max_abs_difference: f32,
+    pub saturated: usize, // Samples where the new output hits a limit
+}
+
+pub fn replay(frames: &[TelemetryFrame], gains: PidGains, mut csv: Option<&mut dyn Write>) -> io::Result<ReplaySummary> {
+    let mut pid = Pid::new(gains, OUTPUT_MIN_PERCENT, OUTPUT_MAX_PERCENT);
+    let mut summary = ReplaySummary::default();
+    let mut total_difference = 0.0;
+    let mut previous_ms: Option<u32> = None;
+
+    if let Some(csv) = csv.as_mut() {
+        writeln!(csv, "timestamp_ms,pv,sp,recorded_output,replay_output,replay_p,replay_i,replay_d")?;
+    }
+
+    for frame in frames {
+        let dt = previous_ms.map_or(0.0, |previous| frame.timestamp_ms.wrapping_sub(previous) as f32 / 1_000.0);
+        previous_ms = Some(frame.timestamp_ms);
+
+        // Outside of the automatic mode the controller only follows the recorded output, as in the firmware
+        if frame.mode != MODE_AUTO {
+            pid.reset(frame.output);
+            continue;
+        }
+        let terms = pid.update(frame.sp, frame.pv, dt);
+
+        let difference = (terms.output - frame.output).abs();
+        total_difference += difference;
+        summary.max_abs_difference = summary.max_abs_difference.max(difference);
+        summary.samples += 1;
+        if terms.output <= OUTPUT_MIN_PERCENT || terms.output >= OUTPUT_MAX_PERCENT {
+            summary.saturated += 1;
+        }
+
+        if let Some(csv) = csv.as_mut() {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                frame.timestamp_ms, frame.pv, frame.sp, frame.output, terms.output, terms.p, terms.i, terms.d
+            )?;
+        }
+    }
+
+    if let Some(csv) = csv.as_mut() {
+        csv.flush()?;
+    }
+    if summary.samples > 0 {
+        summary.mean_abs_difference = total_difference / summary.samples as f32;
+    }
+    Ok(summary)
+}
+
+pub fn print_summary(summary: &ReplaySummary) {
+    println!(
+        "{} samples in auto, output difference mean {:.2}% max {:.2}%, {} samples saturated",
+        summary.samples, summary.mean_abs_difference, summary.max_abs_difference, summary.saturated
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::MODE_OFF;
+
+    const GAINS: PidGains = PidGains { kp: 5.0, ki: 0.2, kd: 1.0 };
+
+    // Frames recorded by a firmware running the given gains
+    fn recorded(gains: PidGains) -> Vec<TelemetryFrame> {
+        let mut pid = Pid::new(gains, OUTPUT_MIN_PERCENT, OUTPUT_MAX_PERCENT);
+        let mut pv = 24.0;
+        (0..20u32)
+            .map(|index| {
+                let dt = if index == 0 { 0.0 } else { 1.0 };
+                let terms = pid.update(28.0, pv, dt);
+                let frame = TelemetryFrame {
+                    timestamp_ms: index * 1_000,
+                    pv,
+                    sp: 28.0,
+                    output: terms.output,
+                    mode: MODE_AUTO,
+                    ..TelemetryFrame::default()
+                };
+                pv += terms.output * 0.01;
+                frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_gains_reproduce_the_recorded_output() {
+        let summary = replay(&recorded(GAINS), GAINS, None).unwrap();
+        assert_eq!(summary.samples, 20);
+        assert!(summary.max_abs_difference < 1e-3);
+    }
+
+    #[test]
+    fn new_gains_are_compared_and_written() {
+        let mut csv = Vec::new();
+        let gains = PidGains { kp: 50.0, ..GAINS };
+        let summary = replay(&recorded(GAINS), gains, Some(&mut csv)).unwrap();
+
+        assert!(summary.max_abs_difference > 1.0);
+        assert!(summary.saturated > 0);
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 21);
+        assert!(csv.starts_with("timestamp_ms,pv,sp,recorded_output,replay_output"));
+    }
+
+    #[test]
+    fn frames_outside_of_auto_are_skipped() {
+        let mut frames = recorded(GAINS);
+        for frame in &mut frames[..5] {
+            frame.mode = MODE_OFF;
+        }
+        assert_eq!(replay(&frames, GAINS, None).unwrap().samples, 15);
+    }
+}
@@ -0,0 +1,58 @@
+// Host build of the Smart Vivarium firmware modules.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Host build of the Smart Vivarium firmware modules.
+ *  File        : lib.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      Builds in the host the modules of the firmware that do not use the hardware (parsers, encoders,
+ *      state machines and renderers), so their unit tests run with `cargo test -p vivarium-host`.
+ *
+ *      The modules are the same files of src/modular, included in a `modular` module so the paths
+ *      `crate::modular::...` and `super::...` used by the firmware are resolved in the same way.
+ *      A module is only added here when all the modules it uses are also here.
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+// The types are public here, in the firmware they are private to the binary and do not need a Default
+#![allow(clippy::new_without_default)]
+// The traits with async functions are only implemented by this crate, as in the firmware
+#![allow(async_fn_in_trait)]
+
+#[cfg(test)]
+mod mock_card;
+#[cfg(test)]
+mod mock_display;
+#[cfg(test)]
+mod mock_flash;
+
+#[path = "../../../src/modular"]
+pub mod modular {
+    pub mod alarm;
+    pub mod bme280;
+    pub mod command;
+    pub mod config;
+    pub mod control;
+    pub mod datalog;
+    pub mod dht;
+    pub mod ds18b20;
+    pub mod fat;
+    pub mod http;
+    pub mod humidity;
+    pub mod led_pattern;
+    pub mod loop_timing;
+    pub mod menu;
+    pub mod modbus;
+    pub mod mqtt;
+    pub mod pid;
+    pub mod screen;
+    pub mod sdlog;
+    pub mod setpoint;
+    pub mod sht3x;
+    pub mod telemetry;
+    pub mod trend;
+}
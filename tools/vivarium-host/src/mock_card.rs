@@ -0,0 +1,163 @@
+// SD card in the memory for the tests of the modules.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : SD card in the memory for the tests of the modules.
+ *  File        : mock_card.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      Card of blocks of 512 bytes kept in a map, the blocks never written read as zeros so a volume
+ *      of 35 MB (the smallest FAT32 with one sector per cluster) only uses the memory of its metadata.
+ *      The card is formatted as FAT32 like the computer does it, with or without the partition table,
+ *      and the files are read back by a reader that does not share any code with the fat.rs.
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+use std::collections::HashMap;
+
+use crate::modular::fat::{BLOCK_SIZE, Block, BlockDevice};
+
+pub const RESERVED_SECTORS: u32 = 32;
+pub const FAT_SIZE: u32 = 547; // Sectors of each copy, 70 002 entries
+pub const CLUSTERS: u32 = 70_000;
+pub const PARTITION_START: u32 = 2_048;
+
+pub struct MemoryCard {
+    pub blocks: HashMap<u32, Block>,
+    pub sectors: u32,
+    pub volume_start: u32,
+}
+
+impl MemoryCard {
+    // FAT32 with one sector per cluster and two copies of the FAT, the root directory is the cluster 2
+    pub fn fat32(partitioned: bool) -> Self {
+        let volume_start = if partitioned { PARTITION_START } else { 0 };
+        let volume_sectors = RESERVED_SECTORS + 2 * FAT_SIZE + CLUSTERS;
+        let mut card = Self {
+            blocks: HashMap::new(),
+            sectors: volume_start + volume_sectors,
+            volume_start,
+        };
+
+        if partitioned {
+            let mbr = card.block_mut(0);
+            mbr[446 + 4] = 0x0C; // FAT32 with LBA
+            mbr[446 + 8..446 + 12].copy_from_slice(&PARTITION_START.to_le_bytes());
+            mbr[446 + 12..446 + 16].copy_from_slice(&volume_sectors.to_le_bytes());
+            mbr[510..512].copy_from_slice(&[0x55, 0xAA]);
+        }
+
+        let boot = card.block_mut(volume_start);
+        boot[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+        boot[3..11].copy_from_slice(b"MSWIN4.1");
+        boot[11..13].copy_from_slice(&(BLOCK_SIZE as u16).to_le_bytes());
+        boot[13] = 1;
+        boot[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+        boot[16] = 2;
+        boot[21] = 0xF8;
+        boot[32..36].copy_from_slice(&volume_sectors.to_le_bytes());
+        boot[36..40].copy_from_slice(&FAT_SIZE.to_le_bytes());
+        boot[44..48].copy_from_slice(&2u32.to_le_bytes());
+        boot[48..50].copy_from_slice(&1u16.to_le_bytes());
+        boot[50..52].copy_from_slice(&6u16.to_le_bytes());
+        boot[66] = 0x29;
+        boot[82..90].copy_from_slice(b"FAT32   ");
+        boot[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        let fs_info = card.block_mut(volume_start + 1);
+        fs_info[0..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+        fs_info[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+        fs_info[488..492].copy_from_slice(&(CLUSTERS - 1).to_le_bytes());
+        fs_info[492..496].copy_from_slice(&3u32.to_le_bytes());
+        fs_info[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        // Media and reserved entries, then the end of the chain of the root directory
+        for copy in 0..2 {
+            let fat = card.block_mut(volume_start + RESERVED_SECTORS + copy * FAT_SIZE);
+            fat[0..4].copy_from_slice(&0x0FFF_FFF8u32.to_le_bytes());
+            fat[4..8].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+            fat[8..12].copy_from_slice(&0x0FFF_FFFFu32.to_le_bytes());
+        }
+        card
+    }
+
+    pub fn block(&self, lba: u32) -> Block {
+        self.blocks.get(&lba).copied().unwrap_or([0; BLOCK_SIZE])
+    }
+
+    pub fn block_mut(&mut self, lba: u32) -> &mut Block {
+        self.blocks.entry(lba).or_insert([0; BLOCK_SIZE])
+    }
+
+    pub fn fat_entry(&self, copy: u32, cluster: u32) -> u32 {
+        let lba = self.volume_start + RESERVED_SECTORS + copy * FAT_SIZE + cluster * 4 / BLOCK_SIZE as u32;
+        let offset = (cluster * 4) as usize % BLOCK_SIZE;
+        let block = self.block(lba);
+        u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap()) & 0x0FFF_FFFF
+    }
+
+    fn chain(&self, first_cluster: u32) -> Vec<u32> {
+        let mut chain = Vec::new();
+        let mut cluster = first_cluster;
+        while (2..CLUSTERS + 2).contains(&cluster) {
+            chain.push(cluster);
+            cluster = self.fat_entry(0, cluster);
+        }
+        chain
+    }
+
+    fn cluster_lba(&self, cluster: u32) -> u32 {
+        self.volume_start + RESERVED_SECTORS + 2 * FAT_SIZE + cluster - 2
+    }
+
+    // Names of the root directory in the 11 bytes format
+    pub fn root_entries(&self) -> Vec<[u8; 32]> {
+        let mut entries = Vec::new();
+        for cluster in self.chain(2) {
+            let block = self.block(self.cluster_lba(cluster));
+            for entry in block.chunks(32) {
+                match entry[0] {
+                    0x00 => return entries,
+                    0xE5 => {}
+                    _ => entries.push(entry.try_into().unwrap()),
+                }
+            }
+        }
+        entries
+    }
+
+    pub fn read_file(&self, name: &[u8; 11]) -> Option<Vec<u8>> {
+        let entry = self.root_entries().into_iter().find(|entry| entry[..11] == name[..])?;
+        let first_cluster = (u16::from_le_bytes([entry[20], entry[21]]) as u32) << 16 | u16::from_le_bytes([entry[26], entry[27]]) as u32;
+        let size = u32::from_le_bytes(entry[28..32].try_into().unwrap()) as usize;
+
+        let mut data = Vec::new();
+        for cluster in self.chain(first_cluster) {
+            data.extend_from_slice(&self.block(self.cluster_lba(cluster)));
+        }
+        (data.len() >= size).then(|| data[..size].to_vec())
+    }
+}
+
+impl BlockDevice for MemoryCard {
+    type Error = u32; // Block out of the card
+
+    async fn read(&mut self, lba: u32, block: &mut Block) -> Result<(), Self::Error> {
+        if lba >= self.sectors {
+            return Err(lba);
+        }
+        *block = self.block(lba);
+        Ok(())
+    }
+
+    async fn write(&mut self, lba: u32, block: &Block) -> Result<(), Self::Error> {
+        if lba >= self.sectors {
+            return Err(lba);
+        }
+        self.blocks.insert(lba, *block);
+        Ok(())
+    }
+}
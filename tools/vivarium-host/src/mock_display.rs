@@ -0,0 +1,91 @@
+// Display in the memory for the tests of the modules.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Display in the memory for the tests of the modules.
+ *  File        : mock_display.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      Frame buffer of 128x64 pixels like the SSD1306, the pages, the trend and the menu are drawn in
+ *      it by the same code of the firmware. The pixels outside of the screen are dropped like the
+ *      driver does, and the tests count the pixels that are on in an area or print the frame when
+ *      an assert fails.
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+use core::convert::Infallible;
+use core::fmt;
+
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 64;
+
+pub struct FrameBuffer {
+    pixels: [[bool; WIDTH as usize]; HEIGHT as usize],
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        Self {
+            pixels: [[false; WIDTH as usize]; HEIGHT as usize],
+        }
+    }
+
+    pub fn pixel(&self, x: i32, y: i32) -> bool {
+        self.bounding_box().contains(Point::new(x, y)) && self.pixels[y as usize][x as usize]
+    }
+
+    // Pixels on inside the rectangle of the corners (included)
+    pub fn count(&self, left: i32, top: i32, right: i32, bottom: i32) -> usize {
+        (top..=bottom)
+            .flat_map(|y| (left..=right).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.pixel(x, y))
+            .count()
+    }
+
+    // Rows with a pixel on in the column, top first
+    pub fn column(&self, x: i32) -> Vec<i32> {
+        (0..HEIGHT as i32).filter(|&y| self.pixel(x, y)).collect()
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH, HEIGHT)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let area: Rectangle = self.bounding_box();
+        for Pixel(point, color) in pixels {
+            if area.contains(point) {
+                self.pixels[point.y as usize][point.x as usize] = color.is_on();
+            }
+        }
+        Ok(())
+    }
+}
+
+// The frame as text, # for the pixels on
+impl fmt::Debug for FrameBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.pixels {
+            let line: String = row.iter().map(|&on| if on { '#' } else { '.' }).collect();
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
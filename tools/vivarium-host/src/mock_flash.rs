@@ -0,0 +1,84 @@
+// Flash in the memory for the tests of the modules.
+/*!
+ * -----------------------------------------------------------------------------
+ *  Project     : Flash in the memory for the tests of the modules.
+ *  File        : mock_flash.rs
+ *  Created by  : Everton Oriente
+ *  Date        : 2026-10-19
+ *  * -----------------------------------------------------------------------------
+ *  Description :
+ *      NOR flash in a vector with the sectors of 4K of the flash of the Pico. Like the real one, an
+ *      erase sets the bytes to 0xFF and a write can only clear bits, so a slot written twice without
+ *      the erase is corrupted in the same way. The bytes are public so a test can cut a write or
+ *      damage a record.
+ *
+ *  Target      : Host (std)
+ *
+ */
+
+use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+
+pub const SECTOR_SIZE: usize = 4096;
+
+pub struct MemoryFlash {
+    pub bytes: Vec<u8>,
+    pub erases: usize, // Sectors erased since the creation
+}
+
+impl MemoryFlash {
+    pub fn new(sectors: usize) -> Self {
+        Self {
+            bytes: vec![0xFF; sectors * SECTOR_SIZE],
+            erases: 0,
+        }
+    }
+
+    fn range(&self, offset: u32, len: usize) -> Result<core::ops::Range<usize>, NorFlashErrorKind> {
+        let start = offset as usize;
+        match start.checked_add(len) {
+            Some(end) if end <= self.bytes.len() => Ok(start..end),
+            _ => Err(NorFlashErrorKind::OutOfBounds),
+        }
+    }
+}
+
+impl ErrorType for MemoryFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for MemoryFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let range = self.range(offset, bytes.len())?;
+        bytes.copy_from_slice(&self.bytes[range]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+impl NorFlash for MemoryFlash {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = SECTOR_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if !(from as usize).is_multiple_of(SECTOR_SIZE) || !(to as usize).is_multiple_of(SECTOR_SIZE) || to < from {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        let range = self.range(from, (to - from) as usize)?;
+        self.erases += range.len() / SECTOR_SIZE;
+        self.bytes[range].fill(0xFF);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let range = self.range(offset, bytes.len())?;
+        for (cell, byte) in self.bytes[range].iter_mut().zip(bytes) {
+            *cell &= byte;
+        }
+        Ok(())
+    }
+}